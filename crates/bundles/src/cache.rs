@@ -15,6 +15,12 @@
 //! which you’ll pass to [`Cache::open()`]. When using this function, you must
 //! explicitly specify the concrete [`CacheBackend`] type that will service
 //! backend requests.
+//!
+//! If the same bundle is served from several places, you can instead use
+//! [`Cache::open_mirrored()`] with an ordered list of mirror URLs. The cache
+//! will try the mirrors in turn until it finds one that works, verifying that
+//! each one serves the expected content, and will remember the mirror that
+//! worked so that it can be tried first next time.
 
 use fs2::FileExt;
 use std::{
@@ -26,6 +32,7 @@ use std::{
     str::FromStr,
 };
 use tectonic_errors::prelude::*;
+use tectonic_geturl::HttpConfig;
 use tectonic_io_base::{
     app_dirs,
     digest::{self, Digest, DigestData},
@@ -39,6 +46,7 @@ use crate::Bundle;
 #[derive(Debug)]
 pub struct Cache {
     root: PathBuf,
+    http: HttpConfig,
 }
 
 impl Cache {
@@ -51,6 +59,10 @@ impl Cache {
     /// This method may perform I/O to create the user cache directory, so it is
     /// fallible. (Due to its `app_dirs2` implementation, it would have to be
     /// fallible even if it didn't perform I/O.)
+    ///
+    /// The HTTP settings used to talk to network backends are initialized from
+    /// the `TECTONIC_HTTP_*` environment variables; see
+    /// [`HttpConfig::apply_env`].
     pub fn get_user_default() -> Result<Self> {
         let env_cache_path = env::var_os("TECTONIC_CACHE_DIR");
 
@@ -63,12 +75,27 @@ impl Cache {
             None => app_dirs::ensure_user_cache_dir("")?,
         };
 
-        Ok(Cache { root: cache_path })
+        Ok(Cache {
+            root: cache_path,
+            http: HttpConfig::from_env()?,
+        })
     }
 
     /// Get a handle to a bundle cache, using a custom cache directory.
+    ///
+    /// The HTTP settings used to talk to network backends are the defaults.
     pub fn get_for_custom_directory<P: Into<PathBuf>>(root: P) -> Self {
-        Cache { root: root.into() }
+        Cache {
+            root: root.into(),
+            http: HttpConfig::default(),
+        }
+    }
+
+    /// Set the HTTP settings that will be used by bundles opened through this
+    /// cache.
+    pub fn set_http_config(&mut self, http: HttpConfig) -> &mut Self {
+        self.http = http;
+        self
     }
 
     /// Get the root directory of this cache.
@@ -89,7 +116,34 @@ impl Cache {
         only_cached: bool,
         status: &mut dyn StatusBackend,
     ) -> Result<CachingBundle<CB>> {
-        CachingBundle::new(url, only_cached, status, &self.root)
+        self.open_mirrored(&[url.to_owned()], None, only_cached, status)
+    }
+
+    /// Open a bundle that is available from several mirrors through the cache
+    /// layer.
+    ///
+    /// The *mirrors* are URLs that should all serve the same bundle, in order
+    /// of preference; there must be at least one. The first one is the
+    /// "primary" URL that identifies the bundle if *expected_digest* is not
+    /// specified. When the backend needs to be contacted, the mirrors are tried
+    /// in turn, starting with whichever one worked most recently. If
+    /// *expected_digest* is specified, any mirror that serves a bundle with a
+    /// different digest is skipped.
+    pub fn open_mirrored<CB: CacheBackend>(
+        &mut self,
+        mirrors: &[String],
+        expected_digest: Option<DigestData>,
+        only_cached: bool,
+        status: &mut dyn StatusBackend,
+    ) -> Result<CachingBundle<CB>> {
+        CachingBundle::new(
+            mirrors,
+            expected_digest,
+            only_cached,
+            &self.http,
+            status,
+            &self.root,
+        )
     }
 }
 
@@ -137,9 +191,10 @@ pub trait CacheBackend: Sized {
     /// This method is used the first time that the cache connects to a backend.
     /// The return value includes a package of information ([`BackendPullData`])
     /// that the cache will store to enable efficient operation on subsequent
-    /// requests.
+    /// requests. Network-based backends should honor the settings in *http*.
     fn open_with_pull(
         start_url: &str,
+        http: &HttpConfig,
         status: &mut dyn StatusBackend,
     ) -> Result<(Self, BackendPullData)>;
 
@@ -156,6 +211,7 @@ pub trait CacheBackend: Sized {
     fn open_with_quick_check(
         resolved_url: &str,
         digest_file_info: &Self::FileInfo,
+        http: &HttpConfig,
         status: &mut dyn StatusBackend,
     ) -> Result<Option<(Self, DigestData)>>;
 
@@ -206,16 +262,25 @@ struct CachedFileInfo {
 /// need to be downloaded.
 #[derive(Debug)]
 pub struct CachingBundle<CB: CacheBackend> {
-    /// The URL specifying where to start looking for the bundle data.
+    /// The URLs specifying where to start looking for the bundle data.
     ///
-    /// The caching layer maintains two URLs: the "start" URL and the "resolved"
-    /// URL. The goal here is to be able to store a single URL for fetching
-    /// data, but maintain the capability to update the bundle data behind that
-    /// URL. Requests to the start URL may get redirected (one or more times)
-    /// until eventually we arrive at the "resolved" URL. While the redirection
-    /// of the start URL might change, the contents of a resolved URL should
-    /// never change once published.
-    start_url: String,
+    /// The caching layer maintains two kinds of URLs: "start" URLs and the
+    /// "resolved" URL. The goal here is to be able to store a single URL for
+    /// fetching data, but maintain the capability to update the bundle data
+    /// behind that URL. Requests to a start URL may get redirected (one or more
+    /// times) until eventually we arrive at the "resolved" URL. While the
+    /// redirection of the start URL might change, the contents of a resolved
+    /// URL should never change once published.
+    ///
+    /// There may be several start URLs if the bundle is mirrored. They are
+    /// tried in order; the mirror that worked most recently is moved to the
+    /// front when the bundle is opened.
+    mirrors: Vec<String>,
+
+    /// The digest that the bundle is required to have, if specified.
+    ///
+    /// Mirrors that serve a bundle with a different digest are ignored.
+    expected_digest: Option<DigestData>,
 
     /// The "resolved" URL for the backing data.
     ///
@@ -265,7 +330,7 @@ pub struct CachingBundle<CB: CacheBackend> {
     /// The path to a file containing a cached copy of the backend's content
     /// digest.
     ///
-    /// This file path is based on [`Self::start_url`].
+    /// This file path is based on the first of [`Self::mirrors`].
     digest_path: PathBuf,
 
     /// A directory where we will save [`Self::resolved_url`].
@@ -275,6 +340,17 @@ pub struct CachingBundle<CB: CacheBackend> {
     /// content digest.
     resolved_base: PathBuf,
 
+    /// A directory where we will save the backend indexing data.
+    ///
+    /// The actual cache file path is based on the backend’s content digest.
+    index_base: PathBuf,
+
+    /// A directory where we will save the start URL of the mirror that most
+    /// recently worked.
+    ///
+    /// The actual cache file path is based on the backend’s content digest.
+    mirror_base: PathBuf,
+
     /// A directory where we will save the cache manifest.
     ///
     /// The manifest file contains information about the files that have
@@ -287,6 +363,9 @@ pub struct CachingBundle<CB: CacheBackend> {
     /// This directory contains the actual cached file contents, in a directory
     /// structured based on the digest of each file’s content.
     data_base: PathBuf,
+
+    /// The HTTP settings to use when connecting to the backend.
+    http: HttpConfig,
 }
 
 /// A locally-cached analogue of [`BackendPullData`].
@@ -307,57 +386,86 @@ struct CachedPullData<FI> {
 
 impl<CB: CacheBackend> CachingBundle<CB> {
     fn new(
-        start_url: &str,
+        mirrors: &[String],
+        expected_digest: Option<DigestData>,
         only_cached: bool,
+        http: &HttpConfig,
         status: &mut dyn StatusBackend,
         cache_root: &Path,
     ) -> Result<Self> {
+        let primary_url = a_ok_or!(
+            mirrors.first();
+            ["at least one URL must be specified for a cached bundle"]
+        );
+
         // Set up our paths.
         let digest_path =
-            ensure_cache_dir(cache_root, "urls")?.join(app_dirs::app_dirs2::sanitized(start_url));
+            ensure_cache_dir(cache_root, "urls")?.join(app_dirs::app_dirs2::sanitized(primary_url));
         let resolved_base = ensure_cache_dir(cache_root, "redirects")?;
         let index_base = ensure_cache_dir(cache_root, "indexes")?;
+        let mirror_base = ensure_cache_dir(cache_root, "mirrors")?;
         let manifest_base = ensure_cache_dir(cache_root, "manifests")?;
         let data_base = ensure_cache_dir(cache_root, "files")?;
 
         // The whole point of this cache is to avoid connecting to the backend
         // if at all possible. So we first see if we have cached the "pull data"
-        // that describe the overall backend contents.
+        // that describe the overall backend contents. If we know the digest
+        // that we want, we don't even need to know what the primary URL
+        // pointed to last time.
 
         let mut backend = None;
 
-        let cached_pull_data =
-            match load_cached_pull_data::<CB>(&digest_path, &resolved_base, &index_base)? {
-                Some(c) => c,
-                None => {
-                    // Some portion of the required cached data is missing. We need to
-                    // do a complete pull and then cache the results.
-
-                    let (new_backend, pull_data) = CB::open_with_pull(start_url, status)?;
-                    backend = Some(new_backend);
-
-                    let digest_text = pull_data.digest.to_string();
-                    file_create_write(&digest_path, |f| writeln!(f, "{}", &digest_text))?;
-                    file_create_write(make_txt_path(&resolved_base, &digest_text), |f| {
-                        f.write_all(pull_data.resolved_url.as_bytes())
-                    })?;
-                    file_create_write(make_txt_path(&index_base, &digest_text), |f| {
-                        f.write_all(pull_data.index.as_bytes())
-                    })?;
-
-                    // Now that we've done that, load_cached_pull_data() really ought to succeed ...
-                    atry!(
-                        load_cached_pull_data::<CB>(&digest_path, &resolved_base, &index_base)?;
-                        ["cache files missing even after they were created"]
-                    )
-                }
-            };
+        let load = || match expected_digest {
+            Some(d) => {
+                load_cached_pull_data_for_digest::<CB>(&d.to_string(), &resolved_base, &index_base)
+            }
+            None => load_cached_pull_data::<CB>(&digest_path, &resolved_base, &index_base),
+        };
+
+        let cached_pull_data = match load()? {
+            Some(c) => c,
+            None => {
+                // Some portion of the required cached data is missing. We need to
+                // do a complete pull and then cache the results.
+
+                let (new_backend, pull_data, mirror) =
+                    pull_from_mirrors::<CB>(mirrors, expected_digest, http, status)?;
+                backend = Some(new_backend);
+
+                let digest_text = pull_data.digest.to_string();
+                file_create_write(&digest_path, |f| writeln!(f, "{}", &digest_text))?;
+                save_pull_data(
+                    &pull_data,
+                    &mirror,
+                    &resolved_base,
+                    &index_base,
+                    &mirror_base,
+                )?;
+
+                // Now that we've done that, load() really ought to succeed ...
+                atry!(
+                    load()?;
+                    ["cache files missing even after they were created"]
+                )
+            }
+        };
 
         // We call this `cached_digest`, but if `backend` is Some, it is a
         // validated, fresh digest.
 
         let cached_digest = cached_pull_data.digest;
 
+        // If we remember which mirror worked last time, try it first next
+        // time.
+
+        let mut mirrors = mirrors.to_vec();
+
+        if let Ok(last_good) =
+            fs::read_to_string(make_txt_path(&mirror_base, &cached_digest.to_string()))
+        {
+            move_to_front(&mut mirrors, &last_good);
+        }
+
         // Now that we have the backend content digest, we know which manifest
         // to use. Read it in, if it exists.
 
@@ -424,17 +532,21 @@ impl<CB: CacheBackend> CachingBundle<CB> {
         // All set.
 
         Ok(CachingBundle {
-            start_url: start_url.to_owned(),
+            mirrors,
+            expected_digest,
             resolved_url: cached_pull_data.resolved_url,
             digest_path,
             cached_digest,
             manifest_path,
             data_base,
             resolved_base,
+            index_base,
+            mirror_base,
             contents,
             only_cached,
             backend,
             index: cached_pull_data.index,
+            http: http.clone(),
         })
     }
 
@@ -494,7 +606,7 @@ impl<CB: CacheBackend> CachingBundle<CB> {
         // fresh pull.
         if let Some(info) = self.index.get(digest::DIGEST_NAME) {
             if let Ok(Some((backend, digest))) =
-                CB::open_with_quick_check(&self.resolved_url, info, status)
+                CB::open_with_quick_check(&self.resolved_url, info, &self.http, status)
             {
                 if self.cached_digest == digest {
                     // We managed to pull some data that match the digest. We
@@ -508,7 +620,9 @@ impl<CB: CacheBackend> CachingBundle<CB> {
 
         // The quick check failed. Try to pull all data to make sure that it
         // wasn't a network error or that the resolved URL hasn't been updated.
-        let (new_backend, pull_data) = CB::open_with_pull(&self.start_url, status)?;
+        // This is also where we fail over to another mirror.
+        let (new_backend, pull_data, mirror) =
+            pull_from_mirrors::<CB>(&self.mirrors, self.expected_digest, &self.http, status)?;
 
         if self.cached_digest != pull_data.digest {
             // Crap! The backend isn't what we thought it was. We may have been
@@ -521,16 +635,20 @@ impl<CB: CacheBackend> CachingBundle<CB> {
             bail!("backend digest changed; rerun tectonic to use updated information");
         }
 
-        if self.resolved_url != pull_data.resolved_url {
-            // The resolved URL has changed, but the digest is the same. So
-            // let's just update the URL and keep going.
-            let resolved_path = make_txt_path(&self.resolved_base, &pull_data.digest.to_string());
-            file_create_write(resolved_path, |f| {
-                f.write_all(pull_data.resolved_url.as_bytes())
-            })?;
-
-            self.resolved_url = pull_data.resolved_url;
-        }
+        // The digest is the same, but the resolved URL or even the mirror that
+        // we're using might have changed. Different mirrors might lay out their
+        // data differently, so we update the index too.
+        save_pull_data(
+            &pull_data,
+            &mirror,
+            &self.resolved_base,
+            &self.index_base,
+            &self.mirror_base,
+        )?;
+
+        self.index = parse_index::<CB>(&pull_data.index);
+        self.resolved_url = pull_data.resolved_url;
+        move_to_front(&mut self.mirrors, &mirror);
 
         // OK, it seems that everything is in order.
         self.backend = Some(new_backend);
@@ -673,16 +791,110 @@ impl<CB: CacheBackend> Bundle for CachingBundle<CB> {
     }
 }
 
-/// Load the cached "pull" data for a backend.
+/// Try to pull data from each of the mirrors of a bundle in turn.
 ///
-/// If any of the files are not found or otherwise have issues, return None.
-fn load_cached_pull_data<CB: CacheBackend>(
-    digest_path: &Path,
+/// If *expected_digest* is specified, mirrors that serve a bundle with a
+/// different digest are skipped. Returns the backend, its pull data, and the
+/// start URL of the mirror that worked.
+fn pull_from_mirrors<CB: CacheBackend>(
+    mirrors: &[String],
+    expected_digest: Option<DigestData>,
+    http: &HttpConfig,
+    status: &mut dyn StatusBackend,
+) -> Result<(CB, BackendPullData, String)> {
+    let mut last_err = None;
+
+    for (i, mirror) in mirrors.iter().enumerate() {
+        let is_last = i + 1 == mirrors.len();
+
+        match CB::open_with_pull(mirror, http, status) {
+            Ok((backend, pull_data)) => match expected_digest {
+                Some(d) if d != pull_data.digest => {
+                    let e = anyhow!(
+                        "bundle at `{}` has digest {} rather than the expected {}",
+                        mirror,
+                        pull_data.digest.to_string(),
+                        d.to_string()
+                    );
+
+                    if !is_last {
+                        tt_warning!(status, "ignoring bundle mirror `{}`", mirror; e);
+                    }
+
+                    last_err = Some(e);
+                }
+
+                _ => return Ok((backend, pull_data, mirror.clone())),
+            },
+
+            Err(e) => {
+                if !is_last {
+                    tt_warning!(status, "failed to connect to bundle mirror `{}`; trying the next one", mirror; e);
+                }
+
+                last_err = Some(e);
+            }
+        }
+    }
+
+    let e = a_ok_or!(
+        last_err;
+        ["at least one URL must be specified for a cached bundle"]
+    );
+
+    if mirrors.len() == 1 {
+        Err(e)
+    } else {
+        Err(e.context(format!(
+            "failed to connect to any of the {} mirrors of the bundle",
+            mirrors.len()
+        )))
+    }
+}
+
+/// Move a mirror to the front of a list of mirrors, if it's in there.
+fn move_to_front(mirrors: &mut Vec<String>, mirror: &str) {
+    if let Some(i) = mirrors.iter().position(|m| m == mirror) {
+        let m = mirrors.remove(i);
+        mirrors.insert(0, m);
+    }
+}
+
+/// Save the data obtained by pulling from a backend, except for the digest
+/// file, into the cache.
+fn save_pull_data(
+    pull_data: &BackendPullData,
+    mirror: &str,
     resolved_base: &Path,
     index_base: &Path,
-) -> Result<Option<CachedPullData<CB::FileInfo>>> {
-    // Convert file-not-found errors into None.
-    return match inner::<CB>(digest_path, resolved_base, index_base) {
+    mirror_base: &Path,
+) -> Result<()> {
+    let digest_text = pull_data.digest.to_string();
+
+    file_create_write(make_txt_path(resolved_base, &digest_text), |f| {
+        f.write_all(pull_data.resolved_url.as_bytes())
+    })?;
+    file_create_write(make_txt_path(index_base, &digest_text), |f| {
+        f.write_all(pull_data.index.as_bytes())
+    })?;
+    file_create_write(make_txt_path(mirror_base, &digest_text), |f| {
+        f.write_all(mirror.as_bytes())
+    })?;
+
+    Ok(())
+}
+
+/// Parse backend indexing data, silently ignoring malformed lines.
+fn parse_index<CB: CacheBackend>(index: &str) -> HashMap<String, CB::FileInfo> {
+    index
+        .lines()
+        .filter_map(|line| CB::parse_index_line(line).ok())
+        .collect()
+}
+
+/// Convert file-not-found errors into None.
+fn not_found_to_none<T>(r: Result<T>) -> Result<Option<T>> {
+    match r {
         Ok(r) => Ok(Some(r)),
         Err(e) => {
             if let Some(ioe) = e.downcast_ref::<IoError>() {
@@ -693,25 +905,53 @@ fn load_cached_pull_data<CB: CacheBackend>(
 
             Err(e)
         }
+    }
+}
+
+/// Load the cached "pull" data for a backend.
+///
+/// The digest of the backend is read from the file at *digest_path*. If any of
+/// the files are not found or otherwise have issues, return None.
+fn load_cached_pull_data<CB: CacheBackend>(
+    digest_path: &Path,
+    resolved_base: &Path,
+    index_base: &Path,
+) -> Result<Option<CachedPullData<CB::FileInfo>>> {
+    let digest_text = match not_found_to_none(read_digest(digest_path))? {
+        Some(d) => d,
+        None => return Ok(None),
     };
 
+    return load_cached_pull_data_for_digest::<CB>(&digest_text, resolved_base, index_base);
+
+    fn read_digest(digest_path: &Path) -> Result<String> {
+        let f = File::open(digest_path)?;
+        let mut digest_text = String::with_capacity(digest::DIGEST_LEN);
+        f.take(digest::DIGEST_LEN as u64)
+            .read_to_string(&mut digest_text)?;
+        Ok(digest_text)
+    }
+}
+
+/// Load the cached "pull" data for a backend with a known digest.
+///
+/// If any of the files are not found or otherwise have issues, return None.
+fn load_cached_pull_data_for_digest<CB: CacheBackend>(
+    digest_text: &str,
+    resolved_base: &Path,
+    index_base: &Path,
+) -> Result<Option<CachedPullData<CB::FileInfo>>> {
+    return not_found_to_none(inner::<CB>(digest_text, resolved_base, index_base));
+
     fn inner<CB: CacheBackend>(
-        digest_path: &Path,
+        digest_text: &str,
         resolved_base: &Path,
         index_base: &Path,
     ) -> Result<CachedPullData<CB::FileInfo>> {
-        let digest_text = {
-            let f = File::open(digest_path)?;
-            let mut digest_text = String::with_capacity(digest::DIGEST_LEN);
-            f.take(digest::DIGEST_LEN as u64)
-                .read_to_string(&mut digest_text)?;
-            digest_text
-        };
-
-        let resolved_path = make_txt_path(resolved_base, &digest_text);
+        let resolved_path = make_txt_path(resolved_base, digest_text);
         let resolved_url = fs::read_to_string(resolved_path)?;
 
-        let index_path = make_txt_path(index_base, &digest_text);
+        let index_path = make_txt_path(index_base, digest_text);
         let index = {
             let f = File::open(index_path)?;
             let mut index = HashMap::new();
@@ -724,7 +964,7 @@ fn load_cached_pull_data<CB: CacheBackend>(
         };
 
         Ok(CachedPullData {
-            digest: DigestData::from_str(&digest_text)?,
+            digest: DigestData::from_str(digest_text)?,
             resolved_url,
             index,
         })
@@ -764,3 +1004,132 @@ fn ensure_cache_dir(root: &Path, path: &str) -> Result<PathBuf> {
 fn make_txt_path(base: &Path, name: &str) -> PathBuf {
     base.join(name).with_extension("txt")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, collections::HashSet};
+    use tectonic_status_base::NoopStatusBackend;
+
+    thread_local! {
+        /// The mirrors that the mock backend should treat as unreachable.
+        static DOWN: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+    }
+
+    fn set_down(mirrors: &[&str]) {
+        DOWN.with(|d| *d.borrow_mut() = mirrors.iter().map(|m| m.to_string()).collect());
+    }
+
+    fn check_up(url: &str) -> Result<()> {
+        DOWN.with(|d| {
+            if d.borrow().iter().any(|m| url.starts_with(m.as_str())) {
+                bail!("mirror `{}` is down", url);
+            }
+            Ok(())
+        })
+    }
+
+    /// A backend whose "index" maps file names directly to their contents.
+    /// Every mirror serves the same bundle.
+    #[derive(Debug)]
+    struct MockBackend;
+
+    const INDEX: &str = "SHA256SUM -\nhello.txt hello\nworld.txt world\n";
+
+    impl CacheBackend for MockBackend {
+        type FileInfo = String;
+
+        fn open_with_pull(
+            start_url: &str,
+            _http: &HttpConfig,
+            _status: &mut dyn StatusBackend,
+        ) -> Result<(Self, BackendPullData)> {
+            check_up(start_url)?;
+            let pull_data = BackendPullData {
+                resolved_url: format!("{start_url}/resolved"),
+                digest: DigestData::of_nothing(),
+                index: INDEX.to_owned(),
+            };
+            Ok((MockBackend, pull_data))
+        }
+
+        fn open_with_quick_check(
+            resolved_url: &str,
+            _digest_file_info: &Self::FileInfo,
+            _http: &HttpConfig,
+            _status: &mut dyn StatusBackend,
+        ) -> Result<Option<(Self, DigestData)>> {
+            check_up(resolved_url)?;
+            Ok(Some((MockBackend, DigestData::of_nothing())))
+        }
+
+        fn parse_index_line(line: &str) -> Result<(String, Self::FileInfo)> {
+            let (name, content) = a_ok_or!(line.split_once(' '); ["bad index line"]);
+            Ok((name.to_owned(), content.to_owned()))
+        }
+
+        fn get_file(
+            &mut self,
+            _name: &str,
+            info: &Self::FileInfo,
+            _status: &mut dyn StatusBackend,
+        ) -> Result<Vec<u8>> {
+            Ok(info.as_bytes().to_vec())
+        }
+    }
+
+    fn read(bundle: &mut CachingBundle<MockBackend>, name: &str) -> String {
+        let mut status = NoopStatusBackend::default();
+        let mut text = String::new();
+        bundle
+            .input_open_name(name, &mut status)
+            .must_exist()
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn mirror_failover() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = Cache::get_for_custom_directory(dir.path());
+        let mut status = NoopStatusBackend::default();
+        let mirrors = ["mock://a".to_owned(), "mock://b".to_owned()];
+
+        // The first mirror is down, so the bundle is pulled from the second,
+        // which should be tried first from then on.
+        set_down(&["mock://a"]);
+        let mut bundle = cache
+            .open_mirrored::<MockBackend>(&mirrors, None, false, &mut status)
+            .unwrap();
+        assert_eq!(read(&mut bundle, "hello.txt"), "hello");
+
+        let mut bundle = cache
+            .open_mirrored::<MockBackend>(&mirrors, None, false, &mut status)
+            .unwrap();
+        assert_eq!(bundle.mirrors, ["mock://b", "mock://a"]);
+
+        // Now the second mirror goes down. The cached file is still
+        // available, and fetching a new one fails back over to the first
+        // mirror.
+        set_down(&["mock://b"]);
+        assert_eq!(read(&mut bundle, "hello.txt"), "hello");
+        assert_eq!(read(&mut bundle, "world.txt"), "world");
+        assert_eq!(bundle.mirrors, ["mock://a", "mock://b"]);
+        assert_eq!(bundle.resolved_url, "mock://a/resolved");
+
+        let bundle = cache
+            .open_mirrored::<MockBackend>(&mirrors, None, false, &mut status)
+            .unwrap();
+        assert_eq!(bundle.mirrors, ["mock://a", "mock://b"]);
+
+        // If every mirror is down, the error says so.
+        set_down(&["mock://a", "mock://b"]);
+        let dir = tempfile::tempdir().unwrap();
+        let err = Cache::get_for_custom_directory(dir.path())
+            .open_mirrored::<MockBackend>(&mirrors, None, false, &mut status)
+            .unwrap_err();
+        assert!(err.to_string().contains("any of the 2 mirrors"));
+    }
+}
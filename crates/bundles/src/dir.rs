@@ -6,9 +6,14 @@
 use std::{
     fs,
     path::{Path, PathBuf},
+    time::UNIX_EPOCH,
 };
 use tectonic_errors::prelude::*;
-use tectonic_io_base::{filesystem::FilesystemIo, InputHandle, IoProvider, OpenResult};
+use tectonic_io_base::{
+    digest::{self, Digest, DigestData},
+    filesystem::FilesystemIo,
+    InputHandle, IoProvider, OpenResult,
+};
 use tectonic_status_base::StatusBackend;
 
 use super::Bundle;
@@ -17,10 +22,15 @@ use super::Bundle;
 ///
 /// This implementation essentially just wraps
 /// [`tectonic_io_base::filesystem::FilesystemIo`], ensuring that it is
-/// read-only, self-contained, and implements the [`Bundle`] trait. The
-/// directory should contain a file named `SHA256SUM` if the bundle fingerprint
-/// will be needed.
-pub struct DirBundle(FilesystemIo);
+/// read-only, self-contained, and implements the [`Bundle`] trait. If the
+/// directory contains a file named `SHA256SUM`, it is used as the bundle
+/// fingerprint. Otherwise, the fingerprint is computed from the names, sizes,
+/// and modification times of the files in the directory, so that it can be
+/// recomputed cheaply whenever it's needed and changes to the files are
+/// noticed without reading all of them.
+pub struct DirBundle {
+    io: FilesystemIo,
+}
 
 impl DirBundle {
     /// Create a new directory bundle.
@@ -28,12 +38,48 @@ impl DirBundle {
     /// No validation of the input path is performed, which is why this function
     /// is infallible.
     pub fn new<P: AsRef<Path>>(dir: P) -> DirBundle {
-        DirBundle(FilesystemIo::new(
-            dir.as_ref(),
-            false,              // no writes
-            false,              // no absolute paths
-            Default::default(), // no hidden files
-        ))
+        DirBundle {
+            io: FilesystemIo::new(
+                dir.as_ref(),
+                false,              // no writes
+                false,              // no absolute paths
+                Default::default(), // no hidden files
+            ),
+        }
+    }
+
+    fn compute_digest(&mut self, status: &mut dyn StatusBackend) -> Result<DigestData> {
+        let root = self.io.root().to_owned();
+
+        if root.join(digest::DIGEST_NAME).is_file() {
+            let mut text = atry!(
+                fs::read_to_string(root.join(digest::DIGEST_NAME));
+                ["failed to read the digest file of bundle directory `{}`", root.display()]
+            );
+            text.truncate(digest::DIGEST_LEN);
+            return Ok(atry!(text.parse(); ["corrupted SHA256 digest data"]));
+        }
+
+        let mut names = self.all_files(status)?;
+        names.sort();
+
+        let mut dc = digest::create();
+
+        for name in names {
+            let md = atry!(
+                fs::metadata(root.join(&name));
+                ["failed to inspect bundle file `{}`", name]
+            );
+            let mtime = md
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos())
+                .unwrap_or(0);
+            dc.update(format!("{name}\0{}\0{mtime}\n", md.len()).as_bytes());
+        }
+
+        Ok(DigestData::from(dc))
     }
 }
 
@@ -43,7 +89,7 @@ impl IoProvider for DirBundle {
         name: &str,
         status: &mut dyn StatusBackend,
     ) -> OpenResult<InputHandle> {
-        self.0.input_open_name(name, status)
+        self.io.input_open_name(name, status)
     }
 
     fn input_open_name_with_abspath(
@@ -51,16 +97,20 @@ impl IoProvider for DirBundle {
         name: &str,
         status: &mut dyn StatusBackend,
     ) -> OpenResult<(InputHandle, Option<PathBuf>)> {
-        self.0.input_open_name_with_abspath(name, status)
+        self.io.input_open_name_with_abspath(name, status)
     }
 }
 
 impl Bundle for DirBundle {
+    fn get_digest(&mut self, status: &mut dyn StatusBackend) -> Result<DigestData> {
+        self.compute_digest(status)
+    }
+
     fn all_files(&mut self, _status: &mut dyn StatusBackend) -> Result<Vec<String>> {
         let mut files = Vec::new();
 
         // We intentionally do not explore the directory recursively.
-        for entry in fs::read_dir(self.0.root())? {
+        for entry in fs::read_dir(self.io.root())? {
             let entry = entry?;
 
             // This catches both regular files and symlinks:`
@@ -74,3 +124,44 @@ impl Bundle for DirBundle {
         Ok(files)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tectonic_status_base::NoopStatusBackend;
+
+    #[test]
+    fn computed_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut status = NoopStatusBackend::default();
+        fs::write(dir.path().join("a.tex"), "a").unwrap();
+        fs::write(dir.path().join("b.tex"), "b").unwrap();
+
+        let mut bundle = DirBundle::new(dir.path());
+        let d1 = bundle.get_digest(&mut status).unwrap();
+        assert_eq!(bundle.get_digest(&mut status).unwrap(), d1);
+        assert_eq!(
+            DirBundle::new(dir.path()).get_digest(&mut status).unwrap(),
+            d1
+        );
+
+        // Changes to the directory are noticed, even by an existing bundle.
+        fs::write(dir.path().join("b.tex"), "changed").unwrap();
+        let d2 = bundle.get_digest(&mut status).unwrap();
+        assert_ne!(d2, d1);
+
+        fs::write(dir.path().join("c.tex"), "c").unwrap();
+        assert_ne!(bundle.get_digest(&mut status).unwrap(), d2);
+    }
+
+    #[test]
+    fn digest_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut status = NoopStatusBackend::default();
+        let text = "0123456789abcdef".repeat(4);
+        fs::write(dir.path().join(digest::DIGEST_NAME), format!("{}\n", text)).unwrap();
+
+        let d = DirBundle::new(dir.path()).get_digest(&mut status).unwrap();
+        assert_eq!(d.to_string(), text);
+    }
+}
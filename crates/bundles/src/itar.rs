@@ -17,14 +17,12 @@
 use flate2::read::GzDecoder;
 use std::{convert::TryInto, io::Read, str::FromStr};
 use tectonic_errors::prelude::*;
-use tectonic_geturl::{DefaultBackend, DefaultRangeReader, GetUrlBackend, RangeReader};
+use tectonic_geturl::{DefaultBackend, DefaultRangeReader, GetUrlBackend, HttpConfig, RangeReader};
 use tectonic_io_base::digest::{self, DigestData};
-use tectonic_status_base::{tt_note, tt_warning, StatusBackend};
+use tectonic_status_base::{tt_note, StatusBackend};
 
 use crate::cache::{BackendPullData, CacheBackend};
 
-const MAX_HTTP_ATTEMPTS: usize = 4;
-
 /// The internal file-information struct used by the [`IndexedTarBackend`].
 #[derive(Clone, Copy, Debug)]
 pub struct FileInfo {
@@ -47,10 +45,12 @@ impl CacheBackend for IndexedTarBackend {
 
     fn open_with_pull(
         start_url: &str,
+        http: &HttpConfig,
         status: &mut dyn StatusBackend,
     ) -> Result<(Self, BackendPullData)> {
         // Step 1: resolve URL
         let mut geturl_backend = DefaultBackend::default();
+        geturl_backend.configure(http)?;
         let resolved_url = geturl_backend.resolve_url(start_url, status)?;
 
         // Step 2: fetch index
@@ -106,10 +106,14 @@ impl CacheBackend for IndexedTarBackend {
     fn open_with_quick_check(
         resolved_url: &str,
         digest_file_info: &Self::FileInfo,
+        http: &HttpConfig,
         status: &mut dyn StatusBackend,
     ) -> Result<Option<(Self, DigestData)>> {
+        let mut geturl_backend = DefaultBackend::default();
+        geturl_backend.configure(http)?;
+
         let mut cache_backend = IndexedTarBackend {
-            reader: DefaultBackend::default().open_range_reader(resolved_url),
+            reader: geturl_backend.open_range_reader(resolved_url),
         };
 
         if let Ok(d) = cache_backend.get_file(digest::DIGEST_NAME, digest_file_info, status) {
@@ -150,49 +154,28 @@ impl CacheBackend for IndexedTarBackend {
 
         // Historically, sometimes our web service would drop connections when
         // fetching a bunch of resource files (i.e., on the first invocation).
-        // The error manifested itself in a way that has a not-so-nice user
-        // experience. Our solution: retry the request a few times in case it
-        // was a transient problem.
+        // The geturl backend takes care of retrying failed requests and
+        // resuming interrupted ones; if it still fails, the problem is most
+        // likely on the user's end.
 
         let n = info.length.try_into().unwrap();
         let mut buf = Vec::with_capacity(n);
-        let mut overall_failed = true;
-        let mut any_failed = false;
 
         // Our HTTP implementation actually has problems with zero-sized ranged
         // reads (Azure gives us a 200 response, which we don't properly
         // handle), but when the file is 0-sized we're all set anyway!
         if n > 0 {
-            for _ in 0..MAX_HTTP_ATTEMPTS {
-                let mut stream = match self.reader.read_range(info.offset, n) {
-                    Ok(r) => r,
-                    Err(e) => {
-                        tt_warning!(status, "failure requesting \"{}\" from network", name; e);
-                        any_failed = true;
-                        continue;
-                    }
-                };
-
-                if let Err(e) = stream.read_to_end(&mut buf) {
-                    tt_warning!(status, "failure downloading \"{}\" from network", name; e.into());
-                    any_failed = true;
-                    continue;
-                }
-
-                overall_failed = false;
-                break;
-            }
+            let mut stream = atry!(
+                self.reader.read_range(info.offset, n);
+                ["failed to retrieve \"{}\" from the network", name]
+                (note "this most probably is not Tectonic's fault -- please check your network connection")
+            );
 
-            if overall_failed {
-                bail!(
-                    "failed to retrieve \"{}\" from the network; \
-                this most probably is not Tectonic's fault \
-                -- please check your network connection.",
-                    name
-                );
-            } else if any_failed {
-                tt_note!(status, "download succeeded after retry");
-            }
+            atry!(
+                stream.read_to_end(&mut buf);
+                ["failed to retrieve \"{}\" from the network", name]
+                (note "this most probably is not Tectonic's fault -- please check your network connection")
+            );
         }
 
         Ok(buf)
@@ -0,0 +1,259 @@
+// Copyright 2024 the Tectonic Project
+// Licensed under the MIT License.
+
+//! A module for the stacked bundle [`LayeredBundle`].
+
+use std::{io::Cursor, path::PathBuf};
+use tectonic_errors::prelude::*;
+use tectonic_io_base::{
+    digest::{self, Digest, DigestData},
+    InputHandle, InputOrigin, IoProvider, OpenResult,
+};
+use tectonic_status_base::StatusBackend;
+
+use super::Bundle;
+
+/// A bundle made by stacking several other bundles on top of one another.
+///
+/// When a file is requested, the layers are consulted in order and the first
+/// one that provides the file wins. This makes it possible to override a few
+/// files of a large standard bundle with local versions — say, an
+/// organization’s document classes on top of the default network bundle —
+/// without forking it.
+///
+/// The digest of the stack combines the digests of all of its layers, in
+/// order, so that anything that depends on the bundle contents (like cached
+/// format files) notices if any layer changes. As a special case, a stack with
+/// only one layer has the same digest as that layer.
+pub struct LayeredBundle {
+    layers: Vec<Box<dyn Bundle>>,
+    digest: Option<DigestData>,
+}
+
+impl LayeredBundle {
+    /// Create a new stack of bundles.
+    ///
+    /// The first layer has the highest precedence. The list must not be empty.
+    pub fn new(layers: Vec<Box<dyn Bundle>>) -> Result<LayeredBundle> {
+        ensure!(
+            !layers.is_empty(),
+            "a layered bundle must have at least one layer"
+        );
+
+        Ok(LayeredBundle {
+            layers,
+            digest: None,
+        })
+    }
+
+    fn combined_digest(&mut self, status: &mut dyn StatusBackend) -> Result<DigestData> {
+        if let Some(d) = self.digest {
+            return Ok(d);
+        }
+
+        let d = if self.layers.len() == 1 {
+            self.layers[0].get_digest(status)?
+        } else {
+            let mut dc = digest::create();
+            dc.update(b"layered\0");
+
+            for (i, layer) in self.layers.iter_mut().enumerate() {
+                let d = atry!(
+                    layer.get_digest(status);
+                    ["failed to get the digest of bundle layer #{}", i + 1]
+                );
+                dc.update(d.to_string().as_bytes());
+                dc.update(b"\n");
+            }
+
+            DigestData::from(dc)
+        };
+
+        self.digest = Some(d);
+        Ok(d)
+    }
+}
+
+impl IoProvider for LayeredBundle {
+    fn input_open_name(
+        &mut self,
+        name: &str,
+        status: &mut dyn StatusBackend,
+    ) -> OpenResult<InputHandle> {
+        match self.input_open_name_with_abspath(name, status) {
+            OpenResult::Ok((h, _path)) => OpenResult::Ok(h),
+            OpenResult::Err(e) => OpenResult::Err(e),
+            OpenResult::NotAvailable => OpenResult::NotAvailable,
+        }
+    }
+
+    fn input_open_name_with_abspath(
+        &mut self,
+        name: &str,
+        status: &mut dyn StatusBackend,
+    ) -> OpenResult<(InputHandle, Option<PathBuf>)> {
+        // The digest file of any one layer doesn't describe the whole stack.
+        if name == digest::DIGEST_NAME {
+            return match self.combined_digest(status) {
+                Ok(d) => OpenResult::Ok((
+                    InputHandle::new_read_only(
+                        name,
+                        Cursor::new(d.to_string().into_bytes()),
+                        InputOrigin::Other,
+                    ),
+                    None,
+                )),
+                Err(e) => OpenResult::Err(e),
+            };
+        }
+
+        for layer in &mut self.layers {
+            match layer.input_open_name_with_abspath(name, status) {
+                OpenResult::NotAvailable => continue,
+                r => return r,
+            }
+        }
+
+        OpenResult::NotAvailable
+    }
+}
+
+impl Bundle for LayeredBundle {
+    fn get_digest(&mut self, status: &mut dyn StatusBackend) -> Result<DigestData> {
+        self.combined_digest(status)
+    }
+
+    fn all_files(&mut self, status: &mut dyn StatusBackend) -> Result<Vec<String>> {
+        let mut files = Vec::new();
+
+        for layer in &mut self.layers {
+            files.extend(layer.all_files(status)?);
+        }
+
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashMap, io::Read};
+    use tectonic_status_base::NoopStatusBackend;
+
+    struct MemoryBundle {
+        files: HashMap<&'static str, &'static str>,
+        digest: DigestData,
+    }
+
+    /// Create a bundle of in-memory files, with a made-up digest.
+    fn memory_bundle(files: &[(&'static str, &'static str)], digest_byte: u8) -> Box<dyn Bundle> {
+        let digest = [digest_byte; 32]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        Box::new(MemoryBundle {
+            files: files.iter().copied().collect(),
+            digest: digest.parse().unwrap(),
+        })
+    }
+
+    impl IoProvider for MemoryBundle {
+        fn input_open_name(
+            &mut self,
+            name: &str,
+            _status: &mut dyn StatusBackend,
+        ) -> OpenResult<InputHandle> {
+            match self.files.get(name) {
+                Some(text) => OpenResult::Ok(InputHandle::new_read_only(
+                    name,
+                    Cursor::new(text.as_bytes().to_vec()),
+                    InputOrigin::Other,
+                )),
+                None => OpenResult::NotAvailable,
+            }
+        }
+    }
+
+    impl Bundle for MemoryBundle {
+        fn get_digest(&mut self, _status: &mut dyn StatusBackend) -> Result<DigestData> {
+            Ok(self.digest)
+        }
+
+        fn all_files(&mut self, _status: &mut dyn StatusBackend) -> Result<Vec<String>> {
+            Ok(self.files.keys().map(|k| k.to_string()).collect())
+        }
+    }
+
+    fn read(bundle: &mut LayeredBundle, name: &str) -> Option<String> {
+        let mut status = NoopStatusBackend::default();
+
+        match bundle.input_open_name(name, &mut status) {
+            OpenResult::Ok(mut h) => {
+                let mut text = String::new();
+                h.read_to_string(&mut text).unwrap();
+                Some(text)
+            }
+            OpenResult::NotAvailable => None,
+            OpenResult::Err(e) => panic!("failed to open `{}`: {}", name, e),
+        }
+    }
+
+    #[test]
+    fn lookup_order() {
+        let mut bundle = LayeredBundle::new(vec![
+            memory_bundle(&[("a.tex", "top a"), ("b.tex", "top b")], 1),
+            memory_bundle(&[("b.tex", "middle b"), ("c.tex", "middle c")], 2),
+            memory_bundle(
+                &[
+                    ("a.tex", "base a"),
+                    ("c.tex", "base c"),
+                    ("d.tex", "base d"),
+                ],
+                3,
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(read(&mut bundle, "a.tex").as_deref(), Some("top a"));
+        assert_eq!(read(&mut bundle, "b.tex").as_deref(), Some("top b"));
+        assert_eq!(read(&mut bundle, "c.tex").as_deref(), Some("middle c"));
+        assert_eq!(read(&mut bundle, "d.tex").as_deref(), Some("base d"));
+        assert_eq!(read(&mut bundle, "e.tex"), None);
+
+        let mut status = NoopStatusBackend::default();
+        assert_eq!(
+            bundle.all_files(&mut status).unwrap(),
+            ["a.tex", "b.tex", "c.tex", "d.tex"]
+        );
+    }
+
+    #[test]
+    fn digests() {
+        let mut status = NoopStatusBackend::default();
+
+        let mut single = LayeredBundle::new(vec![memory_bundle(&[], 1)]).unwrap();
+        let d1 = memory_bundle(&[], 1).get_digest(&mut status).unwrap();
+        assert_eq!(single.get_digest(&mut status).unwrap(), d1);
+
+        let mut stack = LayeredBundle::new(vec![
+            memory_bundle(&[(digest::DIGEST_NAME, "layer digest")], 1),
+            memory_bundle(&[], 2),
+        ])
+        .unwrap();
+        let combined = stack.get_digest(&mut status).unwrap();
+        assert_ne!(combined, d1);
+        assert_eq!(
+            read(&mut stack, digest::DIGEST_NAME),
+            Some(combined.to_string())
+        );
+
+        let mut reversed =
+            LayeredBundle::new(vec![memory_bundle(&[], 2), memory_bundle(&[], 1)]).unwrap();
+        assert_ne!(reversed.get_digest(&mut status).unwrap(), combined);
+
+        assert!(LayeredBundle::new(Vec::new()).is_err());
+    }
+}
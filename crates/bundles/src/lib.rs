@@ -15,6 +15,10 @@
 //!   filesystem caching.
 //! - [`dir::DirBundle`] turns a directory full of files into a bundle; it is
 //!   useful for testing and lightweight usage.
+//! - [`layered::LayeredBundle`] stacks several bundles on top of one
+//!   another, so that local files can override those of a standard bundle.
+//! - [`texlive::TexLiveBundle`] uses the files of an existing TeX Live
+//!   installation.
 //! - [`zip::ZipBundle`] for a ZIP-format bundle.
 
 use std::{io::Read, str::FromStr};
@@ -25,6 +29,8 @@ use tectonic_status_base::StatusBackend;
 pub mod cache;
 pub mod dir;
 pub mod itar;
+pub mod layered;
+pub mod texlive;
 pub mod zip;
 
 /// A trait for bundles of Tectonic support files.
@@ -0,0 +1,624 @@
+// Copyright 2024 the Tectonic Project
+// Licensed under the MIT License.
+
+//! A module for the TeX Live bundle [`TexLiveBundle`].
+//!
+//! This lets Tectonic use the support files of an existing TeX Live
+//! installation directly, rather than downloading its own copies of them.
+//! File lookup is modeled on that of [kpathsea]: a bare file name is looked up
+//! in a database of the whole tree, and if it occurs in several places, the
+//! places in which a XeTeX-based engine would search first win.
+//!
+//! [kpathsea]: https://tug.org/kpathsea/
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, Cursor},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use tectonic_errors::prelude::*;
+use tectonic_io_base::{
+    digest::{self, Digest, DigestData},
+    InputHandle, InputOrigin, IoProvider, OpenResult,
+};
+use tectonic_status_base::StatusBackend;
+
+use super::Bundle;
+
+/// The name of the kpathsea filename database.
+const LS_R_NAME: &str = "ls-R";
+
+/// Files that Tectonic needs but that a TeX Live tree won't provide.
+///
+/// Tectonic generates its format files by processing `tectonic-format-*.tex`.
+/// If the tree doesn't contain these, we fall back to the standard TeX Live
+/// initialization files for XeTeX.
+const SYNTHESIZED_FILES: &[(&str, &str)] = &[
+    ("tectonic-format-latex.tex", "\\input xelatex.ini\n"),
+    ("tectonic-format-plain.tex", "\\input xetex.ini\n"),
+];
+
+/// Subtrees that are searched first, in order, when a name occurs in more than
+/// one place. This roughly mirrors the `TEXINPUTS.xelatex` and
+/// `TEXINPUTS.xetex` paths of the standard `texmf.cnf`.
+const SEARCH_ORDER: &[&str] = &[
+    "tex/xelatex/",
+    "tex/latex/",
+    "tex/xetex/",
+    "tex/plain/",
+    "tex/generic/",
+    "tex/",
+];
+
+/// Subtrees that kpathsea never searches for inputs, and neither do we.
+const IGNORED_SUBTREES: &[&str] = &["doc", "source"];
+
+/// A bundle backed by a TeX Live `texmf` tree.
+///
+/// The tree is indexed using its `ls-R` database if it has one, or by scanning
+/// the directory otherwise. Files are then found by their bare names, no
+/// matter where they live in the TDS hierarchy. A name containing slashes,
+/// like `base/article.cls`, matches any file whose path ends with those
+/// components.
+///
+/// TeX Live trees don’t come with a Tectonic-style `SHA256SUM` file, so this
+/// bundle synthesizes one. Hashing every file in a full TeX Live installation
+/// would be prohibitively slow, so the digest is computed from the file
+/// listing instead: the contents and modification time of `ls-R` if the tree
+/// has one, or the names, sizes, and modification times of all of the files if
+/// it doesn’t. Updating the tree with `tlmgr` rebuilds `ls-R`, so this is good
+/// enough to keep cached format files valid.
+pub struct TexLiveBundle {
+    root: PathBuf,
+
+    /// Maps bare file names to the directories containing them, relative to
+    /// `root` and sorted in search order.
+    index: HashMap<String, Vec<String>>,
+
+    digest: DigestData,
+}
+
+impl TexLiveBundle {
+    /// Open a TeX Live tree as a bundle.
+    ///
+    /// The path may either be a `texmf` tree itself, such as
+    /// `/usr/share/texlive/texmf-dist`, or the root of a TeX Live installation
+    /// containing a `texmf-dist` subdirectory. If the tree has an `ls-R`
+    /// database, it is used; otherwise the tree is scanned.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<TexLiveBundle> {
+        let root = tree_root(path.as_ref());
+        let ls_r = root.join(LS_R_NAME);
+
+        if ls_r.is_file() {
+            let data = atry!(
+                fs::read(&ls_r);
+                ["failed to read the TeX Live file database `{}`", ls_r.display()]
+            );
+
+            let mut dc = digest::create();
+            dc.update(b"ls-R\0");
+            dc.update(&data);
+            dc.update(mtime_secs(&ls_r).to_le_bytes());
+
+            let mut index = HashMap::new();
+            parse_ls_r(&String::from_utf8_lossy(&data), &root, &mut index);
+            Ok(Self::finish(root, index, dc))
+        } else {
+            Self::scan(root)
+        }
+    }
+
+    /// Open a TeX Live tree as a bundle by scanning its contents.
+    ///
+    /// Unlike [`Self::open`], this ignores any `ls-R` database, which may be
+    /// useful if the database is out of date.
+    pub fn scan<P: AsRef<Path>>(path: P) -> Result<TexLiveBundle> {
+        let root = tree_root(path.as_ref());
+        let mut listing = Vec::new();
+        atry!(
+            scan_dir(&root, "", &mut listing);
+            ["failed to scan the TeX Live tree `{}`", root.display()]
+        );
+        listing.sort();
+
+        let mut dc = digest::create();
+        dc.update(b"scan\0");
+        let mut index = HashMap::new();
+
+        for (dir, name, size, mtime) in listing {
+            dc.update(format!("{dir}/{name}\0{size}\0{mtime}\n").as_bytes());
+            index.entry(name).or_insert_with(Vec::new).push(dir);
+        }
+
+        Ok(Self::finish(root, index, dc))
+    }
+
+    fn finish(
+        root: PathBuf,
+        mut index: HashMap<String, Vec<String>>,
+        mut dc: digest::DigestComputer,
+    ) -> TexLiveBundle {
+        for dirs in index.values_mut() {
+            dirs.sort_by(|a, b| (search_rank(a), a).cmp(&(search_rank(b), b)));
+            dirs.dedup();
+        }
+
+        for (name, contents) in SYNTHESIZED_FILES {
+            if !index.contains_key(*name) {
+                dc.update(name.as_bytes());
+                dc.update(b"\0");
+                dc.update(contents.as_bytes());
+            }
+        }
+
+        TexLiveBundle {
+            root,
+            index,
+            digest: DigestData::from(dc),
+        }
+    }
+
+    /// Get the root of the `texmf` tree backing this bundle.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Find the path of the file that a name resolves to, if any.
+    pub fn lookup(&self, name: &str) -> Option<PathBuf> {
+        if name.starts_with('/') || name.split('/').any(|c| c == "..") {
+            return None;
+        }
+
+        let (subdir, base) = match name.rsplit_once('/') {
+            Some((d, b)) => (Some(d), b),
+            None => (None, name),
+        };
+
+        for dir in self.index.get(base)? {
+            if let Some(s) = subdir {
+                if dir != s && !dir.ends_with(&format!("/{s}")) {
+                    continue;
+                }
+            }
+
+            let mut path = self.root.clone();
+
+            if !dir.is_empty() {
+                path.push(dir);
+            }
+
+            path.push(base);
+
+            // The database lists directories as well as files, and may be
+            // stale, so we need to check what's actually there.
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+
+        None
+    }
+}
+
+/// Check whether a directory looks like a TeX Live tree that should be opened
+/// with [`TexLiveBundle`], rather than as a flat [`crate::dir::DirBundle`].
+pub fn is_texlive_tree<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    path.join(LS_R_NAME).is_file() || path.join("texmf-dist").is_dir()
+}
+
+fn tree_root(path: &Path) -> PathBuf {
+    let dist = path.join("texmf-dist");
+
+    if !path.join(LS_R_NAME).is_file() && dist.is_dir() {
+        dist
+    } else {
+        path.to_owned()
+    }
+}
+
+fn mtime_secs(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|md| md.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn is_ignored_dir(dir: &str) -> bool {
+    let top = dir.split('/').next().unwrap_or("");
+    IGNORED_SUBTREES.contains(&top)
+}
+
+fn search_rank(dir: &str) -> usize {
+    let dir = format!("{dir}/");
+    SEARCH_ORDER
+        .iter()
+        .position(|prefix| dir.starts_with(prefix))
+        .unwrap_or(SEARCH_ORDER.len())
+}
+
+/// Parse the contents of an `ls-R` file into `index`.
+///
+/// The format consists of blank-line-separated blocks. Each block starts with
+/// a directory name followed by a colon, and then lists the entries of that
+/// directory one per line. Directory names are either relative to the
+/// location of the `ls-R` file, starting with `./`, or absolute.
+fn parse_ls_r(text: &str, root: &Path, index: &mut HashMap<String, Vec<String>>) {
+    let root_prefix = format!("{}/", root.display());
+    let mut cur_dir: Option<String> = None;
+
+    for line in text.lines() {
+        if line.is_empty() || line.starts_with('%') {
+            continue;
+        }
+
+        if let Some(dir) = line.strip_suffix(':') {
+            let rel = if dir == "." || dir == "./" {
+                Some("")
+            } else if let Some(rel) = dir.strip_prefix("./") {
+                Some(rel)
+            } else if dir.starts_with('/') {
+                dir.strip_prefix(&root_prefix)
+            } else {
+                None
+            };
+
+            // If a directory isn't one that we understand, skip its entries.
+            cur_dir = rel
+                .map(|r| r.trim_end_matches('/').to_owned())
+                .filter(|r| !is_ignored_dir(r));
+            continue;
+        }
+
+        if let Some(dir) = cur_dir.as_ref() {
+            if !line.starts_with('.') {
+                index.entry(line.to_owned()).or_default().push(dir.clone());
+            }
+        }
+    }
+}
+
+/// Recursively list the files in a directory, recording them as
+/// `(dir, name, size, mtime)` tuples where `dir` is relative to the scan root.
+fn scan_dir(path: &Path, rel: &str, listing: &mut Vec<(String, String, u64, u64)>) -> Result<()> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+
+        let name = match entry.file_name().to_str() {
+            Some(s) if !s.starts_with('.') => s.to_owned(),
+            _ => continue,
+        };
+
+        let md = entry.metadata()?;
+
+        if md.is_dir() {
+            let sub = if rel.is_empty() {
+                name
+            } else {
+                format!("{rel}/{name}")
+            };
+
+            if !is_ignored_dir(&sub) {
+                scan_dir(&entry.path(), &sub, listing)?;
+            }
+        } else {
+            let mtime = md
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            listing.push((rel.to_owned(), name, md.len(), mtime));
+        }
+    }
+
+    Ok(())
+}
+
+impl IoProvider for TexLiveBundle {
+    fn input_open_name(
+        &mut self,
+        name: &str,
+        status: &mut dyn StatusBackend,
+    ) -> OpenResult<InputHandle> {
+        match self.input_open_name_with_abspath(name, status) {
+            OpenResult::Ok((h, _path)) => OpenResult::Ok(h),
+            OpenResult::Err(e) => OpenResult::Err(e),
+            OpenResult::NotAvailable => OpenResult::NotAvailable,
+        }
+    }
+
+    fn input_open_name_with_abspath(
+        &mut self,
+        name: &str,
+        _status: &mut dyn StatusBackend,
+    ) -> OpenResult<(InputHandle, Option<PathBuf>)> {
+        if name == digest::DIGEST_NAME {
+            let text = self.digest.to_string().into_bytes();
+            return OpenResult::Ok((
+                InputHandle::new_read_only(name, Cursor::new(text), InputOrigin::Other),
+                None,
+            ));
+        }
+
+        if let Some(path) = self.lookup(name) {
+            return match File::open(&path) {
+                Ok(f) => OpenResult::Ok((
+                    InputHandle::new_read_only(name, BufReader::new(f), InputOrigin::Other),
+                    Some(path),
+                )),
+                Err(e) => OpenResult::Err(e.into()),
+            };
+        }
+
+        for (synth_name, contents) in SYNTHESIZED_FILES {
+            if name == *synth_name {
+                let data = contents.as_bytes().to_vec();
+                return OpenResult::Ok((
+                    InputHandle::new_read_only(name, Cursor::new(data), InputOrigin::Other),
+                    None,
+                ));
+            }
+        }
+
+        OpenResult::NotAvailable
+    }
+}
+
+impl Bundle for TexLiveBundle {
+    fn get_digest(&mut self, _status: &mut dyn StatusBackend) -> Result<DigestData> {
+        Ok(self.digest)
+    }
+
+    fn all_files(&mut self, _status: &mut dyn StatusBackend) -> Result<Vec<String>> {
+        let mut files: Vec<String> = self.index.keys().cloned().collect();
+
+        for (name, _) in SYNTHESIZED_FILES {
+            if !self.index.contains_key(*name) {
+                files.push((*name).to_owned());
+            }
+        }
+
+        files.push(digest::DIGEST_NAME.to_owned());
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use tectonic_status_base::NoopStatusBackend;
+
+    /// Files in a miniature TeX Live installation, relative to its root.
+    const FIXTURE: &[(&str, &str)] = &[
+        ("texmf-dist/tex/latex/base/article.cls", "latex article"),
+        ("texmf-dist/tex/latex/base/latex.ltx", "latex kernel"),
+        (
+            "texmf-dist/tex/xelatex/xetexref/article.cls",
+            "xelatex article",
+        ),
+        ("texmf-dist/tex/generic/hyphen/hyphen.tex", "patterns"),
+        ("texmf-dist/doc/latex/base/manual.tex", "documentation"),
+    ];
+
+    fn make_tree(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+
+        for (path, contents) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        dir
+    }
+
+    fn read(bundle: &mut TexLiveBundle, name: &str) -> Option<String> {
+        let mut status = NoopStatusBackend::default();
+        let mut text = String::new();
+        bundle
+            .input_open_name(name, &mut status)
+            .must_exist()
+            .ok()?
+            .read_to_string(&mut text)
+            .unwrap();
+        Some(text)
+    }
+
+    fn rel(bundle: &TexLiveBundle, name: &str) -> Option<String> {
+        let path = bundle.lookup(name)?;
+        let rel = path.strip_prefix(bundle.root()).unwrap();
+        Some(rel.to_str().unwrap().replace('\\', "/"))
+    }
+
+    #[test]
+    fn scanned_lookup() {
+        let tree = make_tree(FIXTURE);
+        assert!(is_texlive_tree(tree.path()));
+
+        let mut bundle = TexLiveBundle::open(tree.path()).unwrap();
+        assert_eq!(bundle.root(), tree.path().join("texmf-dist"));
+
+        // The XeLaTeX-specific file wins, unless the caller asks for the other
+        // one specifically.
+        assert_eq!(
+            rel(&bundle, "article.cls").as_deref(),
+            Some("tex/xelatex/xetexref/article.cls")
+        );
+        assert_eq!(
+            rel(&bundle, "base/article.cls").as_deref(),
+            Some("tex/latex/base/article.cls")
+        );
+        assert_eq!(
+            rel(&bundle, "hyphen.tex").as_deref(),
+            Some("tex/generic/hyphen/hyphen.tex")
+        );
+
+        assert_eq!(bundle.lookup("manual.tex"), None);
+        assert_eq!(bundle.lookup("missing.sty"), None);
+        assert_eq!(
+            bundle.lookup("../texmf-dist/tex/latex/base/latex.ltx"),
+            None
+        );
+        assert_eq!(bundle.lookup("/etc/passwd"), None);
+
+        assert_eq!(
+            read(&mut bundle, "article.cls").as_deref(),
+            Some("xelatex article")
+        );
+        assert_eq!(
+            read(&mut bundle, "latex.ltx").as_deref(),
+            Some("latex kernel")
+        );
+        assert_eq!(
+            read(&mut bundle, "tectonic-format-latex.tex").as_deref(),
+            Some("\\input xelatex.ini\n")
+        );
+        assert_eq!(read(&mut bundle, "manual.tex"), None);
+
+        let digest = bundle
+            .get_digest(&mut NoopStatusBackend::default())
+            .unwrap();
+        assert_eq!(
+            read(&mut bundle, digest::DIGEST_NAME),
+            Some(digest.to_string())
+        );
+    }
+
+    #[test]
+    fn scanned_all_files() {
+        let tree = make_tree(FIXTURE);
+        let mut status = NoopStatusBackend::default();
+        let mut bundle = TexLiveBundle::scan(tree.path()).unwrap();
+
+        let mut files = bundle.all_files(&mut status).unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                digest::DIGEST_NAME,
+                "article.cls",
+                "hyphen.tex",
+                "latex.ltx",
+                "tectonic-format-latex.tex",
+                "tectonic-format-plain.tex",
+            ]
+        );
+
+        // The digest depends on the listing of the tree.
+        let d1 = bundle.get_digest(&mut status).unwrap();
+        let d2 = TexLiveBundle::scan(tree.path())
+            .unwrap()
+            .get_digest(&mut status)
+            .unwrap();
+        assert_eq!(d1, d2);
+
+        fs::write(tree.path().join("texmf-dist/tex/latex/base/new.sty"), "").unwrap();
+        let d3 = TexLiveBundle::scan(tree.path())
+            .unwrap()
+            .get_digest(&mut status)
+            .unwrap();
+        assert_ne!(d1, d3);
+    }
+
+    #[test]
+    fn ls_r_lookup() {
+        const LS_R: &str = "% ls-R -- filename database for kpathsea; do not change this line.
+./:
+ls-R
+tex
+
+./tex/latex/base:
+article.cls
+gone.sty
+
+./tex/xelatex/xetexref:
+article.cls
+
+./tex/generic/hyphen:
+hyphen.tex
+";
+
+        let mut files = FIXTURE.to_vec();
+        files.push(("texmf-dist/ls-R", LS_R));
+        let tree = make_tree(&files);
+        let texmf = tree.path().join("texmf-dist");
+        let mut status = NoopStatusBackend::default();
+
+        assert!(is_texlive_tree(&texmf));
+        let mut bundle = TexLiveBundle::open(&texmf).unwrap();
+
+        assert_eq!(
+            rel(&bundle, "article.cls").as_deref(),
+            Some("tex/xelatex/xetexref/article.cls")
+        );
+
+        // Stale database entries are skipped, and files missing from the
+        // database aren't found, just as with kpathsea.
+        assert_eq!(bundle.lookup("gone.sty"), None);
+        assert_eq!(bundle.lookup("latex.ltx"), None);
+        assert!(bundle
+            .all_files(&mut status)
+            .unwrap()
+            .contains(&"gone.sty".to_owned()));
+
+        // Scanning the tree picks up what the database missed.
+        let mut scanned = TexLiveBundle::scan(&texmf).unwrap();
+        assert_eq!(
+            rel(&scanned, "latex.ltx").as_deref(),
+            Some("tex/latex/base/latex.ltx")
+        );
+        assert_ne!(
+            bundle.get_digest(&mut status).unwrap(),
+            scanned.get_digest(&mut status).unwrap()
+        );
+    }
+
+    #[test]
+    fn ls_r_parsing() {
+        const LS_R: &str = "% ls-R -- filename database for kpathsea; do not change this line.
+./:
+ls-R
+tex
+doc
+
+./tex/generic/hyphen:
+hyphen.tex
+
+./tex/latex/base:
+article.cls
+latex.ltx
+
+./tex/xelatex/xetexref:
+article.cls
+
+./doc/latex/base:
+article.cls
+
+/opt/texlive/texmf-dist/tex/plain/base:
+plain.tex
+
+/elsewhere/tex:
+bogus.tex
+";
+
+        let mut index = HashMap::new();
+        parse_ls_r(LS_R, Path::new("/opt/texlive/texmf-dist"), &mut index);
+        let bundle = TexLiveBundle::finish(PathBuf::new(), index, digest::create());
+        let index = &bundle.index;
+
+        assert_eq!(index["ls-R"], [""]);
+        assert_eq!(index["hyphen.tex"], ["tex/generic/hyphen"]);
+        assert_eq!(index["plain.tex"], ["tex/plain/base"]);
+        assert_eq!(
+            index["article.cls"],
+            ["tex/xelatex/xetexref", "tex/latex/base"]
+        );
+        assert!(!index.contains_key("bogus.tex"));
+    }
+}
@@ -39,7 +39,8 @@ pub struct Document {
     build_dir: PathBuf,
 
     /// Arbitrary document metadata.
-    /// This has no effect on tectonic's build process.
+    /// Apart from supplying the publication metadata of EPUB outputs, this has
+    /// no effect on tectonic's build process.
     /// Rather, allows users to add easily-accessible information to their documents,
     /// which may be read by external tools.
     pub metadata: Option<toml::Value>,
@@ -53,6 +54,19 @@ pub struct Document {
     /// Either a URL or a local path.
     pub bundle_loc: String,
 
+    /// Additional bundles stacked on top of [`Self::bundle_loc`], highest
+    /// precedence first. Files in these bundles override the files of the
+    /// same name in the main bundle. Each is either a URL or a local path.
+    pub bundle_overlays: Vec<String>,
+
+    /// Alternative URLs for the bundle, tried in order if [`Self::bundle_loc`]
+    /// can't be reached. Only meaningful for network bundles.
+    pub bundle_mirrors: Vec<String>,
+
+    /// The expected digest of the bundle, in hex, if specified. Mirrors
+    /// serving a bundle with a different digest are ignored.
+    pub bundle_digest: Option<String>,
+
     /// The different outputs that are created from the document source. These
     /// may have different formats (e.g., PDF and HTML) or the same format but
     /// different settings (e.g., PDF with A4 paper and PDF with US Letter
@@ -76,6 +90,17 @@ impl Document {
         toml_data.read_to_string(&mut toml_text)?;
         let doc: syntax::TomlDocument = toml::from_str(&toml_text)?;
 
+        let (bundle_overlays, bundle_loc) = match doc.doc.bundle {
+            syntax::StringOrStringVec::String(s) => (Vec::new(), s),
+            syntax::StringOrStringVec::Vec(mut v) => {
+                let base = a_ok_or!(
+                    v.pop();
+                    ["the `bundle` list in the TOML specification must not be empty"]
+                );
+                (v, base)
+            }
+        };
+
         let mut outputs = HashMap::new();
 
         for toml_output in &doc.outputs {
@@ -97,7 +122,10 @@ impl Document {
             src_dir: src_dir.into(),
             build_dir: build_dir.into(),
             name: doc.doc.name,
-            bundle_loc: doc.doc.bundle,
+            bundle_loc,
+            bundle_overlays,
+            bundle_mirrors: doc.doc.bundle_mirrors,
+            bundle_digest: doc.doc.bundle_digest,
             metadata: doc.doc.metadata,
             outputs,
         })
@@ -119,7 +147,15 @@ impl Document {
         let doc = syntax::TomlDocument {
             doc: syntax::TomlDocSection {
                 name: self.name.clone(),
-                bundle: self.bundle_loc.clone(),
+                bundle: if self.bundle_overlays.is_empty() {
+                    syntax::StringOrStringVec::String(self.bundle_loc.clone())
+                } else {
+                    let mut v = self.bundle_overlays.clone();
+                    v.push(self.bundle_loc.clone());
+                    syntax::StringOrStringVec::Vec(v)
+                },
+                bundle_mirrors: self.bundle_mirrors.clone(),
+                bundle_digest: self.bundle_digest.clone(),
                 metadata: None,
             },
             outputs,
@@ -185,6 +221,11 @@ impl Document {
             BuildTargetType::Html => {
                 p.push("index.html");
             }
+
+            BuildTargetType::Epub => {
+                p.push(&profile.name);
+                p.set_extension("epub");
+            }
         }
 
         p
@@ -221,6 +262,32 @@ pub struct OutputProfile {
     /// Directory is not managed and any files created in it will not be deleted.
     ///
     pub shell_escape_cwd: Option<String>,
+
+    /// Whether to precompile the document preamble into a custom format.
+    ///
+    /// If true, the first of the [`Self::inputs`] is treated as the document
+    /// preamble. It is processed once and the resulting engine state is saved
+    /// as a format file, which is reused in later builds as long as the
+    /// preamble and the files that it loads don't change.
+    pub preamble_format: bool,
+
+    /// Whether HTML elements should be annotated with the TeX source positions
+    /// that produced them, using `data-src` attributes.
+    ///
+    /// This only has an effect for HTML and EPUB outputs.
+    pub source_positions: bool,
+
+    /// Whether JSON maps of TeX source positions should be written alongside
+    /// the HTML pages.
+    ///
+    /// This only has an effect for HTML and EPUB outputs. It provides the same
+    /// information as [`Self::source_positions`] without altering the HTML.
+    pub source_maps: bool,
+
+    /// Whether the fonts of HTML outputs should be written in the WOFF2 format.
+    ///
+    /// This only has an effect for HTML and EPUB outputs.
+    pub woff2_fonts: bool,
 }
 
 /// The output target type of a document build.
@@ -231,6 +298,9 @@ pub enum BuildTargetType {
 
     /// Output to the Portable Document Format (PDF).
     Pdf,
+
+    /// Output an EPUB 3 e-book, packaging the HTML output.
+    Epub,
 }
 
 /// An input provided to a document build
@@ -288,6 +358,9 @@ impl Document {
             build_dir,
             name,
             bundle_loc,
+            bundle_overlays: Vec::new(),
+            bundle_mirrors: Vec::new(),
+            bundle_digest: None,
             outputs: crate::document::default_outputs(),
             metadata: None,
         })
@@ -308,6 +381,10 @@ pub(crate) fn default_outputs() -> HashMap<String, OutputProfile> {
                 .collect(),
             shell_escape: false,
             shell_escape_cwd: None,
+            preamble_format: false,
+            source_positions: false,
+            source_maps: false,
+            woff2_fonts: false,
         },
     );
     outputs
@@ -353,4 +430,100 @@ mod tests {
         let doc = Document::new_from_toml(".", ".", &mut c).unwrap();
         assert!(doc.outputs.get("o").unwrap().shell_escape);
     }
+
+    #[test]
+    fn bundle_mirrors() {
+        const TOML: &str = r#"
+        [doc]
+        name = "test"
+        bundle = "https://example.com/bundle.tar"
+        bundle_mirrors = ["https://mirror.example.com/bundle.tar"]
+
+        [[output]]
+        name = "o"
+        type = "pdf"
+        "#;
+
+        let mut c = Cursor::new(TOML.as_bytes());
+        let doc = Document::new_from_toml(".", ".", &mut c).unwrap();
+        assert_eq!(
+            doc.bundle_mirrors,
+            ["https://mirror.example.com/bundle.tar"]
+        );
+        assert_eq!(doc.bundle_digest, None);
+    }
+
+    #[test]
+    fn bundle_layers() {
+        const TOML: &str = r#"
+        [doc]
+        name = "test"
+        bundle = ["overlay", "https://example.com/bundle.tar"]
+
+        [[output]]
+        name = "o"
+        type = "pdf"
+        "#;
+
+        let mut c = Cursor::new(TOML.as_bytes());
+        let doc = Document::new_from_toml(".", ".", &mut c).unwrap();
+        assert_eq!(doc.bundle_loc, "https://example.com/bundle.tar");
+        assert_eq!(doc.bundle_overlays, ["overlay"]);
+    }
+
+    #[test]
+    fn preamble_format() {
+        const TOML: &str = r#"
+        [doc]
+        name = "test"
+        bundle = "na"
+
+        [[output]]
+        name = "o"
+        type = "pdf"
+        inputs = ["preamble.tex", "main.tex"]
+        preamble_format = true
+        "#;
+
+        let mut c = Cursor::new(TOML.as_bytes());
+        let doc = Document::new_from_toml(".", ".", &mut c).unwrap();
+        assert!(doc.outputs["o"].preamble_format);
+    }
+
+    #[test]
+    fn source_positions() {
+        const TOML: &str = r#"
+        [doc]
+        name = "test"
+        bundle = "na"
+
+        [[output]]
+        name = "o"
+        type = "html"
+        source_maps = true
+        "#;
+
+        let mut c = Cursor::new(TOML.as_bytes());
+        let doc = Document::new_from_toml(".", ".", &mut c).unwrap();
+        assert!(!doc.outputs["o"].source_positions);
+        assert!(doc.outputs["o"].source_maps);
+    }
+
+    #[test]
+    fn woff2_fonts() {
+        const TOML: &str = r#"
+        [doc]
+        name = "test"
+        bundle = "na"
+
+        [[output]]
+        name = "o"
+        type = "html"
+        woff2_fonts = true
+        "#;
+
+        let mut c = Cursor::new(TOML.as_bytes());
+        let doc = Document::new_from_toml(".", ".", &mut c).unwrap();
+        assert!(doc.outputs["o"].woff2_fonts);
+    }
 }
@@ -28,10 +28,47 @@ pub struct TomlDocument {
 #[serde(deny_unknown_fields)]
 pub struct TomlDocSection {
     pub name: String,
-    pub bundle: String,
+
+    // Either a single bundle location, or a list of them that are stacked on
+    // top of one another, highest precedence first.
+    pub bundle: StringOrStringVec,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bundle_mirrors: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle_digest: Option<String>,
+
     pub metadata: Option<toml::Value>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum StringOrStringVec {
+    String(String),
+    Vec(Vec<String>),
+}
+
+// As with StringOrInputVec below, omit the array brackets when there's only
+// one item.
+impl Serialize for StringOrStringVec {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::String(s) => s.serialize(serializer),
+            Self::Vec(v) => {
+                if v.len() == 1 {
+                    v[0].serialize(serializer)
+                } else {
+                    v.serialize(serializer)
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum StringOrInputVec {
@@ -95,6 +132,10 @@ pub struct TomlOutputProfile {
     pub tex_format: Option<String>,
     pub shell_escape: Option<bool>,
     pub shell_escape_cwd: Option<String>,
+    pub preamble_format: Option<bool>,
+    pub source_positions: Option<bool>,
+    pub source_maps: Option<bool>,
+    pub woff2_fonts: Option<bool>,
 
     // We cannot handle these two input variants with an enum.
     // The ideal solution requires #[serde(flatten)],
@@ -149,6 +190,10 @@ impl From<&TomlOutputProfile> for OutputProfile {
             inputs,
             shell_escape: val.shell_escape.unwrap_or(shell_escape_default),
             shell_escape_cwd: val.shell_escape_cwd.clone(),
+            preamble_format: val.preamble_format.unwrap_or(false),
+            source_positions: val.source_positions.unwrap_or(false),
+            source_maps: val.source_maps.unwrap_or(false),
+            woff2_fonts: val.woff2_fonts.unwrap_or(false),
         }
     }
 }
@@ -166,6 +211,18 @@ impl From<&OutputProfile> for TomlOutputProfile {
 
         let shell_escape = if !rt.shell_escape { None } else { Some(true) };
         let shell_escape_cwd = rt.shell_escape_cwd.clone();
+        let preamble_format = if !rt.preamble_format {
+            None
+        } else {
+            Some(true)
+        };
+        let source_positions = if !rt.source_positions {
+            None
+        } else {
+            Some(true)
+        };
+        let source_maps = if !rt.source_maps { None } else { Some(true) };
+        let woff2_fonts = if !rt.woff2_fonts { None } else { Some(true) };
 
         TomlOutputProfile {
             name: rt.name.clone(),
@@ -174,6 +231,10 @@ impl From<&OutputProfile> for TomlOutputProfile {
             inputs: Some(inputs),
             shell_escape,
             shell_escape_cwd,
+            preamble_format,
+            source_positions,
+            source_maps,
+            woff2_fonts,
             preamble_file: None,
             index_file: None,
             postamble_file: None,
@@ -188,6 +249,9 @@ pub enum TomlBuildTargetType {
 
     #[serde(rename = "pdf")]
     Pdf,
+
+    #[serde(rename = "epub")]
+    Epub,
 }
 
 impl From<TomlBuildTargetType> for BuildTargetType {
@@ -195,6 +259,7 @@ impl From<TomlBuildTargetType> for BuildTargetType {
         match val {
             TomlBuildTargetType::Html => BuildTargetType::Html,
             TomlBuildTargetType::Pdf => BuildTargetType::Pdf,
+            TomlBuildTargetType::Epub => BuildTargetType::Epub,
         }
     }
 }
@@ -204,6 +269,7 @@ impl From<&BuildTargetType> for TomlBuildTargetType {
         match s {
             BuildTargetType::Html => TomlBuildTargetType::Html,
             BuildTargetType::Pdf => TomlBuildTargetType::Pdf,
+            BuildTargetType::Epub => TomlBuildTargetType::Epub,
         }
     }
 }
@@ -6,20 +6,24 @@
 use serde::Serialize;
 use std::{
     borrow::Cow,
-    collections::{hash_map::Iter, HashMap},
-    fs::File,
+    collections::{hash_map::Iter, HashMap, HashSet},
     io::{Read, Write},
-    path::{Path, PathBuf},
+    path::Path,
 };
-use tectonic_errors::{anyhow::Context, prelude::*};
+use tectonic_errors::prelude::*;
+use tectonic_io_base::digest::{self, Digest, DigestData};
 use tectonic_status_base::tt_warning;
 
-use crate::{fonts::FontEnsemble, specials::Special, Common};
+use crate::{
+    fontfile::output_file_name, fonts::FontEnsemble, search::SEARCH_WIDGET_JS, specials::Special,
+    Common,
+};
 
 /// Runtime state about which non-font assets have been created.
 #[derive(Debug, Default)]
 pub(crate) struct Assets {
     paths: HashMap<String, AssetOrigin>,
+    search_entries: Vec<syntax::SearchIndexEntry>,
 }
 
 /// Different kinds of non-font assets that can be defined at runtime.
@@ -31,6 +35,12 @@ enum AssetOrigin {
     /// Emit a CSS file containing information about the ensemble of fonts
     /// that have been used.
     FontCss,
+
+    /// Emit a JSON index of the text content for client-side search.
+    SearchIndex,
+
+    /// Emit the default JavaScript search widget.
+    SearchWidget,
 }
 
 impl Assets {
@@ -66,6 +76,16 @@ impl Assets {
                         self.emit_font_css(dest_path);
                         true
                     }
+                    "search-index" => {
+                        self.paths
+                            .insert(dest_path.to_owned(), AssetOrigin::SearchIndex);
+                        true
+                    }
+                    "search-js" => {
+                        self.paths
+                            .insert(dest_path.to_owned(), AssetOrigin::SearchWidget);
+                        true
+                    }
                     _ => {
                         tt_warning!(common.status, "ignoring unsupported special `{}`", special);
                         false
@@ -89,108 +109,212 @@ impl Assets {
             .insert(dest_path.to_string(), AssetOrigin::FontCss);
     }
 
-    /// This functional must only be called if `common.out_path` is not None.
-    pub(crate) fn emit(mut self, mut fonts: FontEnsemble, common: &mut Common) -> Result<()> {
-        let faces = fonts.emit(common.out_base)?;
+    /// Check whether any of the assets is a search index.
+    pub(crate) fn has_search_index(&self) -> bool {
+        self.paths
+            .values()
+            .any(|o| matches!(o, AssetOrigin::SearchIndex))
+    }
 
-        for (dest_path, origin) in self.paths.drain() {
-            match origin {
-                AssetOrigin::Copy(ref src_path) => emit_copied_file(src_path, &dest_path, common),
-                AssetOrigin::FontCss => emit_font_css(&dest_path, &faces, common),
-            }?;
+    /// Add entries to the search index.
+    pub(crate) fn add_search_entries(&mut self, entries: Vec<syntax::SearchIndexEntry>) {
+        syntax::merge_search_entries(&mut self.search_entries, &entries);
+    }
+
+    /// Create the contents of all of the asset files, including the fonts.
+    ///
+    /// If `common.hashed_asset_names` is true, the files are given
+    /// content-hashed names.
+    pub(crate) fn render(
+        &self,
+        fonts: &FontEnsemble,
+        common: &mut Common,
+    ) -> Result<RenderedAssets> {
+        let mut rendered = RenderedAssets {
+            hash_names: common.hashed_asset_names,
+            ..Default::default()
+        };
+
+        // The font files come first, since the font CSS needs to know their
+        // names.
+        let faces = fonts.render(common, &mut rendered)?;
+
+        let mut paths: Vec<_> = self.paths.iter().collect();
+        paths.sort_unstable_by_key(|t| t.0);
+
+        for (dest_path, origin) in paths {
+            let contents = match origin {
+                AssetOrigin::Copy(ref src_path) => read_copied_file(src_path, common)?,
+                AssetOrigin::FontCss => faces.clone().into_bytes(),
+                AssetOrigin::SearchIndex => {
+                    let index = syntax::SearchIndexRef {
+                        entries: &self.search_entries,
+                    };
+                    atry!(
+                        serde_json::to_vec(&index);
+                        ["cannot serialize output file `{}`", dest_path]
+                    )
+                }
+                AssetOrigin::SearchWidget => SEARCH_WIDGET_JS.as_bytes().to_owned(),
+            };
+
+            let out_path = rendered.add(dest_path, contents);
+
+            if matches!(origin, AssetOrigin::SearchIndex | AssetOrigin::SearchWidget) {
+                rendered.auxiliary.insert(out_path);
+            }
         }
 
-        Ok(())
+        Ok(rendered)
     }
 
-    pub(crate) fn into_serialize(mut self, fonts: FontEnsemble) -> impl Serialize {
-        let (mut assets, css_data) = fonts.into_serialize();
+    /// Convert these assets into their serializable form.
+    ///
+    /// If asset files are given content-hashed names, *hashed_names* should
+    /// be the map from their logical names to the actual ones.
+    pub(crate) fn into_serialize(
+        mut self,
+        fonts: FontEnsemble,
+        woff2_fonts: bool,
+        hashed_names: Option<&HashMap<String, String>>,
+    ) -> impl Serialize {
+        let (mut assets, css_data) = fonts.into_serialize(woff2_fonts);
 
         for (dest_path, origin) in self.paths.drain() {
             let info = match origin {
                 AssetOrigin::Copy(src_path) => syntax::AssetOrigin::Copy(src_path),
                 AssetOrigin::FontCss => syntax::AssetOrigin::FontCss(css_data.clone()),
+                AssetOrigin::SearchIndex => {
+                    syntax::AssetOrigin::SearchIndex(syntax::SearchIndexAssetData {
+                        entries: self.search_entries.clone(),
+                    })
+                }
+                AssetOrigin::SearchWidget => syntax::AssetOrigin::SearchWidget,
             };
-            assets.0.insert(dest_path, info);
+            assets.assets.insert(dest_path, info);
+        }
+
+        if let Some(names) = hashed_names {
+            assets.hash_names = true;
+            assets.hashed_names = names.clone();
         }
 
         assets
     }
 }
 
-/// This functional must only be called if `common.out_path` is not None.
-fn emit_copied_file(src_tex_path: &str, dest_path: &str, common: &mut Common) -> Result<()> {
+/// The contents of the asset files of a session, ready to be written out.
+#[derive(Debug, Default)]
+pub(crate) struct RenderedAssets {
+    /// Whether the files are given content-hashed names.
+    hash_names: bool,
+
+    /// The files, as pairs of their actual output paths and contents.
+    files: Vec<(String, Vec<u8>)>,
+
+    /// If the files are given content-hashed names, the map from their
+    /// logical output paths to their actual ones.
+    names: HashMap<String, String>,
+
+    /// The actual output paths of the files that aren't part of the document
+    /// itself, like the search index.
+    auxiliary: HashSet<String>,
+}
+
+impl RenderedAssets {
+    /// Add a file, returning the output path that it will actually have.
+    pub(crate) fn add(&mut self, path: &str, contents: Vec<u8>) -> String {
+        let out_path = if self.hash_names {
+            let hashed = hashed_path(path, &contents);
+            self.names.insert(path.to_owned(), hashed.clone());
+            hashed
+        } else {
+            path.to_owned()
+        };
+
+        self.files.push((out_path.clone(), contents));
+        out_path
+    }
+
+    /// Get the map from logical output paths to content-hashed ones. This is
+    /// empty if the files don't have content-hashed names.
+    pub(crate) fn names(&self) -> &HashMap<String, String> {
+        &self.names
+    }
+
+    /// Write the files to the output tree.
+    ///
+    /// This functional must only be called if `common.output` is not None.
+    pub(crate) fn emit(self, common: &mut Common) -> Result<()> {
+        for (path, contents) in &self.files {
+            let (out_path, _) = create_output_path(path, common)?;
+            let out_path = a_ok_or!(out_path; ["no output tree"]);
+            common.write_output_file(&out_path, contents)?;
+
+            if self.auxiliary.contains(path) {
+                common.auxiliary_file_written(&out_path);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Read the source of a copied asset file.
+fn read_copied_file(src_tex_path: &str, common: &mut Common) -> Result<Vec<u8>> {
     let mut ih = atry!(
         common.hooks.io().input_open_name(src_tex_path, common.status).must_exist();
         ["unable to open provideFile source `{}`", &src_tex_path]
     );
 
-    {
-        let (mut out_file, out_path) = create_asset_file(dest_path, common)?;
-
-        atry!(
-            std::io::copy(&mut ih, &mut out_file);
-            ["cannot copy to output file `{}`", out_path.display()]
-        );
-    }
+    let mut contents = Vec::new();
+    atry!(
+        ih.read_to_end(&mut contents);
+        ["unable to read provideFile source `{}`", &src_tex_path]
+    );
 
     let (name, digest_opt) = ih.into_name_digest();
     common
         .hooks
         .event_input_closed(name, digest_opt, common.status);
-    Ok(())
+    Ok(contents)
 }
 
-/// This functional must only be called if `common.out_path` is not None.
-fn emit_font_css(dest_path: &str, faces: &str, common: &mut Common) -> Result<()> {
-    let (mut out_file, out_path) = create_asset_file(dest_path, common)?;
+/// The number of hexadecimal digits of the content hash inserted into hashed
+/// asset file names.
+const HASH_LEN: usize = 10;
 
-    atry!(
-        write!(&mut out_file, "{faces}");
-        ["cannot write output file `{}`", out_path.display()]
-    );
-
-    Ok(())
-}
+/// Get the content-hashed version of an output path, which has a hash of the
+/// file contents inserted before the extension of the file name.
+fn hashed_path(path: &str, contents: &[u8]) -> String {
+    let mut dc = digest::create();
+    dc.update(contents);
+    let hash = DigestData::from(dc).to_string();
+    let hash = &hash[..HASH_LEN];
 
-/// This functional must only be called if `common.out_path` is not None.
-fn create_asset_file(dest_path: &str, common: &mut Common) -> Result<(File, PathBuf)> {
-    let out_path = create_output_path(dest_path, common)?.0.unwrap();
-
-    let out_file = atry!(
-        File::create(&out_path);
-        ["cannot open output file `{}`", out_path.display()]
-    );
+    let name_start = path.rfind('/').map_or(0, |i| i + 1);
 
-    Ok((out_file, out_path))
+    match path[name_start..].rfind('.') {
+        Some(i) if i > 0 => {
+            let (stem, ext) = path.split_at(name_start + i);
+            format!("{stem}.{hash}{ext}")
+        }
+        _ => format!("{path}.{hash}"),
+    }
 }
 
-/// Process a TeX output path into one for the actual filesystem.
+/// Process a TeX output path into one for the output tree.
 ///
-/// We have a separate argument `do_create`, rather than just looking at
-/// `common.do_not_emit`, since this function is used for assets as well as
-/// templated HTML outputs.
+/// The path is validated and normalized. The return value is the normalized
+/// path, or None if we're not actually creating output files, and the number
+/// of levels of directory hierarchy in the path.
 pub(crate) fn create_output_path(
     dest_path: &str,
     common: &mut Common,
-) -> Result<(Option<PathBuf>, usize)> {
-    let mut out_path = common.out_base.map(|p| p.to_owned());
-    let mut n_levels = 0;
+) -> Result<(Option<String>, usize)> {
+    let mut pieces = Vec::new();
 
     for piece in dest_path.split('/') {
-        if let Some(out_path) = out_path.as_mut() {
-            match std::fs::create_dir(&out_path) {
-                Ok(_) => {}
-                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
-                Err(e) => {
-                    return Err(e).context(format!(
-                        "cannot create output parent directory `{}`",
-                        out_path.display()
-                    ));
-                }
-            }
-        }
-
         if piece.is_empty() {
             continue;
         }
@@ -211,13 +335,16 @@ pub(crate) fn create_output_path(
             );
         }
 
-        if let Some(out_path) = out_path.as_mut() {
-            out_path.push(piece);
-        }
-
-        n_levels += 1;
+        pieces.push(piece);
     }
 
+    let n_levels = pieces.len();
+    let out_path = if common.emits_files() {
+        Some(pieces.join("/"))
+    } else {
+        None
+    };
+
     Ok((out_path, n_levels))
 }
 
@@ -247,8 +374,16 @@ impl AssetSpecification {
 
         use syntax::AssetOrigin as AO;
 
-        for (path, new_origin) in &new.0 {
-            if let Some(cur_origin) = self.0 .0.get_mut(path) {
+        if self.0.assets.is_empty() {
+            self.0.hash_names = new.hash_names;
+        } else if new.hash_names != self.0.hash_names {
+            bail!("disagreeing settings for whether asset files have content-hashed names");
+        }
+
+        self.merge_hashed_names(&new);
+
+        for (path, new_origin) in &new.assets {
+            if let Some(cur_origin) = self.0.assets.get_mut(path) {
                 match (new_origin, cur_origin) {
                     (AO::Copy(new_src), AO::Copy(cur_src)) => {
                         if cur_src != new_src {
@@ -280,10 +415,19 @@ impl AssetSpecification {
                             );
                         }
 
+                        if new_ff.woff2 != cur_ff.woff2 {
+                            bail!(
+                                "disagreeing font formats for output font asset `{}`: \
+                                only one is to be emitted in WOFF2 format",
+                                path
+                            );
+                        }
+
                         // We have two font assets with the same source. We need
-                        // to merge the vglyph information, but otherwise we're
-                        // good!
+                        // to merge the vglyph and glyph usage information, but
+                        // otherwise we're good!
                         syntax::merge_vglyphs(&mut cur_ff.vglyphs, &new_ff.vglyphs);
+                        syntax::merge_used_glyphs(&mut cur_ff.used_glyphs, &new_ff.used_glyphs);
                     }
 
                     (AO::FontCss(new_fe), AO::FontCss(cur_fe)) => {
@@ -291,6 +435,13 @@ impl AssetSpecification {
                         syntax::merge_font_ensembles(&mut cur_fe.0, &new_fe.0)?;
                     }
 
+                    (AO::SearchIndex(new_si), AO::SearchIndex(cur_si)) => {
+                        // Each run indexes its own pages, so we combine them.
+                        syntax::merge_search_entries(&mut cur_si.entries, &new_si.entries);
+                    }
+
+                    (AO::SearchWidget, AO::SearchWidget) => {}
+
                     (new2, cur2) => {
                         bail!(
                             "disagreeing origin types {} and {} for output asset `{}`",
@@ -302,13 +453,73 @@ impl AssetSpecification {
                 }
             } else {
                 // This path is undefined in the current object. Just add it!
-                self.0 .0.insert(path.clone(), new_origin.clone());
+                self.0.assets.insert(path.clone(), new_origin.clone());
             }
         }
 
         Ok(self)
     }
 
+    /// Merge the content-hashed names of another specification into this one.
+    ///
+    /// This must be called before the assets themselves are merged. A name is
+    /// only kept if both specifications agree on it, or if the file is only
+    /// created by one of them. Otherwise, the contents of the file will change
+    /// due to the merger, and its name isn't known until a run that uses the
+    /// merged specification.
+    fn merge_hashed_names(&mut self, new: &syntax::Assets) {
+        let new_spec = AssetSpecification(new.clone());
+        let cur_outputs: HashSet<String> = self
+            .logical_output_paths()
+            .map(|p| p.into_owned())
+            .collect();
+        let new_outputs: HashSet<String> = new_spec
+            .logical_output_paths()
+            .map(|p| p.into_owned())
+            .collect();
+
+        // Font files whose glyph usage changes get new contents in all of their
+        // variants, even ones that are only created by one specification.
+        let mut unsettled = HashSet::new();
+
+        for (path, new_origin) in &new.assets {
+            if let (
+                Some(syntax::AssetOrigin::FontFile(cur_ff)),
+                syntax::AssetOrigin::FontFile(new_ff),
+            ) = (self.0.assets.get(path), new_origin)
+            {
+                if cur_ff.used_glyphs != new_ff.used_glyphs || cur_ff.vglyphs != new_ff.vglyphs {
+                    for origin in [self.0.assets[path].clone(), new_origin.clone()] {
+                        let single = AssetSpecification(syntax::Assets {
+                            assets: std::iter::once((path.clone(), origin)).collect(),
+                            ..Default::default()
+                        });
+                        unsettled.extend(single.logical_output_paths().map(|p| p.into_owned()));
+                    }
+                }
+            }
+        }
+
+        let cur_names = std::mem::take(&mut self.0.hashed_names);
+
+        for (path, name) in &cur_names {
+            let keep = match new.hashed_names.get(path) {
+                Some(new_name) => new_name == name,
+                None => !new_outputs.contains(path),
+            };
+
+            if keep && !unsettled.contains(path) {
+                self.0.hashed_names.insert(path.clone(), name.clone());
+            }
+        }
+
+        for (path, name) in &new.hashed_names {
+            if !cur_outputs.contains(path) && !unsettled.contains(path) {
+                self.0.hashed_names.insert(path.clone(), name.clone());
+            }
+        }
+    }
+
     /// Save this asset specification to a stream.
     ///
     /// Currently, this is done in a JSON format, but this is not guaranteed to
@@ -322,9 +533,24 @@ impl AssetSpecification {
 
     /// Produce the TeX paths of the output files associated with this
     /// specification.
+    ///
+    /// If the asset files are given content-hashed names, those names are
+    /// produced, except for files whose names aren't known because the
+    /// specification was created by merging, and the merger changed their
+    /// contents.
     pub fn output_paths(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        self.logical_output_paths()
+            .map(move |p| match self.0.hashed_names.get(p.as_ref()) {
+                Some(hashed) => Cow::Borrowed(hashed.as_ref()),
+                None => p,
+            })
+    }
+
+    /// Produce the logical TeX paths of the output files associated with this
+    /// specification, ignoring any content hashing.
+    fn logical_output_paths(&self) -> AssetOutputsIterator<'_> {
         AssetOutputsIterator {
-            iter: self.0 .0.iter(),
+            iter: self.0.assets.iter(),
             cur_vg_path: None,
             next_vg_index: 0,
         }
@@ -355,7 +581,7 @@ impl AssetSpecification {
     /// the ones this particular session knows about.
     pub(crate) fn check_runtime_assets(&self, assets: &mut Assets) -> Result<()> {
         for (path, run_origin) in &assets.paths {
-            if let Some(pre_origin) = self.0 .0.get(path) {
+            if let Some(pre_origin) = self.0.assets.get(path) {
                 match (run_origin, pre_origin) {
                     (AssetOrigin::Copy(run_path), syntax::AssetOrigin::Copy(pre_path)) => {
                         ensure!(
@@ -370,6 +596,10 @@ impl AssetSpecification {
 
                     (AssetOrigin::FontCss, syntax::AssetOrigin::FontCss(_)) => {}
 
+                    (AssetOrigin::SearchIndex, syntax::AssetOrigin::SearchIndex(_)) => {}
+
+                    (AssetOrigin::SearchWidget, syntax::AssetOrigin::SearchWidget) => {}
+
                     _ => {
                         bail!(
                             "this session and the precomputed assets disagree on `{}`",
@@ -385,13 +615,22 @@ impl AssetSpecification {
             }
         }
 
-        // Now update the runtime assets to include all precomputed ones.
+        // Now update the runtime assets to include all precomputed ones. The
+        // search index needs to cover the pages of all of the runs, so its
+        // entries are combined too.
 
-        for (path, pre_origin) in &self.0 .0 {
+        for (path, pre_origin) in &self.0.assets {
             let mapped = match pre_origin {
                 syntax::AssetOrigin::Copy(pre_path) => AssetOrigin::Copy(pre_path.to_owned()),
                 syntax::AssetOrigin::FontCss(_) => AssetOrigin::FontCss,
                 syntax::AssetOrigin::FontFile(_) => continue,
+                syntax::AssetOrigin::SearchIndex(pre_si) => {
+                    let mut entries = pre_si.entries.clone();
+                    syntax::merge_search_entries(&mut entries, &assets.search_entries);
+                    assets.search_entries = entries;
+                    AssetOrigin::SearchIndex
+                }
+                syntax::AssetOrigin::SearchWidget => AssetOrigin::SearchWidget,
             };
 
             assets.paths.entry(path.to_owned()).or_insert(mapped);
@@ -399,6 +638,39 @@ impl AssetSpecification {
 
         Ok(())
     }
+
+    /// Check that the content-hashed names of the assets created in this
+    /// session agree with the ones in this specification.
+    ///
+    /// This function is used in the "precomputed assets" mode. Since the
+    /// runtime assets have been synced up with this specification, the file
+    /// contents should be the same as when it was created.
+    pub(crate) fn check_hashed_names(
+        &self,
+        rendered: Option<&RenderedAssets>,
+        common: &Common,
+    ) -> Result<()> {
+        ensure!(
+            self.0.hash_names == common.hashed_asset_names,
+            "this session and the precomputed assets disagree on whether asset \
+            files have content-hashed names"
+        );
+
+        if let Some(rendered) = rendered {
+            for (path, pre_name) in &self.0.hashed_names {
+                if let Some(run_name) = rendered.names().get(path) {
+                    ensure!(
+                        run_name == pre_name,
+                        "contents of asset `{}` have changed since the precomputed \
+                        asset specification was created",
+                        path
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 struct AssetOutputsIterator<'a> {
@@ -425,6 +697,8 @@ impl<'a> Iterator for AssetOutputsIterator<'a> {
 
         self.iter.next().map(|(path, origin)| {
             if let syntax::AssetOrigin::FontFile(ref ffi) = origin {
+                let out_name = output_file_name(path, ffi.woff2);
+
                 if !ffi.vglyphs.is_empty() {
                     // If we have moved on to a font file with variant glyphs,
                     // we first (now) yield the unmodified filename, then set up
@@ -435,9 +709,11 @@ impl<'a> Iterator for AssetOutputsIterator<'a> {
                         highest_vg_index = std::cmp::max(highest_vg_index, mapping.index);
                     }
 
-                    self.cur_vg_path = Some(path.to_owned());
+                    self.cur_vg_path = Some(out_name.clone().into_owned());
                     self.next_vg_index = highest_vg_index;
                 }
+
+                return out_name;
             }
 
             Cow::Borrowed(path.as_ref())
@@ -450,14 +726,35 @@ impl<'a> Iterator for AssetOutputsIterator<'a> {
 /// The top-level type is Assets.
 pub(crate) mod syntax {
     use serde::{Deserialize, Serialize, Serializer};
-    use std::collections::{BTreeMap, HashMap};
+    use std::collections::{BTreeMap, HashMap, HashSet};
     use tectonic_errors::prelude::*;
 
-    /// Annoyingly we need to wrap this hashmap in a struct because we need to
-    /// customize the serializer to sort the keys for reproducible outputs.
-    /// Likewise for all other hashmaps in this module.
+    use crate::fontfile::GlyphId;
+
+    /// We customize the serializer of this struct's hashmaps to sort the keys
+    /// for reproducible outputs. Likewise for all other hashmaps in this
+    /// module.
     #[derive(Clone, Debug, Default, Serialize, Deserialize)]
-    pub struct Assets(#[serde(serialize_with = "ordered_map")] pub HashMap<String, AssetOrigin>);
+    pub struct Assets {
+        /// The assets, keyed by their logical output paths.
+        #[serde(serialize_with = "ordered_map")]
+        pub assets: HashMap<String, AssetOrigin>,
+
+        /// If asset files are given content-hashed names, the map from the
+        /// logical output paths of the files to their actual ones. Entries
+        /// are absent for files whose contents aren't yet settled, because
+        /// they're affected by the merging of specifications.
+        #[serde(
+            default,
+            serialize_with = "ordered_map",
+            skip_serializing_if = "HashMap::is_empty"
+        )]
+        pub hashed_names: HashMap<String, String>,
+
+        /// Whether asset files are given content-hashed names.
+        #[serde(default)]
+        pub hash_names: bool,
+    }
 
     fn ordered_map<K: Ord + Serialize, V: Serialize, S>(
         value: &HashMap<K, V>,
@@ -483,6 +780,12 @@ pub(crate) mod syntax {
         /// An OpenType/TrueType font file and variants with customized CMAP tables
         /// allowing access to unusual glyphs.
         FontFile(FontFileAssetData),
+
+        /// A JSON index of the text content for client-side search.
+        SearchIndex(SearchIndexAssetData),
+
+        /// The default JavaScript search widget.
+        SearchWidget,
     }
 
     impl std::fmt::Display for AssetOrigin {
@@ -512,10 +815,54 @@ pub(crate) mod syntax {
                 AssetOrigin::FontFile(ff) => {
                     write!(f, "font face #{} from `{}`", ff.face_index, ff.source)
                 }
+
+                AssetOrigin::SearchIndex(si) => {
+                    write!(f, "search index with {} entries", si.entries.len())
+                }
+
+                AssetOrigin::SearchWidget => write!(f, "search widget script"),
             }
         }
     }
 
+    /// The search index, which is also the format of the emitted index file.
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+    pub struct SearchIndexAssetData {
+        /// The indexed sections of the output pages, in document order.
+        pub entries: Vec<SearchIndexEntry>,
+    }
+
+    /// A borrowed version of [`SearchIndexAssetData`] for serialization.
+    #[derive(Serialize)]
+    pub struct SearchIndexRef<'a> {
+        pub entries: &'a [SearchIndexEntry],
+    }
+
+    /// A section of an output page in the search index.
+    #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+    pub struct SearchIndexEntry {
+        /// The URL of the section, relative to the output root.
+        pub url: String,
+
+        /// The title of the page containing the section.
+        pub page_title: String,
+
+        /// The text of the heading that starts the section, if there is one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub heading: Option<String>,
+
+        /// The plain text content of the section.
+        pub text: String,
+    }
+
+    /// Merge one list of search index entries into another, skipping entries
+    /// for sections that are already indexed.
+    pub(crate) fn merge_search_entries(cur: &mut Vec<SearchIndexEntry>, new: &[SearchIndexEntry]) {
+        let known: HashSet<String> = cur.iter().map(|e| e.url.clone()).collect();
+
+        cur.extend(new.iter().filter(|e| !known.contains(&e.url)).cloned());
+    }
+
     #[derive(Clone, Debug, Default, Deserialize, Serialize)]
     pub struct FontFileAssetData {
         /// The path to find the font file in the source stack.
@@ -532,6 +879,23 @@ pub(crate) mod syntax {
         /// GlyphIds.
         #[serde(serialize_with = "ordered_map")]
         pub vglyphs: HashMap<String, GlyphVariantMapping>,
+
+        /// The glyphs used by the document, in order. The emitted font is
+        /// subsetted to these.
+        #[serde(default)]
+        pub used_glyphs: Vec<GlyphId>,
+
+        /// Whether the font is emitted in the WOFF2 format, which changes the
+        /// names of its output files.
+        #[serde(default)]
+        pub woff2: bool,
+    }
+
+    /// Merge one sorted list of used glyphs into another.
+    pub(crate) fn merge_used_glyphs(cur: &mut Vec<GlyphId>, new: &[GlyphId]) {
+        cur.extend_from_slice(new);
+        cur.sort_unstable();
+        cur.dedup();
     }
 
     /// Merge one table of variant glyph USV mappings into another.
@@ -646,3 +1010,182 @@ pub(crate) mod syntax {
         BoldItalic,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fontfile::GlyphId,
+        testutil::{run_spx2html, spx_of_specials},
+    };
+
+    #[test]
+    fn hashed_paths() {
+        let h = |p| hashed_path(p, b"x");
+        let hash = &h("a")[2..];
+        assert_eq!(hash.len(), HASH_LEN);
+        assert_eq!(h("a.css"), format!("a.{}.css", hash));
+        assert_eq!(h("d/a.tar.gz"), format!("d/a.tar.{}.gz", hash));
+        assert_eq!(h("d.x/a"), format!("d.x/a.{}", hash));
+        assert_eq!(h(".hidden"), format!(".hidden.{}", hash));
+        assert_ne!(hashed_path("a.css", b"y"), h("a.css"));
+    }
+
+    fn font(used_glyphs: &[GlyphId], vglyph: bool) -> syntax::AssetOrigin {
+        let mut vglyphs = HashMap::new();
+
+        if vglyph {
+            vglyphs.insert(
+                "7".to_owned(),
+                syntax::GlyphVariantMapping { usv: 'a', index: 0 },
+            );
+        }
+
+        syntax::AssetOrigin::FontFile(syntax::FontFileAssetData {
+            source: "f.otf".to_owned(),
+            vglyphs,
+            used_glyphs: used_glyphs.to_vec(),
+            ..Default::default()
+        })
+    }
+
+    fn spec(assets: Vec<(&str, syntax::AssetOrigin)>, names: &[(&str, &str)]) -> syntax::Assets {
+        syntax::Assets {
+            assets: assets
+                .into_iter()
+                .map(|(path, origin)| (path.to_owned(), origin))
+                .collect(),
+            hashed_names: names
+                .iter()
+                .map(|(path, name)| (path.to_string(), name.to_string()))
+                .collect(),
+            hash_names: true,
+        }
+    }
+
+    fn merged_names(cur: syntax::Assets, new: syntax::Assets) -> Vec<(String, String)> {
+        let mut merged = AssetSpecification(cur);
+        merged.merge_hashed_names(&new);
+        let mut names: Vec<_> = merged.0.hashed_names.into_iter().collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn merge_agreeing_names() {
+        // Names that agree are kept, as are names of files that are only
+        // created by one specification.
+        let cur = spec(
+            vec![
+                ("s.js", syntax::AssetOrigin::SearchWidget),
+                ("f.otf", font(&[1, 2], false)),
+            ],
+            &[("s.js", "s.1.js"), ("f.otf", "f.1.otf")],
+        );
+        let new = spec(
+            vec![
+                ("s.js", syntax::AssetOrigin::SearchWidget),
+                ("f.otf", font(&[1, 2], false)),
+                ("t.js", syntax::AssetOrigin::SearchWidget),
+            ],
+            &[("s.js", "s.1.js"), ("f.otf", "f.1.otf"), ("t.js", "t.1.js")],
+        );
+
+        assert_eq!(
+            merged_names(cur, new),
+            [("f.otf", "f.1.otf"), ("s.js", "s.1.js"), ("t.js", "t.1.js")]
+                .iter()
+                .map(|(p, n)| (p.to_string(), n.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn merge_disagreeing_names() {
+        // The contents of the file differ between the sessions.
+        let cur = spec(
+            vec![("s.js", syntax::AssetOrigin::SearchWidget)],
+            &[("s.js", "s.1.js")],
+        );
+        let new = spec(
+            vec![("s.js", syntax::AssetOrigin::SearchWidget)],
+            &[("s.js", "s.2.js")],
+        );
+        assert!(merged_names(cur, new).is_empty());
+
+        // A name is missing from a specification that creates the file,
+        // because its contents were already unsettled.
+        let cur = spec(
+            vec![("s.js", syntax::AssetOrigin::SearchWidget)],
+            &[("s.js", "s.1.js")],
+        );
+        let new = spec(vec![("s.js", syntax::AssetOrigin::SearchWidget)], &[]);
+        assert!(merged_names(cur, new).is_empty());
+    }
+
+    #[test]
+    fn merge_unsettled_fonts() {
+        // The merged font uses more glyphs, so it will be subsetted
+        // differently. That changes all of its variants, including the one that
+        // is only created by the current specification.
+        let cur = spec(
+            vec![("f.otf", font(&[1, 2], true))],
+            &[("f.otf", "f.1.otf"), ("vg0f.otf", "vg0f.1.otf")],
+        );
+        let new = spec(
+            vec![("f.otf", font(&[1, 3], false))],
+            &[("f.otf", "f.1.otf")],
+        );
+        assert!(merged_names(cur, new).is_empty());
+
+        // If the glyphs agree, the names can be kept.
+        let cur = spec(
+            vec![("f.otf", font(&[1, 2], true))],
+            &[("f.otf", "f.1.otf"), ("vg0f.otf", "vg0f.1.otf")],
+        );
+        let new = spec(
+            vec![("f.otf", font(&[1, 2], true))],
+            &[("f.otf", "f.1.otf")],
+        );
+        assert_eq!(merged_names(cur, new).len(), 1);
+    }
+
+    /// Process a page that uses the search widget, with the precomputed
+    /// content-hashed name of the widget script given.
+    fn run_precomputed(name: &str) -> Result<HashMap<String, Vec<u8>>> {
+        let spx = spx_of_specials(&[
+            "tdux:addTemplate t.html",
+            "tdux:setTemplate t.html",
+            "tdux:setOutputPath index.html",
+            "tdux:provideSpecial search-js s.js",
+            "tdux:emit",
+            "tdux:contentFinished",
+        ]);
+        let pre = spec(
+            vec![("s.js", syntax::AssetOrigin::SearchWidget)],
+            &[("s.js", name)],
+        );
+
+        run_spx2html(
+            &spx,
+            &[("t.html", b"{{ tduxAsset(path=\"s.js\") }}")],
+            |e| {
+                e.hashed_asset_names(true)
+                    .precomputed_assets(AssetSpecification(pre));
+            },
+        )
+    }
+
+    #[test]
+    fn precomputed_hashed_names() {
+        let name = hashed_path("s.js", SEARCH_WIDGET_JS.as_bytes());
+        let outputs = run_precomputed(&name).unwrap();
+        assert_eq!(outputs["index.html"], name.as_bytes());
+        assert!(outputs.contains_key(&name));
+
+        let err = run_precomputed("s.0123456789.js").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("contents of asset `s.js` have changed"));
+    }
+}
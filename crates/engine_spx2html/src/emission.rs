@@ -16,6 +16,9 @@ use crate::{
     finalization::FinalizingState,
     fonts::{FamilyRelativeFontId, FontEnsemble, FontFamilyAnalysis, PathToNewFont},
     html::Element,
+    mathml::{self, MathGlyph, MathRule},
+    navigation::{self, Heading},
+    sourcepos::{SourceElementKind, SourceMap, SourcePosition},
     specials::Special,
     templating::Templating,
     Common, FixedPoint, TexFontNum,
@@ -31,6 +34,30 @@ pub(crate) struct EmittingState {
     rems_per_tex: f32,
     elem_stack: Vec<ElementState>,
     current_canvas: Option<CanvasState>,
+    canvas_renderings: HashMap<String, CanvasRendering>,
+    open_headings: Vec<OpenHeading>,
+    n_generated_ids: usize,
+
+    /// The most recent source position, if it hasn't yet been associated with
+    /// an element.
+    pending_source: Option<SourcePosition>,
+
+    /// The source positions of the elements of the current page.
+    source_map: SourceMap,
+
+    /// The paragraph that has just been opened, if no content has been added
+    /// to it yet. Its source position may follow.
+    open_paragraph: Option<OpenParagraph>,
+}
+
+/// A paragraph whose opening tag has just been emitted.
+#[derive(Debug)]
+struct OpenParagraph {
+    /// The offset of the `>` ending the opening tag in the content.
+    tag_end: usize,
+
+    /// The index of the paragraph in the source map.
+    index: usize,
 }
 
 #[derive(Debug, Default)]
@@ -72,6 +99,16 @@ impl ContentState {
         html_escape::encode_unquoted_attribute_to_string(raw_text, &mut self.current_content);
     }
 
+    fn len(&self) -> usize {
+        self.current_content.len()
+    }
+
+    /// Get the content added since it had length *start*, if it hasn't been
+    /// taken in the meantime.
+    fn since(&self, start: usize) -> &str {
+        self.current_content.get(start..).unwrap_or_default()
+    }
+
     fn take(&mut self) -> String {
         std::mem::take(&mut self.current_content)
     }
@@ -103,22 +140,12 @@ impl ContentState {
             return true;
         }
 
-        // Check the advance against the size of the space, which can be
-        // determined from either the most recent content or the new content,
-        // since in various circumstances either one or the other might not
-        // be defined. If both are defined, use whatever's smaller. There's
-        // probably a smoother way to do this logic?
-
-        let space_width = match (&self.last_content_space_width, &cur_space_width) {
-            (Some(w1), Some(w2)) => FixedPoint::min(*w1, *w2),
-            (Some(w), None) => *w,
-            (None, Some(w)) => *w,
-            (None, None) => 0,
-        };
-
-        // If the x difference is larger than 1/4 of the space_width, let's say that
-        // we need a space. I made up the 1/4.
-        4 * (x0 - self.last_content_x) > space_width
+        is_word_space(
+            self.last_content_x,
+            x0,
+            self.last_content_space_width,
+            cur_space_width,
+        )
     }
 
     fn update_content_pos(&mut self, x: i32, cur_space_width: Option<FixedPoint>) {
@@ -215,23 +242,90 @@ pub(crate) enum ElementOrigin {
     FontAuto,
 }
 
+/// How the contents of a canvas are rendered into HTML.
+///
+/// The rendering can be chosen for each kind of canvas with the
+/// `tdux:setCanvasRendering {kind} {mode}` special, where `{mode}` is one of
+/// `positioned`, `svg`, or `mathml`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum CanvasRendering {
+    /// Absolutely positioned `<span>`s for each glyph and rule. This is
+    /// visually faithful, but not semantic.
+    #[default]
+    Positioned,
+
+    /// An inline SVG image, with `<text>` elements for the glyphs (using the
+    /// same web fonts as the rest of the document) and `<rect>` elements for
+    /// the rules. This scales more robustly than the positioned rendering.
+    Svg,
+
+    /// MathML, if the structure of the canvas can be recognized. Otherwise,
+    /// the positioned rendering is used as a fallback.
+    MathMl,
+}
+
+/// Parse the argument of a `tdux:setCanvasRendering` special, returning the
+/// canvas kind and its rendering mode.
+pub(crate) fn parse_canvas_rendering(
+    spec: &str,
+    common: &mut Common,
+) -> Option<(String, CanvasRendering)> {
+    let parsed = spec.split_once(' ').and_then(|(kind, mode)| {
+        let mode = match mode.trim() {
+            "positioned" => CanvasRendering::Positioned,
+            "svg" => CanvasRendering::Svg,
+            "mathml" => CanvasRendering::MathMl,
+            _ => return None,
+        };
+
+        Some((kind.to_owned(), mode))
+    });
+
+    if parsed.is_none() {
+        tt_warning!(
+            common.status,
+            "ignoring malformatted tdux:setCanvasRendering special `{}`",
+            spec
+        );
+    }
+
+    parsed
+}
+
+/// Parse a `tdux:srcpos LINE FILENAME` special, warning if it's malformatted.
+pub(crate) fn parse_source_position(spec: &str, common: &mut Common) -> Option<SourcePosition> {
+    let parsed = SourcePosition::parse(spec);
+
+    if parsed.is_none() {
+        tt_warning!(
+            common.status,
+            "ignoring malformatted tdux:srcpos special `{}`",
+            spec
+        );
+    }
+
+    parsed
+}
+
 #[derive(Debug)]
 struct CanvasState {
     kind: String,
     depth: usize,
     x0: i32,
     y0: i32,
+    source: Option<SourcePosition>,
     glyphs: Vec<GlyphInfo>,
     rules: Vec<RuleInfo>,
 }
 
 impl CanvasState {
-    fn new(kind: &str, x0: i32, y0: i32) -> Self {
+    fn new(kind: &str, x0: i32, y0: i32, source: Option<SourcePosition>) -> Self {
         CanvasState {
             kind: kind.to_owned(),
             depth: 1,
             x0,
             y0,
+            source,
             glyphs: Vec::new(),
             rules: Vec::new(),
         }
@@ -246,6 +340,14 @@ struct GlyphInfo {
     glyph: u16,
 }
 
+/// A heading element whose text is being collected for the site navigation.
+#[derive(Debug)]
+struct OpenHeading {
+    level: u8,
+    id: String,
+    content_start: usize,
+}
+
 #[derive(Debug)]
 struct RuleInfo {
     dx: i32,
@@ -260,6 +362,8 @@ impl EmittingState {
         main_body_font_num: Option<TexFontNum>,
         templating: Templating,
         tag_associations: HashMap<Element, TexFontNum>,
+        canvas_renderings: HashMap<String, CanvasRendering>,
+        pending_source: Option<SourcePosition>,
     ) -> Result<Self> {
         let rems_per_tex = 1.0
             / main_body_font_num
@@ -282,6 +386,12 @@ impl EmittingState {
                 active_font: FamilyRelativeFontId::Regular,
             }],
             current_canvas: None,
+            canvas_renderings,
+            open_headings: Vec::new(),
+            n_generated_ids: 0,
+            pending_source,
+            source_map: SourceMap::default(),
+            open_paragraph: None,
         })
     }
 
@@ -436,9 +546,29 @@ impl EmittingState {
                     // you can use <div> elements to group logical paragraphs. So
                     // that's what we do.
                     let el = self.create_elem("div", true, common);
+                    let index = self.source_map.add(SourceElementKind::Paragraph);
+                    let src_attr = match self.pending_source.take() {
+                        Some(pos) => {
+                            self.source_attribute(SourceElementKind::Paragraph, index, pos, common)
+                        }
+                        None => String::new(),
+                    };
                     self.push_space_if_needed(x, None);
-                    self.content.push_str("<div class=\"tdux-p\">");
+                    write!(self.content, "<div class=\"tdux-p\"{src_attr}").unwrap();
+
+                    // The engine inserts the source position of a paragraph
+                    // after `\everypar`, so it usually arrives next.
+                    if src_attr.is_empty() {
+                        self.open_paragraph = Some(OpenParagraph {
+                            tag_end: self.content.len(),
+                            index,
+                        });
+                    }
+
+                    self.content.push_char('>');
                     self.push_elem(el, ElementOrigin::EngineAuto);
+                } else {
+                    self.pending_source = None;
                 }
                 Ok(())
             }
@@ -455,7 +585,8 @@ impl EmittingState {
                     canvas.depth += 1;
                 } else {
                     self.close_automatics();
-                    self.current_canvas = Some(CanvasState::new(kind, x, y));
+                    let source = self.pending_source.take();
+                    self.current_canvas = Some(CanvasState::new(kind, x, y, source));
                 }
                 Ok(())
             }
@@ -482,6 +613,7 @@ impl EmittingState {
             }
 
             Special::ManualEnd(tag) => {
+                self.handle_heading_end(tag);
                 self.pop_elem(tag, common);
                 Ok(())
             }
@@ -491,6 +623,21 @@ impl EmittingState {
                 Ok(())
             }
 
+            Special::Anchor(name) => {
+                self.handle_anchor(x, name, common);
+                Ok(())
+            }
+
+            Special::StartLink(target) => {
+                self.handle_start_link(x, target, common);
+                Ok(())
+            }
+
+            Special::EndLink => {
+                self.pop_elem("a", common);
+                Ok(())
+            }
+
             Special::Emit => self.finish_file(common),
 
             Special::SetTemplate(path) => {
@@ -512,6 +659,35 @@ impl EmittingState {
                 Ok(())
             }
 
+            Special::SetCanvasRendering(spec) => {
+                if let Some((kind, mode)) = parse_canvas_rendering(spec, common) {
+                    self.canvas_renderings.insert(kind, mode);
+                }
+                Ok(())
+            }
+
+            Special::SourcePosition(spec) => {
+                // Material inside canvases isn't annotated separately.
+                if self.current_canvas.is_none() {
+                    let pos = parse_source_position(spec, common);
+                    let open = self.open_paragraph.take();
+
+                    match (pos, open) {
+                        (Some(pos), Some(open)) if self.content.len() == open.tag_end + 1 => {
+                            let attr = self.source_attribute(
+                                SourceElementKind::Paragraph,
+                                open.index,
+                                pos,
+                                common,
+                            );
+                            self.content.current_content.insert_str(open.tag_end, &attr);
+                        }
+                        (pos, _) => self.pending_source = pos,
+                    }
+                }
+                Ok(())
+            }
+
             other => {
                 tt_warning!(common.status, "ignoring unrecognized special: {}", other);
                 Ok(())
@@ -519,6 +695,117 @@ impl EmittingState {
         }
     }
 
+    /// Record the source position of an element, given its index in the
+    /// source map, and get the text of its `data-src` attribute. The attribute
+    /// text starts with a space, and is empty if attributes are disabled.
+    fn source_attribute(
+        &mut self,
+        kind: SourceElementKind,
+        index: usize,
+        pos: SourcePosition,
+        common: &Common,
+    ) -> String {
+        let mut attr = String::new();
+
+        if common.source_positions.attributes {
+            attr.push_str(" data-src=\"");
+            html_escape::encode_double_quoted_attribute_to_string(pos.to_string(), &mut attr);
+            attr.push('"');
+        }
+
+        self.source_map.set_position(kind, index, pos);
+        attr
+    }
+
+    /// Note that a canvas is being emitted, and get the text of its `data-src`
+    /// attribute, as with [`Self::source_attribute`].
+    fn canvas_source_attribute(
+        &mut self,
+        source: Option<SourcePosition>,
+        common: &Common,
+    ) -> String {
+        let index = self.source_map.add(SourceElementKind::Canvas);
+
+        match source {
+            Some(pos) => self.source_attribute(SourceElementKind::Canvas, index, pos, common),
+            None => String::new(),
+        }
+    }
+
+    /// Handle an anchor that can be the target of links.
+    ///
+    /// ```notest
+    /// \special{tdux:anchor name}
+    /// ```
+    ///
+    /// The anchor becomes an HTML element ID. Links to it will work from any
+    /// of the output files created by `tdux:setOutputPath`.
+    fn handle_anchor(&mut self, x: i32, name: &str, common: &mut Common) {
+        let name = name.trim();
+
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            tt_warning!(
+                common.status,
+                "ignoring TDUX anchor -- invalid name: {:?}",
+                name
+            );
+            return;
+        }
+
+        if !self.templating.links_mut().add_anchor(name) {
+            tt_warning!(
+                common.status,
+                "duplicate TDUX anchor `{}`; links will go to its first definition",
+                name
+            );
+            return;
+        }
+
+        self.push_space_if_needed(x, None);
+        self.content.push_str("<a id=\"");
+        self.content
+            .push_with_html_double_quoted_attribute_escaping(name);
+        self.content.push_str("\"></a>");
+    }
+
+    /// Handle the start of a hyperlink.
+    ///
+    /// ```notest
+    /// \special{tdux:startLink #name} % link to the anchor `name`
+    /// \special{tdux:startLink https://example.com/} % link to a URL
+    /// ...
+    /// \special{tdux:endLink}
+    /// ```
+    ///
+    /// Links to anchors are resolved into relative URLs once all of the output
+    /// files are known.
+    fn handle_start_link(&mut self, x: i32, target: &str, common: &mut Common) {
+        let target = target.trim();
+
+        if target.is_empty() || target == "#" {
+            tt_warning!(
+                common.status,
+                "TDUX link has no target; it will not go anywhere"
+            );
+        }
+
+        self.close_automatics();
+        let el = self.create_elem("a", true, common);
+        self.push_space_if_needed(x, None);
+        self.content.push_str("<a href=\"");
+
+        if let Some(anchor) = target.strip_prefix('#').filter(|a| !a.is_empty()) {
+            let placeholder = self.templating.links_mut().placeholder(anchor);
+            self.content.push_str(&placeholder);
+        } else {
+            self.content
+                .push_with_html_double_quoted_attribute_escaping(target);
+        }
+
+        self.content.push_str("\">");
+        self.push_elem(el, ElementOrigin::Manual);
+    }
+
     /// Handle a "flexible" start tag.
     ///
     /// These start tags are built with a line-oriented structure that aims to
@@ -688,6 +975,32 @@ impl EmittingState {
             }
         }
 
+        // Headings are recorded for the site navigation, which needs to be
+        // able to link to them.
+        let heading_level = match tagname.as_bytes() {
+            [b'h', n @ b'1'..=b'6'] => Some(n - b'0'),
+            _ => None,
+        };
+
+        let heading_id = heading_level.map(|_| {
+            let explicit = unquoted_attrs
+                .iter()
+                .chain(double_quoted_attrs.iter())
+                .find(|(name, value)| name == "id" && value.as_deref().unwrap_or_default() != "")
+                .and_then(|(_, value)| value.clone());
+
+            explicit.unwrap_or_else(|| {
+                // An empty `id` is replaced, so that the element doesn't end up
+                // with two of them.
+                unquoted_attrs.retain(|(name, _)| name != "id");
+                double_quoted_attrs.retain(|(name, _)| name != "id");
+                self.n_generated_ids += 1;
+                let id = format!("tdux-h{}", self.n_generated_ids);
+                double_quoted_attrs.push(("id".to_owned(), Some(id.clone())));
+                id
+            })
+        });
+
         self.push_space_if_needed(x, None);
         self.content.push_char('<');
         self.content.push_with_html_escaping(tagname);
@@ -756,9 +1069,40 @@ impl EmittingState {
 
         self.content.push_char('>');
         self.elem_stack.push(elstate);
+
+        if let (Some(level), Some(id)) = (heading_level, heading_id) {
+            self.open_headings.push(OpenHeading {
+                level,
+                id,
+                content_start: self.content.len(),
+            });
+        }
+
         Ok(())
     }
 
+    /// If *tag* closes the innermost open heading, record it for the site
+    /// navigation.
+    fn handle_heading_end(&mut self, tag: &str) {
+        let matches = self
+            .open_headings
+            .last()
+            .map(|h| tag.strip_prefix('h') == Some(&h.level.to_string()))
+            .unwrap_or(false);
+
+        if matches {
+            let h = self.open_headings.pop().unwrap();
+            let text = navigation::html_to_text(self.content.since(h.content_start));
+
+            self.templating.add_heading(Heading {
+                level: h.level,
+                text,
+                id: h.id,
+                content_offset: h.content_start,
+            });
+        }
+    }
+
     pub(crate) fn handle_text_and_glyphs(
         &mut self,
         font_num: TexFontNum,
@@ -769,6 +1113,8 @@ impl EmittingState {
         common: &mut Common,
     ) -> Result<()> {
         if let Some(c) = self.current_canvas.as_mut() {
+            self.fonts.mark_glyphs_used(font_num, glyphs, None);
+
             for i in 0..glyphs.len() {
                 c.glyphs.push(GlyphInfo {
                     dx: xs[i] - c.x0,
@@ -778,6 +1124,8 @@ impl EmittingState {
                 });
             }
         } else if !glyphs.is_empty() {
+            self.fonts.mark_glyphs_used(font_num, glyphs, Some(text));
+
             self.set_up_for_font(xs[0], font_num, common);
             self.push_space_if_needed(xs[0], Some(font_num));
             self.content.push_with_html_escaping(text);
@@ -813,6 +1161,8 @@ impl EmittingState {
         ys: &[i32],
         common: &mut Common,
     ) -> Result<()> {
+        self.fonts.mark_glyphs_used(font_num, glyphs, None);
+
         if let Some(c) = self.current_canvas.as_mut() {
             for i in 0..glyphs.len() {
                 c.glyphs.push(GlyphInfo {
@@ -1006,6 +1356,30 @@ impl EmittingState {
             _ => false,
         };
 
+        let rendering = self
+            .canvas_renderings
+            .get(&canvas.kind)
+            .copied()
+            .unwrap_or_default();
+
+        if rendering == CanvasRendering::MathMl {
+            if let Some((mathml, x_max_tex)) = self.canvas_to_mathml(&canvas, !inline)? {
+                let src_attr = self.canvas_source_attribute(canvas.source.take(), common);
+
+                // The MathML always starts with the opening tag of the `<math>`
+                // element.
+                match mathml.strip_prefix("<math") {
+                    Some(rest) => write!(self.content, "<math{src_attr}{rest}").unwrap(),
+                    None => self.content.push_str(&mathml),
+                }
+
+                let cur_space_width = self.fonts.maybe_get_font_space_width(None);
+                self.content
+                    .update_content_pos(x_max_tex + canvas.x0, cur_space_width);
+                return Ok(());
+            }
+        }
+
         // First pass: get overall bounds of all the glyphs (from their metrics)
         // and rules. We need to gather this information first because as we
         // emit glyphs we have to specify their positions relative to the edges
@@ -1051,10 +1425,11 @@ impl EmittingState {
             // The canvas is constructed so that we know `width` and `height`
             // are positive.
 
+            // Rules extend upwards from their reference point.
             let xmin = ri.dx;
             let xmax = ri.dx + ri.width;
-            let ymin = ri.dy;
-            let ymax = ri.dy + ri.height;
+            let ymin = ri.dy - ri.height;
+            let ymax = ri.dy;
 
             if first {
                 x_min_tex = xmin;
@@ -1079,6 +1454,15 @@ impl EmittingState {
         let mut inner_content = String::default();
         let mut ch_str_buf = [0u8; 4];
 
+        if rendering == CanvasRendering::Svg {
+            self.write_canvas_svg(
+                &mut canvas,
+                (x_min_tex, y_min_tex, x_max_tex, y_max_tex),
+                &mut inner_content,
+                common,
+            );
+        }
+
         for gi in canvas.glyphs.drain(..) {
             let (text_info, size, baseline_factor) =
                 self.fonts
@@ -1171,6 +1555,7 @@ impl EmittingState {
         };
 
         let element = self.create_elem(element, true, common);
+        let src_attr = self.canvas_source_attribute(canvas.source.take(), common);
 
         // Negative padding values are illegal.
         let pad_left = match -x_min_tex as f32 * self.rems_per_tex {
@@ -1180,9 +1565,10 @@ impl EmittingState {
 
         write!(
             self.content,
-            "<{} class=\"canvas {}\" style=\"width: {}rem; height: {}rem; padding-left: {}rem{}\">",
+            "<{} class=\"canvas {}\"{} style=\"width: {}rem; height: {}rem; padding-left: {}rem{}\">",
             element.name(),
             layout_class,
+            src_attr,
             (x_max_tex - x_min_tex) as f32 * self.rems_per_tex,
             (y_max_tex - y_min_tex) as f32 * self.rems_per_tex,
             pad_left,
@@ -1197,10 +1583,148 @@ impl EmittingState {
         Ok(())
     }
 
+    /// Render the contents of a canvas as an inline SVG image, draining its
+    /// glyphs and rules.
+    ///
+    /// The image fills the content box of the canvas element. Its user units
+    /// are rems, and its viewBox is set from the canvas *bounds* (`x_min`,
+    /// `y_min`, `x_max`, `y_max`, in TeX units) so that glyphs and rules can be
+    /// placed at their offsets relative to the canvas reference point, landing
+    /// in the same places as in the positioned rendering.
+    fn write_canvas_svg(
+        &mut self,
+        canvas: &mut CanvasState,
+        bounds: (i32, i32, i32, i32),
+        dest: &mut String,
+        common: &mut Common,
+    ) {
+        let (x_min_tex, y_min_tex, x_max_tex, y_max_tex) = bounds;
+        let mut ch_str_buf = [0u8; 4];
+
+        // The positioned rendering measures offsets from the padding edge of
+        // the canvas element, while the SVG sits inside its padding.
+        let pad_left_tex = std::cmp::max(-x_min_tex, 0);
+
+        write!(
+            dest,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" class=\"canvas-svg\" width=\"100%\" height=\"100%\" \
+             viewBox=\"{} {} {} {}\" fill=\"currentColor\" overflow=\"visible\" style=\"white-space: pre\">",
+            pad_left_tex as f32 * self.rems_per_tex,
+            y_min_tex as f32 * self.rems_per_tex,
+            (x_max_tex - x_min_tex) as f32 * self.rems_per_tex,
+            (y_max_tex - y_min_tex) as f32 * self.rems_per_tex,
+        )
+        .unwrap();
+
+        for gi in canvas.glyphs.drain(..) {
+            let (text_info, size, _baseline_factor) =
+                self.fonts
+                    .process_glyph_for_canvas(gi.font_num, gi.glyph, common.status);
+
+            // Unlike the positioned rendering, SVG text is placed by its
+            // baseline, so the glyph offsets can be used directly.
+            if let Some((ch, font_sel)) = text_info {
+                let ch_as_str = ch.encode_utf8(&mut ch_str_buf);
+
+                write!(
+                    dest,
+                    "<text x=\"{}\" y=\"{}\" font-size=\"{}\" style=\"{}\">",
+                    gi.dx as f32 * self.rems_per_tex,
+                    gi.dy as f32 * self.rems_per_tex,
+                    size as f32 * self.rems_per_tex,
+                    font_sel,
+                )
+                .unwrap();
+                html_escape::encode_text_to_string(ch_as_str, dest);
+                dest.push_str("</text>");
+            }
+        }
+
+        for ri in canvas.rules.drain(..) {
+            write!(
+                dest,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+                ri.dx as f32 * self.rems_per_tex,
+                (ri.dy - ri.height) as f32 * self.rems_per_tex,
+                ri.width as f32 * self.rems_per_tex,
+                ri.height as f32 * self.rems_per_tex,
+            )
+            .unwrap();
+        }
+
+        dest.push_str("</svg>");
+    }
+
+    /// Try to render a canvas as MathML.
+    ///
+    /// If successful, the return value is the MathML and the right edge of the
+    /// canvas contents. If the structure of the canvas can't be recognized,
+    /// `Ok(None)` is returned.
+    fn canvas_to_mathml(
+        &mut self,
+        canvas: &CanvasState,
+        display: bool,
+    ) -> Result<Option<(String, i32)>> {
+        let mut glyphs = Vec::with_capacity(canvas.glyphs.len());
+        let mut x_max_tex = 0;
+
+        for gi in &canvas.glyphs[..] {
+            let mapping = atry!(
+                self.fonts.get_glyph_mapping(gi.font_num, gi.glyph);
+                ["undeclared font {} in canvas", gi.font_num]
+            );
+
+            // Glyphs without a Unicode interpretation can't be expressed in
+            // MathML.
+            let ch = match mapping {
+                Some(m) => m.get_char(),
+                None => return Ok(None),
+            };
+
+            let (advance, ascent, descent) = self
+                .fonts
+                .get_glyph_metrics(gi.font_num, gi.glyph)?
+                .map(|gm| (gm.advance, gm.ascent, gm.descent))
+                .unwrap_or_default();
+
+            x_max_tex = std::cmp::max(x_max_tex, gi.dx + advance);
+
+            glyphs.push(MathGlyph {
+                x: gi.dx,
+                y: gi.dy,
+                advance,
+                ascent,
+                descent,
+                size: self.fonts.get_font_size(gi.font_num),
+                font_num: gi.font_num,
+                upright: self.fonts.is_upright(gi.font_num)?,
+                ch,
+            });
+        }
+
+        let rules: Vec<_> = canvas
+            .rules
+            .iter()
+            .map(|ri| {
+                x_max_tex = std::cmp::max(x_max_tex, ri.dx + ri.width);
+
+                MathRule {
+                    x: ri.dx,
+                    y: ri.dy,
+                    width: ri.width,
+                    height: ri.height,
+                }
+            })
+            .collect();
+
+        Ok(mathml::canvas_to_mathml(&glyphs, &rules, display).map(|m| (m, x_max_tex)))
+    }
+
     fn finish_file(&mut self, common: &mut Common) -> Result<()> {
+        let source_map = std::mem::take(&mut self.source_map);
+        self.open_paragraph = None;
         self.templating
-            .set_variable("tduxContent", self.content.take());
-        self.templating.emit(common)?;
+            .emit(self.content.take(), source_map, common)?;
 
         let cur_space_width = self.fonts.maybe_get_font_space_width(None);
         self.content.update_content_pos(0, cur_space_width);
@@ -1222,3 +1746,199 @@ impl EmittingState {
         FinalizingState::new(self.fonts, self.templating, self.assets)
     }
 }
+
+/// Decide whether the gap between text ending at *x1* and text starting at
+/// *x0* is a word space.
+///
+/// The gap is compared to the size of the space, which can be determined from
+/// either the preceding text or the new text, since in various circumstances
+/// either one or the other might not be defined. If both are defined, use
+/// whatever's smaller. There's probably a smoother way to do this logic?
+pub(crate) fn is_word_space(
+    x1: i32,
+    x0: i32,
+    prev_space_width: Option<FixedPoint>,
+    cur_space_width: Option<FixedPoint>,
+) -> bool {
+    let space_width = match (prev_space_width, cur_space_width) {
+        (Some(w1), Some(w2)) => FixedPoint::min(w1, w2),
+        (Some(w), None) => w,
+        (None, Some(w)) => w,
+        (None, None) => 0,
+    };
+
+    // If the x difference is larger than 1/4 of the space_width, let's say that
+    // we need a space. I made up the 1/4.
+    4 * (x0 - x1) > space_width
+}
+
+#[cfg(test)]
+mod tests {
+    use tectonic_xdv::FileType;
+
+    use crate::testutil::{
+        lmroman_glyph, make_xdv, output_text, run_spx2html, spx_of_specials, LMROMAN, LMROMAN_NAME,
+    };
+
+    const TEMPLATE: &[u8] = b"{{ tduxContent }}";
+
+    fn process(attributes: bool, maps: bool) -> std::collections::HashMap<String, Vec<u8>> {
+        let spx = spx_of_specials(&[
+            "tdux:addTemplate t.html",
+            "tdux:setTemplate t.html",
+            "tdux:setOutputPath index.html",
+            "tdux:asp",
+            "tdux:srcpos 3 main.tex",
+            "tdux:dt First.",
+            "tdux:aep",
+            "tdux:asp",
+            "tdux:dt Second.",
+            "tdux:aep",
+            "tdux:asp",
+            "tdux:srcpos 9 sub dir/ch1.tex",
+            "tdux:dt Third.",
+            "tdux:aep",
+            "tdux:emit",
+            "tdux:contentFinished",
+        ]);
+
+        run_spx2html(&spx, &[("t.html", TEMPLATE)], |e| {
+            e.source_position_attributes(attributes)
+                .source_position_maps(maps);
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn source_position_attributes() {
+        let outputs = process(true, false);
+        let html = output_text(&outputs, "index.html");
+        assert!(html.contains(r#"<div class="tdux-p" data-src="main.tex:3">First."#));
+        assert!(html.contains(r#"<div class="tdux-p">Second."#));
+        assert!(html.contains(r#"<div class="tdux-p" data-src="sub dir/ch1.tex:9">Third."#));
+        assert!(!outputs.contains_key("index.html.srcmap.json"));
+    }
+
+    #[test]
+    fn source_position_maps() {
+        let outputs = process(false, true);
+        assert!(!output_text(&outputs, "index.html").contains("data-src"));
+
+        let map: serde_json::Value =
+            serde_json::from_str(output_text(&outputs, "index.html.srcmap.json")).unwrap();
+        assert_eq!(
+            map,
+            serde_json::json!({
+                "page": "index.html",
+                "entries": [
+                    {"kind": "paragraph", "index": 0, "file": "main.tex", "line": 3},
+                    {"kind": "paragraph", "index": 2, "file": "sub dir/ch1.tex", "line": 9},
+                ],
+            })
+        );
+    }
+
+    const PT: i32 = 65536;
+
+    /// Render an inline math canvas containing two rules: one sitting on the
+    /// baseline, and one hanging below it. Without a main body font, a rem is
+    /// one TeX point.
+    fn render_rules(extra_specials: &[&str]) -> String {
+        let spx = make_xdv(FileType::Spx, |w| {
+            w.begin_page(&[0; 10])?;
+            w.special(b"tdux:addTemplate t.html")?;
+            w.special(b"tdux:setTemplate t.html")?;
+            w.special(b"tdux:setOutputPath index.html")?;
+
+            for s in extra_specials {
+                w.special(s.as_bytes())?;
+            }
+
+            w.special(b"tdux:cs math")?;
+            w.put_rule(3 * PT, 4 * PT)?;
+            w.push()?;
+            w.down(PT)?;
+            w.put_rule(PT, 2 * PT)?;
+            w.pop()?;
+            w.special(b"tdux:ce math")?;
+            w.special(b"tdux:emit")?;
+            w.special(b"tdux:contentFinished")?;
+            w.end_page()
+        });
+
+        let outputs = run_spx2html(&spx, &[("t.html", TEMPLATE)], |_| {}).unwrap();
+        output_text(&outputs, "index.html").to_owned()
+    }
+
+    #[test]
+    fn canvas_rule_bounds() {
+        // Rules extend upwards from their reference points, so the canvas runs
+        // from 3pt above the baseline to 1pt below it.
+        let html = render_rules(&[]);
+        assert!(html.contains(
+            r#"style="width: 4rem; height: 4rem; padding-left: 0rem; vertical-align: -1rem">"#
+        ));
+        assert!(html.contains(
+            r#"<span class="cr" style="top: 0rem; left: 0rem; width: 4rem; height: 3rem;"></span>"#
+        ));
+        assert!(html.contains(
+            r#"<span class="cr" style="top: 3rem; left: 0rem; width: 2rem; height: 1rem;"></span>"#
+        ));
+    }
+
+    #[test]
+    fn svg_canvas_rules() {
+        let html = render_rules(&["tdux:setCanvasRendering math svg"]);
+        assert!(html.contains(r#"viewBox="0 -3 4 4""#));
+        assert!(html.contains(r#"<rect x="0" y="-3" width="4" height="3"/>"#));
+        assert!(html.contains(r#"<rect x="0" y="0" width="2" height="1"/>"#));
+        assert!(!html.contains(r#"class="cr""#));
+
+        // Other kinds of canvas aren't affected.
+        let html = render_rules(&["tdux:setCanvasRendering dmath svg"]);
+        assert!(!html.contains("<svg"));
+        assert!(html.contains(r#"class="cr""#));
+    }
+
+    #[test]
+    fn svg_canvas_glyphs() {
+        let spx = make_xdv(FileType::Spx, |w| {
+            w.begin_page(&[0; 10])?;
+            w.define_native_font(LMROMAN_NAME, 1, 12 * PT, 0, None, None, None, None)?;
+            w.special(b"tdux:addTemplate t.html")?;
+            w.special(b"tdux:setTemplate t.html")?;
+            w.special(b"tdux:setOutputPath index.html")?;
+            w.special(b"tdux:setCanvasRendering dmath svg")?;
+            w.special(b"tdux:cs dmath")?;
+            w.set_font(1)?;
+            w.set_glyphs(
+                6 * PT,
+                &[lmroman_glyph('x'), lmroman_glyph('<')],
+                &[0, 6 * PT],
+                &[0, 0],
+            )?;
+            w.special(b"tdux:ce dmath")?;
+            w.special(b"tdux:emit")?;
+            w.special(b"tdux:contentFinished")?;
+            w.end_page()
+        });
+
+        let outputs = run_spx2html(
+            &spx,
+            &[("t.html", TEMPLATE), (LMROMAN_NAME, LMROMAN)],
+            |_| {},
+        )
+        .unwrap();
+        let html = output_text(&outputs, "index.html");
+        let style = "font-family: lmroman12_regular_otf";
+        assert!(html.contains(&format!(
+            r#"<text x="0" y="0" font-size="12" style="{}">x</text>"#,
+            style
+        )));
+        assert!(html.contains(&format!(
+            r#"<text x="6" y="0" font-size="12" style="{}">&lt;</text>"#,
+            style
+        )));
+        assert!(!html.contains(r#"class="ci""#));
+    }
+}
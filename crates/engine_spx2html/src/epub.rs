@@ -0,0 +1,528 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Packaging of the HTML output tree as an EPUB 3 e-book.
+//!
+//! An EPUB is a ZIP archive containing the XHTML content documents and their
+//! resources, a "package document" listing them along with the publication
+//! metadata, and a navigation document. The [`EpubOutput`] output tree
+//! gathers the files created by an SPX-to-HTML run in memory, then assembles
+//! them into such an archive when processing is finished.
+//!
+//! The HTML templates need to produce well-formed XHTML for the result to be
+//! valid.
+
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::{
+    fmt::Write as FmtWrite,
+    io::{Cursor, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tectonic_bridge_core::DriverHooks;
+use tectonic_errors::prelude::*;
+use tectonic_status_base::StatusBackend;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::output::OutputTree;
+
+/// The directory of the archive containing the publication's files.
+const CONTENT_DIR: &str = "OEBPS";
+
+/// Characters that need to be escaped in the manifest's relative URLs.
+const HREF: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'%').add(b'?');
+
+/// Metadata describing an EPUB publication.
+///
+/// All of the fields are optional, but EPUB readers will display the title
+/// and creators, so they're worth specifying.
+#[derive(Clone, Debug, Default)]
+pub struct EpubMetadata {
+    /// The title of the publication. Defaults to the title of its first page.
+    pub title: Option<String>,
+
+    /// The people or organizations primarily responsible for creating the
+    /// publication, such as its authors.
+    pub creators: Vec<String>,
+
+    /// The language of the publication, as a BCP 47 tag. Defaults to `en`.
+    pub language: Option<String>,
+
+    /// An identifier for the publication, such as an ISBN or a UUID URN.
+    /// Defaults to a URN derived from the name of the EPUB file.
+    pub identifier: Option<String>,
+
+    /// The publisher of the publication.
+    pub publisher: Option<String>,
+
+    /// A description of the publication's content.
+    pub description: Option<String>,
+
+    /// The publication date, in ISO 8601 format.
+    pub date: Option<String>,
+
+    /// When the publication was last modified. Defaults to the Unix epoch, to
+    /// keep the output reproducible.
+    pub modified: Option<SystemTime>,
+}
+
+/// An output tree that packages its files as an EPUB 3 e-book.
+///
+/// Files are kept in memory until processing is finished, at which point the
+/// EPUB archive is written as a single file to another output tree. The pages
+/// are placed in the reading order ("spine") of the publication in the order
+/// in which they were first emitted, and every page appears in the table of
+/// contents. Auxiliary files, like source position maps and the search index,
+/// are left out of the archive.
+#[derive(Debug)]
+pub struct EpubOutput<T> {
+    dest: T,
+    epub_path: String,
+    metadata: EpubMetadata,
+    files: Vec<(String, Vec<u8>)>,
+    pages: Vec<(String, String)>,
+}
+
+impl<T: OutputTree> EpubOutput<T> {
+    /// Create an output tree that writes an EPUB file.
+    ///
+    /// The archive will be written to the output tree *dest* at the path
+    /// *epub_path*.
+    pub fn new(dest: T, epub_path: impl Into<String>, metadata: EpubMetadata) -> Self {
+        EpubOutput {
+            dest,
+            epub_path: epub_path.into(),
+            metadata,
+            files: Vec::new(),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Assemble the EPUB archive.
+    fn package(&self) -> Result<Vec<u8>> {
+        ensure!(
+            !self.pages.is_empty(),
+            "cannot create EPUB `{}`: the document didn't emit any HTML pages",
+            self.epub_path
+        );
+
+        let mut nav_path = "nav.xhtml".to_owned();
+
+        while self.files.iter().any(|(p, _)| *p == nav_path) {
+            nav_path.insert(0, '_');
+        }
+
+        let title = self.metadata.title.as_deref().unwrap_or(&self.pages[0].1);
+        let language = self.metadata.language.as_deref().unwrap_or("en");
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        // The mimetype file must come first, uncompressed, so that the archive
+        // can be identified by its leading bytes.
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(b"application/epub+zip")?;
+
+        zip.start_file("META-INF/container.xml", deflated)?;
+        write!(
+            zip,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="{CONTENT_DIR}/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#
+        )?;
+
+        zip.start_file(format!("{CONTENT_DIR}/content.opf"), deflated)?;
+        zip.write_all(self.package_document(title, language, &nav_path).as_bytes())?;
+
+        zip.start_file(format!("{CONTENT_DIR}/{nav_path}"), deflated)?;
+        zip.write_all(self.nav_document(title, language).as_bytes())?;
+
+        for (path, contents) in &self.files {
+            zip.start_file(format!("{CONTENT_DIR}/{path}"), deflated)?;
+            zip.write_all(contents)?;
+        }
+
+        Ok(zip.finish()?.into_inner())
+    }
+
+    /// Generate the OPF package document.
+    fn package_document(&self, title: &str, language: &str, nav_path: &str) -> String {
+        let md = &self.metadata;
+        let mut opf = String::new();
+
+        let identifier = match md.identifier {
+            Some(ref i) => i.clone(),
+            None => {
+                let stem = self.epub_path.rsplit('/').next().unwrap_or_default();
+                let stem = stem.strip_suffix(".epub").unwrap_or(stem);
+                format!("urn:tectonic:{stem}")
+            }
+        };
+
+        writeln!(
+            opf,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="pub-id" xml:lang="{}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="pub-id">{}</dc:identifier>
+    <dc:title>{}</dc:title>
+    <dc:language>{}</dc:language>"#,
+            encode_double_quoted_attribute(language),
+            encode_text(&identifier),
+            encode_text(title),
+            encode_text(language),
+        )
+        .unwrap();
+
+        for creator in &md.creators {
+            writeln!(opf, "    <dc:creator>{}</dc:creator>", encode_text(creator)).unwrap();
+        }
+
+        for (element, value) in [
+            ("publisher", &md.publisher),
+            ("description", &md.description),
+            ("date", &md.date),
+        ] {
+            if let Some(value) = value {
+                writeln!(
+                    opf,
+                    "    <dc:{element}>{}</dc:{element}>",
+                    encode_text(value)
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(
+            opf,
+            r#"    <meta property="dcterms:modified">{}</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="{}" media-type="application/xhtml+xml" properties="nav"/>"#,
+            format_utc(md.modified.unwrap_or(UNIX_EPOCH)),
+            href(nav_path),
+        )
+        .unwrap();
+
+        for (index, (path, contents)) in self.files.iter().enumerate() {
+            let media_type = media_type(path);
+            let mut properties = Vec::new();
+
+            if media_type == "application/xhtml+xml" {
+                let text = String::from_utf8_lossy(contents);
+
+                if text.contains("<script") {
+                    properties.push("scripted");
+                }
+
+                if text.contains("<svg") {
+                    properties.push("svg");
+                }
+            }
+
+            let properties = if properties.is_empty() {
+                String::new()
+            } else {
+                format!(r#" properties="{}""#, properties.join(" "))
+            };
+
+            writeln!(
+                opf,
+                r#"    <item id="item{index}" href="{}" media-type="{media_type}"{properties}/>"#,
+                href(path),
+            )
+            .unwrap();
+        }
+
+        opf.push_str("  </manifest>\n  <spine>\n");
+
+        for (page_path, _) in &self.pages {
+            if let Some(index) = self.files.iter().position(|(p, _)| p == page_path) {
+                writeln!(opf, r#"    <itemref idref="item{index}"/>"#).unwrap();
+            }
+        }
+
+        opf.push_str("  </spine>\n</package>\n");
+        opf
+    }
+
+    /// Generate the XHTML navigation document.
+    fn nav_document(&self, title: &str, language: &str) -> String {
+        let mut nav = String::new();
+
+        writeln!(
+            nav,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{lang}" lang="{lang}">
+<head>
+  <meta charset="utf-8"/>
+  <title>{title}</title>
+</head>
+<body>
+  <nav epub:type="toc" id="toc">
+    <h1>{title}</h1>
+    <ol>"#,
+            lang = encode_double_quoted_attribute(language),
+            title = encode_text(title),
+        )
+        .unwrap();
+
+        for (path, page_title) in &self.pages {
+            writeln!(
+                nav,
+                r#"      <li><a href="{}">{}</a></li>"#,
+                href(path),
+                encode_text(page_title)
+            )
+            .unwrap();
+        }
+
+        nav.push_str("    </ol>\n  </nav>\n</body>\n</html>\n");
+        nav
+    }
+}
+
+impl<T: OutputTree> OutputTree for EpubOutput<T> {
+    fn write_file(
+        &mut self,
+        path: &str,
+        contents: &[u8],
+        _hooks: &mut dyn DriverHooks,
+        _status: &mut dyn StatusBackend,
+    ) -> Result<()> {
+        match self.files.iter_mut().find(|(p, _)| p == path) {
+            Some(file) => file.1 = contents.to_owned(),
+            None => self.files.push((path.to_owned(), contents.to_owned())),
+        }
+
+        Ok(())
+    }
+
+    fn page_written(&mut self, path: &str, title: &str) {
+        // If a page is written more than once, its contents are replaced, but
+        // it keeps its original place in the reading order.
+        match self.pages.iter_mut().find(|(p, _)| p == path) {
+            Some(page) => page.1 = title.to_owned(),
+            None => self.pages.push((path.to_owned(), title.to_owned())),
+        }
+    }
+
+    fn auxiliary_file_written(&mut self, path: &str) {
+        self.files.retain(|(p, _)| p != path);
+    }
+
+    fn finish(
+        &mut self,
+        hooks: &mut dyn DriverHooks,
+        status: &mut dyn StatusBackend,
+    ) -> Result<()> {
+        let epub = atry!(
+            self.package();
+            ["failed to assemble EPUB `{}`", self.epub_path]
+        );
+
+        self.dest
+            .write_file(&self.epub_path, &epub, hooks, status)?;
+        self.dest.finish(hooks, status)
+    }
+}
+
+/// Get the relative URL of a file in the package, escaped for use in an XML
+/// attribute.
+fn href(path: &str) -> String {
+    let url = utf8_percent_encode(path, HREF).to_string();
+    encode_double_quoted_attribute(&url).into_owned()
+}
+
+/// Guess the media type of a file in the package from its extension.
+fn media_type(path: &str) -> &'static str {
+    let ext = path.rsplit_once('.').map(|t| t.1).unwrap_or_default();
+
+    match ext.to_ascii_lowercase().as_ref() {
+        "html" | "htm" | "xhtml" => "application/xhtml+xml",
+        "css" => "text/css",
+        "js" => "application/javascript",
+        "json" => "application/json",
+        "otf" => "font/otf",
+        "ttf" => "font/ttf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "gif" => "image/gif",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Format a time as an ISO 8601 UTC timestamp with a precision of seconds.
+fn format_utc(t: SystemTime) -> String {
+    let secs = t
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+
+    // Convert the day number to a civil date, following Howard Hinnant's
+    // `civil_from_days` algorithm.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Read, time::Duration};
+    use tectonic_bridge_core::MinimalDriver;
+    use tectonic_status_base::NoopStatusBackend;
+    use zip::ZipArchive;
+
+    use crate::{testutil::MemoryIo, FilesystemOutput};
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_utc(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_utc(UNIX_EPOCH + Duration::from_secs(951_782_400 + 3661)),
+            "2000-02-29T01:01:01Z"
+        );
+        assert_eq!(
+            format_utc(UNIX_EPOCH + Duration::from_secs(1_792_281_599)),
+            "2026-10-17T23:59:59Z"
+        );
+    }
+
+    /// Create an archive from some files and pages, as passed to the
+    /// [`OutputTree`] methods.
+    fn package(files: &[(&str, &str)], pages: &[(&str, &str)], aux: &[&str]) -> Vec<u8> {
+        let mut hooks = MinimalDriver::new(MemoryIo::default());
+        let mut status = NoopStatusBackend::default();
+        let mut epub = EpubOutput::new(
+            FilesystemOutput::new("unused"),
+            "book.epub",
+            EpubMetadata::default(),
+        );
+
+        for (path, contents) in files {
+            epub.write_file(path, contents.as_bytes(), &mut hooks, &mut status)
+                .unwrap();
+        }
+
+        for (path, title) in pages {
+            epub.page_written(path, title);
+        }
+
+        for path in aux {
+            epub.auxiliary_file_written(path);
+        }
+
+        epub.package().unwrap()
+    }
+
+    fn entry_text(zip: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+        let mut text = String::new();
+        zip.by_name(name)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn packaging() {
+        let data = package(
+            &[
+                ("index.html", "<html>old</html>"),
+                ("ch 1/intro.html", "<html><script>x</script></html>"),
+                ("fonts.css", ""),
+                ("index.html.srcmap.json", "{}"),
+                ("search.js", ""),
+                ("index.html", "<html>new</html>"),
+            ],
+            &[
+                ("index.html", "Home"),
+                ("ch 1/intro.html", "Intro"),
+                ("index.html", "Start"),
+            ],
+            &["index.html.srcmap.json", "search.js"],
+        );
+
+        // The mimetype must be first and uncompressed, so that the archive can
+        // be recognized from the leading bytes.
+        assert_eq!(&data[30..38], b"mimetype");
+        let mut zip = ZipArchive::new(Cursor::new(data)).unwrap();
+        let first = zip.by_index(0).unwrap();
+        assert_eq!(first.name(), "mimetype");
+        assert_eq!(first.compression(), CompressionMethod::Stored);
+        drop(first);
+        assert_eq!(entry_text(&mut zip, "mimetype"), "application/epub+zip");
+
+        let container = entry_text(&mut zip, "META-INF/container.xml");
+        assert!(container.contains(
+            r#"<rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>"#
+        ));
+
+        // Every file of the publication is in the manifest, and nothing else.
+        let opf = entry_text(&mut zip, "OEBPS/content.opf");
+        let mut names: Vec<_> = zip.file_names().map(|n| n.to_owned()).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "META-INF/container.xml",
+                "OEBPS/ch 1/intro.html",
+                "OEBPS/content.opf",
+                "OEBPS/fonts.css",
+                "OEBPS/index.html",
+                "OEBPS/nav.xhtml",
+                "mimetype",
+            ]
+        );
+        assert!(opf.contains(
+            r#"<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#
+        ));
+        assert!(opf.contains(
+            r#"<item id="item0" href="index.html" media-type="application/xhtml+xml"/>"#
+        ));
+        assert!(opf.contains(
+            r#"<item id="item1" href="ch%201/intro.html" media-type="application/xhtml+xml" properties="scripted"/>"#
+        ));
+        assert!(opf.contains(r#"<item id="item2" href="fonts.css" media-type="text/css"/>"#));
+        assert_eq!(opf.matches("<item ").count(), 4);
+        assert!(opf.contains("<dc:title>Start</dc:title>\n    <dc:language>en</dc:language>"));
+        assert!(opf.contains(r#"<dc:identifier id="pub-id">urn:tectonic:book</dc:identifier>"#));
+
+        // Pages written twice keep their first place in the reading order.
+        assert!(opf.contains(
+            "<spine>\n    <itemref idref=\"item0\"/>\n    <itemref idref=\"item1\"/>\n  </spine>"
+        ));
+
+        let nav = entry_text(&mut zip, "OEBPS/nav.xhtml");
+        assert!(nav.contains(
+            "<ol>\n      <li><a href=\"index.html\">Start</a></li>\n      \
+             <li><a href=\"ch%201/intro.html\">Intro</a></li>\n    </ol>"
+        ));
+        assert_eq!(entry_text(&mut zip, "OEBPS/index.html"), "<html>new</html>");
+    }
+}
@@ -85,12 +85,14 @@ impl FinalizingState {
     }
 
     fn finish_file(&mut self, common: &mut Common) -> Result<()> {
-        self.templating.set_variable("tduxContent", "");
-        self.templating.emit(common)?;
+        self.templating
+            .emit(String::new(), Default::default(), common)?;
         Ok(())
     }
 
-    pub(crate) fn finished(self) -> (FontEnsemble, Assets) {
-        (self.fonts, self.assets)
+    /// Finish processing the content. The pages still need to be rendered
+    /// with [`Templating::finish`], once the assets are settled.
+    pub(crate) fn finished(self) -> (FontEnsemble, Assets, Templating) {
+        (self.fonts, self.assets, self.templating)
     }
 }
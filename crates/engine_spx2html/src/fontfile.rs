@@ -10,18 +10,24 @@
 //! have to do this, but for math and potentially other situations this is
 //! sometimes necessary.
 
-use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
-use percent_encoding::{utf8_percent_encode, CONTROLS};
+use byteorder::{BigEndian, WriteBytesExt};
 use pinot::{
     math::MathVariants,
     otl::{Feature, SubtableKind},
     types::{FWord, Tag, UfWord},
     FontDataRef, TableProvider,
 };
-use std::{collections::HashMap, num::Wrapping, path::Path};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+};
 use tectonic_errors::prelude::*;
 
-use crate::FixedPoint;
+use crate::{
+    sfnt::{Sfnt, CMAP},
+    subset::subset_font,
+    FixedPoint,
+};
 
 /// A numerical identifier of a glyph in a font.
 pub type GlyphId = u16;
@@ -32,6 +38,10 @@ pub type GlyphId = u16;
 /// inclusive). Values within this range can be converted to Rust "char" values.
 pub type Usv = u32;
 
+/// A rendered font file: its variant-map index, if it's a variant, its output
+/// path, and its contents.
+pub(crate) type RenderedFontFile = (Option<usize>, String, Vec<u8>);
+
 const SSTY: Tag = Tag(0x73_73_74_79);
 
 /// A type for retrieving data about the glyphs used in a particular font.
@@ -42,13 +52,22 @@ pub struct FontFileData {
     /// Currently, this must be an OpenType font.
     buffer: Vec<u8>,
 
+    /// The index of the face within the font data that we're using.
+    face_index: u32,
+
     /// Information about how glyphs can be reverse-mapped to Unicode input
     gmap: HashMap<GlyphId, MapEntry>,
 
+    /// The glyphs that the font's character map assigns to Unicode characters.
+    char_glyphs: HashMap<char, GlyphId>,
+
     /// The glyph for the basic space character, or zero (typically .notdef) if
     /// it can't be found.
     space_glyph: GlyphId,
 
+    /// Whether the font's glyphs are upright, rather than italic or oblique.
+    upright: bool,
+
     units_per_em: UfWord,
 
     hmetrics: Vec<HorizontalMetrics>,
@@ -75,13 +94,9 @@ pub struct FontFileData {
     /// we're not allowed to allocate any new variant glyph mappings.
     no_new_variants: bool,
 
-    /// The index of the CMAP table record in the font data structure. We need
-    /// this for the variant cmap munging.
-    fontdata_cmap_trec_idx: usize,
-
-    /// The offset of the HEAD table within the font data. We need
-    /// this for the variant cmap munging.
-    fontdata_head_offset: u32,
+    /// The glyphs that appear in the document. The emitted font is subsetted
+    /// to these, and whatever else is needed to render them.
+    used_glyphs: BTreeSet<GlyphId>,
 }
 
 /// Information about the reverse-mapping of a glyph to Unicode.
@@ -109,7 +124,7 @@ pub enum MapEntry {
 }
 
 impl MapEntry {
-    fn get_char(&self) -> char {
+    pub(crate) fn get_char(&self) -> char {
         match *self {
             MapEntry::Direct(c) => c,
             MapEntry::SubSuperScript(c, _) => c,
@@ -135,9 +150,6 @@ impl MapEntry {
 /// This would be a great application for OpenType font collections, since they
 /// can have multiple "fonts" that share glyph data. But it looks like browser
 /// CSS support for those is currently poor.
-///
-/// We might also one day wish to extend this system to emit a subsetted version
-/// of the original font.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct GlyphVariantMapping {
     /// The USV that the glyph should be mapped to
@@ -193,6 +205,13 @@ impl FontFileData {
 
         let units_per_em = head.units_per_em();
 
+        // The font is italic if it says so in either the `head` or the `post`
+        // table. The OS/2 table has similar information, but it's optional.
+
+        const MAC_STYLE_ITALIC: u16 = 0x2;
+        let upright = head.mac_style() & MAC_STYLE_ITALIC == 0
+            && font.post().map(|p| p.italic_angle().0 == 0).unwrap_or(true);
+
         // Get the direct mappings. While we're at it, figure out the glyph for
         // the space character, so that we can know how wide spaces are, so that
         // we can guess when to insert spaces into our HTML content.
@@ -203,6 +222,7 @@ impl FontFileData {
         );
 
         let mut gmap = HashMap::new();
+        let mut char_glyphs = HashMap::new();
         let mut space_glyph = 0;
 
         for usv in valid_usvs() {
@@ -220,6 +240,7 @@ impl FontFileData {
             }
 
             gmap.insert(gidx, MapEntry::Direct(c));
+            char_glyphs.insert(c, gidx);
         }
 
         // Check for additional substitution-based mappings.
@@ -278,28 +299,15 @@ impl FontFileData {
             hmetrics.push(HorizontalMetrics { advance, lsb });
         }
 
-        // Get some parameters that we'll if we end up emitting any font
-        // variants with hacked character maps. We know that HEAD and CMAP are
-        // there so we don't worry about these variables never getting
-        // initialized. (Famous last words?)
-
-        let mut fontdata_cmap_trec_idx = 0;
-        let mut fontdata_head_offset = 0;
-
-        for (idx, trec) in font.records().iter().enumerate() {
-            if trec.tag == pinot::head::HEAD {
-                fontdata_head_offset = trec.offset;
-            } else if trec.tag == pinot::cmap::CMAP {
-                fontdata_cmap_trec_idx = idx;
-            }
-        }
-
         // All done!
 
         Ok(FontFileData {
             buffer,
+            face_index,
             gmap,
+            char_glyphs,
             space_glyph,
+            upright,
             units_per_em,
             hmetrics,
             ascender,
@@ -308,8 +316,7 @@ impl FontFileData {
             variant_map_counts: HashMap::new(),
             variant_map_allocations: HashMap::new(),
             no_new_variants: false,
-            fontdata_head_offset,
-            fontdata_cmap_trec_idx,
+            used_glyphs: BTreeSet::new(),
         })
     }
 
@@ -328,6 +335,11 @@ impl FontFileData {
         self.baseline_factor
     }
 
+    /// Whether the font's glyphs are upright, rather than italic or oblique.
+    pub fn is_upright(&self) -> bool {
+        self.upright
+    }
+
     /// Attempt to retrieve metrics information for the given glyph.
     pub fn lookup_metrics(&self, glyph: GlyphId, tex_size: FixedPoint) -> Option<GlyphMetrics> {
         // As of Rust 1.45, the float-to-int cast saturates, which I think
@@ -361,6 +373,60 @@ impl FontFileData {
         }
     }
 
+    /// Record that a glyph appears in the document.
+    pub fn mark_glyph_used(&mut self, glyph: GlyphId) {
+        self.used_glyphs.insert(glyph);
+    }
+
+    /// Record that the document contains text rendered in this font.
+    ///
+    /// The browser will pick the glyphs for the text itself, so we need to
+    /// keep the glyphs that the character map gives for it, which aren't
+    /// necessarily the ones that TeX used.
+    pub fn mark_text_used(&mut self, text: &str) {
+        for c in text.chars() {
+            if let Some(g) = self.char_glyphs.get(&c) {
+                self.used_glyphs.insert(*g);
+            }
+        }
+    }
+
+    /// Get the glyphs that appear in the document, in order.
+    pub fn used_glyphs(&self) -> Vec<GlyphId> {
+        self.used_glyphs.iter().copied().collect()
+    }
+
+    /// Get the glyphs that the emitted font should contain.
+    ///
+    /// Besides the used glyphs themselves, these are the glyphs for the
+    /// characters that they represent, as well as all the forms of those
+    /// characters that we know about. The browser might use any of these if
+    /// it renders those characters.
+    fn glyphs_to_keep(&self) -> BTreeSet<GlyphId> {
+        let mut keep = self.used_glyphs.clone();
+
+        let chars: BTreeSet<char> = self
+            .used_glyphs
+            .iter()
+            .filter_map(|g| self.gmap.get(g))
+            .map(|m| m.get_char())
+            .collect();
+
+        for (g, m) in &self.gmap {
+            if chars.contains(&m.get_char()) {
+                keep.insert(*g);
+            }
+        }
+
+        for c in &chars {
+            if let Some(g) = self.char_glyphs.get(c) {
+                keep.insert(*g);
+            }
+        }
+
+        keep
+    }
+
     /// Request that a variant mapping be allocated for a glyph.
     ///
     /// The caller must suggest a Unicode character to use for the variant, but
@@ -403,45 +469,40 @@ impl FontFileData {
         Some(*map)
     }
 
-    /// Emit customized fonts to the filesystem and return information so that
-    /// appropriate CSS can be generated. Consumes the object.
+    /// Create the contents of customized font files.
+    ///
+    /// The fonts are subsetted to the glyphs that the document uses. If
+    /// `woff2` is true, they're created in the WOFF2 format, with file names
+    /// as given by [`output_file_name`].
     ///
     /// `rel_path` is the path, relative to the output root, where the font
     /// file(s) shouldb emitted. Currently, this may not contain any directory
     /// components, due to the way that the "variant" font file paths are
     /// constructed. This wouldn't be too hard to change.
     ///
-    /// `out_base` is the output directory, or None if we shouldn't be writing
-    /// anything to disk.
-    ///
-    /// Return value is a vec of (variant-map-index, CSS-src-field).
-    pub fn emit(
-        self,
-        out_base: Option<&Path>,
-        rel_path: &str,
-    ) -> Result<Vec<(Option<usize>, String)>> {
-        // Write the main font file ... maybe.
-
-        let mut out_path = out_base.map(|p| p.to_owned());
-
-        if let Some(out_path) = out_path.as_mut() {
-            out_path.push(rel_path);
-            atry!(
-                std::fs::write(&out_path, &self.buffer);
-                ["cannot write output file `{}`", out_path.display()]
-            );
-        }
+    /// Return value is a vec of (variant-map-index, output-path, contents).
+    pub(crate) fn render(&self, rel_path: &str, woff2: bool) -> Result<Vec<RenderedFontFile>> {
+        let mut sfnt = atry!(
+            Sfnt::from_face(&self.buffer, self.face_index);
+            ["unable to parse the tables of font `{}`", rel_path]
+        );
+        subset_font(&mut sfnt, &mut self.glyphs_to_keep());
+
+        let serialize = |sfnt: &Sfnt| {
+            if woff2 {
+                sfnt.to_woff2()
+            } else {
+                sfnt.to_opentype()
+            }
+        };
 
-        // CSS info for the main font.
+        // The main font file.
 
-        let rel_url = utf8_percent_encode(rel_path, CONTROLS).to_string();
-        let mut rv = vec![(None, format!(r#"url("{rel_url}") format("opentype")"#))];
+        let out_name = output_file_name(rel_path, woff2);
+        let mut rv = vec![(None, out_name.to_string(), serialize(&sfnt))];
 
         // Variants until we're done
 
-        let mut buffer = self.buffer;
-        let orig_len = buffer.len();
-
         for cur_map_index in 0.. {
             let mut mappings = Vec::new();
 
@@ -455,53 +516,18 @@ impl FontFileData {
                 break;
             }
 
-            // We have some variants to emit! If we're not actually writing
-            // files, we might not have much work to actually do though.
-
-            let varname = format!("vg{cur_map_index}{rel_path}");
-
-            if let Some(out_path) = out_path.as_mut() {
-                // Step 1: create new CMAP, appending to buffer.
-                //
-                // Might be nice to sort mappings as we construct it, rather than
-                // after the fact?
+            // Replace the CMAP with one for the variants.
+            //
+            // Might be nice to sort mappings as we construct it, rather than
+            // after the fact?
 
-                buffer.truncate(orig_len);
-                mappings.sort_unstable();
-                append_simple_cmap(&mut buffer, &mappings[..]);
-                let cmap_size = buffer.len() - orig_len;
+            mappings.sort_unstable();
+            sfnt.set_table(CMAP, simple_cmap(&mappings[..]));
 
-                // step 2: modify CMAP table record
-
-                let cs = opentype_checksum(&buffer[orig_len..]);
-                let ofs = 12 + self.fontdata_cmap_trec_idx * 16;
-                BigEndian::write_u32(&mut buffer[ofs + 4..ofs + 8], cs); // checksum
-                BigEndian::write_u32(&mut buffer[ofs + 8..ofs + 12], orig_len as u32); // offset
-                BigEndian::write_u32(&mut buffer[ofs + 12..ofs + 16], cmap_size as u32); // length
-
-                // step 3: update HEAD "checksum adjustment" field
-
-                let cs = opentype_checksum(&buffer[..]);
-                let chkadj = Wrapping(0xB1B0AFBA) - Wrapping(cs);
-                let ofs = self.fontdata_head_offset as usize + 8;
-                BigEndian::write_u32(&mut buffer[ofs..ofs + 4], chkadj.0);
-
-                // step 4: write new file
-
-                out_path.pop();
-                out_path.push(&varname);
-                atry!(
-                    std::fs::write(&out_path, &buffer);
-                    ["cannot write output file `{}`", out_path.display()]
-                );
-            }
-
-            // step 5: update CSS
-
-            let rel_url = utf8_percent_encode(&varname, CONTROLS).to_string();
             rv.push((
                 Some(cur_map_index),
-                format!(r#"url("{rel_url}") format("opentype")"#),
+                format!("vg{cur_map_index}{out_name}"),
+                serialize(&sfnt),
             ));
         }
 
@@ -525,8 +551,9 @@ impl FontFileData {
     }
 
     /// Update this "runtime" information to match the precomputed asset
-    /// information. At the moment the only thing we need to change is the table
-    /// of variant glyphs.
+    /// information. We need to change the table of variant glyphs, and include
+    /// the glyphs used by the other sessions that share the precomputed
+    /// assets.
     pub(crate) fn match_to_precomputed(&mut self, ffad: &crate::assets::syntax::FontFileAssetData) {
         self.variant_map_counts.clear();
         self.variant_map_allocations.clear();
@@ -540,6 +567,7 @@ impl FontFileData {
             *c = std::cmp::max(mapping.index + 1, *c);
         }
 
+        self.used_glyphs.extend(ffad.used_glyphs.iter().copied());
         self.no_new_variants = true;
     }
 }
@@ -620,23 +648,29 @@ fn valid_usvs() -> impl Iterator<Item = Usv> {
     (0..0xD800).chain(0xE000..0x11_0000)
 }
 
-fn opentype_checksum(data: &[u8]) -> u32 {
-    let mut iter = data.chunks_exact(4);
-    let cs: Wrapping<u32> = iter
-        .by_ref()
-        .map(|c| Wrapping(BigEndian::read_u32(c)))
-        .sum();
-
-    let rem = iter.remainder();
-    let mut padded = [0u8; 4];
-    padded[..rem.len()].copy_from_slice(rem);
-    (cs + Wrapping(BigEndian::read_u32(&padded[..]))).0
+/// Get the file name under which a font will be emitted.
+///
+/// WOFF2 fonts get the extension `.woff2`, replacing the extension of the
+/// source file.
+pub(crate) fn output_file_name(rel_path: &str, woff2: bool) -> Cow<'_, str> {
+    if woff2 {
+        let stem = match rel_path.rsplit_once('.') {
+            Some((stem, _)) if !stem.is_empty() => stem,
+            _ => rel_path,
+        };
+
+        Cow::Owned(format!("{stem}.woff2"))
+    } else {
+        Cow::Borrowed(rel_path)
+    }
 }
 
-/// Append a dumb OpenType CMAP table to a buffer.
+/// Create a dumb OpenType CMAP table.
 ///
 /// The input *map* must be sorted by USV value.
-fn append_simple_cmap(buf: &mut Vec<u8>, map: &[(char, GlyphId)]) {
+fn simple_cmap(map: &[(char, GlyphId)]) -> Vec<u8> {
+    let mut buf = Vec::new();
+
     buf.write_u16::<BigEndian>(0).unwrap(); // version
     buf.write_u16::<BigEndian>(1).unwrap(); // numTables
 
@@ -661,6 +695,8 @@ fn append_simple_cmap(buf: &mut Vec<u8>, map: &[(char, GlyphId)]) {
         buf.write_u32::<BigEndian>(*usv as u32).unwrap(); // end char
         buf.write_u32::<BigEndian>(*gid as u32).unwrap(); // glyph id
     }
+
+    buf
 }
 
 impl From<crate::assets::syntax::GlyphVariantMapping> for GlyphVariantMapping {
@@ -6,13 +6,14 @@
 //! Here a "font family" is interpreted in the HTML sense, meaning a set of
 //! related fonts. In typography you might call this a typeface.
 
-use std::{collections::HashMap, fmt::Write, io::Read, path::Path};
+use percent_encoding::{utf8_percent_encode, CONTROLS};
+use std::{collections::HashMap, fmt::Write, io::Read};
 use tectonic_errors::prelude::*;
-use tectonic_io_base::InputHandle;
+use tectonic_io_base::{InputHandle, IoProvider, OpenResult};
 use tectonic_status_base::{tt_warning, StatusBackend};
 
 use crate::{
-    assets::syntax,
+    assets::{syntax, RenderedAssets},
     fontfile::{FontFileData, GlyphId, GlyphMetrics, MapEntry},
     Common, FixedPoint, TexFontNum,
 };
@@ -70,8 +71,8 @@ impl FontEnsemble {
     /// they will have the same backing "font file" in our terminology. In
     /// particular, different sizes of the same font get different font-nums.
     ///
-    /// The styling options like *color_rgba* and *slant* are currently stored
-    /// but unused.
+    /// The styling options like *color_rgba* are currently stored but unused,
+    /// except that *slant* is taken into account by [`Self::is_upright`].
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn register_tex_font(
         &mut self,
@@ -255,6 +256,43 @@ impl FontEnsemble {
             .lookup_metrics(glyph, tfi.size))
     }
 
+    /// Determine whether a font's glyphs are upright, rather than italic or
+    /// slanted.
+    ///
+    /// The return value is only `Err` if the font number is undeclared.
+    pub fn is_upright(&self, fnum: TexFontNum) -> Result<bool> {
+        let tfi = self.lookup_tex(fnum)?;
+        Ok(tfi.slant.unwrap_or(0) == 0 && self.font_files[tfi.fid].details.is_upright())
+    }
+
+    /// Get the mapping from a glyph in a font back to Unicode.
+    ///
+    /// The return value is only `Err` if the font number is undeclared. If the
+    /// glyph doesn't map to a Unicode character, `Ok(None)` is returned.
+    pub fn get_glyph_mapping(&self, fnum: TexFontNum, glyph: GlyphId) -> Result<Option<MapEntry>> {
+        let tfi = self.lookup_tex(fnum)?;
+        Ok(self.font_files[tfi.fid].details.lookup_mapping(glyph))
+    }
+
+    /// Record that glyphs from a font appear in the document, so that they'll
+    /// be kept when the font is subsetted.
+    ///
+    /// If the glyphs are rendered as *text*, rather than individually, the
+    /// glyphs that the font would use to render the text are kept too.
+    pub fn mark_glyphs_used(&mut self, fnum: TexFontNum, glyphs: &[GlyphId], text: Option<&str>) {
+        if let Some(tfi) = self.tex_fonts.get(&fnum) {
+            let details = &mut self.font_files[tfi.fid].details;
+
+            for glyph in glyphs {
+                details.mark_glyph_used(*glyph);
+            }
+
+            if let Some(text) = text {
+                details.mark_text_used(text);
+            }
+        }
+    }
+
     /// Get information needed to render a glyph in a canvas context.
     ///
     /// The return value is a tuple `(text_info, size, baseline_factor)`. In
@@ -380,37 +418,48 @@ impl FontEnsemble {
         .map_err(|e| e.into())
     }
 
-    /// Emit the font files and return CSS code setting up the files.
+    /// Create the font files, adding them to *rendered*, and return CSS code
+    /// setting up the files.
     ///
-    /// This function clears this object's internal data structures, making it
-    /// effectively unusable for subsequent operations.
-    pub fn emit(&mut self, out_base: Option<&Path>) -> Result<String> {
+    /// The fonts are subsetted to the glyphs used in the document. If
+    /// `common.woff2_fonts` is true, they're compressed in the WOFF2 format.
+    pub(crate) fn render(
+        &self,
+        common: &mut Common,
+        rendered: &mut RenderedAssets,
+    ) -> Result<String> {
         let mut faces = String::default();
 
-        for font in self.font_files.drain(..) {
-            font.emit(out_base, &mut faces)?;
+        for font in &self.font_files {
+            font.render(common, rendered, &mut faces)?;
         }
 
         Ok(faces)
     }
 
-    pub(crate) fn into_serialize(mut self) -> (syntax::Assets, syntax::FontEnsembleAssetData) {
+    pub(crate) fn into_serialize(
+        mut self,
+        woff2: bool,
+    ) -> (syntax::Assets, syntax::FontEnsembleAssetData) {
         let mut assets: syntax::Assets = Default::default();
         let mut css_data: syntax::FontEnsembleAssetData = Default::default();
         let mut fid_to_filename = Vec::new();
 
         for font in self.font_files.drain(..) {
+            let used_glyphs = font.details.used_glyphs();
             let vglyphs = font.details.into_vglyphs();
 
             let ffad = syntax::FontFileAssetData {
                 source: font.src_tex_path,
                 face_index: font.face_index,
                 vglyphs,
+                used_glyphs,
+                woff2,
             };
 
             let filename = ffad.source.clone();
             assets
-                .0
+                .assets
                 .insert(filename.clone(), syntax::AssetOrigin::FontFile(ffad));
             fid_to_filename.push(filename);
         }
@@ -441,6 +490,10 @@ impl FontEnsemble {
 
     /// Check that the fonts defined at runtime match the serialized assets, and
     /// set up the runtime variant glyphs to align with the precomputed ones.
+    ///
+    /// The precomputed assets must also agree with this session about whether
+    /// fonts are emitted in WOFF2 format, since that determines their file
+    /// names.
     pub(crate) fn match_to_precomputed(
         &mut self,
         precomputed: &syntax::Assets,
@@ -448,12 +501,24 @@ impl FontEnsemble {
     ) -> Result<()> {
         let mut fid_to_filename = Vec::new();
 
+        for (path, origin) in &precomputed.assets {
+            if let syntax::AssetOrigin::FontFile(ff) = origin {
+                ensure!(
+                    ff.woff2 == common.woff2_fonts,
+                    "precomputed font asset `{}` {} in WOFF2 format, but this session's setting \
+                    disagrees",
+                    path,
+                    if ff.woff2 { "is" } else { "is not" }
+                );
+            }
+        }
+
         // For the existing font file data, we need to check that they're
         // present and the basenames match. We'll replace the runtime
         // variant-glyph mappings with the precomputed ones.
 
         for font in &mut self.font_files {
-            match precomputed.0.get(&font.out_rel_path) {
+            match precomputed.assets.get(&font.out_rel_path) {
                 Some(syntax::AssetOrigin::FontFile(ff)) => {
                     ensure!(
                         ff.source == font.out_rel_path,
@@ -487,7 +552,7 @@ impl FontEnsemble {
         // processing, but we still might be responsible for creating the final
         // output files at the end.
 
-        for origin in precomputed.0.values() {
+        for origin in precomputed.assets.values() {
             if let syntax::AssetOrigin::FontFile(ff) = origin {
                 let fid = atry!(
                     self.load_external_font(&ff.source, ff.face_index, common);
@@ -506,7 +571,7 @@ impl FontEnsemble {
 
         let mut precomputed_families = HashMap::new();
 
-        for origin in precomputed.0.values() {
+        for origin in precomputed.assets.values() {
             if let syntax::AssetOrigin::FontCss(fe) = origin {
                 for (fam_name, ff) in &fe.0 {
                     precomputed_families.insert(fam_name.to_owned(), ff);
@@ -607,6 +672,34 @@ impl<'a> Iterator for GlyphTextProcessingIterator<'a> {
     }
 }
 
+/// Open the font file associated with the name given in a native font
+/// definition, returning its TeX path along with the input handle.
+///
+/// At the moment, the name is just the TeX path of the font file, potentially
+/// without its extension. In the future, it is possible that the font name
+/// might be something symbolic like "Times New Roman" that would need to be
+/// looked up differently.
+pub(crate) fn open_native_font(
+    io: &mut dyn IoProvider,
+    name: &str,
+    status: &mut dyn StatusBackend,
+) -> Result<(String, InputHandle)> {
+    for ext in &["", ".otf"] {
+        let texpath = format!("{name}{ext}");
+
+        match io.input_open_name(&texpath, status) {
+            OpenResult::Ok(h) => return Ok((texpath, h)),
+            OpenResult::NotAvailable => continue,
+            OpenResult::Err(e) => return Err(e),
+        };
+    }
+
+    bail!(
+        "failed to find a font file associated with the name `{}`",
+        name
+    )
+}
+
 /// Get information about how to render a desired glyph from a font.
 fn get_text_info(
     font: &mut Font,
@@ -695,7 +788,7 @@ struct TexFontInfo {
     /// Unused TeX/SPX setting.
     extend: Option<u32>,
 
-    /// Unused TeX/SPX setting.
+    /// The synthetic slant applied to the font, if any.
     slant: Option<u32>,
 
     /// Unused TeX/SPX setting.
@@ -748,14 +841,32 @@ impl Font {
         }
     }
 
-    fn emit<W: Write>(self, out_base: Option<&Path>, mut dest: W) -> Result<()> {
-        for (var_index, css_src) in self.details.emit(out_base, &self.out_rel_path)? {
-            // This is almost identical to `selection_style_text`. A major
-            // factor is that we're consuming `self`, with `self.details`
-            // already consumed by the `emit()` call, so we can't borrow &self.
-            // Also, here we have double quotes around the font-family
-            // specifier, which we want to have in the CSS but shouldn't have
-            // (maybe???) in the HTML `style` attribute.
+    fn render<W: Write>(
+        &self,
+        common: &mut Common,
+        rendered: &mut RenderedAssets,
+        mut dest: W,
+    ) -> Result<()> {
+        let css_format = if common.woff2_fonts {
+            "woff2"
+        } else {
+            "opentype"
+        };
+
+        for (var_index, out_name, contents) in self
+            .details
+            .render(&self.out_rel_path, common.woff2_fonts)?
+        {
+            // The CSS may need to refer to the font file under its
+            // content-hashed name.
+            let out_name = rendered.add(&out_name, contents);
+            let rel_url = utf8_percent_encode(&out_name, CONTROLS).to_string();
+            let css_src = format!(r#"url("{rel_url}") format("{css_format}")"#);
+
+            // This is almost identical to `selection_style_text`, but here we
+            // have double quotes around the font-family specifier, which we
+            // want to have in the CSS but shouldn't have (maybe???) in the
+            // HTML `style` attribute.
             let var_text = var_index.map(|i| format!("vg{i}")).unwrap_or_default();
 
             let extra = match self.family_relation {
@@ -5,12 +5,16 @@
 
 use std::{collections::HashMap, io::Read, path::PathBuf};
 use tectonic_errors::prelude::*;
-use tectonic_io_base::OpenResult;
 use tectonic_status_base::tt_warning;
 
 use crate::{
-    fonts::FontEnsemble, html::Element, specials::Special, templating::Templating, Common,
-    EmittingState, FixedPoint, TexFontNum,
+    emission::{parse_canvas_rendering, parse_source_position, CanvasRendering},
+    fonts::{open_native_font, FontEnsemble},
+    html::Element,
+    sourcepos::SourcePosition,
+    specials::Special,
+    templating::Templating,
+    Common, EmittingState, FixedPoint, TexFontNum,
 };
 
 #[derive(Debug)]
@@ -27,6 +31,8 @@ pub(crate) struct InitializationState {
     cur_font_family_tag_associations: Option<FontFamilyTagAssociator>,
 
     variables: HashMap<String, String>,
+    canvas_renderings: HashMap<String, CanvasRendering>,
+    source_position: Option<SourcePosition>,
 }
 
 impl Default for InitializationState {
@@ -44,6 +50,8 @@ impl Default for InitializationState {
             cur_font_family_tag_associations: None,
 
             variables: Default::default(),
+            canvas_renderings: Default::default(),
+            source_position: None,
         }
     }
 }
@@ -86,28 +94,7 @@ impl InitializationState {
         // just the name or something similar, but in principle we might do a
         // lookup based on something like symbolic name.
 
-        let io = common.hooks.io();
-        let mut texpath = String::default();
-        let mut ih = None;
-
-        for ext in &["", ".otf"] {
-            texpath = format!("{name}{ext}");
-
-            match io.input_open_name(&texpath, common.status) {
-                OpenResult::Ok(h) => {
-                    ih = Some(h);
-                    break;
-                }
-
-                OpenResult::NotAvailable => continue,
-
-                OpenResult::Err(e) => return Err(e),
-            };
-        }
-
-        let ih = a_ok_or!(ih;
-            ["failed to find a font file associated with the name `{}`", name]
-        );
+        let (texpath, ih) = open_native_font(common.hooks.io(), name, common.status)?;
 
         // Now that we have that, we can pass off to the font manager.
 
@@ -126,6 +113,12 @@ impl InitializationState {
             Special::SetTemplate(t) => self.handle_set_template(t, common),
             Special::SetOutputPath(t) => self.handle_set_output_path(t, common),
             Special::SetTemplateVariable(t) => self.handle_set_template_variable(t, common),
+            Special::SetCanvasRendering(t) => {
+                if let Some((kind, mode)) = parse_canvas_rendering(t, common) {
+                    self.canvas_renderings.insert(kind, mode);
+                }
+                Ok(())
+            }
             Special::StartDefineFontFamily => self.handle_start_define_font_family(),
             Special::EndDefineFontFamily => self.handle_end_define_font_family(common),
             Special::StartFontFamilyTagAssociations => {
@@ -141,6 +134,13 @@ impl InitializationState {
                 Ok(())
             }
 
+            // The position of a canvas arrives before the special that starts
+            // it, which may end initialization, so we need to hold onto it.
+            Special::SourcePosition(spec) => {
+                self.source_position = parse_source_position(spec, common);
+                Ok(())
+            }
+
             _ => Ok(()),
         }
     }
@@ -355,6 +355,8 @@ impl InitializationState {
             self.main_body_font_num,
             templating,
             self.tag_associations,
+            self.canvas_renderings,
+            self.source_position,
         )
     }
 }
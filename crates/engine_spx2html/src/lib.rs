@@ -7,8 +7,12 @@
 //!
 //! SPX is essentially the same thing as XDV, but we identify it differently to
 //! mark that the semantics of the content wil be set up for HTML output.
+//!
+//! This crate also provides [`Xdv2TextEngine`], which uses the same font
+//! machinery to extract plain text from XDV or SPX files, and
+//! [`Xdv2SvgEngine`], which uses it to render their pages to SVG.
 
-use std::path::{Path, PathBuf};
+use std::{collections::HashMap, path::PathBuf};
 use tectonic_bridge_core::DriverHooks;
 use tectonic_errors::prelude::*;
 use tectonic_status_base::StatusBackend;
@@ -16,17 +20,36 @@ use tectonic_xdv::{FileType, XdvEvents, XdvParser};
 
 mod assets;
 mod emission;
+mod epub;
 mod finalization;
 mod fontfile;
 mod fonts;
 mod html;
 mod initialization;
+mod links;
+mod mathml;
+mod navigation;
+mod output;
+mod search;
+mod sfnt;
+mod sourcepos;
 mod specials;
+mod subset;
+mod svg;
 mod templating;
+mod text;
+
+#[cfg(test)]
+mod testutil;
 
 use self::{
-    assets::Assets, emission::EmittingState, finalization::FinalizingState, fonts::FontEnsemble,
-    initialization::InitializationState, specials::Special,
+    assets::{Assets, RenderedAssets},
+    emission::EmittingState,
+    finalization::FinalizingState,
+    fonts::FontEnsemble,
+    initialization::InitializationState,
+    sourcepos::SourcePositionOptions,
+    specials::Special,
 };
 
 /// An engine that converts SPX to HTML.
@@ -36,14 +59,27 @@ pub struct Spx2HtmlEngine {
     precomputed_assets: Option<AssetSpecification>,
     assets_spec_path: Option<String>,
     do_not_emit_assets: bool,
+    woff2_fonts: bool,
+    hashed_asset_names: bool,
+    source_positions: SourcePositionOptions,
 }
 
-#[derive(Debug, Default)]
+#[derive(Default)]
 enum OutputState {
     #[default]
     Undefined,
     NoOutput,
-    Path(PathBuf),
+    Tree(Box<dyn OutputTree>),
+}
+
+impl std::fmt::Debug for OutputState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputState::Undefined => f.write_str("Undefined"),
+            OutputState::NoOutput => f.write_str("NoOutput"),
+            OutputState::Tree(_) => f.write_str("Tree(..)"),
+        }
+    }
 }
 
 impl Spx2HtmlEngine {
@@ -113,23 +149,96 @@ impl Spx2HtmlEngine {
         self
     }
 
+    /// Specify that font files should be emitted in the WOFF2 format.
+    ///
+    /// Fonts are always subsetted to the glyphs that the document uses. WOFF2
+    /// compression makes them smaller still, and is supported by all modern
+    /// browsers. The font files get the extension `.woff2`. When using
+    /// precomputed assets, this setting must match the one used when the asset
+    /// specification was created.
+    pub fn woff2_fonts(&mut self, woff2: bool) -> &mut Self {
+        self.woff2_fonts = woff2;
+        self
+    }
+
+    /// Specify that asset files should be given content-hashed names.
+    ///
+    /// If this is enabled, a hash of the contents of each asset file, including
+    /// fonts, is inserted into its name before the extension. Browsers and
+    /// CDNs can then cache the files indefinitely, since modified files get new
+    /// names. Templates should refer to assets using the `tduxAsset` Tera
+    /// function or filter, which map the logical path of an asset, relative to
+    /// the output root, to the actual one: for instance, `{{ tduxRelTop }}{{
+    /// tduxAsset(path="fonts.css") }}`.
+    ///
+    /// When using precomputed assets, this setting must match the one used when
+    /// the asset specification was created. Any hashed names recorded in the
+    /// specification are checked against the assets of the current session.
+    ///
+    /// Note that the names can only be computed from the contents of the asset
+    /// files, so all of the assets are rendered, with fonts subsetted and
+    /// possibly compressed, even in sessions that don't emit them, as long as
+    /// they write HTML pages or an asset specification. This can be a
+    /// significant cost when a document is processed in several sessions.
+    pub fn hashed_asset_names(&mut self, hashed: bool) -> &mut Self {
+        self.hashed_asset_names = hashed;
+        self
+    }
+
+    /// Specify whether HTML elements should be annotated with their source
+    /// positions using `data-src` attributes.
+    ///
+    /// Source positions are only available if the SPX file was created with
+    /// them, by enabling the `source_positions` option of the TeX engine. In
+    /// that case, the elements for paragraphs and canvases get attributes like
+    /// `data-src="chapter1.tex:42"`, which can be used to implement features
+    /// like inverse search.
+    pub fn source_position_attributes(&mut self, enabled: bool) -> &mut Self {
+        self.source_positions.attributes = enabled;
+        self
+    }
+
+    /// Specify whether source position maps should be written alongside the
+    /// HTML pages.
+    ///
+    /// This makes the same information as [`Self::source_position_attributes`]
+    /// available without altering the HTML. For each page, a JSON file with
+    /// the name of the page plus `.srcmap.json` lists the source positions of
+    /// its paragraphs and canvases, identifying each one by its index among
+    /// the elements of its kind.
+    pub fn source_position_maps(&mut self, enabled: bool) -> &mut Self {
+        self.source_positions.maps = enabled;
+        self
+    }
+
     /// Specify the root path for output files.
     ///
     /// Because this driver will, in the generic case, produce a tree of HTML
     /// output files that are not going to be used as a basis for any subsequent
-    /// engine stages, it outputs directly to disk rather than using the I/O
-    /// layer. I don't like hardcoding use of the filesystem, but I don't want
-    /// to build up some extra abstraction layer right now.
+    /// engine stages, it usually outputs directly to disk rather than using the
+    /// I/O layer. This is shorthand for calling [`Self::output_tree`] with a
+    /// [`FilesystemOutput`].
     pub fn output_base(&mut self, out_base: impl Into<PathBuf>) -> &mut Self {
-        self.output = OutputState::Path(out_base.into());
+        self.output_tree(FilesystemOutput::new(out_base))
+    }
+
+    /// Specify where output files should be written.
+    ///
+    /// All of the templated HTML files and the assets are written through the
+    /// given [`OutputTree`]. Use [`DriverIoOutput`] to send them through the
+    /// I/O layer of the processing session, so that they can be captured in
+    /// memory.
+    pub fn output_tree(&mut self, tree: impl OutputTree + 'static) -> &mut Self {
+        self.output = OutputState::Tree(Box::new(tree));
         self
     }
 
     /// Process SPX into HTML.
     ///
     /// Before calling this function, you must explicitly specify the output
-    /// mode by calling either [`Self::do_not_emit_files`] or
-    /// [`Self::output_base`]. If you do not, this function will panic.
+    /// mode by calling [`Self::do_not_emit_files`], [`Self::output_base`], or
+    /// [`Self::output_tree`]. If you do not, this function will panic. Despite
+    /// the name, the output need not actually go to the filesystem.
     pub fn process_to_filesystem(
         &mut self,
         hooks: &mut dyn DriverHooks,
@@ -138,26 +247,50 @@ impl Spx2HtmlEngine {
     ) -> Result<()> {
         let mut input = hooks.io().input_open_name(spx, status).must_exist()?;
 
-        let out_base = match self.output {
+        let emit_files = matches!(self.output, OutputState::Tree(_));
+        let emit_assets = self.assets_spec_path.is_none() && !self.do_not_emit_assets && emit_files;
+
+        // Content-hashed asset names are needed by the templates and the asset
+        // specification. Otherwise, we can avoid the work of computing them.
+        let need_asset_names = emit_files || self.assets_spec_path.is_some();
+
+        let output: Option<&mut dyn OutputTree> = match self.output {
             OutputState::NoOutput => None,
-            OutputState::Path(ref p) => Some(p.as_ref()),
+            OutputState::Tree(ref mut t) => Some(t.as_mut()),
             OutputState::Undefined => panic!("spx2html output mode not specified"),
         };
 
         {
-            let state = EngineState::new(hooks, status, out_base, self.precomputed_assets.as_ref());
+            let state = EngineState::new(
+                hooks,
+                status,
+                output,
+                self.precomputed_assets.as_ref(),
+                self.woff2_fonts,
+                self.hashed_asset_names,
+                self.source_positions,
+            );
             let state = XdvParser::process_with_seeks(&mut input, state)?;
-            let (fonts, assets, mut common) = state.finished()?;
+            let (fonts, assets, rendered, mut common) =
+                state.finished(emit_assets, need_asset_names)?;
 
             if let Some(asp) = self.assets_spec_path.as_ref() {
-                let ser = assets.into_serialize(fonts);
-                let mut output = hooks.io().output_open_name(asp).must_exist()?;
+                let names = rendered
+                    .as_ref()
+                    .filter(|_| common.hashed_asset_names)
+                    .map(|r| r.names());
+                let ser = assets.into_serialize(fonts, common.woff2_fonts, names);
+                let mut output = common.hooks.io().output_open_name(asp).must_exist()?;
                 serde_json::to_writer_pretty(&mut output, &ser)?;
                 let (name, digest) = output.into_name_digest();
-                hooks.event_output_closed(name, digest, status);
-            } else if !self.do_not_emit_assets {
-                assets.emit(fonts, &mut common)?;
+                common
+                    .hooks
+                    .event_output_closed(name, digest, common.status);
+            } else if let Some(rendered) = rendered.filter(|_| emit_assets) {
+                rendered.emit(&mut common)?;
             }
+
+            common.finish_output()?;
         }
 
         let (name, digest_opt) = input.into_name_digest();
@@ -167,6 +300,10 @@ impl Spx2HtmlEngine {
 }
 
 pub use assets::AssetSpecification;
+pub use epub::{EpubMetadata, EpubOutput};
+pub use output::{DriverIoOutput, FilesystemOutput, OutputTree};
+pub use svg::Xdv2SvgEngine;
+pub use text::Xdv2TextEngine;
 
 struct EngineState<'a> {
     common: Common<'a>,
@@ -176,23 +313,72 @@ struct EngineState<'a> {
 struct Common<'a> {
     hooks: &'a mut dyn DriverHooks,
     status: &'a mut dyn StatusBackend,
-    out_base: Option<&'a Path>,
+    output: Option<&'a mut dyn OutputTree>,
     precomputed_assets: Option<&'a AssetSpecification>,
+    woff2_fonts: bool,
+    hashed_asset_names: bool,
+    source_positions: SourcePositionOptions,
+}
+
+impl Common<'_> {
+    /// Whether output files are actually being created.
+    fn emits_files(&self) -> bool {
+        self.output.is_some()
+    }
+
+    /// Write a file to the output tree, if we're creating output files. The
+    /// path should have been validated with [`assets::create_output_path`].
+    fn write_output_file(&mut self, path: &str, contents: &[u8]) -> Result<()> {
+        if let Some(output) = self.output.as_mut() {
+            output.write_file(path, contents, self.hooks, self.status)?;
+        }
+
+        Ok(())
+    }
+
+    /// Note that a templated page has been written to the output tree.
+    fn page_written(&mut self, path: &str, title: &str) {
+        if let Some(output) = self.output.as_mut() {
+            output.page_written(path, title);
+        }
+    }
+
+    /// Note that an auxiliary file has been written to the output tree.
+    fn auxiliary_file_written(&mut self, path: &str) {
+        if let Some(output) = self.output.as_mut() {
+            output.auxiliary_file_written(path);
+        }
+    }
+
+    /// Finish creating the output tree, if there is one.
+    fn finish_output(&mut self) -> Result<()> {
+        if let Some(output) = self.output.as_mut() {
+            output.finish(self.hooks, self.status)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl<'a> EngineState<'a> {
     pub fn new(
         hooks: &'a mut dyn DriverHooks,
         status: &'a mut dyn StatusBackend,
-        out_base: Option<&'a Path>,
+        output: Option<&'a mut dyn OutputTree>,
         precomputed_assets: Option<&'a AssetSpecification>,
+        woff2_fonts: bool,
+        hashed_asset_names: bool,
+        source_positions: SourcePositionOptions,
     ) -> Self {
         Self {
             common: Common {
                 hooks,
                 status,
-                out_base,
+                output,
                 precomputed_assets,
+                woff2_fonts,
+                hashed_asset_names,
+                source_positions,
             },
             state: State::Initializing(InitializationState::default()),
         }
@@ -210,11 +396,22 @@ enum State {
 }
 
 impl<'a> EngineState<'a> {
-    pub fn finished(mut self) -> Result<(FontEnsemble, Assets, Common<'a>)> {
+    /// Finish processing, rendering the templated pages.
+    ///
+    /// If *render_assets* is true, the contents of the asset files are created
+    /// so that they can be emitted. They're also created if the asset files
+    /// are given content-hashed names and *need_names* is true, since the
+    /// names depend on the contents.
+    #[allow(clippy::type_complexity)]
+    pub fn finished(
+        mut self,
+        render_assets: bool,
+        need_names: bool,
+    ) -> Result<(FontEnsemble, Assets, Option<RenderedAssets>, Common<'a>)> {
         self.state.ensure_finalizing(&mut self.common)?;
 
         if let State::Finalizing(s) = self.state {
-            let (fonts, mut assets) = s.finished();
+            let (fonts, mut assets, mut templating) = s.finished();
 
             // If we have precomputed assets, make sure that this run didn't
             // define anything surprising, and sync up the runtime manifest with
@@ -223,7 +420,25 @@ impl<'a> EngineState<'a> {
                 precomputed.check_runtime_assets(&mut assets)?;
             }
 
-            Ok((fonts, assets, self.common))
+            if assets.has_search_index() {
+                assets.add_search_entries(templating.search_entries());
+            }
+
+            let rendered = if render_assets || (self.common.hashed_asset_names && need_names) {
+                Some(assets.render(&fonts, &mut self.common)?)
+            } else {
+                None
+            };
+
+            if let Some(precomputed) = self.common.precomputed_assets {
+                precomputed.check_hashed_names(rendered.as_ref(), &self.common)?;
+            }
+
+            let no_names = HashMap::new();
+            let names = rendered.as_ref().map_or(&no_names, |r| r.names());
+            templating.finish(&mut self.common, names)?;
+
+            Ok((fonts, assets, rendered, self.common))
         } else {
             panic!("invalid spx2html finalization state leaked");
         }
@@ -0,0 +1,201 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Hyperlinks and cross-reference anchors.
+//!
+//! A document can be split into several output files with `setOutputPath`, and
+//! a link may point to an anchor in a file that hasn't been emitted yet. So,
+//! links to anchors are written into the HTML content as placeholders. Pages
+//! are only rendered once all of the content has been processed, at which
+//! point every anchor is known, and the placeholders are replaced with
+//! relative URLs.
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::collections::HashMap;
+use tectonic_status_base::{tt_warning, StatusBackend};
+
+/// The character that delimits link placeholders. It's a control character
+/// that won't be altered by HTML escaping and shouldn't appear in real content.
+const PLACEHOLDER_DELIM: char = '\u{1}';
+
+/// Characters that must be percent-encoded in a URL fragment, so that anchor
+/// names can contain spaces and the like.
+const FRAGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`');
+
+#[derive(Debug, Default)]
+pub(crate) struct Links {
+    /// The anchor names referenced by placeholders, indexed by placeholder
+    /// number.
+    targets: Vec<String>,
+
+    /// Anchors that have been seen in the current content, but not yet
+    /// associated with an output file.
+    unbound_anchors: Vec<String>,
+
+    /// The output path of the file containing each known anchor.
+    anchors: HashMap<String, String>,
+}
+
+impl Links {
+    /// Get the placeholder text to use as the `href` of a link to the named
+    /// anchor.
+    pub(crate) fn placeholder(&mut self, anchor: &str) -> String {
+        let index = self.targets.len();
+        self.targets.push(anchor.to_owned());
+        format!("{PLACEHOLDER_DELIM}{index}{PLACEHOLDER_DELIM}")
+    }
+
+    /// Record that the named anchor appears in the current content.
+    ///
+    /// Returns false if the anchor has already been defined, in which case
+    /// links will resolve to the first definition.
+    pub(crate) fn add_anchor(&mut self, name: &str) -> bool {
+        if self.anchors.contains_key(name) || self.unbound_anchors.iter().any(|a| a == name) {
+            return false;
+        }
+
+        self.unbound_anchors.push(name.to_owned());
+        true
+    }
+
+    /// Associate the anchors in the current content with the output file that
+    /// it is being emitted into.
+    pub(crate) fn bind_anchors(&mut self, output_path: &str) {
+        for name in self.unbound_anchors.drain(..) {
+            self.anchors.insert(name, output_path.to_owned());
+        }
+    }
+
+    /// Replace the link placeholders in *text*, which will be saved at
+    /// *output_path*, with URLs relative to that file.
+    pub(crate) fn resolve(
+        &self,
+        text: &str,
+        output_path: &str,
+        status: &mut dyn StatusBackend,
+    ) -> String {
+        let mut resolved = String::with_capacity(text.len());
+        let mut pieces = text.split(PLACEHOLDER_DELIM);
+
+        // Pieces alternate between literal text and placeholder indices.
+        resolved.push_str(pieces.next().unwrap_or_default());
+
+        while let Some(index) = pieces.next() {
+            match index
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| self.targets.get(i))
+            {
+                Some(anchor) => {
+                    let url = match self.anchors.get(anchor) {
+                        Some(anchor_path) => relative_url(output_path, anchor_path, Some(anchor)),
+                        None => {
+                            tt_warning!(status, "link to undefined anchor `{}`", anchor);
+                            format!("#{}", utf8_percent_encode(anchor, FRAGMENT))
+                        }
+                    };
+
+                    html_escape::encode_double_quoted_attribute_to_string(url, &mut resolved);
+                }
+
+                None => {
+                    // Not one of our placeholders after all.
+                    resolved.push(PLACEHOLDER_DELIM);
+                    resolved.push_str(index);
+                    resolved.push(PLACEHOLDER_DELIM);
+                }
+            }
+
+            resolved.push_str(pieces.next().unwrap_or_default());
+        }
+
+        resolved
+    }
+}
+
+/// Compute the URL of the output file *to_path*, or of *anchor* within it,
+/// relative to the output file *from_path*. Both paths are slash-separated and
+/// relative to the output root.
+pub(crate) fn relative_url(from_path: &str, to_path: &str, anchor: Option<&str>) -> String {
+    let from: Vec<&str> = from_path.split('/').filter(|p| !p.is_empty()).collect();
+    let to: Vec<&str> = to_path.split('/').filter(|p| !p.is_empty()).collect();
+
+    let mut url = if from == to && anchor.is_some() {
+        String::new()
+    } else {
+        // The last component of each path is the file name; only the
+        // directories before it can be shared.
+        let from_dirs = &from[..from.len().saturating_sub(1)];
+        let to_dirs = &to[..to.len().saturating_sub(1)];
+        let n_common = from_dirs
+            .iter()
+            .zip(to_dirs)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let mut url = "../".repeat(from_dirs.len() - n_common);
+        url.push_str(&to[n_common..].join("/"));
+        url
+    };
+
+    if let Some(anchor) = anchor {
+        url.push('#');
+        url.extend(utf8_percent_encode(anchor, FRAGMENT));
+    }
+
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tectonic_status_base::NoopStatusBackend;
+
+    #[test]
+    fn relative_urls() {
+        let url = |from, to| relative_url(from, to, Some("x"));
+        assert_eq!(url("index.html", "index.html"), "#x");
+        assert_eq!(url("index.html", "ch1.html"), "ch1.html#x");
+        assert_eq!(url("a/b.html", "a/c.html"), "c.html#x");
+        assert_eq!(url("a/b.html", "c/d.html"), "../c/d.html#x");
+        assert_eq!(url("a/b/c.html", "d.html"), "../../d.html#x");
+        assert_eq!(url("d.html", "a/b/c.html"), "a/b/c.html#x");
+        assert_eq!(relative_url("a/b.html", "a/b.html", None), "b.html");
+        assert_eq!(
+            relative_url("a.html", "b.html", Some("sec 1#50%")),
+            "b.html#sec%201%2350%25"
+        );
+    }
+
+    #[test]
+    fn forward_references() {
+        let mut status = NoopStatusBackend::default();
+        let mut links = Links::default();
+
+        let text = format!(
+            "<a href=\"{}\">A</a> <a href=\"{}\">B</a> <a href=\"{}\">C</a>",
+            links.placeholder("here"),
+            links.placeholder("over there"),
+            links.placeholder("no#where"),
+        );
+
+        assert!(links.add_anchor("here"));
+        links.bind_anchors("ch1/index.html");
+        assert!(links.add_anchor("over there"));
+        assert!(!links.add_anchor("here"));
+        links.bind_anchors("ch2/index.html");
+
+        assert_eq!(
+            links.resolve(&text, "ch1/index.html", &mut status),
+            "<a href=\"#here\">A</a> <a href=\"../ch2/index.html#over%20there\">B</a> \
+             <a href=\"#no%23where\">C</a>"
+        );
+    }
+}
@@ -0,0 +1,802 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Recognizing the structure of math canvases, so that they can be emitted as
+//! MathML.
+//!
+//! By the time that math reaches us, TeX has turned it into a collection of
+//! positioned glyphs and rules. Here we try to reconstruct the most common
+//! structures -- fractions, radicals, sub- and superscripts, and limits -- from
+//! the geometry. The analysis is heuristic. If anything in the canvas can't be
+//! accounted for, we give up and let the caller fall back to the positioned
+//! rendering, rather than producing MathML that reads wrongly.
+
+use std::fmt::Write;
+
+use crate::{FixedPoint, TexFontNum};
+
+/// A glyph in a math canvas, with its Unicode interpretation.
+#[derive(Clone, Debug)]
+pub(crate) struct MathGlyph {
+    /// The horizontal position of the glyph origin.
+    pub x: i32,
+
+    /// The vertical position of the glyph baseline. Larger is lower.
+    pub y: i32,
+
+    /// The advance width of the glyph.
+    pub advance: i32,
+
+    /// The height of the glyph above its baseline.
+    pub ascent: i32,
+
+    /// The depth of the glyph below its baseline. As in the font metrics,
+    /// this value is typically negative.
+    pub descent: i32,
+
+    /// The size of the font used for the glyph.
+    pub size: FixedPoint,
+
+    pub font_num: TexFontNum,
+
+    /// Whether the font is upright, like the roman fonts used for operator
+    /// names such as `\sin`, rather than italic like ordinary math letters.
+    pub upright: bool,
+
+    /// The Unicode character corresponding to the glyph.
+    pub ch: char,
+}
+
+/// A rule in a math canvas.
+#[derive(Clone, Debug)]
+pub(crate) struct MathRule {
+    /// The horizontal position of the left edge of the rule.
+    pub x: i32,
+
+    /// The vertical position of the bottom edge of the rule.
+    pub y: i32,
+
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Try to convert the contents of a math canvas into MathML.
+///
+/// The return value is the `<math>` element, or None if the structure of the
+/// canvas couldn't be recognized.
+pub(crate) fn canvas_to_mathml(
+    glyphs: &[MathGlyph],
+    rules: &[MathRule],
+    display: bool,
+) -> Option<String> {
+    if glyphs.is_empty() {
+        return None;
+    }
+
+    let mut items: Vec<Item> = glyphs.iter().map(Item::from_glyph).collect();
+    items.extend(rules.iter().map(Item::from_rule));
+
+    let node = layout(items)?;
+    let mut mathml = String::new();
+
+    write!(
+        mathml,
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"{}\">",
+        if display { "block" } else { "inline" }
+    )
+    .unwrap();
+    node.write_children(&mut mathml);
+    mathml.push_str("</math>");
+    Some(mathml)
+}
+
+/// A node in the recognized MathML tree.
+#[derive(Clone, Debug)]
+enum Node {
+    /// A token element: `mi`, `mn`, or `mo`.
+    Token(&'static str, String),
+    Row(Vec<Node>),
+    Frac(Box<Node>, Box<Node>),
+    Sqrt(Box<Node>),
+    Root(Box<Node>, Box<Node>),
+    Scripts {
+        base: Box<Node>,
+        sub: Option<Box<Node>>,
+        sup: Option<Box<Node>>,
+
+        /// If true, the scripts are limits set directly under and over the
+        /// base.
+        limits: bool,
+    },
+}
+
+impl Node {
+    /// Write this node as a single MathML element.
+    fn write(&self, dest: &mut String) {
+        match self {
+            Node::Token(tag, text) => {
+                write!(dest, "<{tag}>").unwrap();
+                html_escape::encode_text_to_string(text, dest);
+                write!(dest, "</{tag}>").unwrap();
+            }
+
+            Node::Row(children) => {
+                if children.len() == 1 {
+                    children[0].write(dest);
+                } else {
+                    dest.push_str("<mrow>");
+                    self.write_children(dest);
+                    dest.push_str("</mrow>");
+                }
+            }
+
+            Node::Frac(num, den) => write_element(dest, "mfrac", &[num, den]),
+
+            Node::Sqrt(radicand) => {
+                dest.push_str("<msqrt>");
+                radicand.write_children(dest);
+                dest.push_str("</msqrt>");
+            }
+
+            Node::Root(radicand, index) => write_element(dest, "mroot", &[radicand, index]),
+
+            Node::Scripts {
+                base,
+                sub,
+                sup,
+                limits,
+            } => {
+                let (one, two, both) = if *limits {
+                    ("munder", "mover", "munderover")
+                } else {
+                    ("msub", "msup", "msubsup")
+                };
+
+                match (sub, sup) {
+                    (Some(sub), Some(sup)) => write_element(dest, both, &[base, sub, sup]),
+                    (Some(sub), None) => write_element(dest, one, &[base, sub]),
+                    (None, Some(sup)) => write_element(dest, two, &[base, sup]),
+                    (None, None) => base.write(dest),
+                }
+            }
+        }
+    }
+
+    /// Write the children of this node, if it is a row, or the node itself
+    /// otherwise. This is for contexts that accept any number of elements.
+    fn write_children(&self, dest: &mut String) {
+        if let Node::Row(children) = self {
+            for child in children {
+                child.write(dest);
+            }
+        } else {
+            self.write(dest);
+        }
+    }
+}
+
+fn write_element(dest: &mut String, tag: &str, children: &[&Node]) {
+    write!(dest, "<{tag}>").unwrap();
+
+    for child in children {
+        child.write(dest);
+    }
+
+    write!(dest, "</{tag}>").unwrap();
+}
+
+/// Something in the canvas that we're trying to fit into the math structure.
+#[derive(Clone, Debug)]
+struct Item {
+    x0: i32,
+    x1: i32,
+    top: i32,
+    bottom: i32,
+
+    /// The baseline of a glyph. For other items, this is the bottom edge.
+    baseline: i32,
+
+    /// The size of the font, for glyphs and structures built from them.
+    size: FixedPoint,
+
+    content: Content,
+}
+
+#[derive(Clone, Debug)]
+enum Content {
+    /// A glyph, with its character, font, and whether the font is upright.
+    Glyph(char, TexFontNum, bool),
+    Rule,
+    Node(Node),
+}
+
+impl Item {
+    fn from_glyph(g: &MathGlyph) -> Self {
+        Item {
+            x0: g.x,
+            x1: g.x + g.advance,
+            top: g.y - g.ascent,
+            bottom: g.y - g.descent,
+            baseline: g.y,
+            size: g.size,
+            content: Content::Glyph(g.ch, g.font_num, g.upright),
+        }
+    }
+
+    fn from_rule(r: &MathRule) -> Self {
+        Item {
+            x0: r.x,
+            x1: r.x + r.width,
+            top: r.y - r.height,
+            bottom: r.y,
+            baseline: r.y,
+            size: 0,
+            content: Content::Rule,
+        }
+    }
+
+    fn center_x(&self) -> i32 {
+        self.x0 + (self.x1 - self.x0) / 2
+    }
+
+    fn center_y(&self) -> i32 {
+        self.top + (self.bottom - self.top) / 2
+    }
+
+    fn is_rule(&self) -> bool {
+        matches!(self.content, Content::Rule)
+    }
+
+    /// Whether this item is a glyph that is taller than its font size, like
+    /// an enlarged delimiter or a display-style large operator. TeX centers
+    /// such glyphs on the math axis, so their baselines don't tell us where
+    /// the baseline of the surrounding math is.
+    fn is_enlarged_glyph(&self) -> bool {
+        self.glyph_char().is_some() && self.bottom - self.top > self.size
+    }
+
+    fn glyph_char(&self) -> Option<char> {
+        if let Content::Glyph(c, _, _) = self.content {
+            Some(c)
+        } else {
+            None
+        }
+    }
+
+    /// Create an item for a structure built from other items.
+    fn new_node(node: Node, parts: &[&Item]) -> Self {
+        let bottom = parts.iter().map(|i| i.bottom).max().unwrap_or_default();
+
+        Item {
+            x0: parts.iter().map(|i| i.x0).min().unwrap_or_default(),
+            x1: parts.iter().map(|i| i.x1).max().unwrap_or_default(),
+            top: parts.iter().map(|i| i.top).min().unwrap_or_default(),
+            bottom,
+            baseline: bottom,
+            size: parts.iter().map(|i| i.size).max().unwrap_or_default(),
+            content: Content::Node(node),
+        }
+    }
+}
+
+/// Recognize the structure of a collection of items, which are laid out
+/// horizontally along a common baseline.
+fn layout(mut items: Vec<Item>) -> Option<Node> {
+    // First, find fractions and radicals. These are the structures that
+    // involve rules. We handle the widest rules first, so that the outermost
+    // structures are found first, and inner ones are handled in the recursive
+    // calls that lay out their contents.
+
+    loop {
+        let widest = items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_rule())
+            .max_by_key(|(_, item)| item.x1 - item.x0)
+            .map(|(index, _)| index);
+
+        let rule_index = match widest {
+            Some(i) => i,
+            None => break,
+        };
+
+        let rule = items.remove(rule_index);
+
+        if let Some(radical_index) = find_radical_sign(&items, &rule) {
+            let sign = items.remove(radical_index);
+            items = extract_radical(items, sign, rule)?;
+        } else {
+            items = extract_fraction(items, rule)?;
+        }
+    }
+
+    // Now the remaining items should be glyphs and structures, arranged along
+    // a baseline with possible scripts and limits.
+
+    let main_size = items.iter().map(|i| i.size).max()?;
+    let tol = main_size / 8;
+
+    let main_glyphs = || {
+        items
+            .iter()
+            .filter(|i| i.glyph_char().is_some() && i.size == main_size)
+    };
+
+    let baseline = main_glyphs()
+        .filter(|i| !i.is_enlarged_glyph())
+        .min_by_key(|i| i.x0)
+        .or_else(|| main_glyphs().min_by_key(|i| i.x0))
+        .map(|i| i.baseline);
+
+    let (mut bases, others): (Vec<Item>, Vec<Item>) = items.into_iter().partition(|i| {
+        match baseline {
+            // Structures are on the baseline if they straddle it. Smaller
+            // glyphs straddling it may be subscripts, so for them we also
+            // require a matching baseline.
+            Some(b) => {
+                i.top - tol <= b
+                    && i.bottom + tol >= b
+                    && (i.glyph_char().is_none()
+                        || i.size >= main_size
+                        || (i.baseline - b).abs() <= tol)
+            }
+            None => true,
+        }
+    });
+
+    // Scripts and limits are set smaller than their bases. Anything else off
+    // the baseline is something that we don't understand, like a raised box.
+    if others.iter().any(|i| i.size >= main_size) {
+        return None;
+    }
+
+    bases.sort_by_key(|i| i.x0);
+
+    // Attach each other item to a base, as a script or limit. Items that come
+    // before all of the bases are prescripts; we attach them to an empty base.
+
+    let mut attachments: Vec<Attachments> = vec![Attachments::default(); bases.len()];
+    let mut prescripts = Attachments::default();
+    let baseline = baseline.unwrap_or_default();
+
+    for item in others {
+        let is_above = item.center_y() < baseline;
+        let cx = item.center_x();
+
+        let (att, limit) = if let Some(index) = bases.iter().position(|b| b.x0 <= cx && cx < b.x1) {
+            (&mut attachments[index], true)
+        } else if let Some(index) = bases.iter().rposition(|b| b.x0 <= item.x0) {
+            (&mut attachments[index], false)
+        } else {
+            (&mut prescripts, false)
+        };
+
+        // Scripts and limits can't be mixed on the same base.
+        if !(att.sub.is_empty() && att.sup.is_empty()) && att.limits != limit {
+            return None;
+        }
+
+        att.limits = limit;
+
+        if is_above {
+            att.sup.push(item);
+        } else {
+            att.sub.push(item);
+        }
+    }
+
+    let mut row = Vec::new();
+
+    if !prescripts.is_empty() {
+        row.push(prescripts.into_node(Node::Row(Vec::new()))?);
+    }
+
+    // Merge runs of digits into numbers, and runs of plain letters in the
+    // same upright font into identifiers like "sin". Letters in italic fonts
+    // are separate variables: `$ab$` is a product, not a name.
+
+    let mut pending: Option<PendingToken> = None;
+
+    for (base, att) in bases.into_iter().zip(attachments) {
+        let node = match base.content {
+            Content::Glyph(c, font_num, upright) if att.is_empty() => {
+                if let Some(p) = pending.as_mut() {
+                    if p.try_push(c, font_num) {
+                        continue;
+                    }
+                }
+
+                if let Some(p) = pending.take() {
+                    row.push(p.into_node());
+                }
+
+                pending = PendingToken::new(c, font_num, upright);
+
+                if pending.is_some() {
+                    continue;
+                }
+
+                Node::Token(token_kind(c), c.to_string())
+            }
+
+            Content::Glyph(c, _, _) => Node::Token(token_kind(c), c.to_string()),
+            Content::Node(node) => node,
+            Content::Rule => return None,
+        };
+
+        if let Some(p) = pending.take() {
+            row.push(p.into_node());
+        }
+
+        row.push(att.into_node(node)?);
+    }
+
+    if let Some(p) = pending.take() {
+        row.push(p.into_node());
+    }
+
+    Some(Node::Row(row))
+}
+
+/// The scripts or limits attached to a base.
+#[derive(Clone, Debug, Default)]
+struct Attachments {
+    sub: Vec<Item>,
+    sup: Vec<Item>,
+    limits: bool,
+}
+
+impl Attachments {
+    fn is_empty(&self) -> bool {
+        self.sub.is_empty() && self.sup.is_empty()
+    }
+
+    fn into_node(self, base: Node) -> Option<Node> {
+        if self.is_empty() {
+            return Some(base);
+        }
+
+        let sub = if self.sub.is_empty() {
+            None
+        } else {
+            Some(Box::new(layout(self.sub)?))
+        };
+
+        let sup = if self.sup.is_empty() {
+            None
+        } else {
+            Some(Box::new(layout(self.sup)?))
+        };
+
+        Some(Node::Scripts {
+            base: Box::new(base),
+            sub,
+            sup,
+            limits: self.limits,
+        })
+    }
+}
+
+/// A token that might be extended with following characters.
+#[derive(Debug)]
+struct PendingToken {
+    kind: &'static str,
+    text: String,
+    font_num: TexFontNum,
+}
+
+impl PendingToken {
+    /// Start a token, if the character is one that can begin a multi-character
+    /// token: a digit, or a letter in an upright font.
+    fn new(c: char, font_num: TexFontNum, upright: bool) -> Option<Self> {
+        if c.is_numeric() || (upright && c.is_ascii_alphabetic()) {
+            Some(PendingToken {
+                kind: token_kind(c),
+                text: c.to_string(),
+                font_num,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Try to add a character to the token, returning whether it was added.
+    fn try_push(&mut self, c: char, font_num: TexFontNum) -> bool {
+        let ok = if self.kind == "mn" {
+            c.is_numeric() || c == '.'
+        } else {
+            c.is_ascii_alphabetic() && font_num == self.font_num
+        };
+
+        if ok {
+            self.text.push(c);
+        }
+
+        ok
+    }
+
+    fn into_node(self) -> Node {
+        Node::Token(self.kind, self.text)
+    }
+}
+
+/// Get the MathML token element used for a character.
+fn token_kind(c: char) -> &'static str {
+    if c.is_numeric() {
+        "mn"
+    } else if c.is_alphabetic() {
+        "mi"
+    } else {
+        "mo"
+    }
+}
+
+/// If a rule is the vinculum of a radical, find the radical sign that it is
+/// attached to: a `√` glyph, at whose right edge the rule starts, above the
+/// glyph's baseline.
+fn find_radical_sign(items: &[Item], rule: &Item) -> Option<usize> {
+    items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| {
+            item.glyph_char() == Some('\u{221A}')
+                && rule.bottom <= item.bottom
+                && (rule.x0 - item.x1).abs() <= item.size / 4
+        })
+        .min_by_key(|(_, item)| (rule.x0 - item.x1).abs())
+        .map(|(index, _)| index)
+}
+
+fn extract_radical(items: Vec<Item>, sign: Item, rule: Item) -> Option<Vec<Item>> {
+    let (radicand, rest): (Vec<Item>, Vec<Item>) = items.into_iter().partition(|i| {
+        let cx = i.center_x();
+        cx >= rule.x0 && cx <= rule.x1 && i.top >= rule.bottom
+    });
+
+    // A root index is set small, above and to the left of the radical sign's
+    // baseline.
+    let (index, mut rest): (Vec<Item>, Vec<Item>) = rest.into_iter().partition(|i| {
+        let cx = i.center_x();
+        cx >= sign.x0 - sign.size / 2
+            && cx <= sign.x1
+            && i.bottom < sign.bottom
+            && i.size < sign.size
+    });
+
+    let radicand_node = if radicand.is_empty() {
+        Node::Row(Vec::new())
+    } else {
+        layout(radicand.clone())?
+    };
+
+    let node = if index.is_empty() {
+        Node::Sqrt(Box::new(radicand_node))
+    } else {
+        Node::Root(Box::new(radicand_node), Box::new(layout(index.clone())?))
+    };
+
+    let mut parts: Vec<&Item> = vec![&sign, &rule];
+    parts.extend(radicand.iter());
+    parts.extend(index.iter());
+
+    // The sign's glyph is positioned by its baseline, but the structure as a
+    // whole sits on the baseline of its contents.
+    let mut item = Item::new_node(node, &parts);
+    item.size = sign.size;
+    rest.push(item);
+    Some(rest)
+}
+
+fn extract_fraction(items: Vec<Item>, rule: Item) -> Option<Vec<Item>> {
+    let mut numerator = Vec::new();
+    let mut denominator = Vec::new();
+    let mut rest = Vec::new();
+
+    for item in items {
+        let cx = item.center_x();
+
+        if cx < rule.x0 || cx > rule.x1 {
+            rest.push(item);
+        } else if item.bottom < rule.top {
+            numerator.push(item);
+        } else if item.top > rule.bottom {
+            denominator.push(item);
+        } else {
+            rest.push(item);
+        }
+    }
+
+    // A rule without material above and below it isn't a fraction bar. We
+    // don't know what to do with it, so give up.
+    if numerator.is_empty() || denominator.is_empty() {
+        return None;
+    }
+
+    let node = Node::Frac(
+        Box::new(layout(numerator.clone())?),
+        Box::new(layout(denominator.clone())?),
+    );
+
+    let mut parts: Vec<&Item> = vec![&rule];
+    parts.extend(numerator.iter());
+    parts.extend(denominator.iter());
+    rest.push(Item::new_node(node, &parts));
+    Some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PT: i32 = 65536;
+
+    fn glyph(ch: char, x: i32, y: i32, size: i32) -> MathGlyph {
+        MathGlyph {
+            x: x * PT,
+            y: y * PT,
+            advance: 5 * PT * size / 10,
+            ascent: 7 * PT * size / 10,
+            descent: -2 * PT * size / 10,
+            size: size * PT,
+            font_num: 1,
+            upright: false,
+            ch,
+        }
+    }
+
+    /// A glyph in an upright font, as used for operator names.
+    fn roman(ch: char, x: i32, y: i32, size: i32) -> MathGlyph {
+        MathGlyph {
+            font_num: 2,
+            upright: true,
+            ..glyph(ch, x, y, size)
+        }
+    }
+
+    /// An enlarged glyph, like a large delimiter or operator, that TeX has
+    /// centered on the math axis rather than on the baseline.
+    fn enlarged(ch: char, x: i32, y: i32, ascent: i32, descent: i32) -> MathGlyph {
+        MathGlyph {
+            advance: 10 * PT,
+            ascent: ascent * PT,
+            descent: -descent * PT,
+            ..glyph(ch, x, y, 10)
+        }
+    }
+
+    fn rule(x: i32, y: i32, width: i32) -> MathRule {
+        MathRule {
+            x: x * PT,
+            y: y * PT,
+            width: width * PT,
+            height: PT / 2,
+        }
+    }
+
+    fn body(mathml: Option<String>) -> String {
+        let mathml = mathml.unwrap();
+        let start = mathml.find('>').unwrap() + 1;
+        mathml[start..mathml.len() - "</math>".len()].to_owned()
+    }
+
+    #[test]
+    fn scripts_and_numbers() {
+        // x^2 + y_12
+        let glyphs = [
+            glyph('x', 0, 0, 10),
+            glyph('2', 5, -4, 7),
+            glyph('+', 10, 0, 10),
+            glyph('y', 15, 0, 10),
+            glyph('1', 20, 2, 7),
+            glyph('2', 24, 2, 7),
+        ];
+
+        assert_eq!(
+            body(canvas_to_mathml(&glyphs, &[], false)),
+            "<msup><mi>x</mi><mn>2</mn></msup><mo>+</mo><msub><mi>y</mi><mn>12</mn></msub>"
+        );
+    }
+
+    #[test]
+    fn fraction_and_radical() {
+        // 1 + a/√b
+        let glyphs = [
+            glyph('1', 0, 0, 10),
+            glyph('+', 5, 0, 10),
+            glyph('a', 12, -6, 10),
+            glyph('\u{221A}', 10, 8, 10),
+            glyph('b', 16, 8, 10),
+        ];
+        let rules = [rule(10, -2, 12), rule(15, 1, 6)];
+
+        assert_eq!(
+            body(canvas_to_mathml(&glyphs, &rules, true)),
+            "<mn>1</mn><mo>+</mo><mfrac><mi>a</mi><msqrt><mi>b</mi></msqrt></mfrac>"
+        );
+    }
+
+    #[test]
+    fn limits_and_words() {
+        // sin ∑_{i}^{n}
+        let glyphs = [
+            roman('s', 0, 0, 10),
+            roman('i', 5, 0, 10),
+            roman('n', 10, 0, 10),
+            glyph('\u{2211}', 16, 0, 10),
+            glyph('i', 17, 6, 7),
+            glyph('n', 17, -9, 7),
+        ];
+
+        assert_eq!(
+            body(canvas_to_mathml(&glyphs, &[], true)),
+            "<mi>sin</mi><munderover><mo>\u{2211}</mo><mi>i</mi><mi>n</mi></munderover>"
+        );
+    }
+
+    #[test]
+    fn delimiters() {
+        // \left( a/b \right)
+        let glyphs = [
+            enlarged('(', 0, 2, 10, 8),
+            glyph('a', 12, -6, 10),
+            glyph('b', 12, 8, 10),
+            enlarged(')', 18, 2, 10, 8),
+        ];
+        let rules = [rule(11, -2, 6)];
+
+        assert_eq!(
+            body(canvas_to_mathml(&glyphs, &rules, true)),
+            "<mo>(</mo><mfrac><mi>a</mi><mi>b</mi></mfrac><mo>)</mo>"
+        );
+    }
+
+    #[test]
+    fn display_operator_limits() {
+        // \sum_{i=1}^{n} x_i, in display style
+        let glyphs = [
+            enlarged('\u{2211}', 0, 1, 8, 6),
+            glyph('i', 1, 10, 7),
+            glyph('=', 4, 10, 7),
+            glyph('1', 7, 10, 7),
+            glyph('n', 4, -11, 7),
+            glyph('x', 12, 0, 10),
+            glyph('i', 17, 2, 7),
+        ];
+
+        assert_eq!(
+            body(canvas_to_mathml(&glyphs, &[], true)),
+            "<munderover><mo>\u{2211}</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow>\
+             <mi>n</mi></munderover><msub><mi>x</mi><mi>i</mi></msub>"
+        );
+    }
+
+    #[test]
+    fn separate_variables() {
+        // ab \sin x
+        let glyphs = [
+            glyph('a', 0, 0, 10),
+            glyph('b', 5, 0, 10),
+            roman('s', 12, 0, 10),
+            roman('i', 17, 0, 10),
+            roman('n', 22, 0, 10),
+            glyph('x', 29, 0, 10),
+        ];
+
+        assert_eq!(
+            body(canvas_to_mathml(&glyphs, &[], false)),
+            "<mi>a</mi><mi>b</mi><mi>sin</mi><mi>x</mi>"
+        );
+    }
+
+    #[test]
+    fn unrecognized() {
+        // A stray rule with nothing above it.
+        let glyphs = [glyph('x', 0, 0, 10)];
+        let rules = [rule(0, 5, 10)];
+        assert!(canvas_to_mathml(&glyphs, &rules, false).is_none());
+
+        // A full-size glyph raised off of the baseline isn't a script.
+        let glyphs = [glyph('x', 0, 0, 10), glyph('y', 5, -5, 10)];
+        assert!(canvas_to_mathml(&glyphs, &[], false).is_none());
+    }
+}
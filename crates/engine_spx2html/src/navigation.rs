@@ -0,0 +1,338 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Site-wide navigation for multi-file HTML output.
+//!
+//! Every page emitted by a document is recorded along with its title and
+//! headings. Once all of the pages are known, each template is rendered with
+//! a context describing the whole site, so that templates can generate
+//! navigation bars and tables of contents.
+
+use serde::Serialize;
+
+use crate::links::relative_url;
+
+/// A heading element found in the content of a page.
+#[derive(Clone, Debug)]
+pub(crate) struct Heading {
+    /// The heading level, from 1 (`<h1>`) to 6 (`<h6>`).
+    pub level: u8,
+
+    /// The plain text of the heading.
+    pub text: String,
+
+    /// The ID of the heading element.
+    pub id: String,
+
+    /// The offset in the page content at which the heading's text starts.
+    pub content_offset: usize,
+}
+
+/// Information about an emitted page.
+#[derive(Clone, Debug)]
+pub(crate) struct PageInfo {
+    /// The output path of the page, relative to the output root.
+    pub output_path: String,
+
+    /// The title of the page, if one was set explicitly with the
+    /// `tduxPageTitle` template variable while the page was being built.
+    pub title: Option<String>,
+
+    /// The headings in the page content, in document order.
+    pub headings: Vec<Heading>,
+}
+
+impl PageInfo {
+    /// Get the title of the page: the explicitly specified one, if any, else
+    /// the text of its first heading, else its output path.
+    pub(crate) fn effective_title(&self) -> &str {
+        self.title
+            .as_deref()
+            .or_else(|| self.headings.first().map(|h| h.text.as_ref()))
+            .unwrap_or(&self.output_path)
+    }
+}
+
+/// Get the plain text of a fragment of HTML content, with its tags removed and
+/// its whitespace collapsed.
+///
+/// Block-level tags separate words, while inline ones don't.
+pub(crate) fn html_to_text(html: &str) -> String {
+    let mut stripped = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        stripped.push_str(&rest[..start]);
+
+        let (tag, after) = match rest[start..].find('>') {
+            Some(len) => (&rest[start + 1..start + len], &rest[start + len + 1..]),
+            None => (&rest[start + 1..], ""),
+        };
+
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+
+        if BLOCK_ELEMENTS.contains(&name.to_ascii_lowercase().as_ref()) {
+            stripped.push(' ');
+        }
+
+        rest = after;
+    }
+
+    stripped.push_str(rest);
+
+    html_escape::decode_html_entities(&stripped)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// HTML elements whose boundaries separate words in plain text.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+/// The description of a page provided to templates.
+#[derive(Serialize)]
+struct PageContext<'a> {
+    index: usize,
+    path: &'a str,
+    url: String,
+    title: &'a str,
+    is_current: bool,
+    headings: Vec<HeadingContext<'a>>,
+}
+
+/// The description of a heading provided to templates.
+#[derive(Serialize)]
+struct HeadingContext<'a> {
+    level: u8,
+    text: &'a str,
+    url: String,
+}
+
+/// An entry in the hierarchical table of contents provided to templates.
+#[derive(Serialize)]
+struct TocEntry<'a> {
+    level: u8,
+    title: &'a str,
+    url: String,
+    page_index: usize,
+    is_current: bool,
+    children: Vec<TocEntry<'a>>,
+}
+
+/// Add the site-wide navigation variables to the context used to render the
+/// page with index *index* in *pages*.
+///
+/// These are:
+///
+/// - `tduxPages`: the list of all pages, in order of emission. Each has fields
+///   `index`, `path`, `url`, `title`, `is_current`, and `headings`; the latter
+///   is a list of items with fields `level`, `text`, and `url`.
+/// - `tduxPageIndex`: the index of the current page in `tduxPages`.
+/// - `tduxPageTitle`: the title of the current page. If the document didn't
+///   set one, this is the text of the page's first heading or its path.
+/// - `tduxPrevPage`, `tduxNextPage`: the adjacent pages, if they exist.
+/// - `tduxToc`: a table of contents built from the headings of all pages. Each
+///   entry has fields `level`, `title`, `url`, `page_index`, `is_current`, and
+///   `children`. Pages without headings appear as top-level entries.
+///
+/// All URLs are relative to the current page.
+pub(crate) fn insert_site_context(pages: &[PageInfo], index: usize, context: &mut tera::Context) {
+    let here = &pages[index].output_path;
+
+    let page_contexts: Vec<_> = pages
+        .iter()
+        .enumerate()
+        .map(|(i, page)| PageContext {
+            index: i,
+            path: &page.output_path,
+            url: relative_url(here, &page.output_path, None),
+            title: page.effective_title(),
+            is_current: i == index,
+            headings: page
+                .headings
+                .iter()
+                .map(|h| HeadingContext {
+                    level: h.level,
+                    text: &h.text,
+                    url: relative_url(here, &page.output_path, Some(&h.id)),
+                })
+                .collect(),
+        })
+        .collect();
+
+    let mut toc_items = Vec::new();
+
+    for (i, page) in pages.iter().enumerate() {
+        if page.headings.is_empty() {
+            toc_items.push(TocEntry {
+                level: 1,
+                title: page.effective_title(),
+                url: relative_url(here, &page.output_path, None),
+                page_index: i,
+                is_current: i == index,
+                children: Vec::new(),
+            });
+        }
+
+        for h in &page.headings {
+            toc_items.push(TocEntry {
+                level: h.level,
+                title: &h.text,
+                url: relative_url(here, &page.output_path, Some(&h.id)),
+                page_index: i,
+                is_current: i == index,
+                children: Vec::new(),
+            });
+        }
+    }
+
+    context.insert("tduxPageIndex", &index);
+    context.insert("tduxPageTitle", pages[index].effective_title());
+    context.insert("tduxToc", &build_toc(toc_items));
+
+    if let Some(prev) = index.checked_sub(1) {
+        context.insert("tduxPrevPage", &page_contexts[prev]);
+    } else {
+        context.remove("tduxPrevPage");
+    }
+
+    if let Some(next) = page_contexts.get(index + 1) {
+        context.insert("tduxNextPage", next);
+    } else {
+        context.remove("tduxNextPage");
+    }
+
+    context.insert("tduxPages", &page_contexts);
+}
+
+/// Nest a flat list of TOC entries according to their levels.
+fn build_toc(items: Vec<TocEntry<'_>>) -> Vec<TocEntry<'_>> {
+    let mut roots = Vec::new();
+    let mut open: Vec<TocEntry> = Vec::new();
+
+    for item in items {
+        while open.last().map(|e| e.level >= item.level).unwrap_or(false) {
+            close_toc_entry(&mut open, &mut roots);
+        }
+
+        open.push(item);
+    }
+
+    while !open.is_empty() {
+        close_toc_entry(&mut open, &mut roots);
+    }
+
+    roots
+}
+
+fn close_toc_entry<'a>(open: &mut Vec<TocEntry<'a>>, roots: &mut Vec<TocEntry<'a>>) {
+    let entry = open.pop().unwrap();
+
+    if let Some(parent) = open.last_mut() {
+        parent.children.push(entry);
+    } else {
+        roots.push(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heading(level: u8, text: &str) -> Heading {
+        Heading {
+            level,
+            text: text.to_owned(),
+            id: text.to_lowercase(),
+            content_offset: 0,
+        }
+    }
+
+    #[test]
+    fn heading_text() {
+        assert_eq!(
+            html_to_text("<b>1.2</b>\n  Fish &amp; <a href=\"#x\">Chips</a> "),
+            "1.2 Fish & Chips"
+        );
+        assert_eq!(
+            html_to_text("<div>Para</div><div>graphs</div><b>wo</b>rd<br/>end"),
+            "Para graphs word end"
+        );
+    }
+
+    #[test]
+    fn site_context() {
+        let pages = vec![
+            PageInfo {
+                output_path: "index.html".to_owned(),
+                title: Some("Home".to_owned()),
+                headings: vec![],
+            },
+            PageInfo {
+                output_path: "ch1/index.html".to_owned(),
+                title: None,
+                headings: vec![heading(1, "One"), heading(2, "Sub"), heading(3, "Deep")],
+            },
+            PageInfo {
+                output_path: "ch2/index.html".to_owned(),
+                title: None,
+                headings: vec![heading(1, "Two"), heading(2, "More")],
+            },
+        ];
+
+        let mut context = tera::Context::new();
+        insert_site_context(&pages, 1, &mut context);
+        let json = context.into_json();
+
+        assert_eq!(json["tduxPageIndex"], 1);
+        assert_eq!(json["tduxPrevPage"]["url"], "../index.html");
+        assert_eq!(json["tduxPrevPage"]["title"], "Home");
+        assert_eq!(json["tduxNextPage"]["title"], "Two");
+        assert_eq!(json["tduxPages"][1]["url"], "index.html");
+        assert_eq!(json["tduxPages"][1]["headings"][1]["url"], "#sub");
+
+        let toc = &json["tduxToc"];
+        assert_eq!(toc.as_array().unwrap().len(), 3);
+        assert_eq!(toc[0]["title"], "Home");
+        assert_eq!(toc[1]["children"][0]["children"][0]["title"], "Deep");
+        assert_eq!(toc[1]["children"][0]["is_current"], true);
+        assert_eq!(toc[2]["children"][0]["url"], "../ch2/index.html#more");
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Destinations for the files created by the SPX-to-HTML engine.
+//!
+//! The engine generally produces a whole tree of HTML files and assets that
+//! won't be used by any subsequent processing stage. By default, it writes them
+//! straight to disk, but the [`OutputTree`] trait allows them to go anywhere
+//! else: for instance, into Tectonic's I/O layer, so that they can be captured
+//! in memory.
+
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tectonic_bridge_core::DriverHooks;
+use tectonic_errors::prelude::*;
+use tectonic_status_base::StatusBackend;
+
+/// A destination for the tree of files created by an SPX-to-HTML run.
+pub trait OutputTree {
+    /// Create a file in the output tree with the specified contents.
+    ///
+    /// The *path* is relative to the root of the output tree, with components
+    /// separated by forward slashes. It has been validated to not contain any
+    /// `..` or absolute components. Any parent directories of the file should
+    /// be created as needed. The driver hooks of the processing session are
+    /// provided so that implementations can route files through Tectonic's I/O
+    /// layer.
+    fn write_file(
+        &mut self,
+        path: &str,
+        contents: &[u8],
+        hooks: &mut dyn DriverHooks,
+        status: &mut dyn StatusBackend,
+    ) -> Result<()>;
+
+    /// Note that a templated HTML page has been written.
+    ///
+    /// This is called after the page's contents have been passed to
+    /// [`Self::write_file`], once for each page, in the order in which the
+    /// document emitted them. The default implementation does nothing.
+    fn page_written(&mut self, _path: &str, _title: &str) {}
+
+    /// Note that a file that supports the HTML output, rather than being part
+    /// of the document itself, has been written.
+    ///
+    /// This is called after the file's contents have been passed to
+    /// [`Self::write_file`]. Such files are source position maps and the files
+    /// of the client-side search feature, which are meaningless in formats
+    /// like EPUB. The default implementation does nothing.
+    fn auxiliary_file_written(&mut self, _path: &str) {}
+
+    /// Finish creating the output tree.
+    ///
+    /// This is called once, after all of the files have been written. The
+    /// default implementation does nothing.
+    fn finish(
+        &mut self,
+        _hooks: &mut dyn DriverHooks,
+        _status: &mut dyn StatusBackend,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// An output tree rooted in a directory of the actual filesystem.
+#[derive(Clone, Debug)]
+pub struct FilesystemOutput {
+    root: PathBuf,
+}
+
+impl FilesystemOutput {
+    /// Create an output tree rooted at the specified directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FilesystemOutput { root: root.into() }
+    }
+
+    /// Get the root directory of this output tree.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl OutputTree for FilesystemOutput {
+    fn write_file(
+        &mut self,
+        path: &str,
+        contents: &[u8],
+        _hooks: &mut dyn DriverHooks,
+        _status: &mut dyn StatusBackend,
+    ) -> Result<()> {
+        let mut out_path = self.root.clone();
+        out_path.extend(path.split('/').filter(|p| !p.is_empty()));
+
+        if let Some(parent) = out_path.parent() {
+            atry!(
+                std::fs::create_dir_all(parent);
+                ["cannot create output parent directory `{}`", parent.display()]
+            );
+        }
+
+        let mut out_file = atry!(
+            File::create(&out_path);
+            ["cannot open output file `{}`", out_path.display()]
+        );
+
+        atry!(
+            out_file.write_all(contents);
+            ["cannot write output file `{}`", out_path.display()]
+        );
+
+        Ok(())
+    }
+}
+
+/// An output tree that writes files through the I/O layer of the driver.
+///
+/// Each file is opened with the name of its path relative to the output root,
+/// so that drivers that keep their outputs in memory can capture the complete
+/// HTML tree.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DriverIoOutput;
+
+impl OutputTree for DriverIoOutput {
+    fn write_file(
+        &mut self,
+        path: &str,
+        contents: &[u8],
+        hooks: &mut dyn DriverHooks,
+        status: &mut dyn StatusBackend,
+    ) -> Result<()> {
+        let mut output = atry!(
+            hooks.io().output_open_name(path).must_exist();
+            ["cannot open output file `{}`", path]
+        );
+
+        atry!(
+            output.write_all(contents);
+            ["cannot write output file `{}`", path]
+        );
+
+        let (name, digest) = output.into_name_digest();
+        hooks.event_output_closed(name, digest, status);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tectonic_bridge_core::MinimalDriver;
+    use tectonic_status_base::NoopStatusBackend;
+
+    use crate::testutil::{output_text, run_spx2html, spx_of_specials, MemoryIo};
+
+    #[test]
+    fn driver_io_tree() {
+        let spx = spx_of_specials(&[
+            "tdux:addTemplate t.html",
+            "tdux:setTemplate t.html",
+            "tdux:setOutputPath ch1/index.html",
+            "tdux:provideFile data.txt fonts/data.txt",
+            "tdux:provideSpecial font-css fonts/faces.css",
+            "tdux:asp",
+            "tdux:dt Hello",
+            "tdux:aep",
+            "tdux:emit",
+            "tdux:contentFinished",
+        ]);
+
+        let outputs = run_spx2html(
+            &spx,
+            &[
+                ("t.html", b"<main>{{ tduxContent }}</main>"),
+                ("data.txt", b"some data"),
+            ],
+            |_| {},
+        )
+        .unwrap();
+
+        let mut names: Vec<_> = outputs.keys().map(|k| k.as_str()).collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            ["ch1/index.html", "fonts/data.txt", "fonts/faces.css"]
+        );
+        assert_eq!(
+            output_text(&outputs, "ch1/index.html"),
+            r#"<main><div class="tdux-p">Hello</div></main>"#
+        );
+        assert_eq!(output_text(&outputs, "fonts/data.txt"), "some data");
+    }
+
+    #[test]
+    fn filesystem_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut hooks = MinimalDriver::new(MemoryIo::default());
+        let mut status = NoopStatusBackend::default();
+        let mut tree = FilesystemOutput::new(dir.path().join("out"));
+
+        tree.write_file("a/b/c.html", b"nested", &mut hooks, &mut status)
+            .unwrap();
+        tree.write_file("top.css", b"top", &mut hooks, &mut status)
+            .unwrap();
+
+        let root = tree.root();
+        assert_eq!(std::fs::read(root.join("a/b/c.html")).unwrap(), b"nested");
+        assert_eq!(std::fs::read(root.join("top.css")).unwrap(), b"top");
+    }
+}
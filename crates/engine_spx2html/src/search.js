@@ -0,0 +1,169 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+//
+// Client-side search widget for Tectonic HTML output. Every element with the
+// class `tdux-search` becomes a search box over the index given by its
+// `data-index` attribute. Result URLs are resolved relative to its
+// `data-rel-top` attribute.
+
+(function () {
+  "use strict";
+
+  var MAX_RESULTS = 20;
+  var SNIPPET_RADIUS = 60;
+
+  function loadIndex(widget) {
+    if (!widget.indexPromise) {
+      widget.indexPromise = fetch(widget.dataset.index)
+        .then(function (resp) {
+          if (!resp.ok) {
+            throw new Error("failed to load search index: " + resp.status);
+          }
+          return resp.json();
+        })
+        .catch(function (err) {
+          // Try again on the next query.
+          widget.indexPromise = null;
+          throw err;
+        });
+    }
+
+    return widget.indexPromise;
+  }
+
+  function score(entry, terms) {
+    var heading = (entry.heading || entry.page_title).toLowerCase();
+    var text = entry.text.toLowerCase();
+    var total = 0;
+
+    for (var i = 0; i < terms.length; i++) {
+      var inHeading = heading.indexOf(terms[i]) >= 0;
+      var inText = text.indexOf(terms[i]) >= 0;
+
+      if (!inHeading && !inText) {
+        return 0;
+      }
+
+      total += inHeading ? 10 : 1;
+    }
+
+    return total;
+  }
+
+  function snippet(text, term) {
+    var pos = text.toLowerCase().indexOf(term);
+    var start = Math.max(0, pos - SNIPPET_RADIUS);
+    var end = Math.min(text.length, pos + term.length + SNIPPET_RADIUS);
+    var s = text.slice(start, end);
+
+    if (start > 0) {
+      s = "…" + s;
+    }
+
+    if (end < text.length) {
+      s = s + "…";
+    }
+
+    return s;
+  }
+
+  function render(widget, list, index, query) {
+    var terms = query.toLowerCase().split(/\s+/).filter(function (t) {
+      return t.length > 0;
+    });
+
+    list.textContent = "";
+
+    if (terms.length === 0) {
+      return;
+    }
+
+    var results = index.entries
+      .map(function (entry) {
+        return { entry: entry, score: score(entry, terms) };
+      })
+      .filter(function (r) {
+        return r.score > 0;
+      })
+      .sort(function (a, b) {
+        return b.score - a.score;
+      })
+      .slice(0, MAX_RESULTS);
+
+    var relTop = widget.dataset.relTop || "";
+
+    results.forEach(function (r) {
+      var item = document.createElement("li");
+      var link = document.createElement("a");
+      link.href = relTop + r.entry.url;
+      link.textContent = r.entry.heading
+        ? r.entry.page_title + " — " + r.entry.heading
+        : r.entry.page_title;
+      item.appendChild(link);
+
+      var excerpt = document.createElement("p");
+      excerpt.className = "tdux-search-snippet";
+      excerpt.textContent = snippet(r.entry.text, terms[0]);
+      item.appendChild(excerpt);
+      list.appendChild(item);
+    });
+
+    if (results.length === 0) {
+      var none = document.createElement("li");
+      none.className = "tdux-search-none";
+      none.textContent = "No results";
+      list.appendChild(none);
+    }
+  }
+
+  function showError(list, err) {
+    var item = document.createElement("li");
+    item.className = "tdux-search-error";
+    item.textContent = "Search is unavailable: " + err.message;
+    list.textContent = "";
+    list.appendChild(item);
+  }
+
+  function setUp(widget) {
+    var input = document.createElement("input");
+    input.type = "search";
+    input.placeholder = "Search";
+    input.className = "tdux-search-input";
+
+    var list = document.createElement("ul");
+    list.className = "tdux-search-results";
+
+    input.addEventListener("input", function () {
+      var query = input.value;
+
+      loadIndex(widget)
+        .then(function (index) {
+          if (input.value === query) {
+            render(widget, list, index, query);
+          }
+        })
+        .catch(function (err) {
+          if (input.value === query) {
+            showError(list, err);
+          }
+        });
+    });
+
+    widget.appendChild(input);
+    widget.appendChild(list);
+  }
+
+  function setUpAll() {
+    var widgets = document.querySelectorAll(".tdux-search");
+
+    for (var i = 0; i < widgets.length; i++) {
+      setUp(widgets[i]);
+    }
+  }
+
+  if (document.readyState === "loading") {
+    document.addEventListener("DOMContentLoaded", setUpAll);
+  } else {
+    setUpAll();
+  }
+})();
@@ -0,0 +1,153 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Client-side full-text search of HTML output.
+//!
+//! The text content of each emitted page is split into sections at its
+//! headings. If the document asks for it with `tdux:provideSpecial
+//! search-index {path}`, the sections are saved as a JSON index that can be
+//! searched in the browser by the widget emitted with `tdux:provideSpecial
+//! search-js {path}`.
+
+use crate::{
+    assets::syntax::SearchIndexEntry,
+    navigation::{html_to_text, Heading, PageInfo},
+};
+
+/// The default search widget.
+///
+/// It turns every element with the class `tdux-search` into a search box. The
+/// element's `data-index` attribute gives the URL of the search index, and its
+/// `data-rel-top` attribute gives the relative URL of the output root, which
+/// is usually the `tduxRelTop` template variable.
+pub(crate) const SEARCH_WIDGET_JS: &str = include_str!("search.js");
+
+/// Split the HTML content of a page into the plain text of its sections.
+///
+/// The first item is the text preceding the first heading. Each following item
+/// is the text from a heading up to the next one.
+pub(crate) fn page_sections(content: &str, headings: &[Heading]) -> Vec<String> {
+    let mut sections = Vec::with_capacity(headings.len() + 1);
+    let mut start = 0;
+
+    for h in headings {
+        let end = h.content_offset.clamp(start, content.len());
+        sections.push(html_to_text(content.get(start..end).unwrap_or_default()));
+        start = end;
+    }
+
+    sections.push(html_to_text(content.get(start..).unwrap_or_default()));
+    sections
+}
+
+/// Create the search index entries for a set of pages, given with their HTML
+/// content.
+pub(crate) fn index_entries(pages: &[(&PageInfo, &str)]) -> Vec<SearchIndexEntry> {
+    let mut entries = Vec::new();
+
+    for (page, content) in pages {
+        let sections = page_sections(content, &page.headings);
+
+        for (i, text) in sections.into_iter().enumerate() {
+            if text.is_empty() {
+                continue;
+            }
+
+            // The first section isn't under any heading.
+            let heading = i.checked_sub(1).and_then(|h| page.headings.get(h));
+
+            let url = match heading {
+                Some(h) => format!("{}#{}", page.output_path, h.id),
+                None => page.output_path.clone(),
+            };
+
+            entries.push(SearchIndexEntry {
+                url,
+                page_title: page.effective_title().to_owned(),
+                heading: heading.map(|h| h.text.clone()),
+                text,
+            });
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{output_text, run_spx2html, spx_of_specials};
+
+    #[test]
+    fn sections() {
+        let content = "<p>Intro</p><h2 id=\"a\">Alpha</h2><p>one</p><h2 id=\"b\">Beta</h2>";
+        let headings = vec![
+            Heading {
+                level: 2,
+                text: "Alpha".to_owned(),
+                id: "a".to_owned(),
+                content_offset: content.find("Alpha").unwrap(),
+            },
+            Heading {
+                level: 2,
+                text: "Beta".to_owned(),
+                id: "b".to_owned(),
+                content_offset: content.find("Beta").unwrap(),
+            },
+        ];
+
+        let sections = page_sections(content, &headings);
+        assert_eq!(sections, vec!["Intro", "Alpha one", "Beta"]);
+
+        let page = PageInfo {
+            output_path: "ch/index.html".to_owned(),
+            title: None,
+            headings,
+        };
+
+        let entries = index_entries(&[(&page, content)]);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].url, "ch/index.html");
+        assert_eq!(entries[0].heading, None);
+        assert_eq!(entries[1].url, "ch/index.html#a");
+        assert_eq!(entries[1].page_title, "Alpha");
+        assert_eq!(entries[2].heading.as_deref(), Some("Beta"));
+    }
+
+    #[test]
+    fn index_file() {
+        let page = |path: &'static str, text: &'static str| {
+            [
+                "tdux:setTemplate t.html",
+                path,
+                "tdux:asp",
+                text,
+                "tdux:aep",
+                "tdux:emit",
+            ]
+        };
+
+        let mut specials = vec!["tdux:addTemplate t.html"];
+        specials.extend(page("tdux:setOutputPath a.html", "tdux:dt First page"));
+        specials.extend(page("tdux:setOutputPath b/c.html", "tdux:dt Second page"));
+        // The index can be requested after the pages have been emitted.
+        specials.push("tdux:provideSpecial search-index idx.json");
+        specials.push("tdux:contentFinished");
+
+        let outputs = run_spx2html(
+            &spx_of_specials(&specials),
+            &[("t.html", b"{{ tduxContent }}")],
+            |_| {},
+        )
+        .unwrap();
+
+        assert_eq!(
+            output_text(&outputs, "idx.json"),
+            r#"{"entries":[{"url":"a.html","page_title":"a.html","text":"First page"},{"url":"b/c.html","page_title":"b/c.html","text":"Second page"}]}"#
+        );
+        assert_eq!(
+            output_text(&outputs, "b/c.html"),
+            r#"<div class="tdux-p">Second page</div>"#
+        );
+    }
+}
@@ -0,0 +1,427 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Reading and writing the table structure of OpenType font files.
+//!
+//! We emit fonts by taking apart the input font file into its tables, possibly
+//! modifying some of them, and then reassembling them. The results can be
+//! written as plain OpenType or as [WOFF2].
+//!
+//! [WOFF2]: https://www.w3.org/TR/WOFF2/
+
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use std::num::Wrapping;
+use tectonic_errors::prelude::*;
+
+/// A four-byte OpenType table tag.
+pub(crate) type Tag = [u8; 4];
+
+pub(crate) const CFF: Tag = *b"CFF ";
+pub(crate) const CMAP: Tag = *b"cmap";
+pub(crate) const GLYF: Tag = *b"glyf";
+pub(crate) const GSUB: Tag = *b"GSUB";
+pub(crate) const HEAD: Tag = *b"head";
+pub(crate) const LOCA: Tag = *b"loca";
+pub(crate) const MAXP: Tag = *b"maxp";
+
+const TTC_TAG: u32 = u32::from_be_bytes(*b"ttcf");
+const WOFF2_SIGNATURE: u32 = u32::from_be_bytes(*b"wOF2");
+
+/// The tables of a single font face.
+#[derive(Clone, Debug)]
+pub(crate) struct Sfnt {
+    /// The "sfntVersion" of the font, distinguishing TrueType and CFF outlines.
+    flavor: u32,
+
+    /// The font tables, sorted by tag.
+    tables: Vec<(Tag, Vec<u8>)>,
+}
+
+impl Sfnt {
+    /// Extract the tables of a face from OpenType or TrueType Collection data.
+    pub(crate) fn from_face(data: &[u8], face_index: u32) -> Result<Self> {
+        let truncated = || anyhow!("truncated OpenType font data");
+
+        let mut base = 0;
+
+        if read_u32(data, 0) == Some(TTC_TAG) {
+            base = a_ok_or!(
+                read_u32(data, 12 + 4 * face_index as usize);
+                ["no face #{} in the OpenType font collection", face_index]
+            ) as usize;
+        } else {
+            ensure!(
+                face_index == 0,
+                "no face #{} in the OpenType font",
+                face_index
+            );
+        }
+
+        let flavor = read_u32(data, base).ok_or_else(truncated)?;
+        let num_tables = read_u16(data, base + 4).ok_or_else(truncated)?;
+        let mut tables = Vec::with_capacity(num_tables as usize);
+
+        for i in 0..num_tables as usize {
+            let rec = base + 12 + 16 * i;
+            let mut tag = [0; 4];
+            tag.copy_from_slice(data.get(rec..rec + 4).ok_or_else(truncated)?);
+            let offset = read_u32(data, rec + 8).ok_or_else(truncated)? as usize;
+            let length = read_u32(data, rec + 12).ok_or_else(truncated)? as usize;
+            let contents = data
+                .get(offset..offset.saturating_add(length))
+                .ok_or_else(truncated)?;
+
+            tables.push((tag, contents.to_vec()));
+        }
+
+        tables.sort_by_key(|t| t.0);
+        Ok(Sfnt { flavor, tables })
+    }
+
+    /// Create a font with no tables.
+    #[cfg(test)]
+    pub(crate) fn new(flavor: u32) -> Self {
+        Sfnt {
+            flavor,
+            tables: Vec::new(),
+        }
+    }
+
+    /// Get the contents of a table, if it exists.
+    pub(crate) fn table(&self, tag: Tag) -> Option<&[u8]> {
+        self.tables
+            .binary_search_by_key(&tag, |t| t.0)
+            .ok()
+            .map(|i| &self.tables[i].1[..])
+    }
+
+    /// Set the contents of a table, adding it if necessary.
+    pub(crate) fn set_table(&mut self, tag: Tag, contents: Vec<u8>) {
+        match self.tables.binary_search_by_key(&tag, |t| t.0) {
+            Ok(i) => self.tables[i].1 = contents,
+            Err(i) => self.tables.insert(i, (tag, contents)),
+        }
+    }
+
+    /// Serialize the font as an OpenType file.
+    pub(crate) fn to_opentype(&self) -> Vec<u8> {
+        let n = self.tables.len();
+        let entry_selector = if n == 0 { 0 } else { n.ilog2() };
+        let search_range = 16 << entry_selector;
+
+        let mut buf = Vec::new();
+        buf.write_u32::<BigEndian>(self.flavor).unwrap();
+        buf.write_u16::<BigEndian>(n as u16).unwrap();
+        buf.write_u16::<BigEndian>(search_range as u16).unwrap();
+        buf.write_u16::<BigEndian>(entry_selector as u16).unwrap();
+        buf.write_u16::<BigEndian>((n * 16 - search_range) as u16)
+            .unwrap();
+
+        let mut offset = 12 + 16 * n;
+        let mut head_offset = None;
+
+        for (tag, contents) in &self.tables {
+            let contents = self.prepared_table(*tag, contents);
+
+            if *tag == HEAD {
+                head_offset = Some(offset);
+            }
+
+            buf.extend_from_slice(tag);
+            buf.write_u32::<BigEndian>(opentype_checksum(&contents))
+                .unwrap();
+            buf.write_u32::<BigEndian>(offset as u32).unwrap();
+            buf.write_u32::<BigEndian>(contents.len() as u32).unwrap();
+            offset += padded_len(contents.len());
+        }
+
+        for (tag, contents) in &self.tables {
+            buf.extend_from_slice(&self.prepared_table(*tag, contents));
+            buf.resize(padded_len(buf.len()), 0);
+        }
+
+        // The head table's checksum adjustment makes the checksum of the whole
+        // file come out to a magic number.
+
+        if let Some(ofs) = head_offset {
+            let adj = Wrapping(0xB1B0_AFBA) - Wrapping(opentype_checksum(&buf));
+            BigEndian::write_u32(&mut buf[ofs + 8..ofs + 12], adj.0);
+        }
+
+        buf
+    }
+
+    /// Serialize the font as a WOFF2 file.
+    ///
+    /// We don't apply any of the WOFF2 table transformations, so the
+    /// compression is only somewhat better than that of a generic compressor.
+    pub(crate) fn to_woff2(&self) -> Vec<u8> {
+        let mut directory = Vec::new();
+        let mut stream = Vec::new();
+
+        for (tag, contents) in &self.tables {
+            let contents = self.prepared_table(*tag, contents);
+
+            let mut flags = match KNOWN_TAGS.iter().position(|t| t == tag) {
+                Some(i) => i as u8,
+                None => 63,
+            };
+
+            // For glyf and loca, transformation version 0 is the actual
+            // transform, and 3 is the null transform. For all other tables, 0
+            // is the null transform.
+            if *tag == GLYF || *tag == LOCA {
+                flags |= 3 << 6;
+            }
+
+            directory.push(flags);
+
+            if flags & 63 == 63 {
+                directory.extend_from_slice(tag);
+            }
+
+            write_base128(&mut directory, contents.len() as u32);
+            stream.extend_from_slice(&contents);
+        }
+
+        let compressed = brotli_compress(&stream);
+        let sfnt_size = 12
+            + 16 * self.tables.len()
+            + self
+                .tables
+                .iter()
+                .map(|(_, c)| padded_len(c.len()))
+                .sum::<usize>();
+        let length = padded_len(48 + directory.len() + compressed.len());
+
+        let mut buf = Vec::with_capacity(length);
+        buf.write_u32::<BigEndian>(WOFF2_SIGNATURE).unwrap();
+        buf.write_u32::<BigEndian>(self.flavor).unwrap();
+        buf.write_u32::<BigEndian>(length as u32).unwrap();
+        buf.write_u16::<BigEndian>(self.tables.len() as u16)
+            .unwrap();
+        buf.write_u16::<BigEndian>(0).unwrap(); // reserved
+        buf.write_u32::<BigEndian>(sfnt_size as u32).unwrap();
+        buf.write_u32::<BigEndian>(compressed.len() as u32).unwrap();
+        buf.write_u16::<BigEndian>(1).unwrap(); // majorVersion
+        buf.write_u16::<BigEndian>(0).unwrap(); // minorVersion
+
+        // No metadata or private data blocks.
+        for _ in 0..5 {
+            buf.write_u32::<BigEndian>(0).unwrap();
+        }
+
+        buf.extend_from_slice(&directory);
+        buf.extend_from_slice(&compressed);
+        buf.resize(length, 0);
+        buf
+    }
+
+    /// Get a table's contents as they should be written out. The only change
+    /// is that the head table's checksum adjustment is zeroed, as needed to
+    /// compute checksums.
+    fn prepared_table<'a>(&self, tag: Tag, contents: &'a [u8]) -> std::borrow::Cow<'a, [u8]> {
+        if tag == HEAD && contents.len() >= 12 {
+            let mut head = contents.to_vec();
+            BigEndian::write_u32(&mut head[8..12], 0);
+            head.into()
+        } else {
+            contents.into()
+        }
+    }
+}
+
+/// The table tags that WOFF2 can encode with a single-byte index.
+const KNOWN_TAGS: [Tag; 63] = [
+    *b"cmap", *b"head", *b"hhea", *b"hmtx", *b"maxp", *b"name", *b"OS/2", *b"post", *b"cvt ",
+    *b"fpgm", *b"glyf", *b"loca", *b"prep", *b"CFF ", *b"VORG", *b"EBDT", *b"EBLC", *b"gasp",
+    *b"hdmx", *b"kern", *b"LTSH", *b"PCLT", *b"VDMX", *b"vhea", *b"vmtx", *b"BASE", *b"GDEF",
+    *b"GPOS", *b"GSUB", *b"EBSC", *b"JSTF", *b"MATH", *b"CBDT", *b"CBLC", *b"COLR", *b"CPAL",
+    *b"SVG ", *b"sbix", *b"acnt", *b"avar", *b"bdat", *b"bloc", *b"bsln", *b"cvar", *b"fdsc",
+    *b"feat", *b"fmtx", *b"fvar", *b"gvar", *b"hsty", *b"just", *b"lcar", *b"mort", *b"morx",
+    *b"opbd", *b"prop", *b"trak", *b"Zapf", *b"Silf", *b"Glat", *b"Gloc", *b"Feat", *b"Sill",
+];
+
+/// Write a number in the variable-length UIntBase128 encoding used by WOFF2.
+fn write_base128(buf: &mut Vec<u8>, value: u32) {
+    let n_bytes = std::cmp::max(1, (32 - value.leading_zeros()).div_ceil(7));
+
+    for i in (0..n_bytes).rev() {
+        let byte = ((value >> (7 * i)) & 0x7F) as u8;
+        buf.push(if i > 0 { byte | 0x80 } else { byte });
+    }
+}
+
+/// Compress the table data of a WOFF2 file with Brotli, using the settings of
+/// the reference encoder.
+fn brotli_compress(data: &[u8]) -> Vec<u8> {
+    let params = brotli::enc::BrotliEncoderParams {
+        quality: 11,
+        lgwin: 22,
+        size_hint: data.len(),
+        ..Default::default()
+    };
+
+    let mut compressed = Vec::new();
+    // Compressing into memory can't fail.
+    brotli::BrotliCompress(&mut &data[..], &mut compressed, &params).unwrap();
+    compressed
+}
+
+fn padded_len(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+pub(crate) fn opentype_checksum(data: &[u8]) -> u32 {
+    let mut iter = data.chunks_exact(4);
+    let cs: Wrapping<u32> = iter
+        .by_ref()
+        .map(|c| Wrapping(BigEndian::read_u32(c)))
+        .sum();
+
+    let rem = iter.remainder();
+    let mut padded = [0u8; 4];
+    padded[..rem.len()].copy_from_slice(rem);
+    (cs + Wrapping(BigEndian::read_u32(&padded[..]))).0
+}
+
+/// Read a big-endian u16, if the data are long enough.
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset.checked_add(2)?)
+        .map(BigEndian::read_u16)
+}
+
+/// Read a big-endian u32, if the data are long enough.
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset.checked_add(4)?)
+        .map(BigEndian::read_u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pinot::TableProvider;
+    use std::convert::TryInto;
+
+    #[test]
+    fn base128() {
+        let enc = |v| {
+            let mut buf = Vec::new();
+            write_base128(&mut buf, v);
+            buf
+        };
+
+        assert_eq!(enc(0), [0]);
+        assert_eq!(enc(127), [127]);
+        assert_eq!(enc(128), [0x81, 0]);
+        assert_eq!(enc(63 * 1024), [0x83, 0xF8, 0]);
+    }
+
+    /// A font with a few tables, including one with a tag that WOFF2 doesn't
+    /// know about.
+    fn test_font() -> Sfnt {
+        let mut head = vec![0; 54];
+        head[..4].copy_from_slice(&[0, 1, 0, 0]);
+        head[8..12].copy_from_slice(&[0xAA; 4]);
+        head[12..16].copy_from_slice(&0x5F0F_3CF5u32.to_be_bytes());
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+
+        let mut sfnt = Sfnt::new(0x0001_0000);
+        sfnt.set_table(MAXP, vec![0, 0, 0x50, 0, 0, 2]);
+        sfnt.set_table(HEAD, head);
+        sfnt.set_table(LOCA, vec![0, 0, 0, 3, 0, 3]);
+        sfnt.set_table(GLYF, vec![0, 1, 2, 3, 4, 5]);
+        sfnt.set_table(*b"zTST", b"odd length".to_vec());
+        sfnt
+    }
+
+    #[test]
+    fn opentype_round_trip() {
+        let sfnt = test_font();
+        let otf = sfnt.to_opentype();
+
+        // The checksum adjustment makes the whole file sum to the magic value.
+        assert_eq!(otf.len() % 4, 0);
+        assert_eq!(opentype_checksum(&otf), 0xB1B0_AFBA);
+
+        let font = pinot::FontDataRef::new(&otf).unwrap().get(0).unwrap();
+        assert_eq!(font.len(), 5);
+        assert_eq!(font.head().unwrap().units_per_em(), 1000);
+        assert_eq!(font.maxp().unwrap().num_glyphs(), 2);
+
+        let parsed = Sfnt::from_face(&otf, 0).unwrap();
+        assert_eq!(parsed.flavor, sfnt.flavor);
+        assert_eq!(parsed.tables.len(), sfnt.tables.len());
+
+        for (tag, contents) in &sfnt.tables {
+            if *tag == HEAD {
+                assert_eq!(parsed.table(HEAD).unwrap()[12..], contents[12..]);
+            } else {
+                assert_eq!(parsed.table(*tag).unwrap(), &contents[..]);
+            }
+        }
+    }
+
+    fn read_base128(data: &[u8], pos: &mut usize) -> u32 {
+        let mut value = 0;
+
+        loop {
+            let b = data[*pos];
+            *pos += 1;
+            value = (value << 7) | (b & 0x7F) as u32;
+
+            if b & 0x80 == 0 {
+                return value;
+            }
+        }
+    }
+
+    #[test]
+    fn woff2_round_trip() {
+        let sfnt = test_font();
+        let woff2 = sfnt.to_woff2();
+
+        assert_eq!(read_u32(&woff2, 0), Some(WOFF2_SIGNATURE));
+        assert_eq!(read_u32(&woff2, 4), Some(0x0001_0000));
+        assert_eq!(read_u32(&woff2, 8), Some(woff2.len() as u32));
+        assert_eq!(woff2.len() % 4, 0);
+        assert_eq!(read_u16(&woff2, 12), Some(5));
+        assert_eq!(read_u32(&woff2, 16), Some(sfnt.to_opentype().len() as u32));
+
+        // Read the table directory. We never apply transforms, so there are no
+        // transformed lengths.
+
+        let mut pos = 48;
+        let mut entries = Vec::new();
+
+        for _ in 0..5 {
+            let flags = woff2[pos];
+            pos += 1;
+
+            let tag = if flags & 63 == 63 {
+                pos += 4;
+                woff2[pos - 4..pos].try_into().unwrap()
+            } else {
+                KNOWN_TAGS[(flags & 63) as usize]
+            };
+
+            let transform = flags >> 6;
+            assert_eq!(transform, if tag == GLYF || tag == LOCA { 3 } else { 0 });
+            entries.push((tag, read_base128(&woff2, &mut pos) as usize));
+        }
+
+        let compressed_len = read_u32(&woff2, 20).unwrap() as usize;
+        let mut stream = Vec::new();
+        brotli::BrotliDecompress(&mut &woff2[pos..pos + compressed_len], &mut stream).unwrap();
+
+        let mut offset = 0;
+
+        for (tag, len) in entries {
+            let expected = sfnt.prepared_table(tag, sfnt.table(tag).unwrap());
+            assert_eq!(&stream[offset..offset + len], &expected[..]);
+            offset += len;
+        }
+
+        assert_eq!(offset, stream.len());
+    }
+}
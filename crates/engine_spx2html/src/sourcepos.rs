@@ -0,0 +1,188 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Source-location annotations for HTML output.
+//!
+//! If the TeX engine is configured to record source positions, the SPX stream
+//! contains `tdux:srcpos LINE FILENAME` specials at the starts of paragraphs
+//! and math canvases. For paragraphs, the special comes just after the one that
+//! starts the paragraph, since the engine emits it after `\everypar`; for
+//! canvases, it comes just before. The emitter associates each one with its
+//! element, and can expose it in two ways: as a `data-src` attribute on the
+//! element, and as an entry in a JSON "source map" file written alongside each
+//! page. The latter is intended for tooling that shouldn't alter the DOM. In
+//! the map, elements are identified by their index among the elements of the
+//! same kind in the page, in document order: paragraphs are the `div.tdux-p`
+//! elements, and canvases are the `.canvas` and top-level `math` elements.
+
+use serde::Serialize;
+use std::fmt::{Display, Error, Formatter};
+
+/// Settings for how source positions are exposed in the HTML output.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct SourcePositionOptions {
+    /// Whether elements get `data-src` attributes.
+    pub attributes: bool,
+
+    /// Whether source maps are written alongside the pages.
+    pub maps: bool,
+}
+
+/// A location in the TeX source.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct SourcePosition {
+    /// The name of the source file, as opened by the engine.
+    pub file: String,
+
+    /// The line number in the source file, starting at 1.
+    pub line: u32,
+}
+
+impl SourcePosition {
+    /// Parse the argument of a `tdux:srcpos` special.
+    ///
+    /// The line number comes first so that file names containing spaces are
+    /// handled.
+    pub(crate) fn parse(spec: &str) -> Option<Self> {
+        let (line, file) = spec.split_once(' ')?;
+
+        if file.is_empty() {
+            return None;
+        }
+
+        Some(SourcePosition {
+            file: file.to_owned(),
+            line: line.parse().ok()?,
+        })
+    }
+}
+
+impl Display for SourcePosition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/// The kinds of elements that can be annotated with source positions.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SourceElementKind {
+    Paragraph,
+    Canvas,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct SourceMapEntry {
+    kind: SourceElementKind,
+    index: usize,
+    #[serde(flatten)]
+    position: SourcePosition,
+}
+
+/// The source positions of the elements of one page.
+#[derive(Debug, Default)]
+pub(crate) struct SourceMap {
+    entries: Vec<SourceMapEntry>,
+    n_paragraphs: usize,
+    n_canvases: usize,
+}
+
+impl SourceMap {
+    /// Note that an element of the specified kind has been emitted, returning
+    /// its index among the elements of its kind.
+    pub(crate) fn add(&mut self, kind: SourceElementKind) -> usize {
+        let counter = match kind {
+            SourceElementKind::Paragraph => &mut self.n_paragraphs,
+            SourceElementKind::Canvas => &mut self.n_canvases,
+        };
+
+        let index = *counter;
+        *counter += 1;
+        index
+    }
+
+    /// Record the source position of an element that has been emitted.
+    pub(crate) fn set_position(
+        &mut self,
+        kind: SourceElementKind,
+        index: usize,
+        position: SourcePosition,
+    ) {
+        self.entries.push(SourceMapEntry {
+            kind,
+            index,
+            position,
+        });
+    }
+
+    /// Serialize the map for the page at *page_path*, relative to the output
+    /// root.
+    pub(crate) fn to_json(&self, page_path: &str) -> Vec<u8> {
+        #[derive(Serialize)]
+        struct Syntax<'a> {
+            page: &'a str,
+            entries: &'a [SourceMapEntry],
+        }
+
+        let syntax = Syntax {
+            page: page_path,
+            entries: &self.entries,
+        };
+
+        // Serializing these types can't fail.
+        serde_json::to_vec_pretty(&syntax).unwrap()
+    }
+}
+
+/// Get the path of the source map file associated with a page.
+pub(crate) fn source_map_path(page_path: &str) -> String {
+    format!("{page_path}.srcmap.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_positions() {
+        let pos = SourcePosition::parse("12 chapters/one two.tex").unwrap();
+        assert_eq!(pos.file, "chapters/one two.tex");
+        assert_eq!(pos.line, 12);
+        assert_eq!(pos.to_string(), "chapters/one two.tex:12");
+
+        assert_eq!(SourcePosition::parse("12"), None);
+        assert_eq!(SourcePosition::parse("12 "), None);
+        assert_eq!(SourcePosition::parse("x main.tex"), None);
+    }
+
+    #[test]
+    fn map_indices() {
+        let mut map = SourceMap::default();
+        let mut add = |kind, line: Option<u32>| {
+            let index = map.add(kind);
+
+            if let Some(line) = line {
+                let file = "a.tex".to_owned();
+                map.set_position(kind, index, SourcePosition { file, line });
+            }
+        };
+
+        add(SourceElementKind::Paragraph, Some(1));
+        add(SourceElementKind::Canvas, Some(2));
+        add(SourceElementKind::Paragraph, None);
+        add(SourceElementKind::Paragraph, Some(4));
+
+        let json: serde_json::Value = serde_json::from_slice(&map.to_json("p.html")).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "page": "p.html",
+                "entries": [
+                    {"kind": "paragraph", "index": 0, "file": "a.tex", "line": 1},
+                    {"kind": "canvas", "index": 0, "file": "a.tex", "line": 2},
+                    {"kind": "paragraph", "index": 2, "file": "a.tex", "line": 4},
+                ],
+            })
+        );
+    }
+}
@@ -10,6 +10,7 @@ use tectonic_status_base::{tt_warning, StatusBackend};
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub(crate) enum Special<'a> {
     AddTemplate(&'a str),
+    Anchor(&'a str),
     AutoStartParagraph,
     AutoEndParagraph,
     CanvasEnd(&'a str),
@@ -18,16 +19,20 @@ pub(crate) enum Special<'a> {
     DirectText(&'a str),
     EndDefineFontFamily,
     EndFontFamilyTagAssociations,
+    EndLink,
     Emit,
     ManualEnd(&'a str),
     ManualFlexibleStart(&'a str),
     ProvideFile(&'a str),
     ProvideSpecial(&'a str),
+    SetCanvasRendering(&'a str),
     SetOutputPath(&'a str),
     SetTemplate(&'a str),
     SetTemplateVariable(&'a str),
+    SourcePosition(&'a str),
     StartDefineFontFamily,
     StartFontFamilyTagAssociations,
+    StartLink(&'a str),
 }
 
 impl<'a> Special<'a> {
@@ -55,11 +60,16 @@ impl<'a> Special<'a> {
             "mfs" => Special::ManualFlexibleStart(remainder),
             "me" => Special::ManualEnd(remainder),
             "dt" => Special::DirectText(remainder),
+            "srcpos" => Special::SourcePosition(remainder),
             "emit" => Special::Emit,
+            "anchor" => Special::Anchor(remainder),
+            "startLink" => Special::StartLink(remainder),
+            "endLink" => Special::EndLink,
             "addTemplate" => Special::AddTemplate(remainder),
             "setTemplate" => Special::SetTemplate(remainder),
             "setOutputPath" => Special::SetOutputPath(remainder),
             "setTemplateVariable" => Special::SetTemplateVariable(remainder),
+            "setCanvasRendering" => Special::SetCanvasRendering(remainder),
             "provideFile" => Special::ProvideFile(remainder),
             "provideSpecial" => Special::ProvideSpecial(remainder),
             "contentFinished" => Special::ContentFinished,
@@ -92,6 +102,9 @@ impl<'a> Special<'a> {
                 | Special::ManualFlexibleStart(_)
                 | Special::ManualEnd(_)
                 | Special::DirectText(_)
+                | Special::Anchor(_)
+                | Special::StartLink(_)
+                | Special::EndLink
         )
     }
 }
@@ -100,6 +113,7 @@ impl<'a> Display for Special<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         let (cmd, rest) = match self {
             Special::AddTemplate(t) => ("addTemplate", Some(t)),
+            Special::Anchor(t) => ("anchor", Some(t)),
             Special::AutoStartParagraph => ("asp", None),
             Special::AutoEndParagraph => ("aep", None),
             Special::CanvasEnd(t) => ("ce", Some(t)),
@@ -108,16 +122,20 @@ impl<'a> Display for Special<'a> {
             Special::DirectText(t) => ("dt", Some(t)),
             Special::EndDefineFontFamily => ("endDefineFontFamily", None),
             Special::EndFontFamilyTagAssociations => ("endFontFamilyTagAssociations", None),
+            Special::EndLink => ("endLink", None),
             Special::Emit => ("emit", None),
             Special::ManualEnd(t) => ("me", Some(t)),
             Special::ManualFlexibleStart(t) => ("mfs", Some(t)),
             Special::ProvideFile(t) => ("provideFile", Some(t)),
             Special::ProvideSpecial(t) => ("provideSpecial", Some(t)),
+            Special::SetCanvasRendering(t) => ("setCanvasRendering", Some(t)),
             Special::SetOutputPath(t) => ("setOutputPath", Some(t)),
             Special::SetTemplate(t) => ("setTemplate", Some(t)),
             Special::SetTemplateVariable(t) => ("setTemplateVariable", Some(t)),
+            Special::SourcePosition(t) => ("srcpos", Some(t)),
             Special::StartDefineFontFamily => ("startDefineFontFamily", None),
             Special::StartFontFamilyTagAssociations => ("startFontFamilyTagAssociations", None),
+            Special::StartLink(t) => ("startLink", Some(t)),
         };
 
         if let Some(t) = rest {
@@ -0,0 +1,1226 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Subsetting of emitted fonts to the glyphs that a document uses.
+//!
+//! We don't renumber glyphs, since the variant-glyph character maps and the
+//! font's layout tables refer to them by ID. Instead, the outlines of unused
+//! glyphs are replaced with empty ones. This gets rid of the bulk of the data
+//! in most fonts while leaving the other tables valid as they are.
+
+use byteorder::{BigEndian, WriteBytesExt};
+use std::{collections::BTreeSet, convert::TryFrom};
+
+use crate::{
+    fontfile::GlyphId,
+    sfnt::{read_u16, read_u32, Sfnt, CFF, GLYF, GSUB, HEAD, LOCA, MAXP},
+};
+
+/// Subset a font to the given glyphs.
+///
+/// The set of glyphs is first expanded to include ones that the browser
+/// might need to render them: those produced by glyph substitutions, and the
+/// components of composite glyphs and accented CFF characters. Fonts whose outline format we don't
+/// understand are left unchanged.
+pub(crate) fn subset_font(sfnt: &mut Sfnt, glyphs: &mut BTreeSet<GlyphId>) {
+    // Glyph 0 is the fallback `.notdef` glyph, which should always exist.
+    glyphs.insert(0);
+
+    if let Some(gsub) = sfnt.table(GSUB) {
+        gsub_closure(gsub, glyphs);
+    }
+
+    if sfnt.table(GLYF).is_some() {
+        subset_glyf(sfnt, glyphs);
+    } else if sfnt.table(CFF).is_some() {
+        subset_cff(sfnt, glyphs);
+    }
+}
+
+/// Add to *glyphs* all of the glyphs that GSUB substitutions can produce from
+/// them.
+///
+/// We ignore the contexts in which substitutions apply, so this may add more
+/// glyphs than are needed, but never fewer.
+fn gsub_closure(gsub: &[u8], glyphs: &mut BTreeSet<GlyphId>) -> Option<()> {
+    let lookup_list = read_u16(gsub, 8)? as usize;
+    let n_lookups = read_u16(gsub, lookup_list)?;
+    let mut subtables = Vec::new();
+
+    for i in 0..n_lookups as usize {
+        let lookup = lookup_list + read_u16(gsub, lookup_list + 2 + 2 * i)? as usize;
+        let kind = read_u16(gsub, lookup)?;
+        let n_subtables = read_u16(gsub, lookup + 4)?;
+
+        for j in 0..n_subtables as usize {
+            let st = lookup + read_u16(gsub, lookup + 6 + 2 * j)? as usize;
+
+            if kind == 7 {
+                // Extension subtable, pointing to one of another type.
+                let ext_kind = read_u16(gsub, st + 2)?;
+                let ext_offset = read_u32(gsub, st + 4)? as usize;
+                subtables.push((ext_kind, st + ext_offset));
+            } else {
+                subtables.push((kind, st));
+            }
+        }
+    }
+
+    // Substitutions can chain, so iterate until nothing changes.
+
+    loop {
+        let mut new = Vec::new();
+
+        for (kind, st) in &subtables {
+            // Malformed subtables are skipped.
+            let _ = substitution_outputs(gsub, *kind, *st, glyphs, &mut new);
+        }
+
+        let n_before = glyphs.len();
+        glyphs.extend(new);
+
+        if glyphs.len() == n_before {
+            return Some(());
+        }
+    }
+}
+
+/// Find the glyphs that a GSUB subtable can produce from *glyphs*.
+fn substitution_outputs(
+    gsub: &[u8],
+    kind: u16,
+    st: usize,
+    glyphs: &BTreeSet<GlyphId>,
+    outputs: &mut Vec<GlyphId>,
+) -> Option<()> {
+    let format = read_u16(gsub, st)?;
+    let coverage = st + read_u16(gsub, st + 2)? as usize;
+    let covered = coverage_table(gsub, coverage)?
+        .into_iter()
+        .filter(|(g, _)| glyphs.contains(g));
+
+    match (kind, format) {
+        // Single substitution with a delta.
+        (1, 1) => {
+            let delta = read_u16(gsub, st + 4)?;
+
+            for (g, _) in covered {
+                outputs.push(g.wrapping_add(delta));
+            }
+        }
+
+        // Single substitution with a list of outputs.
+        (1, 2) => {
+            for (_, idx) in covered {
+                outputs.push(read_u16(gsub, st + 6 + 2 * idx)?);
+            }
+        }
+
+        // Multiple and alternate substitutions, which have the same
+        // structure: each input glyph maps to a list of glyphs.
+        (2, 1) | (3, 1) => {
+            for (_, idx) in covered {
+                let seq = st + read_u16(gsub, st + 6 + 2 * idx)? as usize;
+
+                for k in 0..read_u16(gsub, seq)? as usize {
+                    outputs.push(read_u16(gsub, seq + 2 + 2 * k)?);
+                }
+            }
+        }
+
+        // Ligature substitution: the ligature is needed if all of its
+        // components are.
+        (4, 1) => {
+            for (_, idx) in covered {
+                let set = st + read_u16(gsub, st + 6 + 2 * idx)? as usize;
+
+                for k in 0..read_u16(gsub, set)? as usize {
+                    let lig = set + read_u16(gsub, set + 2 + 2 * k)? as usize;
+                    let n_components = read_u16(gsub, lig + 2)? as usize;
+                    let mut all_present = true;
+
+                    for c in 1..n_components {
+                        all_present &= glyphs.contains(&read_u16(gsub, lig + 2 + 2 * c)?);
+                    }
+
+                    if all_present {
+                        outputs.push(read_u16(gsub, lig)?);
+                    }
+                }
+            }
+        }
+
+        // Contextual lookups only invoke other lookups, which we consider
+        // separately. We don't handle the rare reverse-chaining type.
+        _ => {}
+    }
+
+    Some(())
+}
+
+/// Read an OpenType coverage table, returning (glyph, coverage index) pairs.
+fn coverage_table(data: &[u8], offset: usize) -> Option<Vec<(GlyphId, usize)>> {
+    let mut items = Vec::new();
+
+    match read_u16(data, offset)? {
+        1 => {
+            for i in 0..read_u16(data, offset + 2)? as usize {
+                items.push((read_u16(data, offset + 4 + 2 * i)?, i));
+            }
+        }
+
+        2 => {
+            for i in 0..read_u16(data, offset + 2)? as usize {
+                let rec = offset + 4 + 6 * i;
+                let start = read_u16(data, rec)?;
+                let end = read_u16(data, rec + 2)?;
+                let start_index = read_u16(data, rec + 4)? as usize;
+
+                for g in start..=end {
+                    items.push((g, start_index + (g - start) as usize));
+                }
+            }
+        }
+
+        _ => return None,
+    }
+
+    Some(items)
+}
+
+/// Subset a font with TrueType outlines, by emptying unused entries of the
+/// `glyf` table.
+fn subset_glyf(sfnt: &mut Sfnt, glyphs: &mut BTreeSet<GlyphId>) -> Option<()> {
+    let long_loca = read_u16(sfnt.table(HEAD)?, 50)? != 0;
+    let n_glyphs = read_u16(sfnt.table(MAXP)?, 4)? as usize;
+    let loca = sfnt.table(LOCA)?;
+    let glyf = sfnt.table(GLYF)?;
+
+    let mut offsets = Vec::with_capacity(n_glyphs + 1);
+
+    for i in 0..=n_glyphs {
+        offsets.push(if long_loca {
+            read_u32(loca, 4 * i)? as usize
+        } else {
+            2 * read_u16(loca, 2 * i)? as usize
+        });
+    }
+
+    let glyph_data = |g: GlyphId| -> Option<&[u8]> {
+        let g = g as usize;
+
+        if g >= n_glyphs {
+            return Some(&[]);
+        }
+
+        glyf.get(offsets[g]..offsets[g + 1])
+    };
+
+    // Add the components of composite glyphs.
+
+    let mut stack: Vec<GlyphId> = glyphs.iter().copied().collect();
+
+    while let Some(g) = stack.pop() {
+        let data = glyph_data(g)?;
+
+        if data.len() < 10 || (read_u16(data, 0)? as i16) >= 0 {
+            continue;
+        }
+
+        let mut pos = 10;
+
+        loop {
+            let flags = read_u16(data, pos)?;
+            let component = read_u16(data, pos + 2)?;
+
+            if glyphs.insert(component) {
+                stack.push(component);
+            }
+
+            const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+            const WE_HAVE_A_SCALE: u16 = 0x0008;
+            const MORE_COMPONENTS: u16 = 0x0020;
+            const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+            const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+            pos += 4;
+            pos += if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                4
+            } else {
+                2
+            };
+
+            if flags & WE_HAVE_A_SCALE != 0 {
+                pos += 2;
+            } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                pos += 4;
+            } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                pos += 8;
+            }
+
+            if flags & MORE_COMPONENTS == 0 {
+                break;
+            }
+        }
+    }
+
+    // Build the new tables. Glyph data are padded to four bytes.
+
+    let mut new_glyf = Vec::new();
+    let mut new_offsets = Vec::with_capacity(n_glyphs + 1);
+
+    for g in 0..n_glyphs {
+        new_offsets.push(new_glyf.len());
+
+        if glyphs.contains(&(g as GlyphId)) {
+            new_glyf.extend_from_slice(glyph_data(g as GlyphId)?);
+            new_glyf.resize((new_glyf.len() + 3) & !3, 0);
+        }
+    }
+
+    new_offsets.push(new_glyf.len());
+
+    // Short offsets are stored divided by two. Switch to long ones if we
+    // have to.
+
+    let long_loca = long_loca || new_glyf.len() > 2 * 0xFFFF;
+    let mut new_loca = Vec::with_capacity(new_offsets.len() * 4);
+
+    for ofs in new_offsets {
+        if long_loca {
+            new_loca.write_u32::<BigEndian>(ofs as u32).unwrap();
+        } else {
+            new_loca.write_u16::<BigEndian>((ofs / 2) as u16).unwrap();
+        }
+    }
+
+    let mut head = sfnt.table(HEAD)?.to_vec();
+    head[50..52].copy_from_slice(&(long_loca as u16).to_be_bytes());
+
+    sfnt.set_table(HEAD, head);
+    sfnt.set_table(LOCA, new_loca);
+    sfnt.set_table(GLYF, new_glyf);
+    Some(())
+}
+
+/// The CFF DICT operators whose operands include offsets, with the index of
+/// the offset operand. The offsets are from the start of the CFF data, except
+/// that of the Private DICT's `Subrs`, which is from the start of the Private
+/// DICT.
+const CFF_OFFSET_OPERATORS: &[(u16, usize)] = &[
+    (15, 0),     // charset
+    (16, 0),     // Encoding
+    (17, 0),     // CharStrings
+    (18, 1),     // Private: size, offset
+    (19, 0),     // Subrs
+    (0x0C24, 0), // FDArray
+    (0x0C25, 0), // FDSelect
+];
+
+const CFF_ROS: u16 = 0x0C1E;
+
+/// The codes above 127 that are defined in the CFF Standard Encoding. The
+/// characters have consecutive SIDs, starting at 96 for the first one.
+const STANDARD_ENCODING_HIGH_CODES: &[u8] = &[
+    161, 162, 163, 164, 165, 166, 167, 168, 169, 170, 171, 172, 173, 174, 175, 177, 178, 179, 180,
+    182, 183, 184, 185, 186, 187, 188, 189, 191, 193, 194, 195, 196, 197, 198, 199, 200, 202, 203,
+    205, 206, 207, 208, 225, 227, 232, 233, 234, 235, 241, 245, 248, 249, 250, 251,
+];
+
+/// Subset a font with CFF outlines, by replacing unused charstrings with
+/// empty ones.
+///
+/// CID-keyed fonts aren't handled.
+fn subset_cff(sfnt: &mut Sfnt, glyphs: &mut BTreeSet<GlyphId>) -> Option<()> {
+    let cff = sfnt.table(CFF)?;
+    let header_size = *cff.get(2)? as usize;
+    let (_, top_start) = cff_index(cff, header_size)?;
+    let (top_dicts, top_end) = cff_index(cff, top_start)?;
+
+    if top_dicts.len() != 1 {
+        return None;
+    }
+
+    let top_dict = cff_dict(top_dicts[0])?;
+
+    if top_dict.iter().any(|(op, _)| *op == CFF_ROS) {
+        return None;
+    }
+
+    let cs_start = top_dict
+        .iter()
+        .find(|(op, _)| *op == 17)?
+        .1
+        .first()?
+        .value? as usize;
+    let (charstrings, cs_end) = cff_index(cff, cs_start)?;
+
+    // Every offset in the Top DICT must point past the Top DICT INDEX, since
+    // we're changing its size.
+
+    for (op, operands) in &top_dict {
+        if let Some(value) = cff_offset_operand(*op, operands) {
+            if value < top_end {
+                return None;
+            }
+        }
+    }
+
+    if cs_start < top_end {
+        return None;
+    }
+
+    // The Global Subr INDEX follows the String INDEX, after the Top DICT.
+    let (_, strings_end) = cff_index(cff, top_end)?;
+    let (global_subrs, _) = cff_index(cff, strings_end)?;
+
+    let (private_size, private_start) = match top_dict.iter().find(|(op, _)| *op == 18) {
+        Some((_, operands)) => (
+            operands.first()?.value? as usize,
+            operands.get(1)?.value? as usize,
+        ),
+        None => (0, 0),
+    };
+    let private_dict = cff_dict(cff.get(private_start..private_start + private_size)?)?;
+
+    let subrs = private_dict
+        .iter()
+        .find(|(op, _)| *op == 19)
+        .and_then(|(op, operands)| cff_offset_operand(*op, operands))
+        .map(|ofs| private_start + ofs);
+
+    let (local_subrs, subrs_range) = match subrs {
+        Some(start) => {
+            if start < top_end {
+                return None;
+            }
+
+            let (subrs, end) = cff_index(cff, start)?;
+            (subrs, start..end)
+        }
+        None => (Vec::new(), 0..0),
+    };
+
+    // Add the components of accented characters. These are identified by
+    // their codes in the Standard Encoding, which we map to glyphs through
+    // the names in the charset.
+
+    if let Some(charset) = cff_charset(cff, &top_dict, charstrings.len()) {
+        let mut stack: Vec<GlyphId> = glyphs.iter().copied().collect();
+
+        while let Some(g) = stack.pop() {
+            let accent = charstrings
+                .get(g as usize)
+                .and_then(|cs| charstring_seac(cs, &global_subrs, &local_subrs));
+
+            for code in accent.iter().flat_map(|(base, accent)| [*base, *accent]) {
+                let sid = match standard_encoding_sid(code) {
+                    Some(sid) => sid,
+                    None => continue,
+                };
+
+                if let Some(component) = charset.iter().position(|s| *s == sid) {
+                    if glyphs.insert(component as GlyphId) {
+                        stack.push(component as GlyphId);
+                    }
+                }
+            }
+        }
+    }
+
+    const ENDCHAR: &[u8] = &[14];
+
+    let new_charstrings: Vec<&[u8]> = charstrings
+        .iter()
+        .enumerate()
+        .map(|(g, cs)| {
+            if glyphs.contains(&(g as GlyphId)) {
+                cs
+            } else {
+                ENDCHAR
+            }
+        })
+        .collect();
+    let new_cs_index = write_cff_index(&new_charstrings);
+
+    // The offset of the local subroutines is relative to the Private DICT.
+    // If the CharStrings INDEX lies between them, it changes, so we append
+    // a rewritten Private DICT, followed by a copy of the subroutines.
+
+    let mut top_dict = top_dict;
+    let mut relocated_private = Vec::new();
+    let relocated_size: [u8; 5];
+
+    if subrs.is_some() && (private_start < cs_start) != (subrs_range.start < cs_start) {
+        let dict_len = write_cff_dict(&private_dict, |o| o).len();
+        relocated_private = write_cff_dict(&private_dict, |_| dict_len);
+        relocated_private.extend_from_slice(&cff[subrs_range]);
+
+        relocated_size = cff_int5(dict_len);
+        let private = top_dict.iter_mut().find(|(op, _)| *op == 18)?;
+        private.1[0] = CffOperand {
+            raw: &relocated_size,
+            value: Some(dict_len as i32),
+        };
+    }
+
+    // Offsets are always rewritten in the five-byte form, so the size of the
+    // new Top DICT doesn't depend on their values.
+
+    let top_delta = write_cff_index(&[&write_cff_dict(&top_dict, |o| o)]).len() as isize
+        - (top_end - top_start) as isize;
+    let cs_delta = new_cs_index.len() as isize - (cs_end - cs_start) as isize;
+    let new_len = (cff.len() as isize + top_delta + cs_delta) as usize;
+
+    let new_top_dict = write_cff_dict(&top_dict, |o| {
+        if o == private_start && !relocated_private.is_empty() {
+            return new_len;
+        }
+
+        let mut delta = top_delta;
+
+        if o >= cs_end {
+            delta += cs_delta;
+        }
+
+        (o as isize + delta) as usize
+    });
+
+    let mut new_cff = Vec::with_capacity(new_len + relocated_private.len());
+    new_cff.extend_from_slice(&cff[..top_start]);
+    new_cff.extend_from_slice(&write_cff_index(&[&new_top_dict]));
+    new_cff.extend_from_slice(&cff[top_end..cs_start]);
+    new_cff.extend_from_slice(&new_cs_index);
+    new_cff.extend_from_slice(&cff[cs_end..]);
+    new_cff.extend_from_slice(&relocated_private);
+
+    sfnt.set_table(CFF, new_cff);
+    Some(())
+}
+
+/// Get the SIDs of the names of the glyphs in a CFF font, from its charset.
+///
+/// The predefined expert charsets aren't handled.
+fn cff_charset(
+    cff: &[u8],
+    top_dict: &[(u16, Vec<CffOperand>)],
+    n_glyphs: usize,
+) -> Option<Vec<u16>> {
+    let offset = match top_dict.iter().find(|(op, _)| *op == 15) {
+        Some((_, operands)) => operands.first()?.value?,
+        None => 0,
+    };
+
+    // In the predefined ISOAdobe charset, each glyph's SID is its index.
+    match offset {
+        0 => return Some((0..n_glyphs as u16).collect()),
+        1 | 2 => return None,
+        _ => {}
+    }
+
+    let format = *cff.get(offset as usize)?;
+    let mut pos = offset as usize + 1;
+
+    // The `.notdef` glyph is implicit.
+    let mut sids = vec![0];
+
+    while sids.len() < n_glyphs {
+        match format {
+            0 => {
+                sids.push(read_u16(cff, pos)?);
+                pos += 2;
+            }
+
+            1 | 2 => {
+                let first = read_u16(cff, pos)?;
+                let n_left = if format == 1 {
+                    *cff.get(pos + 2)? as u16
+                } else {
+                    read_u16(cff, pos + 2)?
+                };
+
+                pos += 2 + format as usize;
+
+                for i in 0..=n_left {
+                    sids.push(first.checked_add(i)?);
+                }
+            }
+
+            _ => return None,
+        }
+    }
+
+    sids.truncate(n_glyphs);
+    Some(sids)
+}
+
+/// Get the SID of the character with the given code in the CFF Standard
+/// Encoding.
+fn standard_encoding_sid(code: i32) -> Option<u16> {
+    match code {
+        32..=126 => Some(code as u16 - 31),
+        _ => STANDARD_ENCODING_HIGH_CODES
+            .iter()
+            .position(|c| *c as i32 == code)
+            .map(|i| 96 + i as u16),
+    }
+}
+
+/// If a Type 2 charstring ends with the `seac`-like form of `endchar`, which
+/// builds an accented character from two others, get the Standard Encoding
+/// codes of its base and accent characters.
+fn charstring_seac(cs: &[u8], global_subrs: &[&[u8]], local_subrs: &[&[u8]]) -> Option<(i32, i32)> {
+    let mut scan = CharstringScan {
+        global_subrs,
+        local_subrs,
+        stack: Vec::new(),
+        n_stems: 0,
+        seac: None,
+    };
+
+    scan.run(cs, 0)?;
+    scan.seac
+}
+
+/// The state needed to find the `endchar` operator of a Type 2 charstring:
+/// enough to skip over hint masks and follow subroutine calls.
+struct CharstringScan<'a> {
+    global_subrs: &'a [&'a [u8]],
+    local_subrs: &'a [&'a [u8]],
+    stack: Vec<i32>,
+    n_stems: usize,
+    seac: Option<(i32, i32)>,
+}
+
+impl CharstringScan<'_> {
+    /// The maximum nesting depth of subroutine calls.
+    const MAX_DEPTH: usize = 10;
+
+    /// Interpret a charstring or subroutine, returning whether `endchar` was
+    /// reached.
+    fn run(&mut self, cs: &[u8], depth: usize) -> Option<bool> {
+        let mut pos = 0;
+
+        while pos < cs.len() {
+            let b0 = cs[pos];
+
+            match b0 {
+                28 => {
+                    self.stack.push(read_u16(cs, pos + 1)? as i16 as i32);
+                    pos += 3;
+                }
+
+                32..=246 => {
+                    self.stack.push(b0 as i32 - 139);
+                    pos += 1;
+                }
+
+                247..=250 => {
+                    self.stack
+                        .push((b0 as i32 - 247) * 256 + *cs.get(pos + 1)? as i32 + 108);
+                    pos += 2;
+                }
+
+                251..=254 => {
+                    self.stack
+                        .push(-(b0 as i32 - 251) * 256 - *cs.get(pos + 1)? as i32 - 108);
+                    pos += 2;
+                }
+
+                // A 16.16 fixed-point number; we only need its integer part.
+                255 => {
+                    self.stack.push(read_u32(cs, pos + 1)? as i32 >> 16);
+                    pos += 5;
+                }
+
+                // hstem, vstem, hstemhm, vstemhm
+                1 | 3 | 18 | 23 => {
+                    self.n_stems += self.stack.len() / 2;
+                    self.stack.clear();
+                    pos += 1;
+                }
+
+                // hintmask and cntrmask, which may have implicit vstem
+                // arguments, followed by a mask with one bit per stem.
+                19 | 20 => {
+                    self.n_stems += self.stack.len() / 2;
+                    self.stack.clear();
+                    pos += 1 + self.n_stems.div_ceil(8);
+                }
+
+                // callsubr, callgsubr
+                10 | 29 => {
+                    let subrs = if b0 == 10 {
+                        self.local_subrs
+                    } else {
+                        self.global_subrs
+                    };
+
+                    let bias = match subrs.len() {
+                        0..=1239 => 107,
+                        1240..=33899 => 1131,
+                        _ => 32768,
+                    };
+
+                    let index = usize::try_from(self.stack.pop()? + bias).ok()?;
+
+                    if depth >= Self::MAX_DEPTH {
+                        return None;
+                    }
+
+                    if self.run(subrs.get(index)?, depth + 1)? {
+                        return Some(true);
+                    }
+
+                    pos += 1;
+                }
+
+                // return
+                11 => return Some(false),
+
+                // endchar, with optional width and accent arguments
+                14 => {
+                    let n = self.stack.len();
+
+                    if let [_, _, base, accent] = self.stack[n.saturating_sub(4)..] {
+                        self.seac = Some((base, accent));
+                    }
+
+                    return Some(true);
+                }
+
+                12 => {
+                    self.stack.clear();
+                    pos += 2;
+                }
+
+                _ => {
+                    self.stack.clear();
+                    pos += 1;
+                }
+            }
+        }
+
+        Some(false)
+    }
+}
+
+/// Encode an integer as a five-byte CFF DICT operand.
+fn cff_int5(value: usize) -> [u8; 5] {
+    let mut buf = [29, 0, 0, 0, 0];
+    buf[1..].copy_from_slice(&(value as u32).to_be_bytes());
+    buf
+}
+
+/// Parse a CFF INDEX structure, returning its items and the offset of the
+/// data following it.
+fn cff_index(data: &[u8], offset: usize) -> Option<(Vec<&[u8]>, usize)> {
+    let count = read_u16(data, offset)? as usize;
+
+    if count == 0 {
+        return Some((Vec::new(), offset + 2));
+    }
+
+    let off_size = *data.get(offset + 2)? as usize;
+
+    if !(1..=4).contains(&off_size) {
+        return None;
+    }
+
+    let offsets_start = offset + 3;
+    let data_start = offsets_start + (count + 1) * off_size - 1;
+
+    let read_offset = |i: usize| -> Option<usize> {
+        let start = offsets_start + i * off_size;
+        let bytes = data.get(start..start + off_size)?;
+        Some(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize))
+    };
+
+    let mut items = Vec::with_capacity(count);
+
+    for i in 0..count {
+        items.push(data.get(data_start + read_offset(i)?..data_start + read_offset(i + 1)?)?);
+    }
+
+    Some((items, data_start + read_offset(count)?))
+}
+
+fn write_cff_index(items: &[&[u8]]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.write_u16::<BigEndian>(items.len() as u16).unwrap();
+
+    if items.is_empty() {
+        return buf;
+    }
+
+    let data_len: usize = items.iter().map(|i| i.len()).sum();
+    let off_size = match data_len + 1 {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x1_0000..=0xFF_FFFF => 3,
+        _ => 4,
+    };
+
+    buf.push(off_size as u8);
+
+    let mut offset = 1;
+
+    for item in items.iter().map(|i| i.len()).chain(std::iter::once(0)) {
+        buf.extend_from_slice(&(offset as u32).to_be_bytes()[4 - off_size..]);
+        offset += item;
+    }
+
+    for item in items {
+        buf.extend_from_slice(item);
+    }
+
+    buf
+}
+
+/// A CFF DICT operand: its raw encoding, and its value if it's an integer.
+#[derive(Clone, Debug)]
+struct CffOperand<'a> {
+    raw: &'a [u8],
+    value: Option<i32>,
+}
+
+/// Parse a CFF DICT into a list of (operator, operands) pairs. Two-byte
+/// operators are represented as `0x0C00 | second_byte`.
+fn cff_dict(data: &[u8]) -> Option<Vec<(u16, Vec<CffOperand<'_>>)>> {
+    let mut entries = Vec::new();
+    let mut operands = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let b0 = data[pos];
+
+        let (len, value) = match b0 {
+            0..=21 => {
+                let op = if b0 == 12 {
+                    pos += 1;
+                    0x0C00 | *data.get(pos)? as u16
+                } else {
+                    b0 as u16
+                };
+
+                pos += 1;
+                entries.push((op, std::mem::take(&mut operands)));
+                continue;
+            }
+
+            28 => (3, read_u16(data, pos + 1)? as i16 as i32),
+            29 => (5, read_u32(data, pos + 1)? as i32),
+
+            30 => {
+                // A real number, ending with a 0xF nibble.
+                let mut len = 1;
+
+                loop {
+                    let b = *data.get(pos + len)?;
+                    len += 1;
+
+                    if b & 0x0F == 0x0F || b >> 4 == 0x0F {
+                        break;
+                    }
+                }
+
+                operands.push(CffOperand {
+                    raw: &data[pos..pos + len],
+                    value: None,
+                });
+                pos += len;
+                continue;
+            }
+
+            32..=246 => (1, b0 as i32 - 139),
+            247..=250 => (
+                2,
+                (b0 as i32 - 247) * 256 + *data.get(pos + 1)? as i32 + 108,
+            ),
+            251..=254 => (
+                2,
+                -(b0 as i32 - 251) * 256 - *data.get(pos + 1)? as i32 - 108,
+            ),
+            _ => return None,
+        };
+
+        operands.push(CffOperand {
+            raw: data.get(pos..pos + len)?,
+            value: Some(value),
+        });
+        pos += len;
+    }
+
+    Some(entries)
+}
+
+/// If this DICT entry contains an offset, get it.
+fn cff_offset_operand(op: u16, operands: &[CffOperand]) -> Option<usize> {
+    let (_, idx) = CFF_OFFSET_OPERATORS.iter().find(|(o, _)| *o == op)?;
+    let value = operands.get(*idx)?.value?;
+
+    // Small values of the charset and Encoding operators identify predefined
+    // tables rather than offsets.
+    match (op, value) {
+        (15, 0..=2) | (16, 0..=1) => None,
+        _ => Some(value as usize),
+    }
+}
+
+/// Serialize a CFF DICT, using *remap* to update the offsets that it contains.
+fn write_cff_dict(entries: &[(u16, Vec<CffOperand>)], remap: impl Fn(usize) -> usize) -> Vec<u8> {
+    let mut buf = Vec::new();
+
+    for (op, operands) in entries {
+        let offset = cff_offset_operand(*op, operands);
+        let offset_idx = CFF_OFFSET_OPERATORS
+            .iter()
+            .find(|(o, _)| o == op)
+            .map(|(_, i)| *i);
+
+        for (i, operand) in operands.iter().enumerate() {
+            match offset {
+                Some(value) if Some(i) == offset_idx => {
+                    buf.extend_from_slice(&cff_int5(remap(value)));
+                }
+
+                _ => buf.extend_from_slice(operand.raw),
+            }
+        }
+
+        if op >> 8 == 0x0C {
+            buf.push(12);
+        }
+
+        buf.push(*op as u8);
+    }
+
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pinot::{FontDataRef, TableProvider};
+
+    // A real font with CFF outlines and ligatures.
+    use crate::testutil::LMROMAN;
+
+    /// Create a font with TrueType outlines, consisting of the given glyphs.
+    fn truetype_font(glyphs: &[&[u8]], long_loca: bool) -> Sfnt {
+        let mut glyf = Vec::new();
+        let mut loca = Vec::new();
+
+        for g in glyphs.iter().copied().chain(std::iter::once(&[][..])) {
+            if long_loca {
+                loca.write_u32::<BigEndian>(glyf.len() as u32).unwrap();
+            } else {
+                loca.write_u16::<BigEndian>((glyf.len() / 2) as u16)
+                    .unwrap();
+            }
+
+            glyf.extend_from_slice(g);
+            glyf.resize((glyf.len() + 3) & !3, 0);
+        }
+
+        let mut head = vec![0; 54];
+        head[..4].copy_from_slice(&[0, 1, 0, 0]);
+        head[12..16].copy_from_slice(&0x5F0F_3CF5u32.to_be_bytes());
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        head[50..52].copy_from_slice(&(long_loca as u16).to_be_bytes());
+
+        let mut maxp = vec![0, 0, 0x50, 0];
+        maxp.write_u16::<BigEndian>(glyphs.len() as u16).unwrap();
+
+        let mut sfnt = Sfnt::new(0x0001_0000);
+        sfnt.set_table(HEAD, head);
+        sfnt.set_table(MAXP, maxp);
+        sfnt.set_table(LOCA, loca);
+        sfnt.set_table(GLYF, glyf);
+        sfnt
+    }
+
+    /// Get the outline of a glyph in a parsed font.
+    fn glyph_outline(font: &Sfnt, g: usize) -> &[u8] {
+        let glyf = font.table(GLYF).unwrap();
+        let loca = font.table(LOCA).unwrap();
+
+        let (start, end) = if read_u16(font.table(HEAD).unwrap(), 50).unwrap() != 0 {
+            (
+                read_u32(loca, 4 * g).unwrap(),
+                read_u32(loca, 4 * g + 4).unwrap(),
+            )
+        } else {
+            (
+                2 * read_u16(loca, 2 * g).unwrap() as u32,
+                2 * read_u16(loca, 2 * g + 2).unwrap() as u32,
+            )
+        };
+
+        &glyf[start as usize..end as usize]
+    }
+
+    #[test]
+    fn glyf_subsetting() {
+        // Simple glyphs have a nonnegative contour count.
+        const NOTDEF: &[u8] = &[0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        const SIMPLE_1: &[u8] = &[0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        const SIMPLE_2: &[u8] = &[0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        const SIMPLE_4: &[u8] = &[0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 4, 4, 4];
+
+        // Glyph 2 with word arguments and a scale, then glyph 4 with byte
+        // arguments.
+        const COMPOSITE_3: &[u8] = &[
+            0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0, //
+            0x00, 0x29, 0, 2, 0, 10, 0, 20, 0x40, 0, //
+            0x00, 0x00, 0, 4, 5, 6,
+        ];
+        const COMPOSITE_5: &[u8] = &[0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x00, 0, 1, 0, 0];
+
+        let glyphs = [
+            NOTDEF,
+            SIMPLE_1,
+            SIMPLE_2,
+            COMPOSITE_3,
+            SIMPLE_4,
+            COMPOSITE_5,
+        ];
+
+        for long_loca in [false, true] {
+            let mut sfnt = truetype_font(&glyphs, long_loca);
+            let mut keep = BTreeSet::from([3]);
+            subset_font(&mut sfnt, &mut keep);
+            assert_eq!(keep, BTreeSet::from([0, 2, 3, 4]));
+
+            let otf = sfnt.to_opentype();
+            let font = FontDataRef::new(&otf).unwrap().get(0).unwrap();
+            assert_eq!(
+                font.head().unwrap().index_to_location_format(),
+                long_loca as i16
+            );
+            assert_eq!(font.maxp().unwrap().num_glyphs(), 6);
+
+            let parsed = Sfnt::from_face(&otf, 0).unwrap();
+
+            for (g, outline) in glyphs.iter().enumerate() {
+                let new = glyph_outline(&parsed, g);
+
+                if keep.contains(&(g as GlyphId)) {
+                    assert_eq!(&new[..outline.len()], *outline);
+                    assert!(new[outline.len()..].iter().all(|b| *b == 0));
+                } else {
+                    assert!(new.is_empty());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cff_subsetting() {
+        let mut sfnt = Sfnt::from_face(LMROMAN, 0).unwrap();
+        let orig_cff = sfnt.table(CFF).unwrap().to_vec();
+        let font = FontDataRef::new(LMROMAN).unwrap().get(0).unwrap();
+        let cmap = font.cmap().unwrap();
+        let f = cmap.map('f' as u32).unwrap();
+        let i = cmap.map('i' as u32).unwrap();
+        let fi = cmap.map(0xFB01).unwrap();
+        let x = cmap.map('x' as u32).unwrap();
+
+        // The `fi` ligature is added by the GSUB closure.
+        let mut keep = BTreeSet::from([f, i]);
+        subset_font(&mut sfnt, &mut keep);
+        assert!(keep.contains(&0));
+        assert!(keep.contains(&fi));
+        assert!(!keep.contains(&x));
+
+        let otf = sfnt.to_opentype();
+        assert!(sfnt.table(CFF).unwrap().len() < orig_cff.len() / 2);
+
+        let font = FontDataRef::new(&otf).unwrap().get(0).unwrap();
+        assert_eq!(font.cmap().unwrap().map('x' as u32), Some(x));
+        let n_glyphs = font.maxp().unwrap().num_glyphs() as usize;
+
+        // Check that the Top DICT offsets point to the right places in the new
+        // CFF data.
+
+        let top_dict = |cff: &[u8]| -> Vec<(u16, Vec<i32>)> {
+            let (_, top_start) = cff_index(cff, cff[2] as usize).unwrap();
+            let (dicts, _) = cff_index(cff, top_start).unwrap();
+            cff_dict(dicts[0])
+                .unwrap()
+                .into_iter()
+                .map(|(op, operands)| (op, operands.iter().filter_map(|o| o.value).collect()))
+                .collect()
+        };
+
+        let operands = |dict: &[(u16, Vec<i32>)], op| {
+            dict.iter()
+                .find(|(o, _)| *o == op)
+                .map(|(_, v)| v.iter().map(|x| *x as usize).collect::<Vec<_>>())
+                .unwrap()
+        };
+
+        let new_cff = Sfnt::from_face(&otf, 0)
+            .unwrap()
+            .table(CFF)
+            .unwrap()
+            .to_vec();
+        let orig_dict = top_dict(&orig_cff);
+        let new_dict = top_dict(&new_cff);
+
+        let (orig_cs, _) = cff_index(&orig_cff, operands(&orig_dict, 17)[0]).unwrap();
+        let (new_cs, _) = cff_index(&new_cff, operands(&new_dict, 17)[0]).unwrap();
+        assert_eq!(new_cs.len(), n_glyphs);
+
+        for (g, (orig, new)) in orig_cs.iter().zip(&new_cs).enumerate() {
+            if keep.contains(&(g as GlyphId)) {
+                assert_eq!(orig, new);
+            } else {
+                assert_eq!(*new, [14]);
+            }
+        }
+
+        let orig_private = operands(&orig_dict, 18);
+        let new_private = operands(&new_dict, 18);
+        assert_eq!(orig_private[0], new_private[0]);
+        assert_eq!(
+            &orig_cff[orig_private[1]..orig_private[1] + orig_private[0]],
+            &new_cff[new_private[1]..new_private[1] + new_private[0]]
+        );
+
+        let orig_charset = operands(&orig_dict, 15)[0];
+        let new_charset = operands(&new_dict, 15)[0];
+        assert_eq!(
+            &orig_cff[orig_charset..orig_charset + 16],
+            &new_cff[new_charset..new_charset + 16]
+        );
+    }
+
+    #[test]
+    fn cff_offsets_near_charstrings() {
+        // A CFF font whose CharStrings INDEX is shorter than the growth of
+        // the Top DICT when its offsets are rewritten in the five-byte form.
+        let mut cff = vec![1, 0, 4, 1];
+        cff.extend_from_slice(&write_cff_index(&[b"T"]));
+        // charset at 26, CharStrings at 29, Private (size 2) at 40.
+        let top_dict = [26 + 139, 15, 29 + 139, 17, 2 + 139, 40 + 139, 18];
+        cff.extend_from_slice(&write_cff_index(&[&top_dict]));
+        cff.extend_from_slice(&[0, 0, 0, 0]); // String and Global Subr INDEXes
+        assert_eq!(cff.len(), 26);
+        cff.extend_from_slice(&[0, 0, 1]); // charset
+        cff.extend_from_slice(&write_cff_index(&[&[14], &[139, 139, 21, 14]]));
+        assert_eq!(cff.len(), 40);
+        cff.extend_from_slice(&[139, 20]); // Private DICT
+
+        let mut sfnt = Sfnt::new(u32::from_be_bytes(*b"OTTO"));
+        sfnt.set_table(CFF, cff);
+        subset_cff(&mut sfnt, &mut BTreeSet::from([0])).unwrap();
+
+        let cff = sfnt.table(CFF).unwrap();
+        let (_, top_start) = cff_index(cff, 4).unwrap();
+        let (dicts, _) = cff_index(cff, top_start).unwrap();
+        let dict = cff_dict(dicts[0]).unwrap();
+
+        let charset = cff_offset_operand(15, &dict[0].1).unwrap();
+        assert_eq!(&cff[charset..charset + 3], &[0, 0, 1]);
+
+        let cs = cff_offset_operand(17, &dict[1].1).unwrap();
+        let (charstrings, _) = cff_index(cff, cs).unwrap();
+        assert_eq!(charstrings, [&[14][..], &[14][..]]);
+
+        let private = cff_offset_operand(18, &dict[2].1).unwrap();
+        assert_eq!(&cff[private..], &[139, 20]);
+    }
+
+    #[test]
+    fn cff_accents_and_subrs() {
+        // A font with an accented character, `Aacute`, built with `endchar`
+        // in a local subroutine. The Private DICT comes before the CharStrings
+        // INDEX, and its subroutines after it.
+        let notdef: &[u8] = &[14];
+        let a: &[u8] = &[139, 139, 21, 14];
+        let acute: &[u8] = &[139, 22, 14];
+        let aacute: &[u8] = &[32, 10]; // callsubr 0
+        let b: &[u8] = &[139, 139, 21, 139, 139, 5, 139, 139, 5, 14];
+        // 0 0 (base `A`: code 65) (accent `acute`: code 194) endchar
+        let subr: &[u8] = &[139, 139, 65 + 139, 247, 194 - 108, 14];
+
+        let charset = [0, 0, 34, 0, 125, 1, 44, 0, 35];
+        let charstrings = write_cff_index(&[notdef, a, acute, aacute, b]);
+        let subrs = write_cff_index(&[subr]);
+
+        let private_start = 38;
+        let charset_start = private_start + 2;
+        let cs_start = charset_start + charset.len();
+        let subrs_start = cs_start + charstrings.len();
+
+        let mut top_dict = Vec::new();
+        top_dict.extend_from_slice(&cff_int5(charset_start));
+        top_dict.push(15);
+        top_dict.extend_from_slice(&cff_int5(cs_start));
+        top_dict.push(17);
+        top_dict.extend_from_slice(&[2 + 139]);
+        top_dict.extend_from_slice(&cff_int5(private_start));
+        top_dict.push(18);
+
+        let mut cff = vec![1, 0, 4, 1];
+        cff.extend_from_slice(&write_cff_index(&[b"T"]));
+        cff.extend_from_slice(&write_cff_index(&[&top_dict]));
+        cff.extend_from_slice(&[0, 0, 0, 0]); // String and Global Subr INDEXes
+        assert_eq!(cff.len(), private_start);
+        cff.extend_from_slice(&[(subrs_start - private_start + 139) as u8, 19]);
+        cff.extend_from_slice(&charset);
+        cff.extend_from_slice(&charstrings);
+        cff.extend_from_slice(&subrs);
+
+        let mut sfnt = Sfnt::new(u32::from_be_bytes(*b"OTTO"));
+        sfnt.set_table(CFF, cff);
+        let mut keep = BTreeSet::from([3]);
+        subset_font(&mut sfnt, &mut keep);
+        assert_eq!(keep, BTreeSet::from([0, 1, 2, 3]));
+
+        let cff = sfnt.table(CFF).unwrap();
+        let (_, top_start) = cff_index(cff, 4).unwrap();
+        let (dicts, _) = cff_index(cff, top_start).unwrap();
+        let dict = cff_dict(dicts[0]).unwrap();
+
+        let cs = cff_offset_operand(17, &dict[1].1).unwrap();
+        let (new_charstrings, _) = cff_index(cff, cs).unwrap();
+        assert_eq!(new_charstrings, [notdef, a, acute, aacute, &[14][..]]);
+
+        let size = dict[2].1[0].value.unwrap() as usize;
+        let private = cff_offset_operand(18, &dict[2].1).unwrap();
+        let private_dict = cff_dict(&cff[private..private + size]).unwrap();
+        let new_subrs = private + cff_offset_operand(19, &private_dict[0].1).unwrap();
+        let (new_subrs, _) = cff_index(cff, new_subrs).unwrap();
+        assert_eq!(new_subrs, [subr]);
+    }
+
+    #[test]
+    fn cff_structures() {
+        let items: Vec<&[u8]> = vec![b"abc", b"", b"de"];
+        let index = write_cff_index(&items);
+        assert_eq!(index, b"\x00\x03\x01\x01\x04\x04\x06abcde");
+
+        let (parsed, end) = cff_index(&index, 0).unwrap();
+        assert_eq!(parsed, items);
+        assert_eq!(end, index.len());
+
+        // `version` SID 391, then CharStrings at offset 1000 and Private
+        // (size 45, offset 2000).
+        let dict = [
+            0xF8, 0x1B, 0x00, 0xFA, 0x7C, 0x11, 0xB8, 0x1D, 0, 0, 7, 0xD0, 0x12,
+        ];
+        let entries = cff_dict(&dict).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(cff_offset_operand(17, &entries[1].1), Some(1000));
+        assert_eq!(cff_offset_operand(18, &entries[2].1), Some(2000));
+
+        let rewritten = write_cff_dict(&entries, |o| o + 5);
+        let rewritten = cff_dict(&rewritten).unwrap();
+        assert_eq!(rewritten[0].1[0].value, Some(391));
+        assert_eq!(cff_offset_operand(17, &rewritten[1].1), Some(1005));
+        assert_eq!(rewritten[2].1[0].value, Some(45));
+        assert_eq!(cff_offset_operand(18, &rewritten[2].1), Some(2005));
+    }
+}
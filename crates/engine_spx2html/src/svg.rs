@@ -0,0 +1,681 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Rendering of XDV pages to SVG.
+//!
+//! Each page becomes a standalone SVG document. Glyphs are drawn as text using
+//! the same font machinery as the HTML emitter: the font files are emitted
+//! alongside the SVG files, and referenced with CSS `@font-face` rules, with
+//! "variant" fonts created for glyphs that don't correspond directly to a
+//! Unicode character.
+
+use std::{fmt::Write, path::PathBuf};
+use tectonic_bridge_core::DriverHooks;
+use tectonic_errors::prelude::*;
+use tectonic_status_base::{tt_warning, StatusBackend};
+use tectonic_xdv::{FileType, XdvEvents, XdvParser};
+
+use crate::{
+    assets::RenderedAssets,
+    fonts::{open_native_font, FontEnsemble},
+    output::{FilesystemOutput, OutputTree},
+    Common, FixedPoint, TexFontNum,
+};
+
+/// The number of TeX points per inch.
+const TEX_PT_PER_INCH: f64 = 72.27;
+
+/// The default page size, US letter, in TeX points.
+const DEFAULT_PAGE_SIZE: (f64, f64) = (8.5 * TEX_PT_PER_INCH, 11. * TEX_PT_PER_INCH);
+
+/// An engine that renders the pages of an XDV file to SVG.
+pub struct Xdv2SvgEngine {
+    output: Option<Box<dyn OutputTree>>,
+    page_stem: String,
+}
+
+impl Default for Xdv2SvgEngine {
+    fn default() -> Self {
+        Xdv2SvgEngine {
+            output: None,
+            page_stem: "page".to_owned(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Xdv2SvgEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Xdv2SvgEngine")
+            .field("output", &self.output.as_ref().map(|_| ".."))
+            .field("page_stem", &self.page_stem)
+            .finish()
+    }
+}
+
+impl Xdv2SvgEngine {
+    /// Specify the directory into which the output files are written.
+    ///
+    /// This is shorthand for calling [`Self::output_tree`] with a
+    /// [`FilesystemOutput`].
+    pub fn output_base(&mut self, out_base: impl Into<PathBuf>) -> &mut Self {
+        self.output_tree(FilesystemOutput::new(out_base))
+    }
+
+    /// Specify where output files should be written.
+    ///
+    /// As with the HTML output, the SVG files and the font files that they
+    /// reference are written through the given [`OutputTree`]. Use
+    /// [`DriverIoOutput`](crate::DriverIoOutput) to send them through the I/O
+    /// layer of the processing session. The output must be specified before
+    /// processing.
+    pub fn output_tree(&mut self, tree: impl OutputTree + 'static) -> &mut Self {
+        self.output = Some(Box::new(tree));
+        self
+    }
+
+    /// Specify the stem of the names of the output SVG files.
+    ///
+    /// Page *n* (counting from 1) is written to a file named `{stem}-{n}.svg`.
+    /// The default stem is `page`.
+    pub fn page_stem<S: ToString>(&mut self, stem: S) -> &mut Self {
+        self.page_stem = stem.to_string();
+        self
+    }
+
+    /// Render the pages of an XDV or SPX file to SVG files.
+    ///
+    /// Despite the name, the output need not actually go to the filesystem.
+    pub fn process_to_filesystem(
+        &mut self,
+        hooks: &mut dyn DriverHooks,
+        status: &mut dyn StatusBackend,
+        xdv: &str,
+    ) -> Result<()> {
+        let output = a_ok_or!(
+            self.output.as_mut();
+            ["the SVG output location must be specified"]
+        );
+
+        let mut input = hooks.io().input_open_name(xdv, status).must_exist()?;
+
+        {
+            let common = Common {
+                hooks,
+                status,
+                output: Some(output.as_mut()),
+                precomputed_assets: None,
+                woff2_fonts: false,
+                hashed_asset_names: false,
+                source_positions: Default::default(),
+            };
+            let state = XdvParser::process_with_seeks(&mut input, SvgState::new(common))?;
+            let SvgState {
+                fonts,
+                pages,
+                mut common,
+                ..
+            } = state;
+
+            let mut rendered = RenderedAssets::default();
+            let faces = fonts.render(&mut common, &mut rendered)?;
+            rendered.emit(&mut common)?;
+
+            for (n, page) in pages.iter().enumerate() {
+                let path = format!("{}-{}.svg", self.page_stem, n + 1);
+                common.write_output_file(&path, page.to_svg(&faces).as_bytes())?;
+            }
+
+            common.finish_output()?;
+        }
+
+        let (name, digest_opt) = input.into_name_digest();
+        hooks.event_input_closed(name, digest_opt, status);
+        Ok(())
+    }
+}
+
+/// A page being rendered.
+#[derive(Debug)]
+struct Page {
+    /// The page width and height, in TeX points.
+    size: (f64, f64),
+
+    /// The SVG content of the page.
+    body: String,
+}
+
+impl Page {
+    fn new() -> Self {
+        Page {
+            size: DEFAULT_PAGE_SIZE,
+            body: String::new(),
+        }
+    }
+
+    fn to_svg(&self, faces: &str) -> String {
+        let (width, height) = self.size;
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{}pt" height="{}pt" viewBox="0 0 {} {}">
+<style>
+{}text {{ white-space: pre; }}
+</style>
+{}</svg>
+"#,
+            fmt_coord(width * 72. / TEX_PT_PER_INCH),
+            fmt_coord(height * 72. / TEX_PT_PER_INCH),
+            fmt_coord(width),
+            fmt_coord(height),
+            faces,
+            self.body,
+        )
+    }
+}
+
+struct SvgState<'a> {
+    common: Common<'a>,
+    fonts: FontEnsemble,
+    pages: Vec<Page>,
+
+    /// The stack of colors set by `color push` specials, as CSS colors. The
+    /// bottom of the stack is the base color. As with dvipdfmx, the stack
+    /// carries over from one page to the next.
+    colors: Vec<String>,
+
+    warned_tfm: bool,
+}
+
+impl<'a> SvgState<'a> {
+    fn new(common: Common<'a>) -> Self {
+        SvgState {
+            common,
+            fonts: FontEnsemble::default(),
+            pages: Vec::new(),
+            colors: vec!["#000000".to_owned()],
+            warned_tfm: false,
+        }
+    }
+
+    /// Get an SVG `fill` attribute for the current color, with a leading
+    /// space, or an empty string if it's the default.
+    fn fill_attr(&self) -> String {
+        match self.colors.last().map(|c| c.as_ref()) {
+            Some("#000000") | None => String::new(),
+            Some(c) => format!(" fill=\"{c}\""),
+        }
+    }
+
+    fn page(&mut self) -> Result<&mut Page> {
+        Ok(a_ok_or!(
+            self.pages.last_mut();
+            ["XDV content outside of a page"]
+        ))
+    }
+
+    fn handle_pagesize(&mut self, spec: &str) -> Result<()> {
+        if spec.trim() == "default" {
+            return Ok(());
+        }
+
+        let mut width = None;
+        let mut height = None;
+        let mut words = spec.split_whitespace();
+
+        while let Some(key) = words.next() {
+            let value = words.next().and_then(parse_length);
+
+            match key {
+                "width" => width = value,
+                "height" => height = value,
+                _ => {}
+            }
+        }
+
+        if let (Some(w), Some(h)) = (width, height) {
+            self.page()?.size = (w, h);
+        } else {
+            tt_warning!(
+                self.common.status,
+                "ignoring unparseable page size special `pdf:pagesize {}`",
+                spec
+            );
+        }
+
+        Ok(())
+    }
+
+    fn handle_color(&mut self, spec: &str) -> Result<()> {
+        let mut words = spec.split_whitespace();
+        let status = &mut *self.common.status;
+        let colors = &mut self.colors;
+
+        match words.next() {
+            Some("pop") => {
+                if colors.len() > 1 {
+                    colors.pop();
+                } else {
+                    tt_warning!(status, "ignoring `color pop` with an empty color stack");
+                }
+            }
+
+            Some("push") => {
+                let color = parse_color(words).unwrap_or_else(|| {
+                    tt_warning!(
+                        status,
+                        "unsupported color specification in `color {}`",
+                        spec
+                    );
+                    colors.last().cloned().unwrap_or_default()
+                });
+                colors.push(color);
+            }
+
+            Some(_) => {
+                // Setting a color replaces the whole stack.
+                if let Some(color) = parse_color(spec.split_whitespace()) {
+                    *colors = vec![color];
+                } else {
+                    tt_warning!(
+                        status,
+                        "unsupported color specification in `color {}`",
+                        spec
+                    );
+                }
+            }
+
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Add glyphs to the current page, grouping consecutive glyphs that use
+    /// the same font into a single `<text>` element.
+    fn add_glyphs(
+        &mut self,
+        font_num: TexFontNum,
+        glyphs: &[u16],
+        x: &[i32],
+        y: &[i32],
+    ) -> Result<()> {
+        let mut runs: Vec<TextRun> = Vec::new();
+
+        self.fonts.mark_glyphs_used(font_num, glyphs, None);
+
+        for ((glyph, x), y) in glyphs.iter().zip(x).zip(y) {
+            let (text_info, size, _) =
+                self.fonts
+                    .process_glyph_for_canvas(font_num, *glyph, self.common.status);
+
+            if let Some((ch, font_sel)) = text_info {
+                match runs.last_mut() {
+                    Some(run) if run.size == size && run.font_sel == font_sel => {
+                        run.chars.push((ch, *x, *y))
+                    }
+                    _ => runs.push(TextRun {
+                        size,
+                        font_sel,
+                        chars: vec![(ch, *x, *y)],
+                    }),
+                }
+            }
+        }
+
+        let fill = self.fill_attr();
+        let page = self.page()?;
+
+        for run in runs {
+            run.write(&fill, &mut page.body);
+        }
+
+        Ok(())
+    }
+}
+
+/// A sequence of glyphs that are drawn with the same font.
+#[derive(Debug)]
+struct TextRun {
+    size: FixedPoint,
+
+    /// The CSS style selecting the font.
+    font_sel: String,
+
+    /// The characters producing the glyphs, with their XDV positions.
+    chars: Vec<(char, i32, i32)>,
+}
+
+impl TextRun {
+    fn write(&self, fill: &str, dest: &mut String) {
+        let xs: Vec<_> = self.chars.iter().map(|c| fmt_coord(pos(c.1))).collect();
+
+        // If all of the glyphs are on the same baseline, one y coordinate will
+        // do.
+        let y0 = self.chars[0].2;
+        let ys: Vec<_> = if self.chars.iter().all(|c| c.2 == y0) {
+            vec![fmt_coord(pos(y0))]
+        } else {
+            self.chars.iter().map(|c| fmt_coord(pos(c.2))).collect()
+        };
+
+        let text: String = self.chars.iter().map(|c| c.0).collect();
+
+        write!(
+            dest,
+            "<text x=\"{}\" y=\"{}\" font-size=\"{}\" style=\"{}\"{}>",
+            xs.join(" "),
+            ys.join(" "),
+            fmt_coord(sp_to_pt(self.size)),
+            self.font_sel,
+            fill,
+        )
+        .unwrap();
+        html_escape::encode_text_to_string(&text, dest);
+        dest.push_str("</text>\n");
+    }
+}
+
+impl<'a> XdvEvents for SvgState<'a> {
+    type Error = Error;
+
+    fn handle_header(&mut self, filetype: FileType, _comment: &[u8]) -> Result<()> {
+        if filetype == FileType::Dvi {
+            bail!("SVG rendering requires XDV or SPX input, but got DVI");
+        }
+
+        Ok(())
+    }
+
+    fn handle_begin_page(&mut self, _counters: &[i32], _previous_bop: i32) -> Result<()> {
+        self.pages.push(Page::new());
+        Ok(())
+    }
+
+    fn handle_special(&mut self, _x: i32, _y: i32, contents: &[u8]) -> Result<()> {
+        let contents = atry!(std::str::from_utf8(contents); ["could not parse \\special as UTF-8"]);
+
+        if let Some(spec) = contents.strip_prefix("pdf:pagesize ") {
+            self.handle_pagesize(spec)
+        } else if let Some(spec) = contents.strip_prefix("color ") {
+            self.handle_color(spec)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn handle_char_run(&mut self, _font_num: i32, _chars: &[i32]) -> Result<()> {
+        if !self.warned_tfm {
+            tt_warning!(
+                self.common.status,
+                "characters from traditional TeX fonts are not rendered to SVG"
+            );
+            self.warned_tfm = true;
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn handle_define_native_font(
+        &mut self,
+        name: &str,
+        font_num: TexFontNum,
+        size: FixedPoint,
+        face_index: u32,
+        color_rgba: Option<u32>,
+        extend: Option<u32>,
+        slant: Option<u32>,
+        embolden: Option<u32>,
+    ) -> Result<()> {
+        if self.fonts.contains(font_num) {
+            return Ok(());
+        }
+
+        let (texpath, ih) = open_native_font(self.common.hooks.io(), name, self.common.status)?;
+
+        self.fonts.register_tex_font(
+            font_num,
+            size,
+            face_index,
+            color_rgba,
+            extend,
+            slant,
+            embolden,
+            texpath,
+            ih,
+            &mut self.common,
+        )
+    }
+
+    fn handle_glyph_run(
+        &mut self,
+        font_num: TexFontNum,
+        glyphs: &[u16],
+        x: &[i32],
+        y: &[i32],
+    ) -> Result<()> {
+        self.add_glyphs(font_num, glyphs, x, y)
+    }
+
+    fn handle_text_and_glyphs(
+        &mut self,
+        font_num: TexFontNum,
+        _text: &str,
+        _width: i32,
+        glyphs: &[u16],
+        x: &[i32],
+        y: &[i32],
+    ) -> Result<()> {
+        self.add_glyphs(font_num, glyphs, x, y)
+    }
+
+    fn handle_rule(&mut self, x: i32, y: i32, height: i32, width: i32) -> Result<()> {
+        if height <= 0 || width <= 0 {
+            return Ok(());
+        }
+
+        let fill = self.fill_attr();
+        let page = self.page()?;
+
+        writeln!(
+            page.body,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{}/>",
+            fmt_coord(pos(x)),
+            fmt_coord(pos(y) - sp_to_pt(height)),
+            fmt_coord(sp_to_pt(width)),
+            fmt_coord(sp_to_pt(height)),
+            fill,
+        )
+        .unwrap();
+        Ok(())
+    }
+}
+
+fn sp_to_pt(sp: i32) -> f64 {
+    sp as f64 / 65536.
+}
+
+/// Convert an XDV position to an SVG coordinate. The XDV origin is one inch
+/// from the top and left edges of the page.
+fn pos(v: i32) -> f64 {
+    sp_to_pt(v) + TEX_PT_PER_INCH
+}
+
+/// Format a coordinate compactly, with a precision well below what's visible.
+fn fmt_coord(v: f64) -> String {
+    let s = format!("{v:.3}");
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+
+    if s == "-0" {
+        "0".to_owned()
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Parse a length like `597.5pt` into TeX points.
+fn parse_length(text: &str) -> Option<f64> {
+    let split = text.find(|c: char| c.is_ascii_alphabetic())?;
+    let (value, unit) = text.split_at(split);
+    let value: f64 = value.parse().ok()?;
+
+    let factor = match unit {
+        "pt" => 1.,
+        "bp" => TEX_PT_PER_INCH / 72.,
+        "in" => TEX_PT_PER_INCH,
+        "cm" => TEX_PT_PER_INCH / 2.54,
+        "mm" => TEX_PT_PER_INCH / 25.4,
+        _ => return None,
+    };
+
+    Some(value * factor)
+}
+
+/// Parse a color specification like `rgb 1 0 0` into a CSS color.
+fn parse_color<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<String> {
+    let model = words.next()?;
+    let values: Vec<f64> = words.map(|w| w.parse().ok()).collect::<Option<_>>()?;
+    let channel = |v: f64| (v.clamp(0., 1.) * 255.).round() as u8;
+
+    let (r, g, b) = match (model, values.as_slice()) {
+        ("rgb", &[r, g, b]) => (r, g, b),
+        ("gray", &[g]) => (g, g, g),
+        ("cmyk", &[c, m, y, k]) => (
+            (1. - c) * (1. - k),
+            (1. - m) * (1. - k),
+            (1. - y) * (1. - k),
+        ),
+        _ => return None,
+    };
+
+    Some(format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(r),
+        channel(g),
+        channel(b)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tectonic_bridge_core::MinimalDriver;
+    use tectonic_status_base::NoopStatusBackend;
+
+    use crate::{
+        testutil::{lmroman_glyph, make_xdv, output_text, MemoryIo, LMROMAN, LMROMAN_NAME},
+        DriverIoOutput,
+    };
+
+    const PT: i32 = 65536;
+
+    #[test]
+    fn lengths() {
+        assert_eq!(parse_length("597.5pt"), Some(597.5));
+        assert_eq!(parse_length("72bp"), Some(TEX_PT_PER_INCH));
+        assert_eq!(parse_length("2in"), Some(2. * TEX_PT_PER_INCH));
+        assert_eq!(parse_length("2.54cm"), Some(TEX_PT_PER_INCH));
+        assert_eq!(parse_length("25.4mm"), Some(TEX_PT_PER_INCH));
+        assert_eq!(parse_length("12pc"), None);
+        assert_eq!(parse_length("12"), None);
+        assert_eq!(parse_length("pt"), None);
+    }
+
+    #[test]
+    fn colors() {
+        let color = |spec: &str| parse_color(spec.split_whitespace());
+        assert_eq!(color("rgb 1 0 0.5").as_deref(), Some("#ff0080"));
+        assert_eq!(color("gray 0.5").as_deref(), Some("#808080"));
+        assert_eq!(color("cmyk 0 1 1 0.5").as_deref(), Some("#800000"));
+        assert_eq!(color("rgb 2 -1 0").as_deref(), Some("#ff0000"));
+        assert_eq!(color("rgb 1 0"), None);
+        assert_eq!(color("rgb 1 0 x"), None);
+        assert_eq!(color("hsb 0 0 0"), None);
+        assert_eq!(color(""), None);
+    }
+
+    #[test]
+    fn coords() {
+        assert_eq!(fmt_coord(12.), "12");
+        assert_eq!(fmt_coord(1.5), "1.5");
+        assert_eq!(fmt_coord(0.12345), "0.123");
+        assert_eq!(fmt_coord(-2.0004), "-2");
+        assert_eq!(fmt_coord(-0.0001), "0");
+        assert_eq!(fmt_coord(100.), "100");
+    }
+
+    #[test]
+    fn pages() {
+        let xdv = make_xdv(FileType::Xdv, |w| {
+            w.begin_page(&[0; 10])?;
+            w.special(b"pdf:pagesize width 100pt height 2in")?;
+            w.define_native_font(LMROMAN_NAME, 1, 12 * PT, 0, None, None, None, None)?;
+            w.set_font(1)?;
+            w.set_glyphs(
+                12 * PT,
+                &[lmroman_glyph('a'), lmroman_glyph('<')],
+                &[0, 6 * PT],
+                &[0, 0],
+            )?;
+            w.special(b"color push rgb 1 0 0")?;
+            w.put_rule(2 * PT, 3 * PT)?;
+            w.end_page()?;
+
+            // The color stack carries over to the next page.
+            w.begin_page(&[0; 10])?;
+            w.put_rule(PT, PT)?;
+            w.special(b"color pop")?;
+            w.put_rule(PT, PT)?;
+            w.end_page()
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let io = MemoryIo::new(&[("in.xdv", &xdv), (LMROMAN_NAME, LMROMAN)]);
+        let mut hooks = MinimalDriver::new(io);
+        let mut status = NoopStatusBackend::default();
+        Xdv2SvgEngine::default()
+            .output_base(dir.path())
+            .page_stem("p")
+            .process_to_filesystem(&mut hooks, &mut status, "in.xdv")
+            .unwrap();
+
+        let page1 = std::fs::read_to_string(dir.path().join("p-1.svg")).unwrap();
+        assert!(page1.contains(r#"width="99.626pt" height="144pt" viewBox="0 0 100 144.54">"#));
+        assert!(page1.contains("@font-face"));
+        assert!(page1.contains(
+            r#"<text x="72.27 78.27" y="72.27" font-size="12" style="font-family: lmroman12_regular_otf">a&lt;</text>"#
+        ));
+        assert!(
+            page1.contains(r##"<rect x="84.27" y="70.27" width="3" height="2" fill="#ff0000"/>"##)
+        );
+
+        let page2 = std::fs::read_to_string(dir.path().join("p-2.svg")).unwrap();
+        assert!(page2.contains(r#"viewBox="0 0 614.295 794.97""#));
+        assert!(
+            page2.contains(r##"<rect x="72.27" y="71.27" width="1" height="1" fill="#ff0000"/>"##)
+        );
+        assert!(page2.contains(r#"<rect x="72.27" y="71.27" width="1" height="1"/>"#));
+    }
+
+    #[test]
+    fn driver_io_output() {
+        let xdv = make_xdv(FileType::Xdv, |w| {
+            w.begin_page(&[0; 10])?;
+            w.put_rule(PT, PT)?;
+            w.end_page()
+        });
+
+        let io = MemoryIo::new(&[("in.xdv", &xdv)]);
+        let mut hooks = MinimalDriver::new(io.clone());
+        let mut status = NoopStatusBackend::default();
+        Xdv2SvgEngine::default()
+            .output_tree(DriverIoOutput)
+            .process_to_filesystem(&mut hooks, &mut status, "in.xdv")
+            .unwrap();
+
+        let outputs = io.take_outputs();
+        assert_eq!(outputs.len(), 1);
+        assert!(output_text(&outputs, "page-1.svg")
+            .contains(r#"<rect x="72.27" y="71.27" width="1" height="1"/>"#));
+    }
+}
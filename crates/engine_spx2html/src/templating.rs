@@ -3,14 +3,18 @@
 
 //! State relating to handling the Tera templating and file emission.
 
-use std::{
-    fs::File,
-    io::{Read, Write},
-};
+use std::{collections::HashMap, io::Read, sync::Arc};
 use tectonic_errors::prelude::*;
 use tectonic_status_base::tt_warning;
 
-use crate::Common;
+use crate::{
+    assets::syntax::SearchIndexEntry,
+    links::Links,
+    navigation::{insert_site_context, Heading, PageInfo},
+    search,
+    sourcepos::{source_map_path, SourceMap},
+    Common,
+};
 
 #[derive(Debug)]
 pub(crate) struct Templating {
@@ -18,6 +22,25 @@ pub(crate) struct Templating {
     context: tera::Context,
     next_template_path: String,
     next_output_path: String,
+    links: Links,
+    headings: Vec<Heading>,
+    pages: Vec<PendingPage>,
+}
+
+/// A page that has been emitted, but not yet rendered.
+///
+/// Rendering is deferred until all of the pages are known, so that templates
+/// can be given information about the whole site, and so that links can point
+/// to anchors in later pages.
+#[derive(Debug)]
+struct PendingPage {
+    info: PageInfo,
+    content: String,
+    out_path: Option<String>,
+    source_map: Option<SourceMap>,
+    template_path: String,
+    template: String,
+    context: tera::Context,
 }
 
 impl Templating {
@@ -32,9 +55,21 @@ impl Templating {
             context,
             next_template_path,
             next_output_path,
+            links: Links::default(),
+            headings: Vec::new(),
+            pages: Vec::new(),
         }
     }
 
+    pub(crate) fn links_mut(&mut self) -> &mut Links {
+        &mut self.links
+    }
+
+    /// Record a heading in the content of the page being built.
+    pub(crate) fn add_heading(&mut self, heading: Heading) {
+        self.headings.push(heading);
+    }
+
     pub(crate) fn handle_set_template<S: ToString>(&mut self, arg: S) {
         self.next_template_path = arg.to_string();
     }
@@ -71,7 +106,13 @@ impl Templating {
         !self.next_template_path.is_empty() && !self.next_output_path.is_empty()
     }
 
-    pub(crate) fn emit(&mut self, common: &mut Common) -> Result<()> {
+    /// Emit a page with the given HTML content.
+    pub(crate) fn emit(
+        &mut self,
+        content: String,
+        source_map: SourceMap,
+        common: &mut Common,
+    ) -> Result<()> {
         if self.next_template_path.is_empty() {
             bail!("need to emit HTML content but no template has been specified; is your document HTML-compatible?");
         }
@@ -82,6 +123,7 @@ impl Templating {
 
         let (out_path, n_levels) =
             crate::assets::create_output_path(&self.next_output_path, common)?;
+        self.links.bind_anchors(&self.next_output_path);
 
         if n_levels < 2 {
             self.context.insert("tduxRelTop", "");
@@ -116,32 +158,218 @@ impl Templating {
             .hooks
             .event_input_closed(name, digest_opt, common.status);
 
-        // Ready to render!
+        // Rendering happens once all of the pages are known. Unlike other
+        // variables, the page title doesn't carry over to later pages: if a
+        // page doesn't set its own, it gets one from its first heading.
 
-        let rendered = atry!(
-            self.tera.render_str(&template, &self.context);
-            ["failed to render HTML template `{}` while creating `{}`", &self.next_template_path, &self.next_output_path]
-        );
+        let title = self
+            .context
+            .remove("tduxPageTitle")
+            .and_then(|v| v.as_str().map(|t| t.to_owned()));
 
-        // Save it. Unless we shouldn't, actually.
+        self.pages.push(PendingPage {
+            info: PageInfo {
+                output_path: self.next_output_path.clone(),
+                title,
+                headings: std::mem::take(&mut self.headings),
+            },
+            content,
+            out_path,
+            source_map: Some(source_map).filter(|_| common.source_positions.maps),
+            template_path: self.next_template_path.clone(),
+            template,
+            context: self.context.clone(),
+        });
 
-        if let Some(out_path) = out_path {
-            let mut out_file = atry!(
-                File::create(&out_path);
-                ["cannot open output file `{}`", out_path.display()]
+        // Clear the output path, because we don't want people to be accidentally
+        // overwriting the same file by failing to update it.
+
+        self.next_output_path.clear();
+
+        Ok(())
+    }
+
+    /// Get the search index entries for the emitted pages.
+    ///
+    /// Extracting the text of the pages takes some work, so this should only
+    /// be called if the search index will actually be created.
+    pub(crate) fn search_entries(&self) -> Vec<SearchIndexEntry> {
+        let pages: Vec<_> = self
+            .pages
+            .iter()
+            .map(|p| (&p.info, p.content.as_str()))
+            .collect();
+        search::index_entries(&pages)
+    }
+
+    /// Render and save all of the emitted pages, now that the whole site is
+    /// known.
+    ///
+    /// Templates can use the `tduxAsset` function or filter to get the actual
+    /// output path of an asset file, given its logical one. The two differ if
+    /// the asset files have content-hashed names, as recorded in
+    /// *asset_names*.
+    pub(crate) fn finish(
+        &mut self,
+        common: &mut Common,
+        asset_names: &HashMap<String, String>,
+    ) -> Result<()> {
+        let names = Arc::new(asset_names.clone());
+        let fn_names = names.clone();
+
+        self.tera
+            .register_function(
+                "tduxAsset",
+                move |args: &HashMap<String, tera::Value>| match args
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                {
+                    Some(path) => Ok(resolve_asset(&fn_names, path).into()),
+                    None => Err(tera::Error::msg(
+                        "the `tduxAsset` function requires a string `path` argument",
+                    )),
+                },
             );
 
-            atry!(
-                out_file.write_all(rendered.as_bytes());
-                ["cannot write output file `{}`", out_path.display()]
+        self.tera.register_filter(
+            "tduxAsset",
+            move |value: &tera::Value, _: &HashMap<String, tera::Value>| match value.as_str() {
+                Some(path) => Ok(resolve_asset(&names, path).into()),
+                None => Err(tera::Error::msg(
+                    "the `tduxAsset` filter must be applied to a string",
+                )),
+            },
+        );
+
+        let pages = std::mem::take(&mut self.pages);
+        let infos: Vec<_> = pages.iter().map(|p| p.info.clone()).collect();
+
+        for (index, mut page) in pages.into_iter().enumerate() {
+            insert_site_context(&infos, index, &mut page.context);
+            page.context.insert("tduxContent", &page.content);
+
+            let rendered = atry!(
+                self.tera.render_str(&page.template, &page.context);
+                ["failed to render HTML template `{}` while creating `{}`", &page.template_path, &page.info.output_path]
             );
-        }
 
-        // Clear the output path, because we don't want people to be accidentally
-        // overwriting the same file by failing to update it.
+            let rendered = self
+                .links
+                .resolve(&rendered, &page.info.output_path, common.status);
 
-        self.next_output_path.clear();
+            if let Some(out_path) = page.out_path {
+                common.write_output_file(&out_path, rendered.as_bytes())?;
+                common.page_written(&out_path, page.info.effective_title());
+
+                if let Some(map) = page.source_map {
+                    let map_path = source_map_path(&out_path);
+                    common.write_output_file(&map_path, &map.to_json(&out_path))?;
+                    common.auxiliary_file_written(&map_path);
+                }
+            }
+        }
 
         Ok(())
     }
 }
+
+/// Get the actual output path of an asset, given its logical one.
+fn resolve_asset(names: &HashMap<String, String>, path: &str) -> String {
+    names.get(path).cloned().unwrap_or_else(|| path.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::{output_text, run_spx2html, spx_of_specials};
+
+    fn render(template: &str, hashed: bool) -> tectonic_errors::Result<String> {
+        let spx = spx_of_specials(&[
+            "tdux:addTemplate t.html",
+            "tdux:setTemplate t.html",
+            "tdux:setOutputPath index.html",
+            "tdux:provideSpecial search-js s.js",
+            "tdux:emit",
+            "tdux:contentFinished",
+        ]);
+
+        let outputs = run_spx2html(&spx, &[("t.html", template.as_bytes())], |e| {
+            e.hashed_asset_names(hashed);
+        })?;
+        let html = output_text(&outputs, "index.html").to_owned();
+
+        // The page should refer to the asset file that was actually written.
+        let js_name = outputs
+            .keys()
+            .find(|k| k.ends_with(".js"))
+            .expect("no search widget output");
+        assert!(html.contains(js_name.as_str()));
+        Ok(html)
+    }
+
+    const TEMPLATE: &str =
+        r#"{{ tduxAsset(path="s.js") }} {{ "s.js" | tduxAsset }} {{ "t.css" | tduxAsset }}"#;
+
+    #[test]
+    fn asset_names() {
+        assert_eq!(render(TEMPLATE, false).unwrap(), "s.js s.js t.css");
+
+        let html = render(TEMPLATE, true).unwrap();
+        let names: Vec<_> = html.split(' ').collect();
+        assert_eq!(names.len(), 3);
+        assert!(names[0].starts_with("s.") && names[0].ends_with(".js"));
+        assert_ne!(names[0], "s.js");
+        assert_eq!(names[1], names[0]);
+        assert_eq!(names[2], "t.css");
+    }
+
+    #[test]
+    fn asset_name_errors() {
+        let err = render("{{ tduxAsset(name=\"s.js\") }}", true).unwrap_err();
+        assert!(format!("{:?}", err).contains("requires a string `path` argument"));
+
+        let err = render("{{ 1 | tduxAsset }}", true).unwrap_err();
+        assert!(format!("{:?}", err).contains("must be applied to a string"));
+    }
+
+    #[test]
+    fn page_titles_and_heading_ids() {
+        let spx = spx_of_specials(&[
+            "tdux:addTemplate t.html",
+            "tdux:setTemplate t.html",
+            "tdux:setOutputPath a.html",
+            "tdux:setTemplateVariable tduxPageTitle Intro",
+            "tdux:mfs h2\nUid",
+            "tdux:dt One",
+            "tdux:me h2",
+            "tdux:mfs h2\nDid x",
+            "tdux:dt Two",
+            "tdux:me h2",
+            "tdux:mfs h2",
+            "tdux:dt Three",
+            "tdux:me h2",
+            "tdux:emit",
+            "tdux:setOutputPath b.html",
+            "tdux:mfs h2",
+            "tdux:dt Four",
+            "tdux:me h2",
+            "tdux:emit",
+            "tdux:contentFinished",
+        ]);
+
+        const TEMPLATE: &[u8] = b"<title>{{ tduxPageTitle }}</title>{{ tduxContent }}\n\
+            {% for p in tduxPages %}{{ p.title }}:{% for h in p.headings %}{{ h.url }} {% endfor %};{% endfor %}";
+
+        let outputs = run_spx2html(&spx, &[("t.html", TEMPLATE)], |_| {}).unwrap();
+
+        assert_eq!(
+            output_text(&outputs, "a.html"),
+            "<title>Intro</title><h2 id=\"tdux-h1\">One</h2><h2 id=\"x\">Two</h2><h2 id=\"tdux-h2\">Three</h2>\n\
+             Intro:#tdux-h1 #x #tdux-h2 ;Four:b.html#tdux-h3 ;"
+        );
+        assert_eq!(
+            output_text(&outputs, "b.html"),
+            "<title>Four</title><h2 id=\"tdux-h3\">Four</h2>\n\
+             Intro:a.html#tdux-h1 a.html#x a.html#tdux-h2 ;Four:#tdux-h3 ;"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Helpers for testing the engines on synthetic SPX and XDV files, with all
+//! I/O happening in memory.
+
+use pinot::{FontDataRef, TableProvider};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Cursor, Result as IoResult, Write},
+    rc::Rc,
+};
+use tectonic_bridge_core::MinimalDriver;
+use tectonic_errors::prelude::*;
+use tectonic_io_base::{InputHandle, InputOrigin, IoProvider, OpenResult, OutputHandle};
+use tectonic_status_base::{NoopStatusBackend, StatusBackend};
+use tectonic_xdv::{FileType, XdvWriter};
+
+use crate::{DriverIoOutput, Spx2HtmlEngine};
+
+/// The name of the SPX file given to the engines by [`run_spx2html`].
+pub(crate) const INPUT_NAME: &str = "input.spx";
+
+/// The name of a real font, available as [`LMROMAN`].
+pub(crate) const LMROMAN_NAME: &str = "lmroman12-regular.otf";
+
+/// The data of a real OpenType font.
+pub(crate) const LMROMAN: &[u8] = include_bytes!("../../../tests/assets/lmroman12-regular.otf");
+
+type Files = Rc<RefCell<HashMap<String, Vec<u8>>>>;
+
+/// An I/O provider that reads and writes files in memory.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MemoryIo {
+    inputs: HashMap<String, Vec<u8>>,
+    outputs: Files,
+}
+
+impl MemoryIo {
+    /// Create a provider making the specified input files available.
+    pub(crate) fn new(inputs: &[(&str, &[u8])]) -> Self {
+        MemoryIo {
+            inputs: inputs
+                .iter()
+                .map(|(name, data)| (name.to_string(), data.to_vec()))
+                .collect(),
+            outputs: Default::default(),
+        }
+    }
+
+    /// Take the files that have been written so far.
+    pub(crate) fn take_outputs(&self) -> HashMap<String, Vec<u8>> {
+        std::mem::take(&mut self.outputs.borrow_mut())
+    }
+}
+
+struct MemoryOutput {
+    name: String,
+    files: Files,
+}
+
+impl Write for MemoryOutput {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.files
+            .borrow_mut()
+            .entry(self.name.clone())
+            .or_default()
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+impl IoProvider for MemoryIo {
+    fn output_open_name(&mut self, name: &str) -> OpenResult<OutputHandle> {
+        self.outputs
+            .borrow_mut()
+            .insert(name.to_owned(), Vec::new());
+
+        OpenResult::Ok(OutputHandle::new(
+            name,
+            MemoryOutput {
+                name: name.to_owned(),
+                files: self.outputs.clone(),
+            },
+        ))
+    }
+
+    fn input_open_name(
+        &mut self,
+        name: &str,
+        _status: &mut dyn StatusBackend,
+    ) -> OpenResult<InputHandle> {
+        match self.inputs.get(name) {
+            Some(data) => OpenResult::Ok(InputHandle::new(
+                name,
+                Cursor::new(data.clone()),
+                InputOrigin::Other,
+            )),
+            None => OpenResult::NotAvailable,
+        }
+    }
+}
+
+/// Create an SPX or XDV file, with the page contents written by *pages*.
+pub(crate) fn make_xdv(
+    filetype: FileType,
+    pages: impl FnOnce(&mut XdvWriter<Vec<u8>>) -> IoResult<()>,
+) -> Vec<u8> {
+    let mut w = XdvWriter::new(Vec::new(), filetype, b"test").unwrap();
+    pages(&mut w).unwrap();
+    w.finish().unwrap()
+}
+
+/// Create an SPX file with one page containing the specified specials.
+pub(crate) fn spx_of_specials(specials: &[&str]) -> Vec<u8> {
+    make_xdv(FileType::Spx, |w| {
+        w.begin_page(&[0; 10])?;
+
+        for s in specials {
+            w.special(s.as_bytes())?;
+        }
+
+        w.end_page()
+    })
+}
+
+/// Get the glyph that [`LMROMAN`] uses for a character.
+pub(crate) fn lmroman_glyph(c: char) -> u16 {
+    let font = FontDataRef::new(LMROMAN).unwrap().get(0).unwrap();
+    font.cmap().unwrap().map(c as u32).unwrap()
+}
+
+/// Run the SPX-to-HTML engine on *spx*, with the other files in *inputs*
+/// available, and return the files that it writes.
+///
+/// By default, the output goes through [`DriverIoOutput`]; *configure* can
+/// alter that along with any other settings.
+pub(crate) fn run_spx2html(
+    spx: &[u8],
+    inputs: &[(&str, &[u8])],
+    configure: impl FnOnce(&mut Spx2HtmlEngine),
+) -> Result<HashMap<String, Vec<u8>>> {
+    let mut all_inputs = inputs.to_vec();
+    all_inputs.push((INPUT_NAME, spx));
+    let io = MemoryIo::new(&all_inputs);
+    let mut hooks = MinimalDriver::new(io.clone());
+    let mut status = NoopStatusBackend::default();
+
+    let mut engine = Spx2HtmlEngine::default();
+    engine.output_tree(DriverIoOutput);
+    configure(&mut engine);
+    engine.process_to_filesystem(&mut hooks, &mut status, INPUT_NAME)?;
+    Ok(io.take_outputs())
+}
+
+/// Get the text of an output file, panicking if it doesn't exist.
+pub(crate) fn output_text<'a>(outputs: &'a HashMap<String, Vec<u8>>, name: &str) -> &'a str {
+    let data = outputs
+        .get(name)
+        .unwrap_or_else(|| panic!("no output file `{}`", name));
+    std::str::from_utf8(data).unwrap()
+}
@@ -0,0 +1,458 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Extraction of plain text from XDV and SPX files.
+//!
+//! The text is recovered from the glyphs set with native (OpenType) fonts,
+//! using the same glyph-to-Unicode mappings as the HTML emitter. Word spaces
+//! and line breaks aren't explicit in the XDV data, so they're inferred from
+//! the positions of the glyphs on the page.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+use tectonic_bridge_core::DriverHooks;
+use tectonic_errors::prelude::*;
+use tectonic_status_base::StatusBackend;
+use tectonic_xdv::{FileType, XdvEvents, XdvParser};
+
+use crate::{
+    emission::is_word_space, fontfile::FontFileData, fonts::open_native_font, FixedPoint,
+    TexFontNum,
+};
+
+/// The default string inserted between the text of successive pages: an ASCII
+/// form feed, as used by many other text extraction tools.
+const DEFAULT_PAGE_SEPARATOR: &str = "\u{c}";
+
+/// An engine that extracts plain text from XDV or SPX files.
+#[derive(Debug)]
+pub struct Xdv2TextEngine {
+    page_separator: String,
+}
+
+impl Default for Xdv2TextEngine {
+    fn default() -> Self {
+        Xdv2TextEngine {
+            page_separator: DEFAULT_PAGE_SEPARATOR.to_owned(),
+        }
+    }
+}
+
+impl Xdv2TextEngine {
+    /// Specify the string to insert between the text of successive pages.
+    ///
+    /// The text of every page ends with a newline, so the separator appears at
+    /// the start of a line. The default is a form feed character.
+    pub fn page_separator<S: ToString>(&mut self, sep: S) -> &mut Self {
+        self.page_separator = sep.to_string();
+        self
+    }
+
+    /// Extract the text of an XDV or SPX file, returning it as a string.
+    ///
+    /// The input and the font files that it references are opened through the
+    /// I/O layer of *hooks*.
+    pub fn process_to_string(
+        &mut self,
+        hooks: &mut dyn DriverHooks,
+        status: &mut dyn StatusBackend,
+        xdv: &str,
+    ) -> Result<String> {
+        let mut input = hooks.io().input_open_name(xdv, status).must_exist()?;
+
+        let text = {
+            let state = TextState::new(hooks, status, &self.page_separator);
+            let state = XdvParser::process_with_seeks(&mut input, state)?;
+            state.text
+        };
+
+        let (name, digest_opt) = input.into_name_digest();
+        hooks.event_input_closed(name, digest_opt, status);
+        Ok(text)
+    }
+
+    /// Extract the text of an XDV or SPX file, writing it to the named output
+    /// file of the I/O layer.
+    pub fn process(
+        &mut self,
+        hooks: &mut dyn DriverHooks,
+        status: &mut dyn StatusBackend,
+        xdv: &str,
+        txt: &str,
+    ) -> Result<()> {
+        let text = self.process_to_string(hooks, status, xdv)?;
+
+        let mut output = hooks.io().output_open_name(txt).must_exist()?;
+        atry!(
+            output.write_all(text.as_bytes());
+            ["failed to write text output file `{}`", txt]
+        );
+        let (name, digest) = output.into_name_digest();
+        hooks.event_output_closed(name, digest, status);
+        Ok(())
+    }
+}
+
+/// A native font defined in the XDV file.
+#[derive(Clone, Copy, Debug)]
+struct TextFont {
+    /// The index of the font's data in `TextState::font_files`.
+    file: usize,
+
+    /// The font size, in TeX units.
+    size: FixedPoint,
+}
+
+/// The end of the most recent piece of text on the current page.
+#[derive(Clone, Copy, Debug)]
+struct Cursor {
+    x: FixedPoint,
+    y: FixedPoint,
+    size: FixedPoint,
+    space_width: Option<FixedPoint>,
+}
+
+struct TextState<'a> {
+    hooks: &'a mut dyn DriverHooks,
+    status: &'a mut dyn StatusBackend,
+    page_separator: &'a str,
+    font_files: Vec<FontFileData>,
+    font_file_index: HashMap<(String, u32), usize>,
+    fonts: HashMap<TexFontNum, TextFont>,
+    text: String,
+    n_pages: usize,
+    cursor: Option<Cursor>,
+}
+
+impl<'a> TextState<'a> {
+    fn new(
+        hooks: &'a mut dyn DriverHooks,
+        status: &'a mut dyn StatusBackend,
+        page_separator: &'a str,
+    ) -> Self {
+        TextState {
+            hooks,
+            status,
+            page_separator,
+            font_files: Vec::new(),
+            font_file_index: HashMap::new(),
+            fonts: HashMap::new(),
+            text: String::new(),
+            n_pages: 0,
+            cursor: None,
+        }
+    }
+
+    /// Load a font file, if it hasn't been loaded already, returning its index
+    /// in `self.font_files`.
+    ///
+    /// Font names are resolved to files in the same way as in the HTML
+    /// emitter.
+    fn ensure_font_file(&mut self, name: &str, face_index: u32) -> Result<usize> {
+        if let Some(index) = self.font_file_index.get(&(name.to_owned(), face_index)) {
+            return Ok(*index);
+        }
+
+        let (texpath, mut ih) = open_native_font(self.hooks.io(), name, self.status)?;
+
+        let mut contents = Vec::new();
+        atry!(
+            ih.read_to_end(&mut contents);
+            ["unable to read input font file `{}`", texpath]
+        );
+
+        let (ih_name, digest_opt) = ih.into_name_digest();
+        self.hooks
+            .event_input_closed(ih_name, digest_opt, self.status);
+
+        let ffd = atry!(
+            FontFileData::from_opentype(contents, face_index);
+            ["unable to load glyph data for font `{}`", texpath]
+        );
+
+        let index = self.font_files.len();
+        self.font_files.push(ffd);
+        self.font_file_index
+            .insert((name.to_owned(), face_index), index);
+        Ok(index)
+    }
+
+    /// Add a piece of text that starts at (*x0*, *y*) and ends at *x1*,
+    /// separating it from the preceding text with a space or a newline if
+    /// their relative positions call for one.
+    fn push_text(
+        &mut self,
+        text: &str,
+        x0: FixedPoint,
+        x1: FixedPoint,
+        y: FixedPoint,
+        font: TextFont,
+    ) {
+        let space_width = self.font_files[font.file].space_width(font.size);
+
+        if let Some(cursor) = self.cursor {
+            if is_new_line(&cursor, x0, y) {
+                self.end_line();
+            } else if is_space_needed(&cursor, x0, space_width) {
+                self.text.push(' ');
+            }
+        }
+
+        self.text.push_str(text);
+        self.cursor = Some(Cursor {
+            x: x1,
+            y,
+            size: font.size,
+            space_width,
+        });
+    }
+
+    /// Terminate the current line of text, if there is one.
+    fn end_line(&mut self) {
+        let len = self.text.trim_end_matches(' ').len();
+        self.text.truncate(len);
+
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+    }
+}
+
+/// Decide whether text starting at (*x0*, *y*) begins a new line.
+///
+/// Moving down while moving backwards starts a new line. Small vertical shifts
+/// that continue rightwards are sub- or superscripts, but jumps of more than
+/// the font size are new lines regardless of the horizontal position.
+fn is_new_line(cursor: &Cursor, x0: FixedPoint, y: FixedPoint) -> bool {
+    let dy = y.saturating_sub(cursor.y);
+    (x0 < cursor.x && dy > 0) || dy.saturating_abs() > cursor.size
+}
+
+/// Decide whether a word space is needed before text starting at *x0*, using
+/// the same heuristic as the HTML emitter.
+fn is_space_needed(cursor: &Cursor, x0: FixedPoint, space_width: Option<FixedPoint>) -> bool {
+    x0 >= cursor.x && is_word_space(cursor.x, x0, cursor.space_width, space_width)
+}
+
+/// Get the text corresponding to a character, expanding the Latin ligatures
+/// that fonts map to their Unicode compatibility characters.
+fn char_text(c: char, buf: &mut [u8; 4]) -> &str {
+    match c {
+        '\u{FB00}' => "ff",
+        '\u{FB01}' => "fi",
+        '\u{FB02}' => "fl",
+        '\u{FB03}' => "ffi",
+        '\u{FB04}' => "ffl",
+        '\u{FB05}' | '\u{FB06}' => "st",
+        _ => c.encode_utf8(buf),
+    }
+}
+
+impl<'a> XdvEvents for TextState<'a> {
+    type Error = Error;
+
+    fn handle_header(&mut self, filetype: FileType, _comment: &[u8]) -> Result<()> {
+        if filetype == FileType::Dvi {
+            bail!("text extraction requires XDV or SPX input, but got DVI");
+        }
+
+        Ok(())
+    }
+
+    fn handle_begin_page(&mut self, _counters: &[i32], _previous_bop: i32) -> Result<()> {
+        if self.n_pages > 0 {
+            self.text.push_str(self.page_separator);
+        }
+
+        self.n_pages += 1;
+        self.cursor = None;
+        Ok(())
+    }
+
+    fn handle_end_page(&mut self) -> Result<()> {
+        self.end_line();
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn handle_define_native_font(
+        &mut self,
+        name: &str,
+        font_num: TexFontNum,
+        size: FixedPoint,
+        face_index: u32,
+        _color_rgba: Option<u32>,
+        _extend: Option<u32>,
+        _slant: Option<u32>,
+        _embolden: Option<u32>,
+    ) -> Result<()> {
+        if self.fonts.contains_key(&font_num) {
+            return Ok(());
+        }
+
+        let file = self.ensure_font_file(name, face_index)?;
+        self.fonts.insert(font_num, TextFont { file, size });
+        Ok(())
+    }
+
+    fn handle_glyph_run(
+        &mut self,
+        font_num: TexFontNum,
+        glyphs: &[u16],
+        x: &[i32],
+        y: &[i32],
+    ) -> Result<()> {
+        let font = *a_ok_or!(self.fonts.get(&font_num);
+            ["reference to undefined native font {}", font_num]
+        );
+
+        let mut buf = [0u8; 4];
+
+        for ((glyph, &x0), &y0) in glyphs.iter().zip(x).zip(y) {
+            let font_file = &self.font_files[font.file];
+            let advance = font_file
+                .lookup_metrics(*glyph, font.size)
+                .map(|gm| gm.advance)
+                .unwrap_or(0);
+
+            if let Some(mapping) = font_file.lookup_mapping(*glyph) {
+                let text = char_text(mapping.get_char(), &mut buf);
+                self.push_text(text, x0, x0 + advance, y0, font);
+            } else if let Some(cursor) = self.cursor.as_mut() {
+                // An unmappable glyph: don't emit anything, but note that the
+                // current point has moved on.
+                cursor.x = x0 + advance;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_text_and_glyphs(
+        &mut self,
+        font_num: TexFontNum,
+        text: &str,
+        _width: i32,
+        glyphs: &[u16],
+        x: &[i32],
+        y: &[i32],
+    ) -> Result<()> {
+        let font = *a_ok_or!(self.fonts.get(&font_num);
+            ["reference to undefined native font {}", font_num]
+        );
+
+        if let (Some(&x0), Some(&y0), Some(&last_x), Some(last_glyph)) =
+            (x.first(), y.first(), x.last(), glyphs.last())
+        {
+            let advance = self.font_files[font.file]
+                .lookup_metrics(*last_glyph, font.size)
+                .map(|gm| gm.advance)
+                .unwrap_or(0);
+            self.push_text(text, x0, last_x + advance, y0, font);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tectonic_bridge_core::MinimalDriver;
+    use tectonic_status_base::NoopStatusBackend;
+
+    use crate::testutil::{lmroman_glyph, make_xdv, MemoryIo, LMROMAN, LMROMAN_NAME};
+
+    const PT: i32 = 65536;
+
+    fn cursor(x: FixedPoint, y: FixedPoint, space_width: Option<FixedPoint>) -> Cursor {
+        Cursor {
+            x,
+            y,
+            size: 10 * PT,
+            space_width,
+        }
+    }
+
+    #[test]
+    fn new_lines() {
+        let c = cursor(100 * PT, 50 * PT, None);
+
+        // Continuing along the line, or dropping into a subscript.
+        assert!(!is_new_line(&c, 101 * PT, 50 * PT));
+        assert!(!is_new_line(&c, 101 * PT, 53 * PT));
+        assert!(!is_new_line(&c, 101 * PT, 47 * PT));
+
+        // Moving back and down, or jumping by more than the font size.
+        assert!(is_new_line(&c, 10 * PT, 62 * PT));
+        assert!(is_new_line(&c, 10 * PT, 51 * PT));
+        assert!(is_new_line(&c, 101 * PT, 61 * PT));
+        assert!(is_new_line(&c, 101 * PT, 39 * PT));
+
+        // Moving back on the same line, as with an overstrike.
+        assert!(!is_new_line(&c, 90 * PT, 50 * PT));
+        assert!(!is_new_line(&c, 90 * PT, 45 * PT));
+    }
+
+    #[test]
+    fn spaces() {
+        let c = cursor(100 * PT, 50 * PT, Some(4 * PT));
+        assert!(!is_space_needed(&c, 100 * PT, None));
+        assert!(!is_space_needed(&c, 101 * PT, None));
+        assert!(is_space_needed(&c, 102 * PT, None));
+        assert!(!is_space_needed(&c, 90 * PT, None));
+
+        // The smaller of the space widths applies.
+        assert!(is_space_needed(&c, 101 * PT, Some(2 * PT)));
+        assert!(!is_space_needed(&c, 101 * PT, Some(8 * PT)));
+
+        // Without any space width, any gap counts.
+        let c = cursor(100 * PT, 50 * PT, None);
+        assert!(!is_space_needed(&c, 100 * PT, None));
+        assert!(is_space_needed(&c, 100 * PT + 1, None));
+        assert!(!is_space_needed(&c, 102 * PT, Some(16 * PT)));
+    }
+
+    #[test]
+    fn ligatures() {
+        let mut buf = [0; 4];
+        assert_eq!(char_text('\u{FB01}', &mut buf), "fi");
+        assert_eq!(char_text('\u{FB04}', &mut buf), "ffl");
+        assert_eq!(char_text('\u{FB06}', &mut buf), "st");
+        assert_eq!(char_text('a', &mut buf), "a");
+        assert_eq!(char_text('\u{2014}', &mut buf), "\u{2014}");
+    }
+
+    #[test]
+    fn pages() {
+        let glyphs = |s: &str| -> Vec<u16> { s.chars().map(lmroman_glyph).collect() };
+        let advance = 6 * PT;
+        let xs =
+            |x0: i32, n: usize| -> Vec<i32> { (0..n as i32).map(|i| x0 + i * advance).collect() };
+
+        let xdv = make_xdv(FileType::Xdv, |w| {
+            w.begin_page(&[0; 10])?;
+            w.define_native_font(LMROMAN_NAME, 1, 12 * PT, 0, None, None, None, None)?;
+            w.set_font(1)?;
+            w.set_glyphs(0, &glyphs("ab"), &xs(0, 2), &[0; 2])?;
+            w.set_glyphs(0, &glyphs("cd"), &xs(30 * PT, 2), &[0; 2])?;
+            w.set_glyphs(0, &glyphs("ef"), &xs(0, 2), &[20 * PT; 2])?;
+            w.end_page()?;
+            w.begin_page(&[0; 10])?;
+            w.set_font(1)?;
+            w.set_glyphs(0, &glyphs("g"), &xs(0, 1), &[0])?;
+            w.end_page()
+        });
+
+        let io = MemoryIo::new(&[("in.xdv", &xdv), (LMROMAN_NAME, LMROMAN)]);
+        let mut hooks = MinimalDriver::new(io);
+        let mut status = NoopStatusBackend::default();
+        let text = Xdv2TextEngine::default()
+            .page_separator("--\n")
+            .process_to_string(&mut hooks, &mut status, "in.xdv")
+            .unwrap();
+        assert_eq!(text, "ab cd\nef\n--\ng\n");
+    }
+}
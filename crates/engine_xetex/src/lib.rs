@@ -81,8 +81,10 @@ pub struct TexEngine {
     // though, it's just a proxy for the global constants in the C code.
     halt_on_error: bool,
     initex_mode: bool,
+    initex_load_format: bool,
     synctex_enabled: bool,
     semantic_pagination_enabled: bool,
+    source_positions_enabled: bool,
     shell_escape_enabled: bool,
     build_date: SystemTime,
 }
@@ -92,8 +94,10 @@ impl Default for TexEngine {
         TexEngine {
             halt_on_error: true,
             initex_mode: false,
+            initex_load_format: false,
             synctex_enabled: false,
             semantic_pagination_enabled: false,
+            source_positions_enabled: false,
             shell_escape_enabled: false,
             build_date: SystemTime::UNIX_EPOCH,
         }
@@ -119,6 +123,20 @@ impl TexEngine {
         self
     }
 
+    /// Configure whether the engine loads a format file in "initex" mode.
+    ///
+    /// Normally, initex mode starts from a blank slate and the format file
+    /// name passed to [`Self::process`] is ignored. If this setting is true,
+    /// that format is loaded first, so that the new format file builds on top
+    /// of it, like `-ini &fmt` in traditional TeX distributions. This is
+    /// useful for making formats with a document preamble preloaded. Unlike
+    /// formats made from scratch, such formats see the real build date in
+    /// `\year`, `\time`, and friends. The default is false.
+    pub fn initex_load_format(&mut self, load: bool) -> &mut Self {
+        self.initex_load_format = load;
+        self
+    }
+
     /// Configure the engine to produce SyncTeX data.
     ///
     /// The default is false.
@@ -142,6 +160,21 @@ impl TexEngine {
         self
     }
 
+    /// Configure whether the engine records source positions in its output.
+    ///
+    /// This setting only has an effect in [semantic
+    /// pagination](Self::semantic_pagination) mode. If enabled, the engine
+    /// inserts `\special{tdux:srcpos LINE FILENAME}` items at the start of
+    /// each paragraph and each math canvas, so that the SPX-to-HTML engine can
+    /// associate its output with the source. This is analogous to SyncTeX for
+    /// PDF output.
+    ///
+    /// The default is false.
+    pub fn source_positions(&mut self, enabled: bool) -> &mut Self {
+        self.source_positions_enabled = enabled;
+        self
+    }
+
     /// Configure whether the "shell escape" TeX feature is enabled.
     ///
     /// The default is false.
@@ -170,7 +203,8 @@ impl TexEngine {
     /// preloaded engine state. It must be findable in the I/O stack, using the
     /// special hooks that are provided for handing format files, which allow
     /// updates to the file format to be handed (see [`FORMAT_SERIAL`]). If in
-    /// "initex" mode, this parameter will be ignored.
+    /// "initex" mode, this parameter will be ignored unless
+    /// [`Self::initex_load_format`] is set.
     ///
     /// The *input_file_name* is used to name the "primary input file". The I/O
     /// system has special hooks for opening this primary input, so be aware
@@ -205,6 +239,10 @@ impl TexEngine {
                     b"in_initex_mode\0".as_ptr() as _,
                     self.initex_mode.into(),
                 );
+                tt_xetex_set_int_variable(
+                    b"initex_load_format\0".as_ptr() as _,
+                    self.initex_load_format.into(),
+                );
                 tt_xetex_set_int_variable(
                     b"synctex_enabled\0".as_ptr() as _,
                     self.synctex_enabled.into(),
@@ -213,6 +251,10 @@ impl TexEngine {
                     b"semantic_pagination_enabled\0".as_ptr() as _,
                     self.semantic_pagination_enabled.into(),
                 );
+                tt_xetex_set_int_variable(
+                    b"source_positions_enabled\0".as_ptr() as _,
+                    self.source_positions_enabled.into(),
+                );
 
                 tt_engine_xetex_main(
                     state,
@@ -28,10 +28,14 @@ tt_xetex_set_int_variable (const char *var_name, int value)
         halt_on_error_p = value;
     else if (streq_ptr(var_name, "in_initex_mode"))
         in_initex_mode = (value != 0);
+    else if (streq_ptr(var_name, "initex_load_format"))
+        initex_load_format = (value != 0);
     else if (streq_ptr(var_name, "synctex_enabled"))
         synctex_enabled = (value != 0);
     else if (streq_ptr(var_name, "semantic_pagination_enabled"))
         semantic_pagination_enabled = (value != 0);
+    else if (streq_ptr(var_name, "source_positions_enabled"))
+        source_positions_enabled = (value != 0);
     else if (streq_ptr(var_name, "shell_escape_enabled"))
         shell_escape_enabled = (value != 0);
     else
@@ -29,6 +29,7 @@ int32_t first;
 int32_t last;
 int32_t max_buf_stack;
 bool in_initex_mode;
+bool initex_load_format;
 int32_t error_line;
 int32_t half_error_line;
 int32_t max_print_line;
@@ -348,6 +349,7 @@ scaled_t delta;
 int synctex_enabled;
 bool used_tectonic_coda_tokens;
 bool semantic_pagination_enabled;
+bool source_positions_enabled;
 bool gave_char_warning_help;
 
 /* These ought to live in xetex-pagebuilder.c but are shared a lot: */
@@ -3755,7 +3757,9 @@ tt_run_engine(const char *dump_name, const char *input_file_name, time_t build_d
 
     no_new_control_sequence = true;
 
-    if (!in_initex_mode) {
+    /* In initex mode we normally start from a blank slate, but we can also
+     * build on top of an existing format, like web2c's `-ini &fmt`. */
+    if (!in_initex_mode || initex_load_format) {
         if (!load_fmt_file())
             return history;
     }
@@ -3765,9 +3769,11 @@ tt_run_engine(const char *dump_name, const char *input_file_name, time_t build_d
     else
         buffer[cur_input.limit] = INTPAR(end_line_char);
 
-    if (in_initex_mode) {
+    if (in_initex_mode && !initex_load_format) {
         /* TeX initializes with the real date and time, but for format file
-         * reproducibility we do this: */
+         * reproducibility we do this. Formats built on top of other formats,
+         * like precompiled document preambles, get the real date, since the
+         * code that goes into them may well use it. */
         INTPAR(time) = 0;
         INTPAR(day) = 0;
         INTPAR(month) = 0;
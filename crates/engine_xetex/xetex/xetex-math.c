@@ -324,9 +324,12 @@ void init_math(void)
         eq_word_define(DIMEN_BASE + DIMEN_PAR__display_width, l);
         eq_word_define(DIMEN_BASE + DIMEN_PAR__display_indent, s);
 
-        /* Tectonic customization: flag canvas start */
-        if (semantic_pagination_enabled)
+        /* Tectonic customization: flag canvas start. The source position is
+         * inserted second so that it's read first. */
+        if (semantic_pagination_enabled) {
             tt_insert_special("tdux:cs dmath");
+            tt_insert_source_position();
+        }
 
         if (LOCAL(every_display) != TEX_NULL)
             begin_token_list(LOCAL(every_display), EVERY_DISPLAY_TEXT);
@@ -341,9 +344,11 @@ void init_math(void)
             if ((insert_src_special_every_math))
                 insert_src_special();
 
-            /* Tectonic customization: flag canvas start */
-            if (semantic_pagination_enabled)
+            /* Tectonic customization: flag canvas start, as above */
+            if (semantic_pagination_enabled) {
                 tt_insert_special("tdux:cs math");
+                tt_insert_source_position();
+            }
 
             if (LOCAL(every_math) != TEX_NULL)
                 begin_token_list(LOCAL(every_math), EVERY_MATH_TEXT);
@@ -14789,6 +14789,11 @@ new_graf(bool indented)
             insert_src_special();
     }
 
+    /* Tectonic: note where the paragraph comes from. Like the web2c source
+     * specials, this is inserted before \everypar, so that it's read after it
+     * and doesn't get in the way of things like `\setbox0\lastbox`. */
+    tt_insert_source_position();
+
     if (LOCAL(every_par) != TEX_NULL)
         begin_token_list(LOCAL(every_par), EVERY_PAR_TEXT);
 
@@ -16661,19 +16666,12 @@ void append_src_special(void)
 }
 
 
-/* Tectonic: insert a \special into the current token stream */
-void
-tt_insert_special(const char *ascii_text)
+/* Tectonic: insert a \special into the current token stream, with text given
+ * by the contents of the string pool from `start_pool_ptr` to `pool_ptr`. */
+static void
+insert_special_from_pool(pool_pointer start_pool_ptr)
 {
     int32_t toklist_start, p, q;
-    pool_pointer start_pool_ptr = pool_ptr;
-
-    /* Copy the text into the string pool so that we can use str_toks() */
-    if (pool_ptr + strlen(ascii_text) >= (size_t) pool_size)
-        _tt_abort("string pool overflow");
-
-    while (*ascii_text)
-        str_pool[pool_ptr++] = *ascii_text++;
 
     /* Create the linked list of inserted tokens */
     p = toklist_start = get_avail();
@@ -16695,6 +16693,66 @@ tt_insert_special(const char *ascii_text)
     begin_token_list(toklist_start, INSERTED);
 }
 
+
+/* Tectonic: insert a \special into the current token stream */
+void
+tt_insert_special(const char *ascii_text)
+{
+    pool_pointer start_pool_ptr = pool_ptr;
+
+    /* Copy the text into the string pool so that we can use str_toks() */
+    if (pool_ptr + strlen(ascii_text) >= (size_t) pool_size)
+        _tt_abort("string pool overflow");
+
+    while (*ascii_text)
+        str_pool[pool_ptr++] = *ascii_text++;
+
+    insert_special_from_pool(start_pool_ptr);
+}
+
+
+/* Tectonic: in semantic pagination mode, insert a \special recording the
+ * current source file and line, if enabled. This is analogous to the "src:"
+ * specials of web2c TeX, but is addressed to the spx2html engine. Like the
+ * diagnostics machinery, we report the innermost input level that is an
+ * actual file. */
+void
+tt_insert_source_position(void)
+{
+    int32_t level = in_open;
+    int32_t source_line = line;
+    str_number name;
+    pool_pointer start_pool_ptr = pool_ptr, k;
+    char buf[40];
+    char *s;
+
+    if (!(source_positions_enabled && semantic_pagination_enabled))
+        return;
+
+    while (level > 0 && full_source_filename_stack[level] == 0)
+        level--;
+
+    if (level == 0)
+        return;
+
+    if (level != in_open)
+        source_line = line_stack[level + 1];
+
+    name = full_source_filename_stack[level];
+    sprintf(buf, "tdux:srcpos %d ", source_line);
+
+    if (pool_ptr + strlen(buf) + length(name) >= (size_t) pool_size)
+        _tt_abort("string pool overflow");
+
+    for (s = buf; *s; s++)
+        str_pool[pool_ptr++] = *s;
+
+    for (k = str_start[name - 65536L]; k < str_start[name + 1 - 65536L]; k++)
+        str_pool[pool_ptr++] = str_pool[k];
+
+    insert_special_from_pool(start_pool_ptr);
+}
+
 void
 handle_right_brace(void)
 {
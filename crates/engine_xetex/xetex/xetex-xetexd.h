@@ -394,6 +394,7 @@ extern int32_t first;
 extern int32_t last;
 extern int32_t max_buf_stack;
 extern bool in_initex_mode;
+extern bool initex_load_format;
 extern int32_t error_line;
 extern int32_t half_error_line;
 extern int32_t max_print_line;
@@ -722,6 +723,7 @@ extern scaled_t delta;
 extern int synctex_enabled;
 extern bool used_tectonic_coda_tokens;
 extern bool semantic_pagination_enabled;
+extern bool source_positions_enabled;
 extern bool gave_char_warning_help;
 
 /*:1683*/
@@ -1175,6 +1177,7 @@ void tt_cleanup(void);
 tt_history_t tt_run_engine(const char *dump_name, const char *input_file_name, time_t build_date);
 
 void tt_insert_special(const char *ascii_text);
+void tt_insert_source_position(void);
 
 
 /* formerly xetex.h: */
@@ -0,0 +1,184 @@
+// Copyright 2024 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Settings for the HTTP backends.
+//!
+//! Some networks can only be reached through an explicit proxy, use an internal
+//! certificate authority, or require extra authentication headers. The
+//! [`HttpConfig`] type gathers up all of these settings so that they can be
+//! handed to a [`crate::GetUrlBackend`] with
+//! [`crate::GetUrlBackend::configure`].
+
+use std::{env, path::PathBuf, time::Duration};
+use tectonic_errors::{anyhow::Context, prelude::*};
+
+use crate::RetryPolicy;
+
+/// Configuration settings honored by the HTTP backends.
+///
+/// The default configuration uses the backend's built-in defaults for
+/// everything: no explicit proxy (although the backends generally respect the
+/// standard `http_proxy`-style environment variables), the system certificate
+/// store, no extra headers, and the default [`RetryPolicy`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HttpConfig {
+    /// The URL of a proxy through which all requests should be routed.
+    pub proxy: Option<String>,
+
+    /// Paths of PEM files containing additional trusted CA certificates.
+    ///
+    /// With the `reqwest` backend, these certificates are added to the
+    /// system's trust roots. With the `curl` backend, they *replace* the
+    /// default CA bundle, so you may need to include the public roots as well.
+    pub ca_certificates: Vec<PathBuf>,
+
+    /// Extra headers to send with every request, as `(name, value)` pairs.
+    pub headers: Vec<(String, String)>,
+
+    /// The maximum time to wait while establishing a connection.
+    pub connect_timeout: Option<Duration>,
+
+    /// The maximum time that an individual request may take.
+    pub timeout: Option<Duration>,
+
+    /// How to retry failed requests.
+    pub retry: RetryPolicy,
+}
+
+impl HttpConfig {
+    /// Create a configuration based on the backend defaults and the
+    /// `TECTONIC_HTTP_*` environment variables.
+    ///
+    /// See [`Self::apply_env`] for the variables that are consulted.
+    pub fn from_env() -> Result<Self> {
+        let mut config = HttpConfig::default();
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    /// Override settings with values taken from the environment.
+    ///
+    /// The following environment variables are consulted:
+    ///
+    /// - `TECTONIC_HTTP_PROXY`: the proxy URL.
+    /// - `TECTONIC_HTTP_CA_CERTIFICATES`: a list of additional CA certificate
+    ///   files, separated in the same way as `PATH`. These are added to any
+    ///   files that are already configured.
+    /// - `TECTONIC_HTTP_HEADERS`: extra headers in `Name: value` format, one
+    ///   per line. These are added to any headers that are already configured.
+    /// - `TECTONIC_HTTP_CONNECT_TIMEOUT`: the connection timeout, in seconds.
+    /// - `TECTONIC_HTTP_TIMEOUT`: the per-request timeout, in seconds.
+    /// - `TECTONIC_HTTP_MAX_ATTEMPTS`: the maximum number of attempts to make
+    ///   for each request.
+    pub fn apply_env(&mut self) -> Result<()> {
+        if let Some(proxy) = env_string("TECTONIC_HTTP_PROXY")? {
+            self.proxy = Some(proxy);
+        }
+
+        if let Some(paths) = env::var_os("TECTONIC_HTTP_CA_CERTIFICATES") {
+            self.ca_certificates
+                .extend(env::split_paths(&paths).filter(|p| !p.as_os_str().is_empty()));
+        }
+
+        if let Some(headers) = env_string("TECTONIC_HTTP_HEADERS")? {
+            for line in headers.lines() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                self.headers.push(atry!(
+                    parse_header_line(line);
+                    ["malformed header in environment variable `TECTONIC_HTTP_HEADERS`"]
+                ));
+            }
+        }
+
+        if let Some(t) = env_seconds("TECTONIC_HTTP_CONNECT_TIMEOUT")? {
+            self.connect_timeout = Some(t);
+        }
+
+        if let Some(t) = env_seconds("TECTONIC_HTTP_TIMEOUT")? {
+            self.timeout = Some(t);
+        }
+
+        if let Some(n) = env_string("TECTONIC_HTTP_MAX_ATTEMPTS")? {
+            self.retry.max_attempts = atry!(
+                n.trim().parse();
+                ["cannot parse environment variable `TECTONIC_HTTP_MAX_ATTEMPTS`"]
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Load the contents of all of the configured extra CA certificate files.
+    pub fn read_ca_certificates(&self) -> Result<Vec<u8>> {
+        let mut pem = Vec::new();
+
+        for path in &self.ca_certificates {
+            let data = atry!(
+                std::fs::read(path);
+                ["failed to read CA certificate file `{}`", path.display()]
+            );
+            pem.extend_from_slice(&data);
+            pem.push(b'\n');
+        }
+
+        Ok(pem)
+    }
+}
+
+/// Parse a header specification of the form `Name: value`.
+pub fn parse_header_line(line: &str) -> Result<(String, String)> {
+    let (name, value) = a_ok_or!(
+        line.split_once(':');
+        ["header `{}` is not of the form `Name: value`", line]
+    );
+
+    let name = name.trim();
+    ensure!(!name.is_empty(), "header `{}` has an empty name", line);
+    Ok((name.to_owned(), value.trim().to_owned()))
+}
+
+fn env_string(name: &str) -> Result<Option<String>> {
+    match env::var(name) {
+        Ok(v) if v.is_empty() => Ok(None),
+        Ok(v) => Ok(Some(v)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("cannot read environment variable `{name}`")),
+    }
+}
+
+fn env_seconds(name: &str) -> Result<Option<Duration>> {
+    Ok(match env_string(name)? {
+        Some(v) => {
+            let secs: f64 = atry!(
+                v.trim().parse();
+                ["cannot parse environment variable `{}` as a number of seconds", name]
+            );
+            ensure!(
+                secs.is_finite() && secs >= 0.,
+                "environment variable `{}` must be a nonnegative number of seconds",
+                name
+            );
+            Some(Duration::from_secs_f64(secs))
+        }
+
+        None => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_lines() {
+        assert_eq!(
+            parse_header_line("Authorization: Bearer a:b").unwrap(),
+            ("Authorization".to_owned(), "Bearer a:b".to_owned())
+        );
+        assert!(parse_header_line("no colon").is_err());
+        assert!(parse_header_line(": empty").is_err());
+    }
+}
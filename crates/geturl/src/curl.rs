@@ -3,30 +3,79 @@
 
 //! A URL-get backend based on the `curl` crate.
 
-use curl::easy::Easy;
+use curl::easy::{Easy, List};
 use std::io::Cursor;
-use tectonic_errors::{anyhow::bail, Result};
-use tectonic_status_base::StatusBackend;
+use tectonic_errors::{atry, Result};
+use tectonic_status_base::{NoopStatusBackend, StatusBackend};
 
-use crate::{GetUrlBackend, RangeReader};
+use crate::{retry::with_retries, GetUrlBackend, HttpConfig, HttpStatusError, RangeReader};
 
 const MAX_HTTP_REDIRECTS_ALLOWED: u32 = 10;
 
-fn get_url_generic(
+/// Apply the specified settings to a curl handle.
+fn apply_config(handle: &mut Easy, config: &HttpConfig) -> Result<()> {
+    if let Some(proxy) = config.proxy.as_ref() {
+        atry!(
+            handle.proxy(proxy);
+            ["invalid HTTP proxy URL `{}`", proxy]
+        );
+    }
+
+    if !config.ca_certificates.is_empty() {
+        let pem = config.read_ca_certificates()?;
+        atry!(
+            handle.ssl_cainfo_blob(&pem);
+            ["failed to load the configured CA certificates"]
+        );
+    }
+
+    if !config.headers.is_empty() {
+        let mut list = List::new();
+
+        for (name, value) in &config.headers {
+            list.append(&format!("{name}: {value}"))?;
+        }
+
+        handle.http_headers(list)?;
+    }
+
+    if let Some(t) = config.connect_timeout {
+        handle.connect_timeout(t)?;
+    }
+
+    if let Some(t) = config.timeout {
+        handle.timeout(t)?;
+    }
+
+    Ok(())
+}
+
+/// Make one attempt at fetching a URL, appending the data to `buf`.
+///
+/// If `buf` already contains data from an earlier, failed attempt, we ask the
+/// server for just the remaining bytes. On failure, `buf` is left holding
+/// whatever data were successfully received, so that the next attempt can
+/// pick up from there.
+fn fetch_into(
     handle: &mut Easy,
     url: &str,
     range: Option<(u64, usize)>,
-) -> Result<Cursor<Vec<u8>>> {
+    buf: &mut Vec<u8>,
+) -> Result<()> {
+    let resume_at = buf.len();
+
     handle.url(url)?;
     handle.follow_location(true)?;
     handle.max_redirections(MAX_HTTP_REDIRECTS_ALLOWED)?;
 
     if let Some((start, length)) = range {
         let end = start + length as u64 - 1;
+        let start = start + resume_at as u64;
         handle.range(&format!("{start}-{end}"))?;
+    } else {
+        handle.resume_from(resume_at as u64)?;
     }
 
-    let mut buf = Vec::new();
     {
         let mut transfer = handle.transfer();
         transfer.write_function(|data| {
@@ -38,14 +87,50 @@ fn get_url_generic(
 
     let code = handle.response_code()?;
 
-    if !(200..300).contains(&code) {
-        bail!(
-            "unsuccessful HTTP GET status code {} for url `{}`",
-            code,
-            url
-        );
+    let ok = if range.is_some() && resume_at > 0 {
+        // When resuming a range request, the server must honor the range.
+        code == 206
+    } else if resume_at > 0 && code == 200 {
+        // The server ignored our request to resume, and sent the whole thing.
+        buf.drain(..resume_at);
+        true
+    } else {
+        (200..300).contains(&code)
+    };
+
+    if !ok {
+        buf.truncate(resume_at);
+        return Err(HttpStatusError {
+            code: code as u16,
+            url: url.to_owned(),
+        }
+        .into());
     }
 
+    Ok(())
+}
+
+fn get_url_generic(
+    handle: &mut Easy,
+    url: &str,
+    range: Option<(u64, usize)>,
+    config: &HttpConfig,
+    status: &mut dyn StatusBackend,
+) -> Result<Cursor<Vec<u8>>> {
+    let what = match range {
+        Some((start, length)) => format!(
+            "download bytes {}-{} of `{}`",
+            start,
+            start + length as u64 - 1,
+            url
+        ),
+        None => format!("download `{url}`"),
+    };
+
+    let mut buf = Vec::new();
+    with_retries(&config.retry, &what, status, || {
+        fetch_into(handle, url, range, &mut buf)
+    })?;
     Ok(Cursor::new(buf))
 }
 
@@ -53,12 +138,14 @@ fn get_url_generic(
 #[derive(Debug)]
 pub struct CurlBackend {
     handle: Easy,
+    config: HttpConfig,
 }
 
 impl Default for CurlBackend {
     fn default() -> Self {
         CurlBackend {
             handle: Easy::new(),
+            config: HttpConfig::default(),
         }
     }
 }
@@ -67,8 +154,8 @@ impl GetUrlBackend for CurlBackend {
     type Response = Cursor<Vec<u8>>;
     type RangeReader = CurlRangeReader;
 
-    fn get_url(&mut self, url: &str, _status: &mut dyn StatusBackend) -> Result<Self::Response> {
-        get_url_generic(&mut self.handle, url, None)
+    fn get_url(&mut self, url: &str, status: &mut dyn StatusBackend) -> Result<Self::Response> {
+        get_url_generic(&mut self.handle, url, None, &self.config, status)
     }
 
     fn resolve_url(&mut self, url: &str, _status: &mut dyn StatusBackend) -> Result<String> {
@@ -76,7 +163,15 @@ impl GetUrlBackend for CurlBackend {
     }
 
     fn open_range_reader(&self, url: &str) -> Self::RangeReader {
-        CurlRangeReader::new(url)
+        CurlRangeReader::new(url, &self.config)
+    }
+
+    fn configure(&mut self, config: &HttpConfig) -> Result<()> {
+        let mut handle = Easy::new();
+        apply_config(&mut handle, config)?;
+        self.handle = handle;
+        self.config = config.clone();
+        Ok(())
     }
 }
 
@@ -85,13 +180,17 @@ impl GetUrlBackend for CurlBackend {
 pub struct CurlRangeReader {
     url: String,
     handle: Easy,
+    config: HttpConfig,
+    configured: bool,
 }
 
 impl CurlRangeReader {
-    fn new(url: &str) -> CurlRangeReader {
+    fn new(url: &str, config: &HttpConfig) -> CurlRangeReader {
         CurlRangeReader {
             url: url.to_owned(),
             handle: Easy::new(),
+            config: config.clone(),
+            configured: false,
         }
     }
 }
@@ -100,6 +199,19 @@ impl RangeReader for CurlRangeReader {
     type Response = Cursor<Vec<u8>>;
 
     fn read_range(&mut self, offset: u64, length: usize) -> Result<Self::Response> {
-        get_url_generic(&mut self.handle, &self.url, Some((offset, length)))
+        // `open_range_reader()` can't fail, so we defer applying our settings
+        // until the first read.
+        if !self.configured {
+            apply_config(&mut self.handle, &self.config)?;
+            self.configured = true;
+        }
+
+        get_url_generic(
+            &mut self.handle,
+            &self.url,
+            Some((offset, length)),
+            &self.config,
+            &mut NoopStatusBackend {},
+        )
     }
 }
@@ -21,10 +21,16 @@
 //! crates relying on this one need not use a lot of dyns and impl Traits. It is
 //! intended that the choice of HTTP backend is a build-time one, not a runtime
 //! one.
+//!
+//! All of the real backends retry failed requests with exponential backoff, as
+//! configured by a [`RetryPolicy`]. Downloads that are interrupted partway
+//! through are resumed with HTTP range requests rather than restarted from
+//! scratch, when the server allows it. Proxies, extra CA certificates, custom
+//! headers, and timeouts can be set up with an [`HttpConfig`].
 
 use cfg_if::cfg_if;
 use std::io::Read;
-use tectonic_errors::Result;
+use tectonic_errors::{prelude::ensure, Result};
 use tectonic_status_base::StatusBackend;
 
 /// A trait for reading byte ranges from an HTTP resource.
@@ -55,9 +61,31 @@ pub trait GetUrlBackend: Default {
 
     /// Open a range reader that can perform byte-range reads on the specified URL.
     fn open_range_reader(&self, url: &str) -> Self::RangeReader;
+
+    /// Apply the specified HTTP settings to this backend.
+    ///
+    /// Range readers inherit the settings that are in effect when they are
+    /// opened. This may fail if the settings are invalid: for instance, if a CA
+    /// certificate file can't be read.
+    ///
+    /// The default implementation is provided so that backends written before
+    /// this method existed keep compiling. It accepts the default settings,
+    /// which are what such backends already do, and rejects anything else.
+    fn configure(&mut self, config: &HttpConfig) -> Result<()> {
+        ensure!(
+            *config == HttpConfig::default(),
+            "this HTTP backend does not support custom HTTP settings"
+        );
+        Ok(())
+    }
 }
 
+pub mod config;
 pub mod null;
+pub mod retry;
+
+pub use config::HttpConfig;
+pub use retry::{HttpStatusError, RetryPolicy};
 
 #[cfg(feature = "curl")]
 pub mod curl;
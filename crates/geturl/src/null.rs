@@ -12,7 +12,7 @@ use std::{
 use tectonic_errors::Result;
 use tectonic_status_base::StatusBackend;
 
-use crate::{GetUrlBackend, RangeReader};
+use crate::{GetUrlBackend, HttpConfig, RangeReader};
 
 /// The error type for the always-failing geturl backend.
 #[derive(Debug)]
@@ -45,6 +45,10 @@ impl GetUrlBackend for NullBackend {
     fn open_range_reader(&self, _url: &str) -> Self::RangeReader {
         NullRangeReader {}
     }
+
+    fn configure(&mut self, _config: &HttpConfig) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// The "null" URL-get range reader, which always fails.
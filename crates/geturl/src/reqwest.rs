@@ -4,36 +4,151 @@
 //! A URL-get backend based on the `reqwest` crate.
 
 use reqwest::{
-    blocking::{Client, Response},
-    header::{HeaderMap, RANGE},
+    blocking::{Client, ClientBuilder, Response},
+    header::{HeaderMap, HeaderName, HeaderValue, ACCEPT_RANGES, RANGE},
     redirect::Policy,
-    StatusCode, Url,
+    Certificate, Proxy, StatusCode, Url,
 };
-use tectonic_errors::{anyhow::bail, Result};
-use tectonic_status_base::{tt_note, StatusBackend};
+use std::{
+    io::{self, Read},
+    thread,
+};
+use tectonic_errors::{atry, Error, Result};
+use tectonic_status_base::{tt_note, NoopStatusBackend, StatusBackend};
 
-use crate::{GetUrlBackend, RangeReader};
+use crate::{
+    retry::{is_transient, with_retries},
+    GetUrlBackend, HttpConfig, HttpStatusError, RangeReader, RetryPolicy,
+};
 
 const MAX_HTTP_REDIRECTS_ALLOWED: usize = 10;
 
+/// Create a client builder that honors the specified settings.
+fn client_builder(config: &HttpConfig) -> Result<ClientBuilder> {
+    let mut builder = Client::builder();
+
+    if let Some(proxy) = config.proxy.as_ref() {
+        builder = builder.proxy(atry!(
+            Proxy::all(proxy);
+            ["invalid HTTP proxy URL `{}`", proxy]
+        ));
+    }
+
+    if !config.ca_certificates.is_empty() {
+        let pem = config.read_ca_certificates()?;
+
+        for cert in atry!(
+            Certificate::from_pem_bundle(&pem);
+            ["failed to parse the configured CA certificates"]
+        ) {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    if !config.headers.is_empty() {
+        let mut headers = HeaderMap::new();
+
+        for (name, value) in &config.headers {
+            let name = atry!(
+                HeaderName::from_bytes(name.as_bytes());
+                ["invalid HTTP header name `{}`", name]
+            );
+            let value = atry!(
+                HeaderValue::from_str(value);
+                ["invalid value for HTTP header `{}`", name]
+            );
+            headers.append(name, value);
+        }
+
+        builder = builder.default_headers(headers);
+    }
+
+    if let Some(t) = config.connect_timeout {
+        builder = builder.connect_timeout(t);
+    }
+
+    if let Some(t) = config.timeout {
+        builder = builder.timeout(t);
+    }
+
+    Ok(builder)
+}
+
+/// Issue a GET request, optionally for the inclusive byte range `range`.
+///
+/// If a range is requested, anything other than a 206 Partial Content response
+/// is an error.
+fn send_get(client: &Client, url: &str, range: Option<(u64, u64)>) -> Result<Response> {
+    let mut req = client.get(url);
+
+    if let Some((start, end_inclusive)) = range {
+        req = req.header(RANGE, format!("bytes={start}-{end_inclusive}"));
+    }
+
+    let res = req.send()?;
+
+    let ok = match range {
+        Some(_) => res.status() == StatusCode::PARTIAL_CONTENT,
+        None => res.status().is_success(),
+    };
+
+    if !ok {
+        return Err(HttpStatusError {
+            code: res.status().as_u16(),
+            url: url.to_owned(),
+        }
+        .into());
+    }
+
+    Ok(res)
+}
+
 /// URL-get backend implemented using the `reqwest` crate.
-#[derive(Debug, Default)]
-pub struct ReqwestBackend {}
+#[derive(Debug)]
+pub struct ReqwestBackend {
+    config: HttpConfig,
+    client: Client,
+}
+
+impl Default for ReqwestBackend {
+    fn default() -> Self {
+        ReqwestBackend {
+            config: HttpConfig::default(),
+            client: Client::new(),
+        }
+    }
+}
 
 impl GetUrlBackend for ReqwestBackend {
-    type Response = Response;
+    type Response = ReqwestResponse;
     type RangeReader = ReqwestRangeReader;
 
-    fn get_url(&mut self, url: &str, _status: &mut dyn StatusBackend) -> Result<Response> {
-        let res = Client::new().get(url).send()?;
-        if !res.status().is_success() {
-            bail!(
-                "unexpected HTTP response code {} for URL {}",
-                res.status(),
-                url
-            );
-        }
-        Ok(res)
+    fn get_url(&mut self, url: &str, status: &mut dyn StatusBackend) -> Result<ReqwestResponse> {
+        let client = self.client.clone();
+        let what = format!("download `{url}`");
+        let res = with_retries(&self.config.retry, &what, status, || {
+            send_get(&client, url, None)
+        })?;
+
+        // We can only pick up an interrupted download where it left off if we
+        // know how big it is supposed to be, and the server says that it will
+        // honor range requests.
+        let accepts_ranges = res
+            .headers()
+            .get(ACCEPT_RANGES)
+            .map(|v| v.as_bytes() == b"bytes")
+            .unwrap_or(false);
+        let end = res.content_length().filter(|_| accepts_ranges);
+
+        Ok(ReqwestResponse {
+            client,
+            url: url.to_owned(),
+            inner: res,
+            offset: 0,
+            end,
+            retry: self.config.retry,
+            failures: 0,
+        })
     }
 
     fn resolve_url(&mut self, url: &str, status: &mut dyn StatusBackend) -> Result<String> {
@@ -78,22 +193,27 @@ impl GetUrlBackend for ReqwestBackend {
             }
         });
 
-        let res = Client::builder()
+        let client = client_builder(&self.config)?
             .redirect(redirect_policy)
-            .build()?
-            .head(url)
-            .send()?;
-
-        if !(res.status().is_success()
-            || res.status() == StatusCode::FOUND
-            || res.status() == StatusCode::TEMPORARY_REDIRECT)
-        {
-            bail!(
-                "unexpected HTTP response code {} for URL {}",
-                res.status(),
-                url
-            );
-        }
+            .build()?;
+        let what = format!("connect to `{url}`");
+
+        let res = with_retries(&self.config.retry, &what, status, || {
+            let res = client.head(url).send()?;
+
+            if !(res.status().is_success()
+                || res.status() == StatusCode::FOUND
+                || res.status() == StatusCode::TEMPORARY_REDIRECT)
+            {
+                return Err(HttpStatusError {
+                    code: res.status().as_u16(),
+                    url: url.to_owned(),
+                }
+                .into());
+            }
+
+            Ok(res)
+        })?;
 
         let final_url: String = res.url().clone().into();
         if final_url != url {
@@ -104,7 +224,99 @@ impl GetUrlBackend for ReqwestBackend {
     }
 
     fn open_range_reader(&self, url: &str) -> Self::RangeReader {
-        ReqwestRangeReader::new(url)
+        ReqwestRangeReader {
+            url: url.to_owned(),
+            client: self.client.clone(),
+            retry: self.config.retry,
+        }
+    }
+
+    fn configure(&mut self, config: &HttpConfig) -> Result<()> {
+        self.client = client_builder(config)?.build()?;
+        self.config = config.clone();
+        Ok(())
+    }
+}
+
+/// The response body of a request made by the [`ReqwestBackend`].
+///
+/// If the connection drops while the body is being read, this type will
+/// transparently issue a new request for the remaining bytes and carry on, as
+/// long as the server supports range requests and the retry policy allows it.
+#[derive(Debug)]
+pub struct ReqwestResponse {
+    client: Client,
+    url: String,
+    inner: Response,
+
+    /// The absolute offset of the next byte that will be read.
+    offset: u64,
+
+    /// The absolute offset just past the last byte that we expect to read, if
+    /// the download is resumable.
+    end: Option<u64>,
+
+    retry: RetryPolicy,
+    failures: u32,
+}
+
+impl ReqwestResponse {
+    /// Try to set up a new inner response that starts where the failed one
+    /// left off.
+    fn resume(&mut self, mut err: Error) -> io::Result<()> {
+        let end = match self.end {
+            Some(e) => e,
+            None => return Err(io::Error::other(err)),
+        };
+
+        loop {
+            self.failures += 1;
+
+            if self.failures >= self.retry.attempts() || !is_transient(&err) {
+                let err = err.context(format!(
+                    "download of `{}` was interrupted at byte offset {}",
+                    self.url, self.offset
+                ));
+                return Err(io::Error::other(err));
+            }
+
+            thread::sleep(self.retry.backoff_after(self.failures));
+
+            match send_get(&self.client, &self.url, Some((self.offset, end - 1))) {
+                Ok(res) => {
+                    self.inner = res;
+                    return Ok(());
+                }
+
+                Err(e) => err = e,
+            }
+        }
+    }
+}
+
+impl Read for ReqwestResponse {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let err = match self.inner.read(buf) {
+                Ok(0) if !buf.is_empty() && self.end.is_some_and(|e| self.offset < e) => {
+                    io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "connection closed before the download completed",
+                    )
+                }
+
+                Ok(n) => {
+                    self.offset += n as u64;
+                    return Ok(n);
+                }
+
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+
+                Err(e) => e,
+            };
+
+            self.resume(err.into())?;
+        }
     }
 }
 
@@ -115,37 +327,127 @@ impl GetUrlBackend for ReqwestBackend {
 pub struct ReqwestRangeReader {
     url: String,
     client: Client,
+    retry: RetryPolicy,
 }
 
-impl ReqwestRangeReader {
-    fn new(url: &str) -> ReqwestRangeReader {
-        ReqwestRangeReader {
-            url: url.to_owned(),
-            client: Client::new(),
-        }
+impl RangeReader for ReqwestRangeReader {
+    type Response = ReqwestResponse;
+
+    fn read_range(&mut self, offset: u64, length: usize) -> Result<ReqwestResponse> {
+        let end = offset + length as u64;
+        let what = format!("download bytes {}-{} of `{}`", offset, end - 1, self.url);
+
+        let res = with_retries(&self.retry, &what, &mut NoopStatusBackend {}, || {
+            send_get(&self.client, &self.url, Some((offset, end - 1)))
+        })?;
+
+        Ok(ReqwestResponse {
+            client: self.client.clone(),
+            url: self.url.clone(),
+            inner: res,
+            offset,
+            end: Some(end),
+            retry: self.retry,
+            failures: 0,
+        })
     }
 }
 
-impl RangeReader for ReqwestRangeReader {
-    type Response = Response;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream},
+        time::Duration,
+    };
 
-    fn read_range(&mut self, offset: u64, length: usize) -> Result<Response> {
-        let end_inclusive = offset + length as u64 - 1;
-        let header_val = format!("bytes={offset}-{end_inclusive}").parse()?;
+    /// Consume the head of an HTTP request, returning its `Range` header, if
+    /// any.
+    fn read_request(stream: &TcpStream) -> Option<String> {
+        let mut reader = BufReader::new(stream);
+        let mut range = None;
 
-        let mut headers = HeaderMap::new();
-        headers.insert(RANGE, header_val);
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
 
-        let res = self.client.get(&self.url).headers(headers).send()?;
+            if line.is_empty() {
+                return range;
+            }
 
-        if res.status() != StatusCode::PARTIAL_CONTENT {
-            bail!(
-                "unexpected HTTP response code {} for URL {}",
-                res.status(),
-                self.url
-            );
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("range") {
+                    range = Some(value.trim().to_owned());
+                }
+            }
         }
+    }
+
+    #[test]
+    fn resume_after_interruption() {
+        const CUT: usize = 4000;
+
+        let body: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/bundle.tar", listener.local_addr().unwrap());
+
+        let served = body.clone();
+        let server = thread::spawn(move || {
+            let mut ranges = Vec::new();
+
+            // The first response promises the whole file, but the connection
+            // goes away partway through the body.
+            let (mut stream, _) = listener.accept().unwrap();
+            ranges.push(read_request(&stream));
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n\r\n",
+                served.len()
+            )
+            .unwrap();
+            stream.write_all(&served[..CUT]).unwrap();
+            drop(stream);
+
+            // The client should then ask for exactly the bytes that it's
+            // missing.
+            let (mut stream, _) = listener.accept().unwrap();
+            ranges.push(read_request(&stream));
+            write!(
+                stream,
+                "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nConnection: close\r\n\r\n",
+                served.len() - CUT,
+                CUT,
+                served.len() - 1,
+                served.len()
+            )
+            .unwrap();
+            stream.write_all(&served[CUT..]).unwrap();
+
+            ranges
+        });
+
+        let mut backend = ReqwestBackend::default();
+        backend
+            .configure(&HttpConfig {
+                retry: RetryPolicy {
+                    max_attempts: 3,
+                    initial_backoff: Duration::from_millis(1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .unwrap();
+
+        let mut res = backend.get_url(&url, &mut NoopStatusBackend {}).unwrap();
+        let mut data = Vec::new();
+        res.read_to_end(&mut data).unwrap();
 
-        Ok(res)
+        assert_eq!(data, body);
+        assert_eq!(
+            server.join().unwrap(),
+            vec![None, Some(format!("bytes={}-{}", CUT, body.len() - 1))]
+        );
     }
 }
@@ -0,0 +1,250 @@
+// Copyright 2024 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Retrying HTTP operations with exponential backoff.
+//!
+//! Network hiccups are common enough that a long build shouldn't die on the
+//! first dropped connection. All of the real backends in this crate route
+//! their requests through the machinery here, so that they all behave the same
+//! way when it comes to retries.
+
+use std::{
+    error::Error,
+    fmt::{Display, Error as FmtError, Formatter},
+    result::Result as StdResult,
+    thread,
+    time::Duration,
+};
+use tectonic_errors::{anyhow::Context, Error as AnyError, Result};
+use tectonic_status_base::{tt_warning, StatusBackend};
+
+/// How a backend should retry failed HTTP operations.
+///
+/// A request is attempted up to `max_attempts` times in total. After the
+/// *n*th failure, the backend waits `initial_backoff * multiplier^(n-1)`,
+/// capped at `max_backoff`, before trying again. Only failures that look
+/// transient are retried: an HTTP 404, for instance, fails immediately.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// The total number of attempts to make, including the first one. A value
+    /// of zero is treated like one.
+    pub max_attempts: u32,
+
+    /// The delay before the first retry.
+    pub initial_backoff: Duration,
+
+    /// The maximum delay between two attempts.
+    pub max_backoff: Duration,
+
+    /// The factor by which the delay grows after each failed attempt.
+    pub multiplier: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries anything.
+    pub fn no_retries() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// The total number of attempts this policy allows, which is always at
+    /// least one.
+    pub fn attempts(&self) -> u32 {
+        self.max_attempts.max(1)
+    }
+
+    /// Get the delay to wait after the specified failed attempt.
+    ///
+    /// Attempts are numbered starting at 1.
+    pub fn backoff_after(&self, attempt: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .max(1)
+            .checked_pow(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+
+        self.initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+/// The error returned when an HTTP server responds with an unsuccessful status
+/// code.
+#[derive(Debug)]
+pub struct HttpStatusError {
+    /// The HTTP status code.
+    pub code: u16,
+
+    /// The URL that was requested.
+    pub url: String,
+}
+
+impl HttpStatusError {
+    /// Returns true if this status suggests that the same request might
+    /// succeed if it is tried again later.
+    pub fn is_transient(&self) -> bool {
+        // 408 Request Timeout, 425 Too Early, 429 Too Many Requests, and all
+        // server-side errors.
+        matches!(self.code, 408 | 425 | 429) || self.code >= 500
+    }
+}
+
+impl Display for HttpStatusError {
+    fn fmt(&self, f: &mut Formatter) -> StdResult<(), FmtError> {
+        write!(
+            f,
+            "unexpected HTTP response code {} for URL `{}`",
+            self.code, self.url
+        )
+    }
+}
+
+impl Error for HttpStatusError {}
+
+/// Decide whether a failure is worth retrying.
+///
+/// We err on the side of retrying: anything that we don't positively know to
+/// be permanent is considered transient.
+#[cfg_attr(not(any(feature = "curl", feature = "reqwest")), allow(dead_code))]
+pub(crate) fn is_transient(err: &AnyError) -> bool {
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<HttpStatusError>() {
+            return e.is_transient();
+        }
+
+        #[cfg(feature = "reqwest")]
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            if e.is_builder() || e.is_redirect() {
+                return false;
+            }
+        }
+
+        #[cfg(feature = "curl")]
+        if let Some(e) = cause.downcast_ref::<curl::Error>() {
+            if e.is_url_malformed() || e.is_unsupported_protocol() || e.is_too_many_redirects() {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// Run an operation, retrying it according to a [`RetryPolicy`].
+///
+/// If the operation fails permanently, or every attempt fails, the error from
+/// the last attempt is returned, annotated with a description of what we were
+/// trying to do.
+#[cfg_attr(not(any(feature = "curl", feature = "reqwest")), allow(dead_code))]
+pub(crate) fn with_retries<T, F>(
+    policy: &RetryPolicy,
+    what: &str,
+    status: &mut dyn StatusBackend,
+    mut op: F,
+) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let attempts = policy.attempts();
+    let mut attempt = 1;
+
+    loop {
+        let err = match op() {
+            Ok(v) => return Ok(v),
+            Err(e) => e,
+        };
+
+        if attempt >= attempts || !is_transient(&err) {
+            return Err(err).with_context(|| {
+                if attempt == 1 {
+                    format!("failed to {what}")
+                } else {
+                    format!("failed to {what} after {attempt} attempts")
+                }
+            });
+        }
+
+        let delay = policy.backoff_after(attempt);
+        tt_warning!(
+            status,
+            "failed to {} (attempt {} of {}); retrying in {:.1}s",
+            what,
+            attempt,
+            attempts,
+            delay.as_secs_f32();
+            err
+        );
+        thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_schedule() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2,
+        };
+
+        assert_eq!(policy.backoff_after(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff_after(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff_after(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff_after(4), Duration::from_secs(5));
+        assert_eq!(policy.backoff_after(40), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retried() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut status = tectonic_status_base::NoopStatusBackend {};
+
+        let mut n = 0;
+        let r: Result<()> = with_retries(&policy, "test", &mut status, || {
+            n += 1;
+            Err(HttpStatusError {
+                code: 404,
+                url: "x".into(),
+            }
+            .into())
+        });
+        assert!(r.is_err());
+        assert_eq!(n, 1);
+
+        let mut n = 0;
+        let r: Result<()> = with_retries(&policy, "test", &mut status, || {
+            n += 1;
+            Err(HttpStatusError {
+                code: 503,
+                url: "x".into(),
+            }
+            .into())
+        });
+        assert!(r.is_err());
+        assert_eq!(n, 3);
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Extract a subset of the pages of an XDV/SPX file into a new file.
+//!
+//! The output can be processed with `xdvipdfmx` to regenerate a PDF of just
+//! those pages without having to rerun TeX.
+
+use clap::{crate_version, Arg, Command};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::process;
+use tectonic_xdv::XdvReader;
+
+/// Parse a page specification like `1-3,7,10-` into zero-based page indices.
+fn parse_pages(spec: &str, n_pages: usize) -> Result<Vec<usize>, String> {
+    let mut pages = Vec::new();
+
+    for item in spec.split(',') {
+        let item = item.trim();
+        let parse = |s: &str, default: usize| -> Result<usize, String> {
+            if s.is_empty() {
+                return Ok(default);
+            }
+
+            match s.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= n_pages => Ok(n),
+                _ => Err(format!(
+                    "invalid page number `{s}`; there are {n_pages} pages"
+                )),
+            }
+        };
+
+        let (first, last) = match item.split_once('-') {
+            Some((a, b)) => (parse(a, 1)?, parse(b, n_pages)?),
+            None => {
+                let n = parse(item, 0)?;
+                (n, n)
+            }
+        };
+
+        if first == 0 || first > last {
+            return Err(format!("invalid page range `{item}`"));
+        }
+
+        pages.extend(first - 1..last);
+    }
+
+    Ok(pages)
+}
+
+fn main() {
+    let matches = Command::new("xdvextract")
+        .version(crate_version!())
+        .about("Extract a subset of the pages of an XDV or SPX file")
+        .arg(
+            Arg::new("PAGES")
+                .help("The pages to extract, counting from 1, e.g. `1-3,7,10-`")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::new("INPUT")
+                .help("The path to the input XDV or SPX file")
+                .value_parser(clap::value_parser!(PathBuf))
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::new("OUTPUT")
+                .help("The path to the output file")
+                .value_parser(clap::value_parser!(PathBuf))
+                .required(true)
+                .index(3),
+        )
+        .get_matches();
+
+    let spec: &String = matches.get_one("PAGES").unwrap();
+    let input: &PathBuf = matches.get_one("INPUT").unwrap();
+    let output: &PathBuf = matches.get_one("OUTPUT").unwrap();
+
+    let mut reader = match File::open(input).and_then(|f| XdvReader::new(BufReader::new(f))) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("error: could not read \"{}\": {}", input.display(), e);
+            process::exit(1);
+        }
+    };
+
+    let pages = match parse_pages(spec, reader.n_pages()) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+    };
+
+    let n_written = pages.len();
+
+    if let Err(e) = File::create(output).and_then(|f| reader.write_pages(pages, BufWriter::new(f)))
+    {
+        eprintln!("error: could not write \"{}\": {}", output.display(), e);
+        process::exit(1);
+    }
+
+    println!("wrote {} of {} pages", n_written, reader.n_pages());
+}
@@ -12,16 +12,33 @@
 //! ("semantically-paginated XDV") is essentially the same as XDV, but
 //! expresses output that is not paginated for print — this is what Tectonic
 //! uses to produce its HTML output.
+//!
+//! Streams in all of these formats can be read with [`XdvParser`] and written
+//! with [`XdvWriter`]. The [`XdvReader`] provides random access to the pages of
+//! a seekable stream, and the [`PositionAdapter`] resolves the contents of a
+//! stream to absolute positions on the page.
 
 use byteorder::{BigEndian, ByteOrder};
 use std::{
     error,
     fmt::{Debug, Display, Error as FmtError, Formatter},
-    io::{Error as IoError, Read, Seek, SeekFrom},
+    io::{Error as IoError, ErrorKind, Read, Seek, SeekFrom},
     marker::PhantomData,
     mem,
 };
 
+mod positioned;
+mod reader;
+mod tfm;
+mod writer;
+
+pub use positioned::{
+    PageExtents, PageSize, PositionAdapter, PositionedChar, PositionedEvents, PositionedGlyph,
+};
+pub use reader::{PageInfo, XdvReader};
+pub use tfm::{CharMetrics, TfmMetrics};
+pub use writer::XdvWriter;
+
 /// Errors that can occur when parsing XDV/SPX files.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum XdvError {
@@ -86,6 +103,13 @@ impl From<XdvError> for String {
     }
 }
 
+/// For APIs that use `std::io` error types.
+impl From<XdvError> for IoError {
+    fn from(e: XdvError) -> Self {
+        IoError::new(ErrorKind::InvalidData, e)
+    }
+}
+
 impl XdvError {
     /// We can't implement this as a From trait on InternalError because it
     /// conflicts with the generic From: XdvError satisfies `Debug +
@@ -136,6 +160,22 @@ pub trait XdvEvents {
         Ok(())
     }
 
+    /// End the current page.
+    #[allow(unused)]
+    fn handle_end_page(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Handle the postamble, which records the height plus depth of the
+    /// tallest page and the width of the widest page.
+    ///
+    /// The postamble comes after all of the pages, but
+    /// [`XdvParser::process_with_seeks`] reads it first.
+    #[allow(unused)]
+    fn handle_postamble(&mut self, max_height: i32, max_width: i32) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     /// Handle a `\special`.
     #[allow(unused)]
     fn handle_special(&mut self, x: i32, y: i32, contents: &[u8]) -> Result<(), Self::Error> {
@@ -148,6 +188,29 @@ pub trait XdvEvents {
         Ok(())
     }
 
+    /// Handle a single character from a traditional TeX font, set at the
+    /// absolute position (*x*, *y*).
+    ///
+    /// Characters are also reported in batches by [`Self::handle_char_run`],
+    /// which is called after all of the characters in the run have been
+    /// passed to this method.
+    #[allow(unused)]
+    fn handle_char(&mut self, font_num: i32, chr: i32, x: i32, y: i32) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Get the width of a character from a traditional TeX font.
+    ///
+    /// Setting a character moves the current point right by its width, but
+    /// the stream doesn't record font metrics. Implementors that know the
+    /// metrics of the fonts being used should return the width here, so that
+    /// the positions passed to other event handlers are correct. The default
+    /// implementation returns zero.
+    #[allow(unused)]
+    fn char_width(&mut self, font_num: i32, chr: i32) -> Result<i32, Self::Error> {
+        Ok(0)
+    }
+
     /// Handle a sequence of glyphs.
     #[allow(unused)]
     fn handle_glyph_run(
@@ -174,6 +237,20 @@ pub trait XdvEvents {
         Ok(())
     }
 
+    /// Handle the definition of a traditional TeX font
+    #[allow(unused)]
+    fn handle_define_font(
+        &mut self,
+        area: &str,
+        name: &str,
+        font_num: i32,
+        checksum: u32,
+        scale_factor: u32,
+        design_size: u32,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     /// Handle the definition of a native font
     #[allow(unused)]
     #[allow(clippy::too_many_arguments)]
@@ -198,6 +275,70 @@ pub trait XdvEvents {
     }
 }
 
+/// The definition of a font in an XDV/SPX stream.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FontDefinition {
+    /// A traditional TeX font, as loaded from a TFM file.
+    Tfm {
+        /// The font number.
+        font_num: i32,
+
+        /// The checksum of the TFM file.
+        checksum: u32,
+
+        /// The size at which the font is used, in TeX scaled points.
+        scale_factor: u32,
+
+        /// The design size of the font, in TeX scaled points.
+        design_size: u32,
+
+        /// The directory of the font, usually empty.
+        area: String,
+
+        /// The name of the font.
+        name: String,
+    },
+
+    /// A XeTeX "native" font. See
+    /// [`XdvEvents::handle_define_native_font`] for the meanings of the
+    /// fields.
+    Native {
+        /// The font number.
+        font_num: i32,
+
+        /// The name or path of the font file.
+        name: String,
+
+        /// The size of the font, in TeX scaled points.
+        size: i32,
+
+        /// The index of the face within the font file.
+        face_index: u32,
+
+        /// The color of the font.
+        color_rgba: Option<u32>,
+
+        /// The horizontal extension factor.
+        extend: Option<u32>,
+
+        /// The slant factor.
+        slant: Option<u32>,
+
+        /// The emboldening factor.
+        embolden: Option<u32>,
+    },
+}
+
+impl FontDefinition {
+    /// Get the font number being defined.
+    pub fn font_num(&self) -> i32 {
+        match *self {
+            FontDefinition::Tfm { font_num, .. } => font_num,
+            FontDefinition::Native { font_num, .. } => font_num,
+        }
+    }
+}
+
 /// State for parsing an XDV file.
 #[derive(Debug)]
 pub struct XdvParser<T: XdvEvents> {
@@ -212,8 +353,15 @@ pub struct XdvParser<T: XdvEvents> {
 }
 
 /// Which type of file is being parsed.
+///
+/// More types may be supported in the future, so matches on this type need a
+/// wildcard arm.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum FileType {
+    /// Plain DVI, as produced by Knuth's TeX.
+    Dvi,
+
     /// Traditional XDV.
     Xdv,
 
@@ -227,6 +375,7 @@ impl Display for FileType {
             f,
             "{}",
             match *self {
+                FileType::Dvi => "DVI",
                 FileType::Xdv => "XDV",
                 FileType::Spx => "SPX",
             }
@@ -240,6 +389,7 @@ enum ParserMode {
     AllTheWayThrough,
     UntilDoublePostamble,
     UntilPostamble,
+    UntilEndOfPage,
 }
 
 /// The current state of the parser.
@@ -341,6 +491,7 @@ impl<T: XdvEvents> XdvParser<T> {
         let postamble_offset = cursor.get_u32().unwrap();
 
         parser.filetype = match cursor.get_u8().unwrap() {
+            b if b == IdByte::Dvi as u8 => FileType::Dvi,
             b if b == IdByte::Xdv as u8 => FileType::Xdv,
             b if b == IdByte::Spx as u8 => FileType::Spx,
             _ => {
@@ -366,6 +517,63 @@ impl<T: XdvEvents> XdvParser<T> {
         parser.finish()
     }
 
+    /// Parse one page of an XDV/SPX stream of type *filetype*.
+    ///
+    /// The stream should be positioned at the beginning of the page, which is
+    /// at byte offset *offset* in the file. On success, the stream will have
+    /// been read past the end of the page, and the byte offset just after its
+    /// end is returned along with the events.
+    pub(crate) fn process_page<R: Read>(
+        stream: &mut R,
+        filetype: FileType,
+        offset: u64,
+        events: T,
+    ) -> Result<(T, u64), T::Error>
+    where
+        T::Error: From<IoError>,
+    {
+        let mut parser = Self::new(events);
+        parser.filetype = filetype;
+        parser.offset = offset;
+        parser.mode = ParserMode::UntilEndOfPage;
+        parser.state = ParserState::BetweenPages;
+        parser.process_part(stream)?;
+
+        if parser.offset == offset || parser.state != ParserState::BetweenPages {
+            return Err(XdvError::UnexpectedEndOfStream.into());
+        }
+
+        Ok((parser.events, parser.offset))
+    }
+
+    /// Parse the postamble of an XDV/SPX stream of type *filetype*.
+    ///
+    /// The stream should be positioned at the beginning of the postamble, which
+    /// is at byte offset *offset* in the file. Parsing stops just before the
+    /// post-postamble.
+    pub(crate) fn process_postamble<R: Read>(
+        stream: &mut R,
+        filetype: FileType,
+        offset: u64,
+        events: T,
+    ) -> Result<T, T::Error>
+    where
+        T::Error: From<IoError>,
+    {
+        let mut parser = Self::new(events);
+        parser.filetype = filetype;
+        parser.offset = offset;
+        parser.mode = ParserMode::UntilDoublePostamble;
+        parser.state = ParserState::BetweenPages;
+        parser.process_part(stream)?;
+
+        if parser.state != ParserState::PostambleFontDefinitions {
+            return Err(XdvError::UnexpectedEndOfStream.into());
+        }
+
+        Ok(parser.events)
+    }
+
     /// Parse part of an XDV/SPX stream.
     ///
     /// We should probably use `nom` instead of doing this all ourselves.
@@ -590,6 +798,11 @@ impl<T: XdvEvents> XdvParser<T> {
                     .handle_char_run(self.cur_font_num, &self.cur_char_run)?;
                 self.cur_char_run.clear();
             }
+
+            if opcode == Opcode::EndOfPage as u8 && self.mode == ParserMode::UntilEndOfPage {
+                keep_going = false;
+                break;
+            }
         }
 
         self.offset += cursor.checkpoint as u64;
@@ -602,6 +815,7 @@ impl<T: XdvEvents> XdvParser<T> {
         }
 
         self.filetype = match cursor.get_u8()? {
+            b if b == IdByte::Dvi as u8 => FileType::Dvi,
             b if b == IdByte::Xdv as u8 => FileType::Xdv,
             b if b == IdByte::Spx as u8 => FileType::Spx,
             _ => {
@@ -629,26 +843,36 @@ impl<T: XdvEvents> XdvParser<T> {
             return Err(XdvError::IllegalOpcode(opcode, cursor.global_offset()).into_internal());
         }
 
-        let _font_num = cursor.get_compact_i32_smpos(opcode - Opcode::DefineFont1 as u8)?;
-        let _checksum = cursor.get_u32()?;
-        let _scale_factor = cursor.get_u32()?;
-        let _design_size = cursor.get_u32()?;
+        let font_num = cursor.get_compact_i32_smpos(opcode - Opcode::DefineFont1 as u8)?;
+        let checksum = cursor.get_u32()?;
+        let scale_factor = cursor.get_u32()?;
+        let design_size = cursor.get_u32()?;
         let area_len = cursor.get_u8()?;
         let name_len = cursor.get_u8()?;
 
-        // TODO: figure out what to do with these. In Tectonic's context,
-        // non-"native" font definitions are a bad sign, since they correspond
-        // to fonts that we wouldn't be able to express in HTML. But note that
-        // this crate should support generic XDV decoding, not necessarily
-        // targeting HTML, as best it can.
+        // Note that in Tectonic's context, non-"native" font definitions are a
+        // bad sign, since they correspond to fonts that we wouldn't be able to
+        // express in HTML. But this crate should support generic XDV decoding,
+        // not necessarily targeting HTML, as best it can.
 
         use std::str::from_utf8;
-        let _area_str = from_utf8(cursor.get_slice(area_len as usize)?)
-            .unwrap()
+        let offset = cursor.global_offset();
+        let area_str = from_utf8(cursor.get_slice(area_len as usize)?)
+            .map_err(|_| XdvError::FromUTF8(offset).into_internal())?
             .to_owned();
-        let _name_str = from_utf8(cursor.get_slice(name_len as usize)?)
-            .unwrap()
+        let offset = cursor.global_offset();
+        let name_str = from_utf8(cursor.get_slice(name_len as usize)?)
+            .map_err(|_| XdvError::FromUTF8(offset).into_internal())?
             .to_owned();
+
+        self.events.handle_define_font(
+            &area_str,
+            &name_str,
+            font_num,
+            checksum,
+            scale_factor,
+            design_size,
+        )?;
         Ok(())
     }
 
@@ -741,6 +965,14 @@ impl<T: XdvEvents> XdvParser<T> {
             return Err(XdvError::Malformed(cursor.global_offset()).into_internal());
         }
 
+        // Any run of characters must be reported before the page ends.
+        if !self.cur_char_run.is_empty() {
+            self.events
+                .handle_char_run(self.cur_font_num, &self.cur_char_run)?;
+            self.cur_char_run.clear();
+        }
+
+        self.events.handle_end_page()?;
         self.state = ParserState::BetweenPages;
         Ok(())
     }
@@ -778,7 +1010,8 @@ impl<T: XdvEvents> XdvParser<T> {
         }
 
         let n = cursor.get_compact_i32_smneg(opcode - Opcode::Right1 as u8)?;
-        self.stack.last_mut().unwrap().h += n;
+        let state = self.stack.last_mut().unwrap();
+        state.h = state.h.wrapping_add(n);
         Ok(())
     }
 
@@ -792,7 +1025,7 @@ impl<T: XdvEvents> XdvParser<T> {
         }
 
         let state = self.stack.last_mut().unwrap();
-        state.h += state.w;
+        state.h = state.h.wrapping_add(state.w);
         Ok(())
     }
 
@@ -804,7 +1037,7 @@ impl<T: XdvEvents> XdvParser<T> {
         let n = cursor.get_compact_i32_smneg(opcode - Opcode::SetW1 as u8)?;
         let state = self.stack.last_mut().unwrap();
         state.w = n;
-        state.h += n;
+        state.h = state.h.wrapping_add(n);
         Ok(())
     }
 
@@ -818,7 +1051,7 @@ impl<T: XdvEvents> XdvParser<T> {
         }
 
         let state = self.stack.last_mut().unwrap();
-        state.h += state.x;
+        state.h = state.h.wrapping_add(state.x);
         Ok(())
     }
 
@@ -830,7 +1063,7 @@ impl<T: XdvEvents> XdvParser<T> {
         let n = cursor.get_compact_i32_smneg(opcode - Opcode::SetX1 as u8)?;
         let state = self.stack.last_mut().unwrap();
         state.x = n;
-        state.h += n;
+        state.h = state.h.wrapping_add(n);
         Ok(())
     }
 
@@ -840,7 +1073,8 @@ impl<T: XdvEvents> XdvParser<T> {
         }
 
         let n = cursor.get_compact_i32_smneg(opcode - Opcode::Down1 as u8)?;
-        self.stack.last_mut().unwrap().v += n;
+        let state = self.stack.last_mut().unwrap();
+        state.v = state.v.wrapping_add(n);
         Ok(())
     }
 
@@ -850,7 +1084,7 @@ impl<T: XdvEvents> XdvParser<T> {
         }
 
         let state = self.stack.last_mut().unwrap();
-        state.v += state.y;
+        state.v = state.v.wrapping_add(state.y);
         Ok(())
     }
 
@@ -862,7 +1096,7 @@ impl<T: XdvEvents> XdvParser<T> {
         let n = cursor.get_compact_i32_smneg(opcode - Opcode::SetY1 as u8)?;
         let state = self.stack.last_mut().unwrap();
         state.y = n;
-        state.v += n;
+        state.v = state.v.wrapping_add(n);
         Ok(())
     }
 
@@ -872,7 +1106,7 @@ impl<T: XdvEvents> XdvParser<T> {
         }
 
         let state = self.stack.last_mut().unwrap();
-        state.v += state.z;
+        state.v = state.v.wrapping_add(state.z);
         Ok(())
     }
 
@@ -884,7 +1118,7 @@ impl<T: XdvEvents> XdvParser<T> {
         let n = cursor.get_compact_i32_smneg(opcode - Opcode::SetZ1 as u8)?;
         let state = self.stack.last_mut().unwrap();
         state.z = n;
-        state.v += n;
+        state.v = state.v.wrapping_add(n);
         Ok(())
     }
 
@@ -939,8 +1173,7 @@ impl<T: XdvEvents> XdvParser<T> {
         }
 
         let char_num = opcode - Opcode::SetCharNumber0 as u8;
-        self.cur_char_run.push(i32::from(char_num));
-        Ok(())
+        self.set_char(i32::from(char_num))
     }
 
     /// This variant takes an argument that encodes the character number.
@@ -950,7 +1183,17 @@ impl<T: XdvEvents> XdvParser<T> {
         }
 
         let char_num = cursor.get_compact_i32_smpos(opcode - Opcode::SetChar1 as u8)?;
-        self.cur_char_run.push(char_num);
+        self.set_char(char_num)
+    }
+
+    fn set_char(&mut self, chr: i32) -> InternalResult<(), T::Error> {
+        let state = self.stack.last_mut().unwrap();
+        self.events
+            .handle_char(self.cur_font_num, chr, state.h, state.v)?;
+        state.h = state
+            .h
+            .wrapping_add(self.events.char_width(self.cur_font_num, chr)?);
+        self.cur_char_run.push(chr);
         Ok(())
     }
 
@@ -970,8 +1213,8 @@ impl<T: XdvEvents> XdvParser<T> {
         let state = self.stack.last_mut().unwrap();
 
         for _ in 0..n_glyphs {
-            xs.push(state.h + cursor.get_i32()?);
-            ys.push(state.v + cursor.get_i32()?);
+            xs.push(state.h.wrapping_add(cursor.get_i32()?));
+            ys.push(state.v.wrapping_add(cursor.get_i32()?));
         }
 
         let mut glyphs = Vec::with_capacity(n_glyphs as usize);
@@ -981,7 +1224,7 @@ impl<T: XdvEvents> XdvParser<T> {
             glyphs.push(glyph_id);
         }
 
-        state.h += width;
+        state.h = state.h.wrapping_add(width);
         self.events
             .handle_glyph_run(self.cur_font_num, &glyphs[..], &xs[..], &ys[..])?;
         Ok(())
@@ -1015,15 +1258,15 @@ impl<T: XdvEvents> XdvParser<T> {
         let state = self.stack.last_mut().unwrap();
 
         for _ in 0..n_glyphs {
-            x.push(state.h + cursor.get_i32()?);
-            y.push(state.v + cursor.get_i32()?);
+            x.push(state.h.wrapping_add(cursor.get_i32()?));
+            y.push(state.v.wrapping_add(cursor.get_i32()?));
         }
 
         for _ in 0..n_glyphs {
             glyphs.push(cursor.get_u16()?);
         }
 
-        state.h += width;
+        state.h = state.h.wrapping_add(width);
         self.events.handle_text_and_glyphs(
             self.cur_font_num,
             &text,
@@ -1064,7 +1307,7 @@ impl<T: XdvEvents> XdvParser<T> {
         self.events.handle_rule(state.h, state.v, height, width)?;
 
         if move_point {
-            state.h += width;
+            state.h = state.h.wrapping_add(width);
         }
 
         Ok(())
@@ -1079,11 +1322,12 @@ impl<T: XdvEvents> XdvParser<T> {
         cursor.assert_u32(25_400_000)?; // dimensions unit numerator
         cursor.assert_u32(473_628_672)?; // dimensions unit denominator
         cursor.get_u32()?; // 'mag' factor
-        cursor.get_u32()?; // largest height+depth of tallest page
-        cursor.get_u32()?; // largest width of widest page
+        let max_height = cursor.get_i32()?; // largest height+depth of tallest page
+        let max_width = cursor.get_i32()?; // largest width of widest page
         cursor.get_u16()?; // maximum stack depth
         cursor.get_u16()?; // number of pages
 
+        self.events.handle_postamble(max_height, max_width)?;
         self.state = ParserState::PostambleFontDefinitions;
         Ok(())
     }
@@ -1099,6 +1343,7 @@ impl<T: XdvEvents> XdvParser<T> {
 
         cursor.get_u32()?; // pointer to postamble
         cursor.assert_u8(match self.filetype {
+            FileType::Dvi => IdByte::Dvi,
             FileType::Xdv => IdByte::Xdv,
             FileType::Spx => IdByte::Spx,
         } as u8)?;
@@ -1400,10 +1645,11 @@ enum Opcode {
     SetTextAndGlyphs = 254, // "SET_TEXT_AND_GLYPHS", "XDV_TEXT_AND_GLYPHS"
 }
 
-/// Identifier bytes used by the XDV writing code.
+/// Identifier bytes used by the DVI/XDV writing code.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(u8)]
 enum IdByte {
+    Dvi = 2,
     Xdv = 7,
     Spx = 100,
 }
@@ -0,0 +1,728 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Delivering the contents of XDV/SPX streams at absolute positions.
+//!
+//! The [`XdvEvents`] interface reports items roughly as they're encoded in the
+//! stream. The [`PositionAdapter`] here sits on top of it and resolves
+//! everything to absolute positions on the page, loading the metrics of
+//! traditional TeX fonts so that the advances of characters are accounted for.
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io::{Error as IoError, Read},
+};
+
+use super::{
+    tfm::{CharMetrics, TfmMetrics},
+    FileType, FontDefinition, XdvError, XdvEvents, XdvParser,
+};
+
+/// A character from a traditional TeX font, set at an absolute position.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PositionedChar {
+    /// The number of the font.
+    pub font_num: i32,
+
+    /// The character code.
+    pub chr: i32,
+
+    /// The horizontal position of the character's reference point.
+    pub x: i32,
+
+    /// The vertical position of the character's baseline.
+    pub y: i32,
+
+    /// The metrics of the character, if the font's TFM file could be loaded
+    /// and contains the character.
+    pub metrics: Option<CharMetrics>,
+}
+
+/// A glyph from a native font, set at an absolute position.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PositionedGlyph {
+    /// The number of the font.
+    pub font_num: i32,
+
+    /// The glyph ID.
+    pub glyph: u16,
+
+    /// The horizontal position of the glyph's origin.
+    pub x: i32,
+
+    /// The vertical position of the glyph's origin.
+    pub y: i32,
+}
+
+/// The extents of the material on a page.
+///
+/// Characters from TFM fonts are included using their metrics, and rules using
+/// their dimensions. The metrics of native fonts aren't available, so only the
+/// origins of their glyphs are included.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PageExtents {
+    /// The leftmost horizontal position.
+    pub min_x: i32,
+
+    /// The topmost vertical position.
+    pub min_y: i32,
+
+    /// The rightmost horizontal position.
+    pub max_x: i32,
+
+    /// The bottommost vertical position.
+    pub max_y: i32,
+}
+
+impl PageExtents {
+    /// Get the width of the material.
+    pub fn width(&self) -> i32 {
+        self.max_x - self.min_x
+    }
+
+    /// Get the height of the material.
+    pub fn height(&self) -> i32 {
+        self.max_y - self.min_y
+    }
+
+    fn add(extents: &mut Option<PageExtents>, x0: i32, y0: i32, x1: i32, y1: i32) {
+        let (x0, x1) = (x0.min(x1), x0.max(x1));
+        let (y0, y1) = (y0.min(y1), y0.max(y1));
+
+        *extents = Some(match *extents {
+            None => PageExtents {
+                min_x: x0,
+                min_y: y0,
+                max_x: x1,
+                max_y: y1,
+            },
+            Some(e) => PageExtents {
+                min_x: e.min_x.min(x0),
+                min_y: e.min_y.min(y0),
+                max_x: e.max_x.max(x1),
+                max_y: e.max_y.max(y1),
+            },
+        });
+    }
+}
+
+/// The dimensions of a page.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PageSize {
+    /// The width of the page.
+    pub width: i32,
+
+    /// The height of the page.
+    pub height: i32,
+}
+
+/// Types implementing this trait accept events from a [`PositionAdapter`].
+///
+/// All positions are absolute, in TeX scaled points, with vertical positions
+/// increasing downwards. They're relative to the DVI origin, not the corner of
+/// the page: by convention, the origin is one inch (72.27 TeX points) to the
+/// right of and below the top-left corner of the page, so to get positions
+/// relative to that corner, add one inch to each coordinate. Positions of
+/// material in the top or left margins are negative.
+pub trait PositionedEvents {
+    /// An error type returned by the handler functions defined in this trait. It
+    /// must implement `From<XdvError>`.
+    type Error: Debug + From<XdvError>;
+
+    /// Handle the XDV header
+    #[allow(unused)] // <= prevents underscore-prefixed names from showing up in docs
+    fn handle_header(&mut self, filetype: FileType, comment: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Load the metrics of a traditional TeX font.
+    ///
+    /// This is called when such a font is defined. The TFM file is usually
+    /// named *name* with a `.tfm` extension; the *area* is usually empty. If
+    /// `None` is returned, characters from the font are treated as having no
+    /// size, and positions after them will be off. The default implementation
+    /// returns `None`.
+    #[allow(unused)]
+    fn load_tfm(&mut self, area: &str, name: &str) -> Result<Option<TfmMetrics>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Handle the definition of a font.
+    ///
+    /// Font definitions in the postamble that repeat ones seen earlier aren't
+    /// reported again.
+    #[allow(unused)]
+    fn handle_define_font(&mut self, def: &FontDefinition) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Begin a new page.
+    #[allow(unused)]
+    fn handle_begin_page(&mut self, counters: &[i32]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// End the current page, reporting the extents of the material on it, if
+    /// there was any, and the size of the page, if it's known.
+    ///
+    /// As with dvipdfmx, the page size is set by `pdf:pagesize` specials, which
+    /// also apply to the following pages. Without one, the size is the largest
+    /// page size recorded in the postamble, if it has been read by then, as
+    /// with [`XdvParser::process_with_seeks`].
+    #[allow(unused)]
+    fn handle_end_page(
+        &mut self,
+        extents: Option<PageExtents>,
+        size: Option<PageSize>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Handle a character from a traditional TeX font.
+    #[allow(unused)]
+    fn handle_char(&mut self, chr: &PositionedChar) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Handle a glyph from a native font.
+    #[allow(unused)]
+    fn handle_glyph(&mut self, glyph: &PositionedGlyph) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Handle a rule, with its bottom-left corner at (*x*, *y*).
+    ///
+    /// Rules with nonpositive dimensions, which produce no output, aren't
+    /// reported.
+    #[allow(unused)]
+    fn handle_rule(&mut self, x: i32, y: i32, height: i32, width: i32) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Handle a `\special`.
+    #[allow(unused)]
+    fn handle_special(&mut self, x: i32, y: i32, contents: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct LoadedFont {
+    def: FontDefinition,
+    tfm: Option<TfmMetrics>,
+}
+
+impl LoadedFont {
+    fn char_metrics(&self, chr: i32) -> Option<CharMetrics> {
+        match (&self.def, &self.tfm) {
+            (
+                FontDefinition::Tfm {
+                    scale_factor: size, ..
+                },
+                Some(tfm),
+            ) => tfm.char_metrics(chr, *size),
+            _ => None,
+        }
+    }
+}
+
+/// An adapter that resolves XDV/SPX contents to absolute positions.
+///
+/// This type implements [`XdvEvents`], so it can be driven by an
+/// [`XdvParser`], and delivers events to an inner [`PositionedEvents`]
+/// implementation.
+#[derive(Debug)]
+pub struct PositionAdapter<T: PositionedEvents> {
+    events: T,
+    fonts: HashMap<i32, LoadedFont>,
+    extents: Option<PageExtents>,
+    special_size: Option<PageSize>,
+    postamble_size: Option<PageSize>,
+}
+
+impl<T: PositionedEvents> PositionAdapter<T> {
+    /// Create a new adapter delivering events to *events*.
+    pub fn new(events: T) -> Self {
+        PositionAdapter {
+            events,
+            fonts: HashMap::new(),
+            extents: None,
+            special_size: None,
+            postamble_size: None,
+        }
+    }
+
+    /// Parse an entire XDV/SPX stream, returning the input "events" variable.
+    ///
+    /// This is a convenience wrapper around [`XdvParser::process`].
+    pub fn process<R: Read>(stream: R, events: T) -> Result<T, T::Error>
+    where
+        T::Error: From<IoError>,
+    {
+        let (adapter, _) = XdvParser::process(stream, Self::new(events))?;
+        Ok(adapter.into_inner())
+    }
+
+    /// Get the definition of a font.
+    pub fn font(&self, font_num: i32) -> Option<&FontDefinition> {
+        self.fonts.get(&font_num).map(|f| &f.def)
+    }
+
+    /// Get the metrics of a traditional TeX font, if they could be loaded.
+    pub fn tfm(&self, font_num: i32) -> Option<&TfmMetrics> {
+        self.fonts.get(&font_num).and_then(|f| f.tfm.as_ref())
+    }
+
+    /// Consume the adapter and return the inner event handler.
+    pub fn into_inner(self) -> T {
+        self.events
+    }
+
+    fn define(&mut self, def: FontDefinition) -> Result<(), T::Error> {
+        let font_num = def.font_num();
+
+        if self.fonts.get(&font_num).map(|f| &f.def) == Some(&def) {
+            return Ok(());
+        }
+
+        let tfm = match &def {
+            FontDefinition::Tfm { area, name, .. } => self.events.load_tfm(area, name)?,
+            FontDefinition::Native { .. } => None,
+        };
+
+        self.events.handle_define_font(&def)?;
+        self.fonts.insert(font_num, LoadedFont { def, tfm });
+        Ok(())
+    }
+
+    fn glyphs(
+        &mut self,
+        font_num: i32,
+        glyphs: &[u16],
+        x: &[i32],
+        y: &[i32],
+    ) -> Result<(), T::Error> {
+        for ((glyph, x), y) in glyphs.iter().zip(x).zip(y) {
+            PageExtents::add(&mut self.extents, *x, *y, *x, *y);
+            self.events.handle_glyph(&PositionedGlyph {
+                font_num,
+                glyph: *glyph,
+                x: *x,
+                y: *y,
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: PositionedEvents> XdvEvents for PositionAdapter<T> {
+    type Error = T::Error;
+
+    fn handle_header(&mut self, filetype: FileType, comment: &[u8]) -> Result<(), Self::Error> {
+        self.events.handle_header(filetype, comment)
+    }
+
+    fn handle_begin_page(
+        &mut self,
+        counters: &[i32],
+        _previous_bop: i32,
+    ) -> Result<(), Self::Error> {
+        self.extents = None;
+        self.events.handle_begin_page(counters)
+    }
+
+    fn handle_end_page(&mut self) -> Result<(), Self::Error> {
+        let extents = self.extents.take();
+        let size = self.special_size.or(self.postamble_size);
+        self.events.handle_end_page(extents, size)
+    }
+
+    fn handle_postamble(&mut self, max_height: i32, max_width: i32) -> Result<(), Self::Error> {
+        if max_height > 0 && max_width > 0 {
+            self.postamble_size = Some(PageSize {
+                width: max_width,
+                height: max_height,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn handle_special(&mut self, x: i32, y: i32, contents: &[u8]) -> Result<(), Self::Error> {
+        if let Some(spec) = contents.strip_prefix(b"pdf:pagesize ") {
+            match std::str::from_utf8(spec).map(|s| s.trim()) {
+                Ok("default") => self.special_size = None,
+                Ok(spec) => {
+                    if let Some(size) = parse_pagesize(spec) {
+                        self.special_size = Some(size);
+                    }
+                }
+                Err(_) => {}
+            }
+        }
+
+        self.events.handle_special(x, y, contents)
+    }
+
+    fn handle_char(&mut self, font_num: i32, chr: i32, x: i32, y: i32) -> Result<(), Self::Error> {
+        let metrics = self.fonts.get(&font_num).and_then(|f| f.char_metrics(chr));
+
+        match metrics {
+            Some(m) => PageExtents::add(
+                &mut self.extents,
+                x,
+                y.saturating_sub(m.height),
+                x.saturating_add(m.width),
+                y.saturating_add(m.depth),
+            ),
+            None => PageExtents::add(&mut self.extents, x, y, x, y),
+        }
+
+        self.events.handle_char(&PositionedChar {
+            font_num,
+            chr,
+            x,
+            y,
+            metrics,
+        })
+    }
+
+    fn char_width(&mut self, font_num: i32, chr: i32) -> Result<i32, Self::Error> {
+        Ok(self
+            .fonts
+            .get(&font_num)
+            .and_then(|f| f.char_metrics(chr))
+            .map(|m| m.width)
+            .unwrap_or(0))
+    }
+
+    fn handle_glyph_run(
+        &mut self,
+        font_num: i32,
+        glyphs: &[u16],
+        x: &[i32],
+        y: &[i32],
+    ) -> Result<(), Self::Error> {
+        self.glyphs(font_num, glyphs, x, y)
+    }
+
+    fn handle_text_and_glyphs(
+        &mut self,
+        font_num: i32,
+        _text: &str,
+        _width: i32,
+        glyphs: &[u16],
+        x: &[i32],
+        y: &[i32],
+    ) -> Result<(), Self::Error> {
+        self.glyphs(font_num, glyphs, x, y)
+    }
+
+    fn handle_define_font(
+        &mut self,
+        area: &str,
+        name: &str,
+        font_num: i32,
+        checksum: u32,
+        scale_factor: u32,
+        design_size: u32,
+    ) -> Result<(), Self::Error> {
+        self.define(FontDefinition::Tfm {
+            font_num,
+            checksum,
+            scale_factor,
+            design_size,
+            area: area.to_owned(),
+            name: name.to_owned(),
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn handle_define_native_font(
+        &mut self,
+        name: &str,
+        font_num: i32,
+        size: i32,
+        face_index: u32,
+        color_rgba: Option<u32>,
+        extend: Option<u32>,
+        slant: Option<u32>,
+        embolden: Option<u32>,
+    ) -> Result<(), Self::Error> {
+        self.define(FontDefinition::Native {
+            font_num,
+            name: name.to_owned(),
+            size,
+            face_index,
+            color_rgba,
+            extend,
+            slant,
+            embolden,
+        })
+    }
+
+    fn handle_rule(&mut self, x: i32, y: i32, height: i32, width: i32) -> Result<(), Self::Error> {
+        if height <= 0 || width <= 0 {
+            return Ok(());
+        }
+
+        PageExtents::add(
+            &mut self.extents,
+            x,
+            y.saturating_sub(height),
+            x.saturating_add(width),
+            y,
+        );
+        self.events.handle_rule(x, y, height, width)
+    }
+}
+
+/// Parse the argument of a `pdf:pagesize` special, like `width 597.5pt
+/// height 845bp`.
+fn parse_pagesize(spec: &str) -> Option<PageSize> {
+    let mut width = None;
+    let mut height = None;
+    let mut words = spec.split_whitespace();
+
+    while let Some(key) = words.next() {
+        let value = words.next().and_then(parse_dimen);
+
+        match key {
+            "width" => width = value,
+            "height" => height = value,
+            _ => {}
+        }
+    }
+
+    Some(PageSize {
+        width: width?,
+        height: height?,
+    })
+}
+
+/// Parse a TeX dimension like `597.5pt` into scaled points. Magnification is
+/// ignored, so `true` units are the same as the plain ones.
+fn parse_dimen(text: &str) -> Option<i32> {
+    let split = text.find(|c: char| c.is_ascii_alphabetic())?;
+    let (value, unit) = text.split_at(split);
+    let value: f64 = value.parse().ok()?;
+    let unit = unit.strip_prefix("true").unwrap_or(unit);
+
+    let pt_per_unit = match unit {
+        "pt" => 1.,
+        "bp" => 72.27 / 72.,
+        "in" => 72.27,
+        "cm" => 72.27 / 2.54,
+        "mm" => 72.27 / 25.4,
+        "pc" => 12.,
+        "dd" => 1238. / 1157.,
+        "cc" => 12. * 1238. / 1157.,
+        "sp" => 1. / 65536.,
+        _ => return None,
+    };
+
+    Some((value * pt_per_unit * 65536.).round() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tfm::tests::sample, XdvWriter};
+
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl PositionedEvents for Recorder {
+        type Error = IoError;
+
+        fn load_tfm(&mut self, _area: &str, name: &str) -> Result<Option<TfmMetrics>, IoError> {
+            assert_eq!(name, "sample");
+            Ok(Some(TfmMetrics::parse(&sample())?))
+        }
+
+        fn handle_define_font(&mut self, def: &FontDefinition) -> Result<(), IoError> {
+            self.0.push(format!("font {}", def.font_num()));
+            Ok(())
+        }
+
+        fn handle_end_page(
+            &mut self,
+            extents: Option<PageExtents>,
+            size: Option<PageSize>,
+        ) -> Result<(), IoError> {
+            self.0.push(format!("eop {extents:?} {size:?}"));
+            Ok(())
+        }
+
+        fn handle_char(&mut self, c: &PositionedChar) -> Result<(), IoError> {
+            self.0.push(format!("char {} {} {}", c.chr, c.x, c.y));
+            Ok(())
+        }
+
+        fn handle_glyph(&mut self, g: &PositionedGlyph) -> Result<(), IoError> {
+            self.0.push(format!("glyph {} {} {}", g.glyph, g.x, g.y));
+            Ok(())
+        }
+
+        fn handle_rule(&mut self, x: i32, y: i32, height: i32, width: i32) -> Result<(), IoError> {
+            self.0.push(format!("rule {x} {y} {height} {width}"));
+            Ok(())
+        }
+
+        fn handle_special(&mut self, x: i32, y: i32, _contents: &[u8]) -> Result<(), IoError> {
+            self.0.push(format!("special {x} {y}"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn positions() {
+        // Use the sample font at 10pt, so that char 65 is 5pt wide and 67 is 10pt.
+        const PT: i32 = 1 << 16;
+
+        let mut w = XdvWriter::new(Vec::new(), FileType::Xdv, b"").unwrap();
+        w.define_font(3, 0, 10 << 16, 10 << 16, "", "sample")
+            .unwrap();
+        w.define_native_font("a.otf", 4, 10 << 16, 0, None, None, None, None)
+            .unwrap();
+        w.begin_page(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        w.down(20 * PT).unwrap();
+        w.set_font(3).unwrap();
+        w.set_char(65).unwrap();
+        w.set_char(67).unwrap();
+        w.special(b"here").unwrap();
+        w.push().unwrap();
+        w.set_y(PT).unwrap();
+        w.set_w(2 * PT).unwrap();
+        w.right_by_w().unwrap();
+        w.set_font(4).unwrap();
+        w.set_glyphs(PT, &[7], &[0], &[0]).unwrap();
+        w.pop().unwrap();
+        w.down_by_y().unwrap();
+        w.set_rule(PT, 3 * PT).unwrap();
+        w.put_rule(0, PT).unwrap();
+        w.end_page().unwrap();
+        let data = w.finish().unwrap();
+
+        let events = PositionAdapter::process(&data[..], Recorder::default()).unwrap();
+        assert_eq!(
+            events.0,
+            vec![
+                "font 3".to_owned(),
+                "font 4".to_owned(),
+                format!("char 65 0 {}", 20 * PT),
+                format!("char 67 {} {}", 5 * PT, 20 * PT),
+                format!("special {} {}", 15 * PT, 20 * PT),
+                format!("glyph 7 {} {}", 19 * PT, 21 * PT),
+                format!("rule {} {} {} {}", 15 * PT, 20 * PT, PT, 3 * PT),
+                format!(
+                    "eop {:?} None",
+                    Some(PageExtents {
+                        min_x: 0,
+                        min_y: 20 * PT - (10 << 14),
+                        max_x: 19 * PT,
+                        max_y: 21 * PT,
+                    })
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn dimens() {
+        const PT: i32 = 1 << 16;
+        assert_eq!(parse_dimen("597.5pt"), Some(597 * PT + PT / 2));
+        assert_eq!(parse_dimen("72bp"), Some(parse_dimen("1in").unwrap()));
+        assert_eq!(
+            parse_dimen("1truein"),
+            Some(parse_dimen("72.27pt").unwrap())
+        );
+        assert_eq!(parse_dimen("2.54cm"), parse_dimen("1in"));
+        assert_eq!(parse_dimen("1pc"), Some(12 * PT));
+        assert_eq!(parse_dimen("100sp"), Some(100));
+        assert_eq!(parse_dimen("12em"), None);
+        assert_eq!(parse_dimen("12"), None);
+        assert_eq!(
+            parse_pagesize("height 2pt width 1pt"),
+            Some(PageSize {
+                width: PT,
+                height: 2 * PT
+            })
+        );
+        assert_eq!(parse_pagesize("width 1pt"), None);
+    }
+
+    #[test]
+    fn page_sizes() {
+        const PT: i32 = 1 << 16;
+
+        let mut w = XdvWriter::new(Vec::new(), FileType::Xdv, b"").unwrap();
+        w.note_extents(500 * PT, 700 * PT, 0);
+
+        for special in [
+            None,
+            Some("pdf:pagesize width 100pt height 2in"),
+            None,
+            Some("pdf:pagesize bogus"),
+            Some("pdf:pagesize default"),
+        ] {
+            w.begin_page(&[0; 10]).unwrap();
+
+            if let Some(s) = special {
+                w.special(s.as_bytes()).unwrap();
+            }
+
+            w.end_page().unwrap();
+        }
+
+        let data = w.finish().unwrap();
+        let sizes = |events: Recorder| -> Vec<String> {
+            events
+                .0
+                .into_iter()
+                .filter_map(|e| e.strip_prefix("eop None ").map(|s| s.to_owned()))
+                .collect()
+        };
+
+        let from_special: &str = &format!(
+            "{:?}",
+            Some(PageSize {
+                width: 100 * PT,
+                height: parse_dimen("2in").unwrap(),
+            })
+        );
+
+        // Without seeking, the postamble isn't read until the end.
+        let events = PositionAdapter::process(&data[..], Recorder::default()).unwrap();
+        assert_eq!(
+            sizes(events),
+            ["None", from_special, from_special, from_special, "None"]
+        );
+
+        let from_postamble: &str = &format!(
+            "{:?}",
+            Some(PageSize {
+                width: 500 * PT,
+                height: 700 * PT,
+            })
+        );
+
+        let adapter = XdvParser::process_with_seeks(
+            std::io::Cursor::new(&data),
+            PositionAdapter::new(Recorder::default()),
+        )
+        .unwrap();
+        assert_eq!(
+            sizes(adapter.into_inner()),
+            [
+                from_postamble,
+                from_special,
+                from_special,
+                from_special,
+                from_postamble
+            ]
+        );
+    }
+}
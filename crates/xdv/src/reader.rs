@@ -0,0 +1,399 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Random access to the pages of XDV/SPX streams.
+
+use byteorder::{BigEndian, ByteOrder};
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Seek, SeekFrom, Write};
+
+use super::{FileType, FontDefinition, IdByte, Opcode, XdvError, XdvEvents, XdvParser, XdvWriter};
+
+/// The size of a `bop` command, including its opcode.
+const BOP_SIZE: usize = 45;
+
+/// The size of the fixed part of a `post` command, including its opcode.
+const POSTAMBLE_SIZE: usize = 29;
+
+/// Information about a page in an XDV/SPX stream.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PageInfo {
+    /// The byte offset of the page's `bop` command.
+    pub offset: u64,
+
+    /// The page's counters (usually `\count0` through `\count9`).
+    pub counters: [i32; 10],
+}
+
+/// A reader providing random access to the pages of an XDV/SPX stream.
+///
+/// When a reader is created, it loads the postamble of the stream and follows
+/// the chain of back-pointers from the last page to build a table of all of the
+/// pages. Individual pages can then be parsed with [`Self::read_page`], or
+/// copied into a new stream with [`Self::write_pages`].
+#[derive(Debug)]
+pub struct XdvReader<R: Read + Seek> {
+    stream: R,
+    filetype: FileType,
+    comment: Vec<u8>,
+    max_h: i32,
+    max_v: i32,
+    max_stack_depth: u16,
+    pages: Vec<PageInfo>,
+    fonts: Vec<FontDefinition>,
+}
+
+impl<R: Read + Seek> XdvReader<R> {
+    /// Index a stream.
+    pub fn new(mut stream: R) -> IoResult<Self> {
+        const EOF_WORK_SIZE: usize = 16;
+
+        // The comment is in the preamble.
+
+        let pre = read_at(&mut stream, 0, 15)?;
+
+        if pre[0] != Opcode::Preamble as u8 {
+            return Err(XdvError::Malformed(0).into());
+        }
+
+        let n_comment = pre[14] as usize;
+        let mut comment = vec![0; n_comment];
+        stream.read_exact(&mut comment)?;
+
+        // Locate the postamble from the post-postamble. See also
+        // `XdvParser::process_with_seeks`.
+
+        let end = stream.seek(SeekFrom::End(0))?;
+
+        if end < EOF_WORK_SIZE as u64 {
+            return Err(XdvError::UnexpectedEndOfStream.into());
+        }
+
+        let offset = end - EOF_WORK_SIZE as u64;
+        let buf = read_at(&mut stream, offset, EOF_WORK_SIZE)?;
+        let mut delta = EOF_WORK_SIZE - 1;
+
+        while buf[delta] == 0xDF && delta > 0 {
+            delta -= 1;
+        }
+
+        if !(6..=EOF_WORK_SIZE - 4).contains(&delta) {
+            return Err(XdvError::Malformed(offset + delta as u64).into());
+        }
+
+        delta -= 5; // 4 bytes for offset, 1 for opcode
+
+        if buf[delta] != Opcode::DoublePostamble as u8 {
+            return Err(XdvError::Malformed(offset + delta as u64).into());
+        }
+
+        let postamble_offset = BigEndian::read_u32(&buf[delta + 1..]) as u64;
+
+        let filetype = match buf[delta + 5] {
+            b if b == IdByte::Dvi as u8 => FileType::Dvi,
+            b if b == IdByte::Xdv as u8 => FileType::Xdv,
+            b if b == IdByte::Spx as u8 => FileType::Spx,
+            _ => {
+                return Err(XdvError::Malformed(offset + delta as u64 + 5).into());
+            }
+        };
+
+        if pre[1] != buf[delta + 5] {
+            return Err(XdvError::Malformed(1).into());
+        }
+
+        // Now the postamble.
+
+        let post = read_at(&mut stream, postamble_offset, POSTAMBLE_SIZE)?;
+
+        if post[0] != Opcode::Postamble as u8 {
+            return Err(XdvError::Malformed(postamble_offset).into());
+        }
+
+        let last_bop = BigEndian::read_i32(&post[1..]);
+        let max_v = BigEndian::read_i32(&post[17..]);
+        let max_h = BigEndian::read_i32(&post[21..]);
+        let max_stack_depth = BigEndian::read_u16(&post[25..]);
+
+        stream.seek(SeekFrom::Start(postamble_offset))?;
+        let fonts = XdvParser::process_postamble(
+            &mut stream,
+            filetype,
+            postamble_offset,
+            FontCollector::default(),
+        )?
+        .0;
+
+        // Finally, walk the pages backwards. Each page must come before the one
+        // that points to it, so this can't loop forever.
+
+        let mut pages = Vec::new();
+        let mut bop = last_bop;
+        let mut limit = postamble_offset;
+
+        while bop != -1 {
+            let offset = bop as u64;
+
+            if bop < 0 || offset + BOP_SIZE as u64 > limit {
+                return Err(XdvError::Malformed(limit).into());
+            }
+
+            let data = read_at(&mut stream, offset, BOP_SIZE)?;
+
+            if data[0] != Opcode::BeginningOfPage as u8 {
+                return Err(XdvError::Malformed(offset).into());
+            }
+
+            let mut counters = [0; 10];
+            BigEndian::read_i32_into(&data[1..41], &mut counters);
+            pages.push(PageInfo { offset, counters });
+
+            bop = BigEndian::read_i32(&data[41..]);
+            limit = offset;
+        }
+
+        pages.reverse();
+
+        Ok(XdvReader {
+            stream,
+            filetype,
+            comment,
+            max_h,
+            max_v,
+            max_stack_depth,
+            pages,
+            fonts,
+        })
+    }
+
+    /// Get the type of the stream.
+    pub fn filetype(&self) -> FileType {
+        self.filetype
+    }
+
+    /// Get the comment from the stream preamble.
+    pub fn comment(&self) -> &[u8] {
+        &self.comment[..]
+    }
+
+    /// Get information about the pages of the stream, in order.
+    pub fn pages(&self) -> &[PageInfo] {
+        &self.pages[..]
+    }
+
+    /// Get the number of pages in the stream.
+    pub fn n_pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Get the fonts defined in the postamble of the stream, which should
+    /// include all of the fonts used in it.
+    pub fn fonts(&self) -> &[FontDefinition] {
+        &self.fonts[..]
+    }
+
+    /// Consume the reader and return the underlying stream.
+    pub fn into_inner(self) -> R {
+        self.stream
+    }
+
+    /// Parse the page with the (zero-based) *index*, delivering its contents to
+    /// *events*.
+    ///
+    /// Only page-level events are delivered; in particular,
+    /// [`XdvEvents::handle_header`] isn't called. Fonts defined elsewhere in the
+    /// file can be obtained from [`Self::fonts`].
+    pub fn read_page<T: XdvEvents>(&mut self, index: usize, events: T) -> Result<T, T::Error>
+    where
+        T::Error: From<IoError>,
+    {
+        Ok(self.process_page(index, events)?.0)
+    }
+
+    fn process_page<T: XdvEvents>(&mut self, index: usize, events: T) -> Result<(T, u64), T::Error>
+    where
+        T::Error: From<IoError>,
+    {
+        let offset = self.page_offset(index)?;
+        self.stream.seek(SeekFrom::Start(offset))?;
+        XdvParser::process_page(&mut self.stream, self.filetype, offset, events)
+    }
+
+    fn page_offset(&self, index: usize) -> IoResult<u64> {
+        self.pages.get(index).map(|p| p.offset).ok_or_else(|| {
+            IoError::new(
+                ErrorKind::InvalidInput,
+                format!("page index {index} out of range"),
+            )
+        })
+    }
+
+    /// Get the encoded commands of the page with the (zero-based) *index*,
+    /// excluding its `bop` and `eop` codes.
+    pub fn page_body(&mut self, index: usize) -> IoResult<Vec<u8>> {
+        let offset = self.page_offset(index)?;
+        let end = self.process_page(index, NoEvents)?.1;
+        let start = offset + BOP_SIZE as u64;
+        read_at(&mut self.stream, start, (end - 1 - start) as usize)
+    }
+
+    /// Write a new stream containing a subset of the pages of this one.
+    ///
+    /// The pages are identified by their zero-based indices, and are written in
+    /// the order given. The new stream has the same type and preamble comment
+    /// as this one, and defines all of the fonts defined in this one. The page
+    /// contents are copied verbatim, so the extracted pages will render
+    /// identically to the originals. Returns the output stream.
+    pub fn write_pages<W: Write, I: IntoIterator<Item = usize>>(
+        &mut self,
+        pages: I,
+        stream: W,
+    ) -> IoResult<W> {
+        let mut w = XdvWriter::new(stream, self.filetype, &self.comment)?;
+
+        for f in &self.fonts {
+            w.define(f)?;
+        }
+
+        // The page extents aren't recorded per-page, so the best we can do is
+        // copy the values for the whole original file.
+        w.note_extents(self.max_h, self.max_v, self.max_stack_depth);
+
+        for index in pages {
+            let body = self.page_body(index)?;
+            w.raw_page(&self.pages[index].counters, &body)?;
+        }
+
+        w.finish()
+    }
+}
+
+fn read_at<R: Read + Seek>(stream: &mut R, offset: u64, n: usize) -> IoResult<Vec<u8>> {
+    let mut buf = vec![0; n];
+    stream.seek(SeekFrom::Start(offset))?;
+    stream.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Collects the font definitions in a postamble.
+#[derive(Debug, Default)]
+struct FontCollector(Vec<FontDefinition>);
+
+impl XdvEvents for FontCollector {
+    type Error = IoError;
+
+    fn handle_define_font(
+        &mut self,
+        area: &str,
+        name: &str,
+        font_num: i32,
+        checksum: u32,
+        scale_factor: u32,
+        design_size: u32,
+    ) -> Result<(), Self::Error> {
+        self.0.push(FontDefinition::Tfm {
+            font_num,
+            checksum,
+            scale_factor,
+            design_size,
+            area: area.to_owned(),
+            name: name.to_owned(),
+        });
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn handle_define_native_font(
+        &mut self,
+        name: &str,
+        font_num: i32,
+        size: i32,
+        face_index: u32,
+        color_rgba: Option<u32>,
+        extend: Option<u32>,
+        slant: Option<u32>,
+        embolden: Option<u32>,
+    ) -> Result<(), Self::Error> {
+        self.0.push(FontDefinition::Native {
+            font_num,
+            name: name.to_owned(),
+            size,
+            face_index,
+            color_rgba,
+            extend,
+            slant,
+            embolden,
+        });
+        Ok(())
+    }
+}
+
+/// Ignores all events.
+struct NoEvents;
+
+impl XdvEvents for NoEvents {
+    type Error = IoError;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[derive(Default)]
+    struct Specials(Vec<String>);
+
+    impl XdvEvents for Specials {
+        type Error = IoError;
+
+        fn handle_special(&mut self, _x: i32, _y: i32, contents: &[u8]) -> IoResult<()> {
+            self.0.push(String::from_utf8_lossy(contents).into_owned());
+            Ok(())
+        }
+    }
+
+    fn sample() -> Vec<u8> {
+        let mut w = XdvWriter::new(Vec::new(), FileType::Xdv, b"sample").unwrap();
+        w.define_native_font("a.otf", 1, 655360, 0, None, None, None, None)
+            .unwrap();
+
+        for i in 1..=4 {
+            w.begin_page(&[i, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+            w.push().unwrap();
+            w.down(i * 1000).unwrap();
+
+            if i == 3 {
+                w.define_font(2, 0, 0, 0, "", "cmr10").unwrap();
+            }
+
+            w.special(format!("page {i}").as_bytes()).unwrap();
+            w.pop().unwrap();
+            w.end_page().unwrap();
+        }
+
+        w.finish().unwrap()
+    }
+
+    #[test]
+    fn index_and_extract() {
+        let mut r = XdvReader::new(Cursor::new(sample())).unwrap();
+        assert_eq!(r.filetype(), FileType::Xdv);
+        assert_eq!(r.comment(), b"sample");
+        assert_eq!(r.n_pages(), 4);
+        assert_eq!(r.pages()[2].counters[0], 3);
+        assert_eq!(r.fonts().len(), 2);
+
+        let s = r.read_page(1, Specials::default()).unwrap();
+        assert_eq!(s.0, vec!["page 2"]);
+        assert!(r.read_page(4, Specials::default()).is_err());
+
+        let subset = r.write_pages(vec![3, 2], Vec::new()).unwrap();
+        let mut r2 = XdvReader::new(Cursor::new(subset)).unwrap();
+        assert_eq!(r2.n_pages(), 2);
+        assert_eq!(r2.pages()[0].counters[0], 4);
+        assert_eq!(r2.fonts(), r.fonts());
+        assert_eq!(r2.page_body(1).unwrap(), r.page_body(2).unwrap());
+
+        let s = r2.read_page(1, Specials::default()).unwrap();
+        assert_eq!(s.0, vec!["page 3"]);
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Metrics of traditional TeX fonts, as stored in TFM files.
+
+use byteorder::{BigEndian, ByteOrder};
+use std::convert::TryFrom;
+
+use super::XdvError;
+
+/// The dimensions of a character, in TeX scaled points.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CharMetrics {
+    /// The width of the character, by which the current point advances when
+    /// it is set.
+    pub width: i32,
+
+    /// The height of the character above the baseline.
+    pub height: i32,
+
+    /// The depth of the character below the baseline.
+    pub depth: i32,
+}
+
+/// The character metrics of a traditional TeX font, loaded from a TFM file.
+///
+/// Only the information needed to position characters is retained: ligature,
+/// kerning, and extensible character data are ignored.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TfmMetrics {
+    checksum: u32,
+    design_size: i32,
+    first_char: i32,
+
+    /// For each character, indices into the width, height, and depth tables.
+    /// A width index of zero means that the character doesn't exist.
+    char_info: Vec<(u8, u8, u8)>,
+
+    widths: Vec<i32>,
+    heights: Vec<i32>,
+    depths: Vec<i32>,
+}
+
+impl TfmMetrics {
+    /// Parse the contents of a TFM file.
+    ///
+    /// If the data are malformed, an [`XdvError::Malformed`] error is returned
+    /// with an offset into the TFM data.
+    pub fn parse(data: &[u8]) -> Result<Self, XdvError> {
+        if data.len() < 24 {
+            return Err(XdvError::Malformed(data.len() as u64));
+        }
+
+        let mut lengths = [0u16; 12];
+        BigEndian::read_u16_into(&data[..24], &mut lengths);
+        let [lf, lh, bc, ec, nw, nh, nd, ni, nl, nk, ne, np] = lengths.map(usize::from);
+
+        // A font with no characters has bc = ec + 1.
+        let n_chars = (ec + 1).checked_sub(bc).ok_or(XdvError::Malformed(4))?;
+
+        if lh < 2
+            || lf * 4 > data.len()
+            || lf != 6 + lh + n_chars + nw + nh + nd + ni + nl + nk + ne + np
+        {
+            return Err(XdvError::Malformed(0));
+        }
+
+        let word = |index: usize| BigEndian::read_i32(&data[index * 4..]);
+        let table = |start: usize, n: usize| (start..start + n).map(word).collect::<Vec<_>>();
+
+        let char_info_start = 6 + lh;
+        let widths_start = char_info_start + n_chars;
+        let heights_start = widths_start + nw;
+        let depths_start = heights_start + nh;
+
+        let mut char_info = Vec::with_capacity(n_chars);
+
+        for i in 0..n_chars {
+            let offset = (char_info_start + i) * 4;
+            let w = data[offset];
+            let h = data[offset + 1] >> 4;
+            let d = data[offset + 1] & 0xF;
+
+            if w as usize >= nw || h as usize >= nh || d as usize >= nd {
+                return Err(XdvError::Malformed(offset as u64));
+            }
+
+            char_info.push((w, h, d));
+        }
+
+        Ok(TfmMetrics {
+            checksum: word(6) as u32,
+            design_size: word(7),
+            first_char: bc as i32,
+            char_info,
+            widths: table(widths_start, nw),
+            heights: table(heights_start, nh),
+            depths: table(depths_start, nd),
+        })
+    }
+
+    /// Get the checksum of the font.
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    /// Get the design size of the font, in TeX points as a fixed-point number
+    /// with 20 fractional bits.
+    pub fn design_size(&self) -> i32 {
+        self.design_size
+    }
+
+    /// Get the metrics of a character when the font is used at *size* (in TeX
+    /// scaled points), or `None` if the font doesn't contain the character.
+    pub fn char_metrics(&self, chr: i32, size: u32) -> Option<CharMetrics> {
+        let index = usize::try_from(chr.checked_sub(self.first_char)?).ok()?;
+        let (w, h, d) = *self.char_info.get(index)?;
+
+        if w == 0 {
+            return None;
+        }
+
+        Some(CharMetrics {
+            width: scale(self.widths[w as usize], size),
+            height: scale(self.heights[h as usize], size),
+            depth: scale(self.depths[d as usize], size),
+        })
+    }
+}
+
+/// Scale a TFM "fix word" by a font size. This matches TeX's computation
+/// exactly for sizes below 128pt.
+fn scale(fix: i32, size: u32) -> i32 {
+    ((fix as i64 * size as i64) >> 20) as i32
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Build a minimal TFM file with characters 65 and 67.
+    pub(crate) fn sample() -> Vec<u8> {
+        let words: Vec<u32> = vec![
+            // lf, lh; bc, ec; nw, nh; nd, ni; nl, nk; ne, np
+            (18 << 16) | 2,
+            (65 << 16) | 67,
+            (3 << 16) | 2,
+            2 << 16,
+            0,
+            0,
+            // header: checksum, design size (10pt)
+            0x1234_5678,
+            10 << 20,
+            // char_info for 65, 66, 67
+            0x0111_0000,
+            0,
+            0x0200_0000,
+            // widths
+            0,
+            1 << 19,
+            1 << 20,
+            // heights
+            0,
+            1 << 18,
+            // depths
+            0,
+            -(1 << 17) as u32,
+        ];
+
+        words.iter().flat_map(|w| w.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn parse() {
+        let tfm = TfmMetrics::parse(&sample()).unwrap();
+        assert_eq!(tfm.checksum(), 0x1234_5678);
+        assert_eq!(tfm.design_size(), 10 << 20);
+
+        // 10pt font: width 0.5 * 10pt = 5pt
+        assert_eq!(
+            tfm.char_metrics(65, 10 << 16),
+            Some(CharMetrics {
+                width: 5 << 16,
+                height: 10 << 14,
+                depth: -(10 << 13),
+            })
+        );
+        assert_eq!(tfm.char_metrics(66, 10 << 16), None);
+        assert_eq!(tfm.char_metrics(67, 10 << 16).unwrap().width, 10 << 16);
+        assert_eq!(tfm.char_metrics(68, 10 << 16), None);
+        assert_eq!(tfm.char_metrics(-1, 10 << 16), None);
+
+        assert!(TfmMetrics::parse(&sample()[..60]).is_err());
+    }
+}
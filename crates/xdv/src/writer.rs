@@ -0,0 +1,912 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! Writing DVI, XDV, and SPX streams.
+
+use byteorder::{BigEndian, WriteBytesExt};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    io::{Error as IoError, ErrorKind, Result as IoResult, Write},
+};
+
+use super::{FileType, FontDefinition, IdByte, NativeFontFlags, Opcode, State};
+
+/// The numerator of the DVI unit of measure. Together with the denominator,
+/// this establishes that the units are TeX's scaled points.
+const UNIT_NUMERATOR: u32 = 25_400_000;
+
+/// The denominator of the DVI unit of measure.
+const UNIT_DENOMINATOR: u32 = 473_628_672;
+
+/// The magnification factor written to the preamble and postamble.
+const MAGNIFICATION: u32 = 1000;
+
+/// A writer of DVI, XDV, and SPX streams.
+///
+/// The writer takes care of the bookkeeping needed to produce a well-formed
+/// file: it tracks the back-pointers between pages, the DVI positioning stack,
+/// and the fonts that have been defined, and on [`Self::finish`] it emits a
+/// postamble recording them. Numeric operands are written using the most
+/// compact available opcodes.
+///
+/// Coordinates and dimensions are in TeX scaled points. Methods that are used
+/// out of order, such as drawing outside of a page, or that would produce
+/// codes not allowed in the output file type, return an error of kind
+/// [`ErrorKind::InvalidInput`] without writing anything.
+///
+/// The maximum page dimensions recorded in the postamble are the largest
+/// horizontal and vertical positions reached on any page, including the extents
+/// of rules and glyph runs.
+#[derive(Debug)]
+pub struct XdvWriter<W: Write> {
+    stream: W,
+    filetype: FileType,
+    offset: u64,
+    last_bop: i32,
+    n_pages: u32,
+    stack: Vec<State>,
+    max_stack_depth: usize,
+    max_h: i32,
+    max_v: i32,
+    fonts: BTreeMap<i32, Vec<u8>>,
+}
+
+impl<W: Write> XdvWriter<W> {
+    /// Create a new writer, emitting the file preamble.
+    ///
+    /// The *comment* is limited to 255 bytes.
+    pub fn new(stream: W, filetype: FileType, comment: &[u8]) -> IoResult<Self> {
+        if comment.len() > 255 {
+            return Err(invalid("DVI comments are limited to 255 bytes"));
+        }
+
+        let mut w = XdvWriter {
+            stream,
+            filetype,
+            offset: 0,
+            last_bop: -1,
+            n_pages: 0,
+            stack: Vec::new(),
+            max_stack_depth: 0,
+            max_h: 0,
+            max_v: 0,
+            fonts: BTreeMap::new(),
+        };
+
+        let mut buf = vec![Opcode::Preamble as u8, id_byte(filetype)];
+        buf.write_u32::<BigEndian>(UNIT_NUMERATOR)?;
+        buf.write_u32::<BigEndian>(UNIT_DENOMINATOR)?;
+        buf.write_u32::<BigEndian>(MAGNIFICATION)?;
+        buf.push(comment.len() as u8);
+        buf.extend_from_slice(comment);
+        w.emit(&buf)?;
+        Ok(w)
+    }
+
+    /// Get the type of file being written.
+    pub fn filetype(&self) -> FileType {
+        self.filetype
+    }
+
+    /// Get the number of bytes written so far.
+    pub fn current_offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get the number of pages that have been started.
+    pub fn n_pages(&self) -> u32 {
+        self.n_pages
+    }
+
+    /// Get the current horizontal and vertical position, if inside a page.
+    pub fn position(&self) -> Option<(i32, i32)> {
+        self.stack.last().map(|s| (s.h, s.v))
+    }
+
+    /// Begin a new page with the given counters (usually `\count0` through
+    /// `\count9`).
+    pub fn begin_page(&mut self, counters: &[i32; 10]) -> IoResult<()> {
+        if !self.stack.is_empty() {
+            return Err(invalid("cannot begin a page inside another page"));
+        }
+
+        let bop = u32::try_from(self.offset)
+            .map_err(|_| invalid("DVI output is limited to 4 GiB"))? as i32;
+        let mut buf = vec![Opcode::BeginningOfPage as u8];
+
+        for c in counters {
+            buf.write_i32::<BigEndian>(*c)?;
+        }
+
+        buf.write_i32::<BigEndian>(self.last_bop)?;
+        self.emit(&buf)?;
+
+        self.last_bop = bop;
+        self.n_pages += 1;
+        self.stack.push(State::new());
+        Ok(())
+    }
+
+    /// End the current page.
+    ///
+    /// Any entries pushed onto the positioning stack must have been popped.
+    pub fn end_page(&mut self) -> IoResult<()> {
+        self.in_page()?;
+
+        if self.stack.len() != 1 {
+            return Err(invalid("cannot end a page with items on the stack"));
+        }
+
+        self.emit(&[Opcode::EndOfPage as u8])?;
+        self.stack.clear();
+        Ok(())
+    }
+
+    /// Write a complete page whose contents have already been encoded.
+    ///
+    /// The *body* consists of the commands between the `bop` and `eop` codes,
+    /// which are written verbatim: it is up to the caller to ensure that they
+    /// are well-formed and that any fonts they use are defined in the
+    /// postamble. Because the writer doesn't examine the commands, the maximum
+    /// page dimensions and stack depth aren't updated; use
+    /// [`Self::note_extents`] to account for them.
+    pub fn raw_page(&mut self, counters: &[i32; 10], body: &[u8]) -> IoResult<()> {
+        self.begin_page(counters)?;
+        self.emit(body)?;
+        self.emit(&[Opcode::EndOfPage as u8])?;
+        self.stack.clear();
+        Ok(())
+    }
+
+    /// Increase the maximum page dimensions and stack depth recorded in the
+    /// postamble, if the values given are larger than the current ones.
+    pub fn note_extents(&mut self, max_h: i32, max_v: i32, max_stack_depth: u16) {
+        self.note_extent(max_h, max_v);
+        self.max_stack_depth = self.max_stack_depth.max(max_stack_depth as usize);
+    }
+
+    /// Define a traditional TeX font, as loaded from a TFM file.
+    ///
+    /// Fonts may be defined inside or between pages, but must be defined before
+    /// they are selected with [`Self::set_font`]. A font may be redefined with
+    /// the same number, in which case the latest definition is recorded in the
+    /// postamble.
+    pub fn define_font(
+        &mut self,
+        font_num: i32,
+        checksum: u32,
+        scale_factor: u32,
+        design_size: u32,
+        area: &str,
+        name: &str,
+    ) -> IoResult<()> {
+        if area.len() > 255 || name.len() > 255 {
+            return Err(invalid("DVI font names are limited to 255 bytes"));
+        }
+
+        let mut buf = Vec::new();
+        push_compact_unsigned(&mut buf, Opcode::DefineFont1 as u8, font_num);
+        buf.write_u32::<BigEndian>(checksum)?;
+        buf.write_u32::<BigEndian>(scale_factor)?;
+        buf.write_u32::<BigEndian>(design_size)?;
+        buf.push(area.len() as u8);
+        buf.push(name.len() as u8);
+        buf.extend_from_slice(area.as_bytes());
+        buf.extend_from_slice(name.as_bytes());
+        self.emit(&buf)?;
+        self.fonts.insert(font_num, buf);
+        Ok(())
+    }
+
+    /// Define a XeTeX "native" font.
+    ///
+    /// The optional values have the same meanings as in
+    /// [`crate::XdvEvents::handle_define_native_font`]. This is not allowed in
+    /// plain DVI files.
+    #[allow(clippy::too_many_arguments)]
+    pub fn define_native_font(
+        &mut self,
+        name: &str,
+        font_num: i32,
+        size: i32,
+        face_index: u32,
+        color_rgba: Option<u32>,
+        extend: Option<u32>,
+        slant: Option<u32>,
+        embolden: Option<u32>,
+    ) -> IoResult<()> {
+        self.extended()?;
+
+        if name.len() > 255 {
+            return Err(invalid("native font names are limited to 255 bytes"));
+        }
+
+        let mut flags = 0;
+
+        for (value, flag) in &[
+            (color_rgba, NativeFontFlags::Colored),
+            (extend, NativeFontFlags::Extend),
+            (slant, NativeFontFlags::Slant),
+            (embolden, NativeFontFlags::Embolden),
+        ] {
+            if value.is_some() {
+                flags |= *flag as u16;
+            }
+        }
+
+        let mut buf = vec![Opcode::DefineNativeFont as u8];
+        buf.write_i32::<BigEndian>(font_num)?;
+        buf.write_i32::<BigEndian>(size)?;
+        buf.write_u16::<BigEndian>(flags)?;
+        buf.push(name.len() as u8);
+        buf.extend_from_slice(name.as_bytes());
+        buf.write_u32::<BigEndian>(face_index)?;
+
+        for v in [color_rgba, extend, slant, embolden].iter().flatten() {
+            buf.write_u32::<BigEndian>(*v)?;
+        }
+
+        self.emit(&buf)?;
+        self.fonts.insert(font_num, buf);
+        Ok(())
+    }
+
+    /// Define a font from its [`FontDefinition`].
+    pub fn define(&mut self, def: &FontDefinition) -> IoResult<()> {
+        match def {
+            FontDefinition::Tfm {
+                font_num,
+                checksum,
+                scale_factor,
+                design_size,
+                area,
+                name,
+            } => self.define_font(
+                *font_num,
+                *checksum,
+                *scale_factor,
+                *design_size,
+                area,
+                name,
+            ),
+
+            FontDefinition::Native {
+                font_num,
+                name,
+                size,
+                face_index,
+                color_rgba,
+                extend,
+                slant,
+                embolden,
+            } => self.define_native_font(
+                name,
+                *font_num,
+                *size,
+                *face_index,
+                *color_rgba,
+                *extend,
+                *slant,
+                *embolden,
+            ),
+        }
+    }
+
+    /// Select the current font.
+    pub fn set_font(&mut self, font_num: i32) -> IoResult<()> {
+        self.in_page()?;
+
+        if !self.fonts.contains_key(&font_num) {
+            return Err(invalid("cannot select a font that has not been defined"));
+        }
+
+        let mut buf = Vec::new();
+
+        if (0..64).contains(&font_num) {
+            buf.push(Opcode::SetFontNumber0 as u8 + font_num as u8);
+        } else {
+            push_compact_unsigned(&mut buf, Opcode::SetFont1 as u8, font_num);
+        }
+
+        self.emit(&buf)
+    }
+
+    /// Typeset a character from the current font.
+    ///
+    /// The DVI format expects the current position to advance by the width of
+    /// the character, but this writer doesn't know about font metrics. Callers
+    /// that care about the positions recorded by the writer should follow up
+    /// with an appropriate [`Self::right`] with an opposite sign or use
+    /// explicit positioning.
+    pub fn set_char(&mut self, chr: i32) -> IoResult<()> {
+        self.in_page()?;
+        let mut buf = Vec::new();
+
+        if (0..128).contains(&chr) {
+            buf.push(Opcode::SetCharNumber0 as u8 + chr as u8);
+        } else {
+            push_compact_unsigned(&mut buf, Opcode::SetChar1 as u8, chr);
+        }
+
+        self.emit(&buf)
+    }
+
+    /// Typeset a run of glyphs from the current native font.
+    ///
+    /// The glyph positions *x* and *y* are relative to the current point, and
+    /// the current point then moves right by *width*. This is not allowed in
+    /// plain DVI files.
+    pub fn set_glyphs(&mut self, width: i32, glyphs: &[u16], x: &[i32], y: &[i32]) -> IoResult<()> {
+        self.in_page()?;
+        self.extended()?;
+        check_glyphs(glyphs, x, y)?;
+
+        let mut buf = vec![Opcode::SetGlyphs as u8];
+        buf.write_i32::<BigEndian>(width)?;
+        buf.write_u16::<BigEndian>(glyphs.len() as u16)?;
+        push_glyphs(&mut buf, glyphs, x, y)?;
+        self.emit(&buf)?;
+        self.note_glyphs(width, x, y);
+        Ok(())
+    }
+
+    /// Typeset a run of glyphs from the current native font, annotated with
+    /// the text that they represent.
+    ///
+    /// This works like [`Self::set_glyphs`]. The text may contain at most
+    /// 65535 UTF-16 code units.
+    pub fn set_text_and_glyphs(
+        &mut self,
+        text: &str,
+        width: i32,
+        glyphs: &[u16],
+        x: &[i32],
+        y: &[i32],
+    ) -> IoResult<()> {
+        self.in_page()?;
+        self.extended()?;
+        check_glyphs(glyphs, x, y)?;
+
+        let text: Vec<u16> = text.encode_utf16().collect();
+
+        if text.len() > u16::MAX as usize {
+            return Err(invalid("the text of a glyph run is too long"));
+        }
+
+        let mut buf = vec![Opcode::SetTextAndGlyphs as u8];
+        buf.write_u16::<BigEndian>(text.len() as u16)?;
+
+        for c in &text {
+            buf.write_u16::<BigEndian>(*c)?;
+        }
+
+        buf.write_i32::<BigEndian>(width)?;
+        buf.write_u16::<BigEndian>(glyphs.len() as u16)?;
+        push_glyphs(&mut buf, glyphs, x, y)?;
+        self.emit(&buf)?;
+        self.note_glyphs(width, x, y);
+        Ok(())
+    }
+
+    /// Typeset a rule with its bottom left corner at the current point, then
+    /// move right by its width.
+    pub fn set_rule(&mut self, height: i32, width: i32) -> IoResult<()> {
+        self.rule(Opcode::SetRule, height, width)
+    }
+
+    /// Typeset a rule with its bottom left corner at the current point,
+    /// without moving.
+    pub fn put_rule(&mut self, height: i32, width: i32) -> IoResult<()> {
+        self.rule(Opcode::PutRule, height, width)
+    }
+
+    fn rule(&mut self, opcode: Opcode, height: i32, width: i32) -> IoResult<()> {
+        self.in_page()?;
+        let mut buf = vec![opcode as u8];
+        buf.write_i32::<BigEndian>(height)?;
+        buf.write_i32::<BigEndian>(width)?;
+        self.emit(&buf)?;
+
+        let state = self.stack.last_mut().unwrap();
+        let (h, v) = (state.h, state.v);
+
+        if opcode == Opcode::SetRule {
+            state.h = state.h.wrapping_add(width);
+        }
+
+        if height > 0 && width > 0 {
+            self.note_extent(h.saturating_add(width), v);
+        }
+
+        Ok(())
+    }
+
+    /// Emit a `\special` at the current point.
+    pub fn special(&mut self, contents: &[u8]) -> IoResult<()> {
+        self.in_page()?;
+
+        let n = u32::try_from(contents.len()).map_err(|_| invalid("special is too long"))?;
+        let mut buf = Vec::with_capacity(contents.len() + 5);
+        push_compact_unsigned(&mut buf, Opcode::Special1 as u8, n as i32);
+        buf.extend_from_slice(contents);
+        self.emit(&buf)
+    }
+
+    /// Push the current positioning state onto the stack.
+    pub fn push(&mut self) -> IoResult<()> {
+        self.in_page()?;
+        self.emit(&[Opcode::PushStack as u8])?;
+        let dup = self.stack.last().unwrap().clone();
+        self.stack.push(dup);
+        self.max_stack_depth = self.max_stack_depth.max(self.stack.len() - 1);
+        Ok(())
+    }
+
+    /// Pop the positioning state from the stack.
+    pub fn pop(&mut self) -> IoResult<()> {
+        self.in_page()?;
+
+        if self.stack.len() < 2 {
+            return Err(invalid("cannot pop an empty DVI stack"));
+        }
+
+        self.emit(&[Opcode::PopStack as u8])?;
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// Move right by *n*.
+    pub fn right(&mut self, n: i32) -> IoResult<()> {
+        self.movement(Opcode::Right1, n, |s| s.h = s.h.wrapping_add(n))
+    }
+
+    /// Move right by the current value of *w*.
+    pub fn right_by_w(&mut self) -> IoResult<()> {
+        self.movement0(Opcode::RightByW, |s| s.h = s.h.wrapping_add(s.w))
+    }
+
+    /// Set *w* to *n* and move right by it.
+    pub fn set_w(&mut self, n: i32) -> IoResult<()> {
+        self.movement(Opcode::SetW1, n, |s| {
+            s.w = n;
+            s.h = s.h.wrapping_add(n);
+        })
+    }
+
+    /// Move right by the current value of *x*.
+    pub fn right_by_x(&mut self) -> IoResult<()> {
+        self.movement0(Opcode::RightByX, |s| s.h = s.h.wrapping_add(s.x))
+    }
+
+    /// Set *x* to *n* and move right by it.
+    pub fn set_x(&mut self, n: i32) -> IoResult<()> {
+        self.movement(Opcode::SetX1, n, |s| {
+            s.x = n;
+            s.h = s.h.wrapping_add(n);
+        })
+    }
+
+    /// Move down by *n*.
+    pub fn down(&mut self, n: i32) -> IoResult<()> {
+        self.movement(Opcode::Down1, n, |s| s.v = s.v.wrapping_add(n))
+    }
+
+    /// Move down by the current value of *y*.
+    pub fn down_by_y(&mut self) -> IoResult<()> {
+        self.movement0(Opcode::DownByY, |s| s.v = s.v.wrapping_add(s.y))
+    }
+
+    /// Set *y* to *n* and move down by it.
+    pub fn set_y(&mut self, n: i32) -> IoResult<()> {
+        self.movement(Opcode::SetY1, n, |s| {
+            s.y = n;
+            s.v = s.v.wrapping_add(n);
+        })
+    }
+
+    /// Move down by the current value of *z*.
+    pub fn down_by_z(&mut self) -> IoResult<()> {
+        self.movement0(Opcode::DownByZ, |s| s.v = s.v.wrapping_add(s.z))
+    }
+
+    /// Set *z* to *n* and move down by it.
+    pub fn set_z(&mut self, n: i32) -> IoResult<()> {
+        self.movement(Opcode::SetZ1, n, |s| {
+            s.z = n;
+            s.v = s.v.wrapping_add(n);
+        })
+    }
+
+    fn movement<F: FnOnce(&mut State)>(&mut self, opcode: Opcode, n: i32, f: F) -> IoResult<()> {
+        self.in_page()?;
+        let mut buf = Vec::new();
+        push_compact_signed(&mut buf, opcode as u8, n);
+        self.emit(&buf)?;
+        self.apply(f);
+        Ok(())
+    }
+
+    fn movement0<F: FnOnce(&mut State)>(&mut self, opcode: Opcode, f: F) -> IoResult<()> {
+        self.in_page()?;
+        self.emit(&[opcode as u8])?;
+        self.apply(f);
+        Ok(())
+    }
+
+    fn apply<F: FnOnce(&mut State)>(&mut self, f: F) {
+        let state = self.stack.last_mut().unwrap();
+        f(state);
+        let (h, v) = (state.h, state.v);
+        self.note_extent(h, v);
+    }
+
+    /// Finish the stream, emitting the postamble, and return the underlying
+    /// writer.
+    ///
+    /// This must not be called inside a page.
+    pub fn finish(mut self) -> IoResult<W> {
+        if !self.stack.is_empty() {
+            return Err(invalid("cannot finish the output inside a page"));
+        }
+
+        let post =
+            u32::try_from(self.offset).map_err(|_| invalid("DVI output is limited to 4 GiB"))?;
+
+        let mut buf = vec![Opcode::Postamble as u8];
+        buf.write_i32::<BigEndian>(self.last_bop)?;
+        buf.write_u32::<BigEndian>(UNIT_NUMERATOR)?;
+        buf.write_u32::<BigEndian>(UNIT_DENOMINATOR)?;
+        buf.write_u32::<BigEndian>(MAGNIFICATION)?;
+        buf.write_i32::<BigEndian>(self.max_v)?;
+        buf.write_i32::<BigEndian>(self.max_h)?;
+        buf.write_u16::<BigEndian>(self.max_stack_depth.min(u16::MAX as usize) as u16)?;
+        // Like TeX, keep only the low bits of the page count.
+        buf.write_u16::<BigEndian>(self.n_pages as u16)?;
+
+        for def in self.fonts.values() {
+            buf.extend_from_slice(def);
+        }
+
+        buf.push(Opcode::DoublePostamble as u8);
+        buf.write_u32::<BigEndian>(post)?;
+        buf.push(id_byte(self.filetype));
+
+        // At least four trailing 223's, padding the file to a multiple of four
+        // bytes.
+        let len = self.offset + buf.len() as u64;
+        let n_pad = 4 + (4 - len % 4) % 4;
+        buf.resize(buf.len() + n_pad as usize, 0xDF);
+
+        self.emit(&buf)?;
+        self.stream.flush()?;
+        Ok(self.stream)
+    }
+
+    fn emit(&mut self, buf: &[u8]) -> IoResult<()> {
+        self.stream.write_all(buf)?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+
+    fn in_page(&self) -> IoResult<()> {
+        if self.stack.is_empty() {
+            Err(invalid("this DVI command is only allowed inside a page"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn extended(&self) -> IoResult<()> {
+        if self.filetype == FileType::Dvi {
+            Err(invalid("native fonts are not allowed in plain DVI files"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn note_glyphs(&mut self, width: i32, x: &[i32], y: &[i32]) {
+        let state = self.stack.last_mut().unwrap();
+        let (h, v) = (state.h, state.v);
+        state.h = state.h.wrapping_add(width);
+
+        for (dx, dy) in x.iter().zip(y) {
+            self.note_extent(h.saturating_add(*dx), v.saturating_add(*dy));
+        }
+
+        self.note_extent(h.saturating_add(width), v);
+    }
+
+    fn note_extent(&mut self, h: i32, v: i32) {
+        self.max_h = self.max_h.max(h);
+        self.max_v = self.max_v.max(v);
+    }
+}
+
+fn invalid(msg: &str) -> IoError {
+    IoError::new(ErrorKind::InvalidInput, msg)
+}
+
+fn id_byte(filetype: FileType) -> u8 {
+    let id = match filetype {
+        FileType::Dvi => IdByte::Dvi,
+        FileType::Xdv => IdByte::Xdv,
+        FileType::Spx => IdByte::Spx,
+    };
+
+    id as u8
+}
+
+fn check_glyphs(glyphs: &[u16], x: &[i32], y: &[i32]) -> IoResult<()> {
+    if glyphs.len() != x.len() || glyphs.len() != y.len() {
+        return Err(invalid("glyph and position counts must agree"));
+    }
+
+    if glyphs.len() > u16::MAX as usize {
+        return Err(invalid("too many glyphs in one run"));
+    }
+
+    Ok(())
+}
+
+fn push_glyphs(buf: &mut Vec<u8>, glyphs: &[u16], x: &[i32], y: &[i32]) -> IoResult<()> {
+    for (dx, dy) in x.iter().zip(y) {
+        buf.write_i32::<BigEndian>(*dx)?;
+        buf.write_i32::<BigEndian>(*dy)?;
+    }
+
+    for g in glyphs {
+        buf.write_u16::<BigEndian>(*g)?;
+    }
+
+    Ok(())
+}
+
+/// Write an opcode with a one- to four-byte operand, where the one- to
+/// three-byte variants are unsigned (used by fnt_def, set_char, etc.).
+fn push_compact_unsigned(buf: &mut Vec<u8>, opcode1: u8, n: i32) {
+    let size = match n {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x1_0000..=0xFF_FFFF => 3,
+        _ => 4,
+    };
+
+    buf.push(opcode1 + size - 1);
+    buf.extend_from_slice(&n.to_be_bytes()[4 - size as usize..]);
+}
+
+/// Write an opcode with a one- to four-byte signed operand (used by right,
+/// down, etc.).
+fn push_compact_signed(buf: &mut Vec<u8>, opcode1: u8, n: i32) {
+    let size = match n {
+        -0x80..=0x7F => 1,
+        -0x8000..=0x7FFF => 2,
+        -0x80_0000..=0x7F_FFFF => 3,
+        _ => 4,
+    };
+
+    buf.push(opcode1 + size - 1);
+    buf.extend_from_slice(&n.to_be_bytes()[4 - size as usize..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{XdvError, XdvEvents, XdvParser};
+
+    #[derive(Debug)]
+    struct TestError;
+
+    impl From<XdvError> for TestError {
+        fn from(_: XdvError) -> Self {
+            TestError
+        }
+    }
+
+    impl From<IoError> for TestError {
+        fn from(_: IoError) -> Self {
+            TestError
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl XdvEvents for Recorder {
+        type Error = TestError;
+
+        fn handle_header(&mut self, filetype: FileType, comment: &[u8]) -> Result<(), TestError> {
+            self.0.push(format!(
+                "header {filetype} {}",
+                String::from_utf8_lossy(comment)
+            ));
+            Ok(())
+        }
+
+        fn handle_begin_page(
+            &mut self,
+            counters: &[i32],
+            previous_bop: i32,
+        ) -> Result<(), TestError> {
+            self.0.push(format!("bop {} {previous_bop}", counters[0]));
+            Ok(())
+        }
+
+        fn handle_special(&mut self, x: i32, y: i32, contents: &[u8]) -> Result<(), TestError> {
+            self.0.push(format!(
+                "special {x} {y} {}",
+                String::from_utf8_lossy(contents)
+            ));
+            Ok(())
+        }
+
+        fn handle_char_run(&mut self, font_num: i32, chars: &[i32]) -> Result<(), TestError> {
+            self.0.push(format!("chars {font_num} {chars:?}"));
+            Ok(())
+        }
+
+        fn handle_glyph_run(
+            &mut self,
+            font_num: i32,
+            glyphs: &[u16],
+            x: &[i32],
+            y: &[i32],
+        ) -> Result<(), TestError> {
+            self.0
+                .push(format!("glyphs {font_num} {glyphs:?} {x:?} {y:?}"));
+            Ok(())
+        }
+
+        fn handle_define_font(
+            &mut self,
+            area: &str,
+            name: &str,
+            font_num: i32,
+            _checksum: u32,
+            scale_factor: u32,
+            _design_size: u32,
+        ) -> Result<(), TestError> {
+            self.0
+                .push(format!("font {font_num} {area}{name} {scale_factor}"));
+            Ok(())
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        fn handle_define_native_font(
+            &mut self,
+            name: &str,
+            font_num: i32,
+            size: i32,
+            face_index: u32,
+            color_rgba: Option<u32>,
+            _extend: Option<u32>,
+            slant: Option<u32>,
+            _embolden: Option<u32>,
+        ) -> Result<(), TestError> {
+            self.0.push(format!(
+                "native {font_num} {name} {size} {face_index} {color_rgba:?} {slant:?}"
+            ));
+            Ok(())
+        }
+
+        fn handle_rule(
+            &mut self,
+            x: i32,
+            y: i32,
+            height: i32,
+            width: i32,
+        ) -> Result<(), TestError> {
+            self.0.push(format!("rule {x} {y} {height} {width}"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut w = XdvWriter::new(Vec::new(), FileType::Xdv, b"hello").unwrap();
+        w.define_font(300, 0xABCD, 655360, 655360, "", "cmr10")
+            .unwrap();
+        w.define_native_font(
+            "lmroman10-regular.otf",
+            1,
+            655360,
+            0,
+            Some(0xFF0000FF),
+            None,
+            Some(0x3333),
+            None,
+        )
+        .unwrap();
+
+        w.begin_page(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        w.set_font(300).unwrap();
+        w.set_char(65).unwrap();
+        w.set_char(1000).unwrap();
+        w.push().unwrap();
+        w.down(-200_000).unwrap();
+        w.set_w(70_000).unwrap();
+        w.special(b"color push red").unwrap();
+        w.pop().unwrap();
+        w.set_font(1).unwrap();
+        w.set_glyphs(1000, &[5, 6], &[0, 500], &[0, -10]).unwrap();
+        w.set_rule(400, 3000).unwrap();
+        assert!(w.pop().is_err());
+        w.end_page().unwrap();
+
+        w.begin_page(&[2, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        w.set_x(-5).unwrap();
+        w.right_by_x().unwrap();
+        w.put_rule(10, 20).unwrap();
+        w.end_page().unwrap();
+
+        assert!(w.special(b"outside").is_err());
+        let data = w.finish().unwrap();
+        assert_eq!(data.len() % 4, 0);
+
+        let expected = vec![
+            "header XDV hello",
+            "font 300 cmr10 655360",
+            "native 1 lmroman10-regular.otf 655360 0 Some(4278190335) Some(13107)",
+            "bop 1 -1",
+            "chars 300 [65, 1000]",
+            "special 70000 -200000 color push red",
+            "glyphs 1 [5, 6] [0, 500] [0, -10]",
+            "rule 1000 0 400 3000",
+            "bop 2 87",
+            "rule -10 0 10 20",
+            "native 1 lmroman10-regular.otf 655360 0 Some(4278190335) Some(13107)",
+            "font 300 cmr10 655360",
+        ];
+
+        let (events, n_bytes) = XdvParser::process(&data[..], Recorder::default()).unwrap();
+        assert_eq!(n_bytes, data.len() as u64);
+        assert_eq!(events.0, expected);
+
+        // Postamble-first parsing sees the postamble font definitions first.
+        let events =
+            XdvParser::process_with_seeks(std::io::Cursor::new(&data), Recorder::default())
+                .unwrap();
+        assert_eq!(events.0[..2], expected[10..]);
+        assert_eq!(events.0[2..], expected[..10]);
+    }
+
+    #[test]
+    fn dvi_restrictions() {
+        let mut w = XdvWriter::new(Vec::new(), FileType::Dvi, b"").unwrap();
+        assert!(w
+            .define_native_font("x", 0, 0, 0, None, None, None, None)
+            .is_err());
+        assert!(w.set_font(0).is_err());
+        w.define_font(0, 0, 0, 0, "", "cmr10").unwrap();
+        w.begin_page(&[0; 10]).unwrap();
+        w.set_font(0).unwrap();
+        assert!(w.set_glyphs(0, &[], &[], &[]).is_err());
+        w.end_page().unwrap();
+        let data = w.finish().unwrap();
+
+        let (events, _) = XdvParser::process(&data[..], Recorder::default()).unwrap();
+        assert_eq!(events.0[0], "header DVI ");
+    }
+
+    #[test]
+    fn positions_wrap() {
+        // Like TeX's, positions wrap around rather than overflowing.
+        let mut w = XdvWriter::new(Vec::new(), FileType::Xdv, b"").unwrap();
+        w.begin_page(&[0; 10]).unwrap();
+        w.right(i32::MAX).unwrap();
+        w.set_rule(1, 10).unwrap();
+        w.set_rule(1, 10).unwrap();
+        w.end_page().unwrap();
+        let data = w.finish().unwrap();
+
+        let (events, _) = XdvParser::process(&data[..], Recorder::default()).unwrap();
+        assert_eq!(
+            events.0[2..],
+            [
+                format!("rule {} 0 1 10", i32::MAX),
+                format!("rule {} 0 1 10", i32::MIN + 9),
+            ]
+        );
+    }
+}
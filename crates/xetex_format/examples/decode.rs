@@ -6,7 +6,7 @@
 use clap::Parser;
 use std::{fs::File, io::Read, path::PathBuf, process};
 use tectonic_errors::prelude::*;
-use tectonic_xetex_format::format::Format;
+use tectonic_xetex_format::{export::FormatExport, format::Format};
 
 #[derive(Debug, Parser)]
 #[clap(name = "decode", about = "Decode a Tectonic format file")]
@@ -21,6 +21,8 @@ impl Options {
             Commands::Actives(c) => c.execute_actives(),
             Commands::Catcodes(c) => c.execute_catcodes(),
             Commands::ControlSequences(c) => c.execute(),
+            Commands::Show(c) => c.execute(),
+            Commands::Json(c) => c.execute_json(),
             Commands::Strings(c) => c.execute_strings(),
         }
     }
@@ -35,6 +37,10 @@ enum Commands {
     #[command(name = "cseqs")]
     /// Dump the control sequences
     ControlSequences(CseqsCommand),
+    /// Export all decoded contents as JSON
+    Json(GenericCommand),
+    /// Show the meanings of control sequences, like TeX's `\show`
+    Show(ShowCommand),
     /// Dump the strings table
     Strings(GenericCommand),
 }
@@ -70,6 +76,14 @@ impl GenericCommand {
         Ok(())
     }
 
+    fn execute_json(self) -> Result<()> {
+        let fmt = self.parse()?;
+        let stdout = std::io::stdout();
+        let mut lock = stdout.lock();
+        FormatExport::new(&fmt)?.write_json(&mut lock)?;
+        Ok(())
+    }
+
     fn execute_strings(self) -> Result<()> {
         let fmt = self.parse()?;
         let stdout = std::io::stdout();
@@ -107,6 +121,38 @@ impl CseqsCommand {
     }
 }
 
+#[derive(Debug, Eq, PartialEq, Parser)]
+pub struct ShowCommand {
+    /// The format filename.
+    #[arg()]
+    path: PathBuf,
+
+    /// The names of the control sequences to show, without backslashes.
+    #[arg(required = true)]
+    names: Vec<String>,
+}
+
+impl ShowCommand {
+    fn execute(self) -> Result<()> {
+        let mut file = File::open(&self.path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        let fmt = Format::parse(&data[..])?;
+
+        for name in &self.names {
+            match fmt.show_cseq(name) {
+                Some(text) => println!("{text}"),
+                None => println!(
+                    "{}=undefined",
+                    tectonic_xetex_format::format::fmt_csname(name)
+                ),
+            }
+        }
+
+        Ok(())
+    }
+}
+
 fn main() {
     let options = Options::parse();
 
@@ -372,6 +372,12 @@ impl Commands {
         }
     }
 
+    /// Get the name of the symbol associated with a command code, e.g.
+    /// `CALL`.
+    pub fn symbol(&self, code: CommandCode) -> Option<&'static str> {
+        self.codes.get(&code).map(|cmd| cmd.symbol())
+    }
+
     pub fn describe_extended(
         &self,
         code: CommandCode,
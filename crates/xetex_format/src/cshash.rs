@@ -211,6 +211,11 @@ impl ControlSeqHash {
         // `if (text(p) >= str_ptr) => "NONEXISTENT."`
 
         let (text_ptr, _next_ptr) = self.decode(p);
-        Some(strings.lookup(text_ptr).to_owned())
+
+        if text_ptr <= 0 {
+            return None;
+        }
+
+        strings.resolve(text_ptr)
     }
 }
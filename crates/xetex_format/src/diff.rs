@@ -0,0 +1,330 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+#![deny(missing_docs)]
+
+//! Semantic comparison of two format files.
+//!
+//! Two format files generated from slightly different sources can differ
+//! byte-wise in all sorts of uninteresting ways. The comparison here is instead
+//! based on things that affect how documents are processed: the meanings of
+//! control sequences, character category codes, and the values of the integer
+//! and dimension parameters. Because everything is compared by name, formats
+//! from different engine versions can be compared, although some differences
+//! (e.g., in the numbering of registers) may then show up as noise.
+
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt::Display, io::Write};
+use tectonic_errors::prelude::*;
+
+use crate::{
+    catcodes::CatCode,
+    format::{fmt_csname, fmt_usv, valid_usvs, Format},
+    FormatVersion,
+};
+
+/// A change in a named item between two formats.
+///
+/// If `old` is `None`, the item was added; if `new` is `None`, it was removed.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Change<T> {
+    /// The name of the item.
+    pub name: String,
+
+    /// The value in the old format, if present.
+    pub old: Option<T>,
+
+    /// The value in the new format, if present.
+    pub new: Option<T>,
+}
+
+/// A change in the category codes of a range of characters.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct CatcodeChange {
+    /// The first character in the range, as a USV.
+    pub first: i32,
+
+    /// The last character in the range (inclusive), as a USV.
+    pub last: i32,
+
+    /// The category code in the old format.
+    pub old: u8,
+
+    /// The category code in the new format.
+    pub new: u8,
+}
+
+/// The differences between two format files.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct FormatDiff {
+    /// The engine version of the old format.
+    pub old_version: FormatVersion,
+
+    /// The engine version of the new format.
+    pub new_version: FormatVersion,
+
+    /// Control sequences whose meanings changed, as rendered by
+    /// [`Format::show_cseq`].
+    pub control_sequences: Vec<Change<String>>,
+
+    /// Characters whose category codes changed.
+    pub catcodes: Vec<CatcodeChange>,
+
+    /// Integer parameters whose values changed.
+    pub int_pars: Vec<Change<i32>>,
+
+    /// Dimension parameters whose values changed, in TeX scaled points.
+    pub dimen_pars: Vec<Change<i32>>,
+}
+
+impl FormatDiff {
+    /// Compare two formats.
+    pub fn new(old: &Format, new: &Format) -> Result<Self> {
+        let control_sequences = diff_maps(old.cseq_meanings(), new.cseq_meanings());
+        let int_pars = diff_maps(old.int_pars(), new.int_pars());
+        let dimen_pars = diff_maps(old.dimen_pars(), new.dimen_pars());
+
+        let mut catcodes: Vec<CatcodeChange> = Vec::new();
+
+        for chr in valid_usvs() {
+            let o = old.eqtb_catcode(chr)? as u8;
+            let n = new.eqtb_catcode(chr)? as u8;
+
+            if o == n {
+                continue;
+            }
+
+            if let Some(prev) = catcodes.last_mut() {
+                if prev.last == chr - 1 && prev.old == o && prev.new == n {
+                    prev.last = chr;
+                    continue;
+                }
+            }
+
+            catcodes.push(CatcodeChange {
+                first: chr,
+                last: chr,
+                old: o,
+                new: n,
+            });
+        }
+
+        Ok(FormatDiff {
+            old_version: old.engine().version,
+            new_version: new.engine().version,
+            control_sequences,
+            catcodes,
+            int_pars,
+            dimen_pars,
+        })
+    }
+
+    /// Returns true if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.control_sequences.is_empty()
+            && self.catcodes.is_empty()
+            && self.int_pars.is_empty()
+            && self.dimen_pars.is_empty()
+    }
+
+    /// Write out the differences in a human-readable form.
+    ///
+    /// Removed items are prefixed with `-` and added ones with `+`, as with
+    /// unified diffs.
+    pub fn write_text<W: Write>(&self, stream: &mut W) -> Result<()> {
+        if self.old_version != self.new_version {
+            writeln!(
+                stream,
+                "format versions differ: {} vs. {}",
+                self.old_version, self.new_version
+            )?;
+        }
+
+        for c in &self.control_sequences {
+            writeln!(stream, "{}", fmt_csname(&c.name))?;
+            write_change(stream, c, |m| m.clone())?;
+        }
+
+        for c in &self.catcodes {
+            if c.first == c.last {
+                writeln!(stream, "catcode of {}", fmt_usv(c.first))?;
+            } else {
+                writeln!(
+                    stream,
+                    "catcodes of {} - {}",
+                    fmt_usv(c.first),
+                    fmt_usv(c.last)
+                )?;
+            }
+
+            writeln!(stream, "- {}", describe_catcode(c.old))?;
+            writeln!(stream, "+ {}", describe_catcode(c.new))?;
+        }
+
+        for c in &self.int_pars {
+            writeln!(stream, "integer parameter {}", c.name)?;
+            write_change(stream, c, |v| v.to_string())?;
+        }
+
+        for c in &self.dimen_pars {
+            writeln!(stream, "dimension parameter {}", c.name)?;
+            write_change(stream, c, |v| fmt_scaled(*v))?;
+        }
+
+        Ok(())
+    }
+
+    /// Write out the differences as JSON.
+    pub fn write_json<W: Write>(&self, stream: &mut W) -> Result<()> {
+        serde_json::to_writer_pretty(&mut *stream, self)?;
+        writeln!(stream)?;
+        Ok(())
+    }
+}
+
+fn diff_maps<K: Display + Ord, V: PartialEq>(
+    mut old: BTreeMap<K, V>,
+    new: BTreeMap<K, V>,
+) -> Vec<Change<V>> {
+    let mut changes = Vec::new();
+
+    for (name, n) in new {
+        match old.remove(&name) {
+            Some(o) if o == n => {}
+            o => changes.push(Change {
+                name: name.to_string(),
+                old: o,
+                new: Some(n),
+            }),
+        }
+    }
+
+    for (name, o) in old {
+        changes.push(Change {
+            name: name.to_string(),
+            old: Some(o),
+            new: None,
+        });
+    }
+
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    changes
+}
+
+fn write_change<W: Write, T, F: Fn(&T) -> String>(
+    stream: &mut W,
+    change: &Change<T>,
+    fmt: F,
+) -> Result<()> {
+    if let Some(o) = &change.old {
+        writeln!(stream, "- {}", fmt(o))?;
+    }
+
+    if let Some(n) = &change.new {
+        writeln!(stream, "+ {}", fmt(n))?;
+    }
+
+    Ok(())
+}
+
+fn describe_catcode(cat: u8) -> &'static str {
+    CatCode::from_i32(cat as i32)
+        .map(|c| c.description())
+        .unwrap_or("invalid")
+}
+
+/// Format a dimension in scaled points as a TeX-style `pt` length.
+fn fmt_scaled(sp: i32) -> String {
+    format!("{}pt", sp as f64 / 65536.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{char_token, FormatBuilder, SUPPORTED_VERSIONS};
+
+    #[test]
+    fn map_diffs() {
+        let old = BTreeMap::from([("a", 1), ("b", 2), ("c", 3)]);
+        let new = BTreeMap::from([("b", 2), ("c", 4), ("d", 5)]);
+
+        let changes = diff_maps(old, new);
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    name: "a".into(),
+                    old: Some(1),
+                    new: None
+                },
+                Change {
+                    name: "c".into(),
+                    old: Some(3),
+                    new: Some(4)
+                },
+                Change {
+                    name: "d".into(),
+                    old: None,
+                    new: Some(5)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn format_diffs() {
+        for &version in SUPPORTED_VERSIONS {
+            let mut b = FormatBuilder::new(version);
+            b.define_macro("same", &[b.cmd_token("END_MATCH", '{')]);
+            b.define_macro(
+                "changed",
+                &[b.cmd_token("END_MATCH", '{'), char_token(11, 'a')],
+            );
+            b.define("removed", "RELAX", 0x11_0000);
+            let old = Format::parse(&b.build()).unwrap();
+
+            let mut b = FormatBuilder::new(version);
+            b.define_macro("same", &[b.cmd_token("END_MATCH", '{')]);
+            b.define_macro(
+                "changed",
+                &[
+                    b.cmd_token("MATCH", '#'),
+                    b.cmd_token("END_MATCH", '{'),
+                    b.cmd_token("OUT_PARAM", '\u{1}'),
+                ],
+            );
+            let new = Format::parse(&b.build()).unwrap();
+
+            let diff = FormatDiff::new(&old, &new).unwrap();
+            assert_eq!(diff.old_version, version);
+            assert_eq!(
+                diff.control_sequences,
+                vec![
+                    Change {
+                        name: "changed".into(),
+                        old: Some("\\changed=macro:->a".into()),
+                        new: Some("\\changed=macro:#1->#1".into()),
+                    },
+                    Change {
+                        name: "removed".into(),
+                        old: Some("\\removed=[relax]".into()),
+                        new: None,
+                    },
+                ]
+            );
+            assert!(diff.catcodes.is_empty());
+            assert!(diff.int_pars.is_empty());
+            assert!(diff.dimen_pars.is_empty());
+
+            let mut text = Vec::new();
+            diff.write_text(&mut text).unwrap();
+            assert_eq!(
+                String::from_utf8(text).unwrap(),
+                "\\changed\n- \\changed=macro:->a\n+ \\changed=macro:#1->#1\n\
+                 \\removed\n- \\removed=[relax]\n"
+            );
+
+            assert!(FormatDiff::new(&new, &new).unwrap().is_empty());
+        }
+    }
+}
@@ -23,6 +23,19 @@ pub struct DimenPar {
     since: FormatVersion,
 }
 
+impl DimenPar {
+    /// The internal name of the parameter, e.g. `par_indent`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The name of the TeX primitive that accesses this parameter, without a
+    /// leading backslash.
+    pub fn primitive_name(&self) -> String {
+        self.name.replace('_', "")
+    }
+}
+
 const DIMEN_PARS: &[DimenPar] = &[
     DimenPar {
         name: "par_indent",
@@ -149,7 +162,7 @@ pub fn emit_c_header_primitives<W: Write>(pars: &[DimenPar], mut stream: W) -> R
         writeln!(
             stream,
             "    {{ \"{}\", ASSIGN_DIMEN, DIMEN_BASE + DIMEN_PAR__{}, xf_prim_init_none }}, \\",
-            par.name.replace('_', ""),
+            par.primitive_name(),
             par.name.to_lowercase(),
         )?;
     }
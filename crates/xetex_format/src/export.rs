@@ -0,0 +1,430 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+#![deny(missing_docs)]
+
+//! Export of decoded format file contents as structured data.
+//!
+//! The types here define a stable JSON schema for the contents of a format
+//! file, so that tools can, say, index the macros defined by LaTeX without
+//! having to understand the binary format. They're built with
+//! [`FormatExport::new`] and serialized with `serde`. The schema is versioned
+//! with [`SCHEMA_VERSION`], which will be incremented if fields are ever
+//! removed or changed in incompatible ways. New fields may be added without
+//! changing the version.
+//!
+//! Not everything in the eqtb is exported yet. Missing are the box registers,
+//! the current and math fonts, the `\lccode`, `\uccode`, `\sfcode`,
+//! `\mathcode`, and `\delcode` tables, `\parshape` and the e-TeX penalty
+//! arrays, and the e-TeX registers numbered above 255, which are kept
+//! outside of the eqtb.
+
+use serde::Serialize;
+use std::{collections::BTreeMap, io::Write};
+use tectonic_errors::prelude::*;
+
+use crate::{
+    commands::CommandCode,
+    eqtb::EqtbPointer,
+    format::{valid_usvs, FontInfo, Format, GlueSpec, HyphenationInfo, Registers},
+    tokenlist::Token,
+    FormatVersion,
+};
+
+/// The version of the export schema.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The decoded contents of a format file.
+#[derive(Clone, Debug, Serialize)]
+pub struct FormatExport {
+    /// The version of this schema; see [`SCHEMA_VERSION`].
+    pub schema_version: u32,
+
+    /// The engine format version of the format file.
+    pub format_version: FormatVersion,
+
+    /// The string table. Strings with pointers below 0x10000 are implicit
+    /// single-character strings and aren't included.
+    pub strings: Vec<ExportedString>,
+
+    /// All defined control sequences, sorted by name.
+    pub control_sequences: Vec<ExportedControlSequence>,
+
+    /// Runs of characters with the same category code, in order.
+    pub catcodes: Vec<CatcodeRange>,
+
+    /// The values of the integer parameters, keyed by their internal names.
+    pub int_pars: BTreeMap<&'static str, i32>,
+
+    /// The values of the dimension parameters, in TeX scaled points, keyed by
+    /// their internal names.
+    pub dimen_pars: BTreeMap<&'static str, i32>,
+
+    /// The values of the glue parameters, keyed by their internal names.
+    pub glue_pars: BTreeMap<&'static str, GlueSpec>,
+
+    /// The nonempty token list parameters, such as `\everypar`, rendered in
+    /// TeX syntax and keyed by their internal names.
+    pub token_list_pars: BTreeMap<&'static str, String>,
+
+    /// The registers numbered 0 to 255 that have nonzero or nonempty values.
+    pub registers: Registers,
+
+    /// The fonts loaded in the format.
+    pub fonts: Vec<FontInfo>,
+
+    /// Information about the hyphenation data.
+    pub hyphenation: HyphenationInfo,
+}
+
+/// An entry in the string table.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportedString {
+    /// The string pointer.
+    pub pointer: i32,
+
+    /// The text of the string.
+    pub value: String,
+}
+
+/// A defined control sequence and its equivalent in the eqtb.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportedControlSequence {
+    /// The name of the control sequence, without the escape character.
+    pub name: String,
+
+    /// The location of the control sequence's entry in the eqtb.
+    pub eqtb_pointer: EqtbPointer,
+
+    /// The grouping level of the eqtb entry.
+    pub level: i16,
+
+    /// The command code of the eqtb entry.
+    pub command_code: i16,
+
+    /// The symbolic name of the command code, e.g. `CALL`, if known.
+    pub command: Option<&'static str>,
+
+    /// The raw value of the eqtb entry. Its meaning depends on the command.
+    pub value: i32,
+
+    /// The meaning of the control sequence, as would be printed by TeX’s
+    /// `\show`.
+    pub meaning: String,
+
+    /// If the control sequence is a macro, its definition.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub macro_definition: Option<ExportedMacro>,
+}
+
+/// A macro definition.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportedMacro {
+    /// Whether the macro is `\long`.
+    pub long: bool,
+
+    /// Whether the macro is `\outer`.
+    pub outer: bool,
+
+    /// Whether the macro is `\protected`.
+    pub protected: bool,
+
+    /// The parameter text, rendered in TeX syntax.
+    pub parameter_text: String,
+
+    /// The replacement text, rendered in TeX syntax.
+    pub replacement_text: String,
+
+    /// The tokens of the definition, including the parameter text.
+    pub tokens: Vec<ExportedToken>,
+}
+
+/// A token in a macro definition.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExportedToken {
+    /// A character token.
+    Char {
+        /// The character.
+        char: String,
+
+        /// Its category code.
+        catcode: i16,
+    },
+
+    /// A control sequence token.
+    ControlSequence {
+        /// The name of the control sequence, if it could be decoded.
+        name: Option<String>,
+
+        /// The eqtb pointer of the control sequence.
+        eqtb_pointer: EqtbPointer,
+    },
+
+    /// A parameter in the parameter text (e.g. `#1`).
+    Match {
+        /// The parameter number, counting from 1.
+        number: u32,
+    },
+
+    /// The end of the parameter text.
+    EndMatch,
+
+    /// A reference to a parameter in the replacement text (e.g. `#1`).
+    OutParam {
+        /// The parameter number.
+        number: i32,
+    },
+}
+
+/// A run of characters with the same category code.
+#[derive(Clone, Debug, Serialize)]
+pub struct CatcodeRange {
+    /// The first character of the run, as a USV.
+    pub first: i32,
+
+    /// The last character of the run (inclusive), as a USV.
+    pub last: i32,
+
+    /// The category code.
+    pub catcode: u8,
+}
+
+impl FormatExport {
+    /// Decode the contents of a format.
+    pub fn new(format: &Format) -> Result<Self> {
+        let strings = format
+            .strings()
+            .all_sps()
+            .map(|sp| ExportedString {
+                pointer: sp,
+                value: format.strings().lookup(sp).to_owned(),
+            })
+            .collect();
+
+        let mut control_sequences = Vec::new();
+
+        for (name, ptr) in format.cseqs() {
+            let meaning = match format.show_cseq_at(&name, ptr) {
+                Some(m) => m,
+                None => continue,
+            };
+
+            let entry = format.eqtb().decode(ptr);
+
+            let macro_definition = format.macro_kind(entry.ty).map(|(long, outer)| {
+                let text = format.show_macro(entry.value);
+
+                ExportedMacro {
+                    long,
+                    outer,
+                    protected: text.protected,
+                    parameter_text: text.parameters,
+                    replacement_text: text.replacement,
+                    tokens: export_macro_tokens(format, entry.value),
+                }
+            });
+
+            control_sequences.push(ExportedControlSequence {
+                name,
+                eqtb_pointer: ptr,
+                level: entry.level,
+                command_code: entry.ty,
+                command: format.engine().commands.symbol(entry.ty),
+                value: entry.value,
+                meaning,
+                macro_definition,
+            });
+        }
+
+        control_sequences.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut catcodes: Vec<CatcodeRange> = Vec::new();
+
+        for chr in valid_usvs() {
+            let cat = format.eqtb_catcode(chr)? as u8;
+
+            if let Some(prev) = catcodes.last_mut() {
+                if prev.catcode == cat && prev.last == chr - 1 {
+                    prev.last = chr;
+                    continue;
+                }
+            }
+
+            catcodes.push(CatcodeRange {
+                first: chr,
+                last: chr,
+                catcode: cat,
+            });
+        }
+
+        Ok(FormatExport {
+            schema_version: SCHEMA_VERSION,
+            format_version: format.engine().version,
+            strings,
+            control_sequences,
+            catcodes,
+            int_pars: format.int_pars(),
+            dimen_pars: format.dimen_pars(),
+            glue_pars: format.glue_pars(),
+            token_list_pars: format.token_list_pars(),
+            registers: format.registers(),
+            fonts: format.fonts().to_vec(),
+            hyphenation: format.hyphenation().clone(),
+        })
+    }
+
+    /// Write out the export as JSON.
+    pub fn write_json<W: Write>(&self, stream: &mut W) -> Result<()> {
+        serde_json::to_writer_pretty(&mut *stream, self)?;
+        writeln!(stream)?;
+        Ok(())
+    }
+}
+
+fn export_macro_tokens(format: &Format, p: i32) -> Vec<ExportedToken> {
+    let symbols = &format.engine().symbols;
+    let match_cmd = symbols.lookup("MATCH") as CommandCode;
+    let end_match_cmd = symbols.lookup("END_MATCH") as CommandCode;
+    let out_param_cmd = symbols.lookup("OUT_PARAM") as CommandCode;
+    let mut n_params = 0;
+
+    format
+        .toklist(p, true)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, tok)| {
+            Some(match tok {
+                // The marker for protected macros is reflected in `ExportedMacro`.
+                Token::Char { cmd, chr: 1 } if i == 0 && cmd == end_match_cmd => return None,
+
+                Token::Char { cmd, .. } if cmd == end_match_cmd => ExportedToken::EndMatch,
+
+                Token::Char { cmd, .. } if cmd == match_cmd => {
+                    n_params += 1;
+                    ExportedToken::Match { number: n_params }
+                }
+
+                Token::Char { cmd, chr } if cmd == out_param_cmd => {
+                    ExportedToken::OutParam { number: chr }
+                }
+
+                Token::Char { cmd, chr } => ExportedToken::Char {
+                    char: char::from_u32(chr as u32)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER)
+                        .to_string(),
+                    catcode: cmd,
+                },
+
+                Token::ControlSeq { ptr } => ExportedToken::ControlSequence {
+                    name: format.cs_name(ptr),
+                    eqtb_pointer: ptr,
+                },
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{char_token, FormatBuilder, SUPPORTED_VERSIONS};
+
+    fn export_json(b: &FormatBuilder) -> serde_json::Value {
+        let format = Format::parse(&b.build()).unwrap();
+        let export = FormatExport::new(&format).unwrap();
+
+        let mut json = Vec::new();
+        export.write_json(&mut json).unwrap();
+        serde_json::from_slice(&json).unwrap()
+    }
+
+    #[test]
+    fn export_hyphenation() {
+        for &version in SUPPORTED_VERSIONS {
+            let mut b = FormatBuilder::new(version);
+            b.hyphenation("manuscript", &[3, 5]);
+            let json = export_json(&b);
+            assert_eq!(json["format_version"], version);
+            assert_eq!(
+                json["hyphenation"]["exceptions"],
+                serde_json::json!([{ "word": "manuscript", "positions": [3, 5] }])
+            );
+        }
+    }
+
+    #[test]
+    fn export_macros() {
+        for &version in SUPPORTED_VERSIONS {
+            let mut b = FormatBuilder::new(version);
+            b.define_macro(
+                "foo",
+                &[
+                    b.cmd_token("END_MATCH", '\u{1}'),
+                    b.cmd_token("MATCH", '#'),
+                    b.cmd_token("END_MATCH", '{'),
+                    char_token(11, 'a'),
+                    b.cmd_token("OUT_PARAM", '\u{1}'),
+                ],
+            );
+            let json = export_json(&b);
+            let foo = &json["control_sequences"][0];
+            assert_eq!(foo["name"], "foo");
+            assert_eq!(foo["meaning"], "\\foo=\\protected macro:#1->a#1");
+
+            let m = &foo["macro_definition"];
+            assert_eq!(m["protected"], true);
+            assert_eq!(m["parameter_text"], "#1");
+            assert_eq!(m["replacement_text"], "a#1");
+            assert_eq!(
+                m["tokens"],
+                serde_json::json!([
+                    { "type": "match", "number": 1 },
+                    { "type": "end_match" },
+                    { "type": "char", "char": "a", "catcode": 11 },
+                    { "type": "out_param", "number": 1 },
+                ])
+            );
+        }
+    }
+
+    #[test]
+    fn export_parameters_and_registers() {
+        for &version in SUPPORTED_VERSIONS {
+            let mut b = FormatBuilder::new(version);
+            let fil = b.glue_spec(65536, (65536, 1), (0, 0));
+            b.set_eqtb("GLUE_BASE", 2, fil); // par_skip
+            b.set_eqtb("SKIP_BASE", 10, fil);
+            b.set_eqtb("COUNT_BASE", 1, 42);
+            b.set_eqtb("SCALED_BASE", 255, -1);
+            let toks = b.toks(&[char_token(11, 'x'), b.cmd_token("MAC_PARAM", '#')]);
+            b.set_eqtb("LOCAL_BASE", 2, toks); // every_par
+            b.set_eqtb("TOKS_BASE", 0, toks);
+
+            let json = export_json(&b);
+            let fil = serde_json::json!({
+                "width": 65536,
+                "stretch": 65536,
+                "stretch_order": 1,
+                "shrink": 0,
+                "shrink_order": 0,
+            });
+            assert_eq!(json["glue_pars"]["par_skip"], fil);
+            assert_eq!(json["glue_pars"]["line_skip"]["width"], 0);
+            assert_eq!(
+                json["token_list_pars"],
+                serde_json::json!({ "every_par": "x##" })
+            );
+            assert_eq!(
+                json["registers"],
+                serde_json::json!({
+                    "counts": { "1": 42 },
+                    "dimens": { "255": -1 },
+                    "skips": { "10": fil },
+                    "mu_skips": {},
+                    "toks": { "0": "x##" },
+                })
+            );
+        }
+    }
+}
@@ -8,16 +8,18 @@ use nom::{
     number::complete::{be_i16, be_i32, be_i64, be_u16},
     Err as NomErr, IResult,
 };
-use std::io::Write;
+use serde::Serialize;
+use std::{collections::BTreeMap, io::Write};
 use tectonic_errors::prelude::*;
 
 use crate::{
-    base::{MAX_HALFWORD, MIN_HALFWORD, TEX_NULL},
+    base::{self, MAX_HALFWORD, MIN_HALFWORD, NUMBER_REGS, SIZEOF_MEMORY_WORD, TEX_NULL},
     catcodes::CatCode,
     commands::CommandCode,
     cshash,
     engine::Engine,
     eqtb::{self, EqtbPointer},
+    locals::LocalParKind,
     mem, parseutils, stringtable,
     tokenlist::Token,
     FormatVersion,
@@ -35,6 +37,122 @@ pub struct Format {
     mem: mem::Memory,
     eqtb: eqtb::EquivalenciesTable,
     cshash: cshash::ControlSeqHash,
+    fonts: Vec<FontInfo>,
+    hyphenation: HyphenationInfo,
+}
+
+/// Information about a font that was loaded when a format was dumped.
+#[derive(Clone, Debug, Serialize)]
+pub struct FontInfo {
+    /// The name of the font, as given to `\font`.
+    pub name: String,
+
+    /// The "area" (directory) of the font, usually empty.
+    pub area: String,
+
+    /// The size at which the font is loaded, in TeX scaled points.
+    pub size: i32,
+
+    /// The design size of the font, in TeX scaled points.
+    pub design_size: i32,
+
+    /// The number of font parameters (`\fontdimen`s).
+    pub n_params: i32,
+
+    /// The font's `\hyphenchar`.
+    pub hyphen_char: i32,
+
+    /// The font's `\skewchar`.
+    pub skew_char: i32,
+
+    /// The smallest character code in the font.
+    pub first_char: i16,
+
+    /// The largest character code in the font.
+    pub last_char: i16,
+}
+
+/// A hyphenation exception defined with `\hyphenation`.
+#[derive(Clone, Debug, Serialize)]
+pub struct HyphenationException {
+    /// The word, in lowercase.
+    pub word: String,
+
+    /// The positions after which the word may be hyphenated, counting from 1,
+    /// in ascending order.
+    pub positions: Vec<i32>,
+}
+
+/// Information about the hyphenation data stored in a format.
+#[derive(Clone, Debug, Serialize)]
+pub struct HyphenationInfo {
+    /// The hyphenation exceptions.
+    pub exceptions: Vec<HyphenationException>,
+
+    /// The size of the packed hyphenation pattern trie.
+    pub trie_size: i32,
+
+    /// The number of hyphenation trie operations.
+    pub trie_op_count: i32,
+
+    /// The largest character code that appears in the patterns.
+    pub max_hyph_char: i32,
+
+    /// The language numbers for which hyphenation patterns are defined.
+    pub pattern_languages: Vec<usize>,
+}
+
+/// A glue specification, as stored in a glue parameter or register.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct GlueSpec {
+    /// The natural width, in TeX scaled points.
+    pub width: i32,
+
+    /// The stretchability, in TeX scaled points if finite.
+    pub stretch: i32,
+
+    /// The order of infinity of the stretchability: 0 for finite, 1 for
+    /// `fil`, 2 for `fill`, and 3 for `filll`.
+    pub stretch_order: i16,
+
+    /// The shrinkability, in TeX scaled points if finite.
+    pub shrink: i32,
+
+    /// The order of infinity of the shrinkability, as with `stretch_order`.
+    pub shrink_order: i16,
+}
+
+/// The contents of the registers that are stored in the eqtb: those numbered
+/// 0 to 255. Only registers with nonzero or nonempty values are included.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Registers {
+    /// The `\count` registers.
+    pub counts: BTreeMap<usize, i32>,
+
+    /// The `\dimen` registers, in TeX scaled points.
+    pub dimens: BTreeMap<usize, i32>,
+
+    /// The `\skip` registers.
+    pub skips: BTreeMap<usize, GlueSpec>,
+
+    /// The `\muskip` registers, in units of 1/65536 mu.
+    pub mu_skips: BTreeMap<usize, GlueSpec>,
+
+    /// The `\toks` registers, rendered in TeX syntax.
+    pub toks: BTreeMap<usize, String>,
+}
+
+/// A macro definition rendered in TeX syntax.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MacroText {
+    /// Whether the macro is `\protected`.
+    pub protected: bool,
+
+    /// The parameter text, e.g. `#1#2`.
+    pub parameters: String,
+
+    /// The replacement text.
+    pub replacement: String,
 }
 
 // Parsing
@@ -175,7 +293,361 @@ impl Format {
         Ok(())
     }
 
-    fn cseqs(&self) -> impl Iterator<Item = (String, EqtbPointer)> {
+    /// Look up a control sequence by name.
+    ///
+    /// As in TeX, single-character names refer to the single-character control
+    /// sequences, and the empty name refers to the null control sequence
+    /// (`\csname\endcsname`). Returns `None` if the name has never been used
+    /// as a multi-letter control sequence.
+    pub fn lookup_cseq(&self, name: &str) -> Option<EqtbPointer> {
+        let mut chars = name.chars();
+
+        match (chars.next(), chars.next()) {
+            (None, _) => Some(self.engine.symbols.lookup("NULL_CS") as EqtbPointer),
+            (Some(c), None) => {
+                Some(self.engine.symbols.lookup("SINGLE_BASE") as EqtbPointer + c as i32)
+            }
+            _ => self.cshash.lookup(name, &self.strings),
+        }
+    }
+
+    /// Describe the meaning of a control sequence, like TeX's `\show`
+    /// primitive.
+    ///
+    /// Macros are rendered in TeX syntax, with their prefixes, parameter text,
+    /// and replacement text, e.g. `\foo=\long macro:#1->\bar {#1}`. Other
+    /// commands are described the same way as in [`Self::dump_cseqs`]. Returns
+    /// `None` if the control sequence is undefined.
+    pub fn show_cseq(&self, name: &str) -> Option<String> {
+        self.show_cseq_at(name, self.lookup_cseq(name)?)
+    }
+
+    pub(crate) fn show_cseq_at(&self, name: &str, ptr: EqtbPointer) -> Option<String> {
+        let undefined_cs_cmd = self.engine.symbols.lookup("UNDEFINED_CS") as CommandCode;
+
+        let entry = self.eqtb.decode(ptr);
+
+        if entry.ty == undefined_cs_cmd {
+            return None;
+        }
+
+        let cs_desc = fmt_csname(name);
+
+        if let Some((is_long, is_outer)) = self.macro_kind(entry.ty) {
+            let m = self.show_macro(entry.value);
+            let mut prefixes = String::new();
+
+            if m.protected {
+                prefixes.push_str("\\protected");
+            }
+            if is_long {
+                prefixes.push_str("\\long");
+            }
+            if is_outer {
+                prefixes.push_str("\\outer");
+            }
+            if !prefixes.is_empty() {
+                prefixes.push(' ');
+            }
+
+            return Some(format!(
+                "{cs_desc}={prefixes}macro:{}->{}",
+                m.parameters, m.replacement
+            ));
+        }
+
+        let cmd_desc = self.engine.commands.describe(entry.ty, entry.value);
+        Some(format!("{cs_desc}={cmd_desc}"))
+    }
+
+    /// Get the meanings of all of the defined control sequences, as rendered by
+    /// [`Self::show_cseq`], keyed by name.
+    pub fn cseq_meanings(&self) -> BTreeMap<String, String> {
+        self.cseqs()
+            .filter_map(|(name, ptr)| {
+                let meaning = self.show_cseq_at(&name, ptr)?;
+                Some((name, meaning))
+            })
+            .collect()
+    }
+
+    /// Get the values of the engine's integer parameters, keyed by their
+    /// internal names.
+    pub fn int_pars(&self) -> BTreeMap<&'static str, i32> {
+        let int_base = self.engine.symbols.lookup("INT_BASE") as EqtbPointer;
+
+        self.engine
+            .int_pars
+            .iter()
+            .enumerate()
+            .map(|(i, par)| (par.name(), self.eqtb.decode(int_base + i as i32).value))
+            .collect()
+    }
+
+    /// Get the values of the engine's dimension parameters, in TeX scaled
+    /// points, keyed by their internal names.
+    pub fn dimen_pars(&self) -> BTreeMap<&'static str, i32> {
+        let dimen_base = self.engine.symbols.lookup("DIMEN_BASE") as EqtbPointer;
+
+        self.engine
+            .dimen_pars
+            .iter()
+            .enumerate()
+            .map(|(i, par)| (par.name(), self.eqtb.decode(dimen_base + i as i32).value))
+            .collect()
+    }
+
+    /// Get the values of the engine's glue parameters, keyed by their
+    /// internal names.
+    pub fn glue_pars(&self) -> BTreeMap<&'static str, GlueSpec> {
+        let glue_base = self.engine.symbols.lookup("GLUE_BASE") as EqtbPointer;
+
+        self.engine
+            .glue_pars
+            .iter()
+            .enumerate()
+            .map(|(i, par)| {
+                let p = self.eqtb.decode(glue_base + i as i32).value;
+                (par.name(), self.glue_spec(p))
+            })
+            .collect()
+    }
+
+    /// Get the values of the engine's token list parameters, such as
+    /// `\everypar`, rendered in TeX syntax and keyed by their internal names.
+    /// Empty token lists are omitted.
+    pub fn token_list_pars(&self) -> BTreeMap<&'static str, String> {
+        let local_base = self.engine.symbols.lookup("LOCAL_BASE") as EqtbPointer;
+
+        self.engine
+            .local_pars
+            .iter()
+            .enumerate()
+            .filter(|(_, par)| par.kind() == LocalParKind::TokenList)
+            .filter_map(|(i, par)| {
+                let p = self.eqtb.decode(local_base + i as i32).value;
+                self.show_toks(p).map(|text| (par.name(), text))
+            })
+            .collect()
+    }
+
+    /// Get the contents of the registers stored in the eqtb.
+    pub fn registers(&self) -> Registers {
+        let sym = |name| self.engine.symbols.lookup(name) as EqtbPointer;
+        let (count_base, scaled_base) = (sym("COUNT_BASE"), sym("SCALED_BASE"));
+        let (skip_base, mu_skip_base) = (sym("SKIP_BASE"), sym("MU_SKIP_BASE"));
+        let toks_base = sym("TOKS_BASE");
+        let value = |base: EqtbPointer, n: usize| self.eqtb.decode(base + n as i32).value;
+        let mut regs = Registers::default();
+
+        for n in 0..NUMBER_REGS {
+            if value(count_base, n) != 0 {
+                regs.counts.insert(n, value(count_base, n));
+            }
+
+            if value(scaled_base, n) != 0 {
+                regs.dimens.insert(n, value(scaled_base, n));
+            }
+
+            let skip = self.glue_spec(value(skip_base, n));
+            if skip != GlueSpec::default() {
+                regs.skips.insert(n, skip);
+            }
+
+            let mu_skip = self.glue_spec(value(mu_skip_base, n));
+            if mu_skip != GlueSpec::default() {
+                regs.mu_skips.insert(n, mu_skip);
+            }
+
+            if let Some(text) = self.show_toks(value(toks_base, n)) {
+                regs.toks.insert(n, text);
+            }
+        }
+
+        regs
+    }
+
+    /// Decode the glue specification node at *p*.
+    fn glue_spec(&self, p: mem::MemPointer) -> GlueSpec {
+        let mem = &self.mem.mem[..];
+
+        GlueSpec {
+            width: base::memword_read_b32_s1(mem, p + 1),
+            stretch: base::memword_read_b32_s1(mem, p + 2),
+            stretch_order: base::memword_read_b16_s1(mem, p),
+            shrink: base::memword_read_b32_s1(mem, p + 3),
+            shrink_order: base::memword_read_b16_s0(mem, p),
+        }
+    }
+
+    /// Render the token list at *p*, which starts with a reference count, in
+    /// TeX syntax. Returns `None` if the list is empty.
+    fn show_toks(&self, p: mem::MemPointer) -> Option<String> {
+        if p == TEX_NULL {
+            return None;
+        }
+
+        let text = self.show_macro(p).parameters;
+
+        if text.is_empty() {
+            None
+        } else {
+            Some(text)
+        }
+    }
+
+    /// The engine that this format was created for.
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    pub(crate) fn strings(&self) -> &stringtable::StringTable {
+        &self.strings
+    }
+
+    pub(crate) fn eqtb(&self) -> &eqtb::EquivalenciesTable {
+        &self.eqtb
+    }
+
+    /// Get the name of the control sequence at an eqtb location, if it can
+    /// be decoded.
+    pub(crate) fn cs_name(&self, ptr: EqtbPointer) -> Option<String> {
+        self.cshash.stringify(ptr, &self.strings)
+    }
+
+    /// The fonts that were loaded when the format was dumped. The first one is
+    /// always the `\nullfont`.
+    pub fn fonts(&self) -> &[FontInfo] {
+        &self.fonts
+    }
+
+    /// Information about the hyphenation data stored in the format.
+    pub fn hyphenation(&self) -> &HyphenationInfo {
+        &self.hyphenation
+    }
+
+    /// If the command code is one of the macro-call commands, return whether
+    /// the macro is `\long` and whether it is `\outer`.
+    pub(crate) fn macro_kind(&self, cmd: CommandCode) -> Option<(bool, bool)> {
+        let macro_kinds = [
+            ("CALL", false, false),
+            ("LONG_CALL", true, false),
+            ("OUTER_CALL", false, true),
+            ("LONG_OUTER_CALL", true, true),
+        ];
+
+        macro_kinds
+            .iter()
+            .find(|(sym, _, _)| cmd == self.engine.symbols.lookup(sym) as CommandCode)
+            .map(|(_, is_long, is_outer)| (*is_long, *is_outer))
+    }
+
+    /// Get the tokens of a token list, skipping the reference count if it is
+    /// a macro definition.
+    pub(crate) fn toklist(&self, mut p: mem::MemPointer, is_macro: bool) -> Vec<Token> {
+        let mut tokens = Vec::new();
+
+        if is_macro {
+            p = self.mem.decode_toklist(p).1;
+        }
+
+        while p != TEX_NULL {
+            let (value, next) = self.mem.decode_toklist(p);
+            tokens.push(Token::from(value));
+            p = next;
+        }
+
+        tokens
+    }
+
+    /// Render a macro definition in TeX syntax, similarly to TeX's
+    /// `show_token_list`.
+    pub(crate) fn show_macro(&self, p: mem::MemPointer) -> MacroText {
+        let mut m = MacroText::default();
+        let mut in_replacement = false;
+        let mut n_params = 0;
+        let c = |chr: i32| char::from_u32(chr as u32).unwrap_or(char::REPLACEMENT_CHARACTER);
+        let match_cmd = self.engine.symbols.lookup("MATCH") as CommandCode;
+        let end_match_cmd = self.engine.symbols.lookup("END_MATCH") as CommandCode;
+        let out_param_cmd = self.engine.symbols.lookup("OUT_PARAM") as CommandCode;
+        let mac_param_cmd = self.engine.symbols.lookup("MAC_PARAM") as CommandCode;
+
+        for (i, tok) in self.toklist(p, true).into_iter().enumerate() {
+            let text = if in_replacement {
+                &mut m.replacement
+            } else {
+                &mut m.parameters
+            };
+
+            match tok {
+                // e-TeX marks protected macros with a special first token.
+                Token::Char { cmd, chr: 1 } if i == 0 && cmd == end_match_cmd => m.protected = true,
+
+                Token::Char { cmd, .. } if cmd == end_match_cmd => in_replacement = true,
+
+                // The character is the parameter character in use.
+                Token::Char { cmd, chr } if cmd == match_cmd => {
+                    n_params += 1;
+                    text.push(c(chr));
+                    text.push_str(&n_params.to_string());
+                }
+
+                Token::Char { cmd, chr } if cmd == out_param_cmd => {
+                    text.push('#');
+                    text.push_str(&chr.to_string());
+                }
+
+                // Parameter characters in the replacement text are doubled.
+                Token::Char { cmd, chr } if cmd == mac_param_cmd => {
+                    text.push(c(chr));
+                    text.push(c(chr));
+                }
+
+                Token::Char { chr, .. } => text.push(c(chr)),
+
+                Token::ControlSeq { ptr } => text.push_str(&self.show_cs_pointer(ptr)),
+            }
+        }
+
+        m
+    }
+
+    /// Similar to TeX's `print_cs`: the control sequence name is followed by a
+    /// space unless it consists of a single non-letter.
+    pub(crate) fn show_cs_pointer(&self, ptr: EqtbPointer) -> String {
+        let active_base = self.engine.symbols.lookup("ACTIVE_BASE") as EqtbPointer;
+        let single_base = self.engine.symbols.lookup("SINGLE_BASE") as EqtbPointer;
+        let null_cs = self.engine.symbols.lookup("NULL_CS") as EqtbPointer;
+
+        if ptr == null_cs {
+            return "\\csname\\endcsname ".to_owned();
+        }
+
+        if (active_base..single_base).contains(&ptr) {
+            if let Some(c) = char::from_u32((ptr - active_base) as u32) {
+                return c.to_string();
+            }
+        }
+
+        match self.cshash.stringify(ptr, &self.strings) {
+            Some(text) => {
+                let mut chars = text.chars();
+
+                match (chars.next(), chars.next()) {
+                    (Some(c), None)
+                        if !matches!(self.eqtb_catcode(c as i32), Ok(CatCode::Letter)) =>
+                    {
+                        format!("\\{c}")
+                    }
+                    _ => format!("\\{text} "),
+                }
+            }
+
+            None => format!("[undecodable cseq pointer {ptr}]"),
+        }
+    }
+
+    pub(crate) fn cseqs(&self) -> impl Iterator<Item = (String, EqtbPointer)> {
         // This is lame; we shouldn't need to make a big buffer, but I'm too
         // lazy to write real iterater implementation right now.
 
@@ -287,7 +759,7 @@ impl Format {
             .decode(self.engine.symbols.lookup("ACTIVE_BASE") as EqtbPointer + c)
     }
 
-    fn eqtb_catcode(&self, c: i32) -> Result<CatCode> {
+    pub(crate) fn eqtb_catcode(&self, c: i32) -> Result<CatCode> {
         assert!((0..MAX_USV).contains(&c));
         CatCode::from_i32(
             self.eqtb
@@ -353,18 +825,32 @@ fn parse_body(engine: Engine, input: &[u8]) -> IResult<&[u8], Format> {
 
     let n_fonts = font_ptr as usize + 1;
     let (input, _font_check) = count(be_i64, n_fonts)(input)?;
-    let (input, _font_size) = count(be_i32, n_fonts)(input)?;
-    let (input, _font_dsize) = count(be_i32, n_fonts)(input)?;
-    let (input, _font_params) = count(
+    let (input, font_size) = count(be_i32, n_fonts)(input)?;
+    let (input, font_dsize) = count(be_i32, n_fonts)(input)?;
+    let (input, font_params) = count(
         parseutils::ranged_be_i32(MIN_HALFWORD, MAX_HALFWORD),
         n_fonts,
     )(input)?;
-    let (input, _hyphen_char) = count(be_i32, n_fonts)(input)?;
-    let (input, _skew_char) = count(be_i32, n_fonts)(input)?;
-    let (input, _font_name) = count(be_i32, n_fonts)(input)?;
-    let (input, _font_area) = count(be_i32, n_fonts)(input)?;
-    let (input, _font_bc) = count(be_i16, n_fonts)(input)?;
-    let (input, _font_ec) = count(be_i16, n_fonts)(input)?;
+    let (input, hyphen_char) = count(be_i32, n_fonts)(input)?;
+    let (input, skew_char) = count(be_i32, n_fonts)(input)?;
+    let (input, font_name) = count(be_i32, n_fonts)(input)?;
+    let (input, font_area) = count(be_i32, n_fonts)(input)?;
+    let (input, font_bc) = count(be_i16, n_fonts)(input)?;
+    let (input, font_ec) = count(be_i16, n_fonts)(input)?;
+
+    let fonts = (0..n_fonts)
+        .map(|k| FontInfo {
+            name: strings.resolve(font_name[k]).unwrap_or_default(),
+            area: strings.resolve(font_area[k]).unwrap_or_default(),
+            size: font_size[k],
+            design_size: font_dsize[k],
+            n_params: font_params[k],
+            hyphen_char: hyphen_char[k],
+            skew_char: skew_char[k],
+            first_char: font_bc[k],
+            last_char: font_ec[k],
+        })
+        .collect();
     let (input, _char_base) = count(be_i32, n_fonts)(input)?;
     let (input, _width_base) = count(be_i32, n_fonts)(input)?;
     let (input, _height_base) = count(be_i32, n_fonts)(input)?;
@@ -395,6 +881,8 @@ fn parse_body(engine: Engine, input: &[u8]) -> IResult<&[u8], Format> {
     let mut hyph_list = vec![0i32; HYPH_SIZE];
     let mut input = input;
     let max_word = strings.len() as i32 + TOO_BIG_CHAR - 1;
+    let mut hyph_exceptions = Vec::new();
+    let mem_range = 0..(mem.mem.len() / SIZEOF_MEMORY_WORD) as i32;
 
     for _ in 0..hyph_count {
         let (ii, mut j) = be_i32(input)?;
@@ -414,6 +902,26 @@ fn parse_body(engine: Engine, input: &[u8]) -> IResult<&[u8], Format> {
         let (ii, l) = parseutils::ranged_be_i32(MIN_HALFWORD, MAX_HALFWORD)(ii)?;
         hyph_list[j as usize] = l;
 
+        // The hyphen positions are stored as a linked list in `mem`, with the
+        // same structure as a token list. TeX prepends each position as it
+        // scans the word, so they come out in descending order. Words are at
+        // most 63 characters long, which protects us from looping forever.
+        let mut positions = Vec::new();
+        let mut p = l;
+
+        while mem_range.contains(&p) && positions.len() < 64 {
+            let (pos, next) = mem.decode_toklist(p);
+            positions.push(pos);
+            p = next;
+        }
+
+        positions.reverse();
+
+        hyph_exceptions.push(HyphenationException {
+            word: strings.resolve(w).unwrap_or_default(),
+            positions,
+        });
+
         input = ii;
     }
 
@@ -428,7 +936,7 @@ fn parse_body(engine: Engine, input: &[u8]) -> IResult<&[u8], Format> {
     let (input, _trie_tro) = count(be_i32, n_trie)(input)?;
     let (input, _trie_trc) = count(be_u16, n_trie)(input)?;
 
-    let (input, _max_hyph_char) = be_i32(input)?;
+    let (input, max_hyph_char) = be_i32(input)?;
 
     let (input, trie_op_ptr) = parseutils::ranged_be_i32(0, TRIE_OP_SIZE)(input)?;
 
@@ -458,17 +966,27 @@ fn parse_body(engine: Engine, input: &[u8]) -> IResult<&[u8], Format> {
 
     let (input, _) = parseutils::satisfy_be_i32(FOOTER_MAGIC)(input)?;
 
+    let hyphenation = HyphenationInfo {
+        exceptions: hyph_exceptions,
+        trie_size: trie_max,
+        trie_op_count: trie_op_ptr,
+        max_hyph_char,
+        pattern_languages: (0..=BIGGEST_LANG).filter(|k| trie_used[*k] > 0).collect(),
+    };
+
     let fmt = Format {
         engine,
         strings,
         mem,
         eqtb,
         cshash,
+        fonts,
+        hyphenation,
     };
     Ok((input, fmt))
 }
 
-fn valid_usvs() -> impl Iterator<Item = i32> {
+pub(crate) fn valid_usvs() -> impl Iterator<Item = i32> {
     (0..0xD800).chain(0xE000..0x11_0000)
 }
 
@@ -507,3 +1025,83 @@ pub fn fmt_csname<S: AsRef<str>>(name: S) -> String {
         (_, true) => format!("\"\\{name}\""),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{char_token, cs_token, FormatBuilder, SUPPORTED_VERSIONS};
+
+    #[test]
+    fn hyphenation_positions() {
+        for &version in SUPPORTED_VERSIONS {
+            let mut b = FormatBuilder::new(version);
+            b.hyphenation("manuscript", &[3, 5]);
+            b.hyphenation("table", &[]);
+            let format = Format::parse(&b.build()).unwrap();
+            assert_eq!(format.engine.version, version);
+
+            let exceptions = &format.hyphenation().exceptions;
+            assert_eq!(exceptions.len(), 2);
+            assert_eq!(exceptions[0].word, "manuscript");
+            assert_eq!(exceptions[0].positions, vec![3, 5]);
+            assert_eq!(exceptions[1].word, "table");
+            assert!(exceptions[1].positions.is_empty());
+        }
+    }
+
+    #[test]
+    fn cseq_lookups() {
+        for &version in SUPPORTED_VERSIONS {
+            let mut b = FormatBuilder::new(version);
+            let relax = b.define("relax", "RELAX", 0x11_0000);
+            let foo = b.define_macro(
+                "foo",
+                &[
+                    // The parameter text `#1`, then `a#1` with the parameter
+                    // referenced by its number.
+                    b.cmd_token("MATCH", '#'),
+                    b.cmd_token("END_MATCH", '{'),
+                    char_token(11, 'a'),
+                    b.cmd_token("OUT_PARAM", '\u{1}'),
+                    cs_token(relax),
+                ],
+            );
+            b.define_macro(
+                "bar",
+                &[
+                    // A protected macro expanding to `##`.
+                    b.cmd_token("END_MATCH", '\u{1}'),
+                    b.cmd_token("END_MATCH", '{'),
+                    b.cmd_token("MAC_PARAM", '#'),
+                ],
+            );
+            let format = Format::parse(&b.build()).unwrap();
+
+            assert_eq!(format.lookup_cseq("relax"), Some(relax));
+            assert_eq!(
+                format.show_cseq("relax").as_deref(),
+                Some("\\relax=[relax]")
+            );
+
+            assert_eq!(format.lookup_cseq("foo"), Some(foo));
+            assert_eq!(
+                format.show_cseq("foo").as_deref(),
+                Some("\\foo=macro:#1->a#1\\relax ")
+            );
+            assert_eq!(
+                format.show_cseq("bar").as_deref(),
+                Some("\\bar=\\protected macro:->##")
+            );
+
+            assert_eq!(format.lookup_cseq("undefined"), None);
+            assert_eq!(format.show_cseq("undefined"), None);
+
+            // Single-character control sequences are always found, but these
+            // aren't defined.
+            let single_base = format.engine.symbols.lookup("SINGLE_BASE") as EqtbPointer;
+            assert_eq!(format.lookup_cseq("a"), Some(single_base + 'a' as i32));
+            assert_eq!(format.show_cseq("a"), None);
+            assert_eq!(format.show_cseq(""), None);
+        }
+    }
+}
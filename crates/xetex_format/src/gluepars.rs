@@ -36,6 +36,13 @@ pub struct GluePar {
     since: FormatVersion,
 }
 
+impl GluePar {
+    /// The internal name of the parameter, e.g. `baseline_skip`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
 const GLUE_PARS: &[GluePar] = &[
     GluePar {
         name: "line_skip",
@@ -42,6 +42,23 @@ pub struct IntPar {
     until: FormatVersion,
 }
 
+impl IntPar {
+    /// The internal name of the parameter, e.g. `line_penalty`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The name of the TeX primitive that accesses this parameter, without a
+    /// leading backslash, if there is one.
+    pub fn primitive_name(&self) -> Option<String> {
+        match self.primitive_kind {
+            IntParPrimitiveKind::None => None,
+            IntParPrimitiveKind::Standard => Some(self.name.replace('_', "")),
+            IntParPrimitiveKind::CustomName(s) => Some(s.replace('_', "")),
+        }
+    }
+}
+
 const INT_PARS: &[IntPar] = &[
     IntPar {
         name: "pretolerance",
@@ -589,17 +606,11 @@ pub fn get_intpars_for_version(
 /// Emit initializers for intpar primitives in the C header.
 pub fn emit_c_header_primitives<W: Write>(pars: &[IntPar], mut stream: W) -> Result<()> {
     for par in pars {
-        let (has_prim, prim_name) = match par.primitive_kind {
-            IntParPrimitiveKind::None => (false, ""),
-            IntParPrimitiveKind::Standard => (true, par.name),
-            IntParPrimitiveKind::CustomName(s) => (true, s),
-        };
-
-        if has_prim {
+        if let Some(prim_name) = par.primitive_name() {
             writeln!(
                 stream,
                 "    {{ \"{}\", ASSIGN_INT, INT_BASE + INT_PAR__{}, xf_prim_init_none }}, \\",
-                prim_name.replace('_', ""),
+                prim_name,
                 par.name.to_lowercase(),
             )?;
         }
@@ -33,6 +33,10 @@
 //! # }
 //! ```
 //!
+//! Once parsed, the decoded contents of a format can be exported as structured
+//! data with the [`export`] module, and two formats can be compared with the
+//! [`diff`] module.
+//!
 //! The intention is to add enough infrastructure so that all saved macros and
 //! control strings can be decoded. On Linux systems, Tectonic's auto-generated
 //! format files are saved in the `~/.cache/Tectonic/formats/` directory.
@@ -52,11 +56,13 @@ pub mod base;
 pub mod catcodes;
 pub mod commands;
 pub mod cshash;
+pub mod diff;
 pub mod dimenpars;
 pub mod engine;
 pub mod enums;
 pub mod eqtb;
 pub mod etexpenalties;
+pub mod export;
 pub mod format;
 pub mod gluepars;
 pub mod intpars;
@@ -65,3 +71,6 @@ pub mod mem;
 pub mod stringtable;
 pub mod symbols;
 pub mod tokenlist;
+
+#[cfg(test)]
+mod testutil;
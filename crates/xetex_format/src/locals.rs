@@ -44,6 +44,18 @@ pub struct LocalPar {
     since: FormatVersion,
 }
 
+impl LocalPar {
+    /// The internal name of the parameter, e.g. `every_par`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The kind of the parameter.
+    pub fn kind(&self) -> LocalParKind {
+        self.kind
+    }
+}
+
 const LOCAL_PARS: &[LocalPar] = &[
     LocalPar {
         name: "par_shape",
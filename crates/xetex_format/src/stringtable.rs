@@ -45,6 +45,17 @@ impl StringTable {
         &self.strings[sp as usize - 0x10000]
     }
 
+    /// Get the text of a string, including the implicit single-character
+    /// strings. Unlike [`Self::lookup`], this doesn't panic if the pointer
+    /// is invalid.
+    pub fn resolve(&self, sp: StrPointer) -> Option<String> {
+        if sp > 0xFFFF {
+            self.strings.get(sp as usize - 0x10000).cloned()
+        } else {
+            char::from_u32(sp as u32).map(|c| c.to_string())
+        }
+    }
+
     pub fn utf16_length(&self, sp: StrPointer) -> usize {
         if sp > 0xFFFF {
             len_utf16(self.lookup(sp))
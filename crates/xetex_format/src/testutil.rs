@@ -0,0 +1,361 @@
+// Copyright 2026 the Tectonic Project
+// Licensed under the MIT License.
+
+//! A writer for small synthetic format files, so that the parser can be tested
+//! without running the engine.
+//!
+//! The files have the structure that the engine would dump, but contain only
+//! the items that a test adds to them: everything else is empty or undefined.
+
+use byteorder::{WriteBytesExt, BE};
+use std::collections::BTreeMap;
+
+use crate::{
+    base::{self, SIZEOF_MEMORY_WORD, TEX_NULL},
+    commands::CommandCode,
+    engine::Engine,
+    eqtb::EqtbPointer,
+    mem::MemPointer,
+    stringtable::StrPointer,
+    FormatVersion,
+};
+
+/// The format versions that the parser supports, for tests that should work
+/// with all of them.
+pub(crate) const SUPPORTED_VERSIONS: &[FormatVersion] = &[31, 32, 33];
+
+const HEADER_MAGIC: i32 = 0x54_54_4E_43;
+const FOOTER_MAGIC: i32 = 0x00_00_02_9A;
+
+/// The lowest address used for nodes in `mem`.
+const FIRST_NODE: MemPointer = 100;
+
+/// The only free block of variable-size memory, just past the nodes.
+const ROVER: MemPointer = 1000;
+
+const LO_MEM_MAX: MemPointer = 1100;
+const HI_MEM_STAT_USAGE: i32 = 15;
+const N_SERIALIZED_SA_ROOTS: usize = 7;
+const CS_TOKEN_FLAG: i32 = 0x1FF_FFFF;
+
+/// Get the token for a character with the specified category code.
+pub(crate) fn char_token(catcode: i32, chr: char) -> i32 {
+    (catcode << 21) | chr as i32
+}
+
+/// Get the token for a control sequence.
+pub(crate) fn cs_token(ptr: EqtbPointer) -> i32 {
+    CS_TOKEN_FLAG + ptr
+}
+
+/// Build a format file for a particular engine version.
+pub(crate) struct FormatBuilder {
+    engine: Engine,
+    strings: Vec<String>,
+    mem: Vec<u8>,
+    mem_ptr: MemPointer,
+    eqtb: BTreeMap<EqtbPointer, [u8; SIZEOF_MEMORY_WORD]>,
+    hash: BTreeMap<EqtbPointer, StrPointer>,
+    hyphenation: Vec<(StrPointer, MemPointer)>,
+}
+
+impl FormatBuilder {
+    pub(crate) fn new(version: FormatVersion) -> Self {
+        let mut b = FormatBuilder {
+            engine: Engine::new_for_version(version).unwrap(),
+            strings: Vec::new(),
+            mem: vec![0; (LO_MEM_MAX as usize + 1) * SIZEOF_MEMORY_WORD],
+            mem_ptr: FIRST_NODE,
+            eqtb: BTreeMap::new(),
+            hash: BTreeMap::new(),
+            hyphenation: Vec::new(),
+        };
+
+        // As in TeX, the token list parameters and registers start out null
+        // rather than pointing at `mem[0]`, which holds the zero glue
+        // specification that the other entries point to.
+        let n_null = b.symbol("NUM_LOCALS") + base::NUMBER_REGS as i32;
+        let null = b.eqtb_word(0, "UNDEFINED_CS", TEX_NULL);
+
+        for i in 0..n_null {
+            b.eqtb.insert(b.symbol("LOCAL_BASE") + i, null);
+        }
+
+        b
+    }
+
+    fn symbol(&self, name: &str) -> i32 {
+        self.engine.symbols.lookup(name) as i32
+    }
+
+    /// Get the token for a character with the command code named by the
+    /// symbol *cmd*, such as `MATCH`, which may vary between versions.
+    pub(crate) fn cmd_token(&self, cmd: &str, chr: char) -> i32 {
+        char_token(self.symbol(cmd), chr)
+    }
+
+    fn string(&mut self, text: &str) -> StrPointer {
+        self.strings.push(text.to_owned());
+        0x10000 + self.strings.len() as StrPointer - 1
+    }
+
+    /// Store a linked list of values in `mem`, like a token list, returning
+    /// a pointer to its head.
+    fn list(&mut self, values: &[i32]) -> MemPointer {
+        let mut head = TEX_NULL;
+
+        for value in values.iter().rev() {
+            let p = self.mem_ptr;
+            assert!(p < ROVER, "test format memory is full");
+            self.mem_ptr += 1;
+            base::memword_write_b32_s0(&mut self.mem, p, *value);
+            base::memword_write_b32_s1(&mut self.mem, p, head);
+            head = p;
+        }
+
+        head
+    }
+
+    /// Add a hyphenation exception with hyphens allowed after the specified
+    /// positions, which must be in ascending order.
+    pub(crate) fn hyphenation(&mut self, word: &str, positions: &[i32]) {
+        // TeX builds the list by prepending each position as it scans the
+        // word.
+        let reversed: Vec<i32> = positions.iter().rev().copied().collect();
+        let list = self.list(&reversed);
+        let word = self.string(word);
+        self.hyphenation.push((word, list));
+    }
+
+    /// Encode an eqtb entry.
+    fn eqtb_word(&self, level: i16, cmd: &str, value: i32) -> [u8; SIZEOF_MEMORY_WORD] {
+        let mut word = [0; SIZEOF_MEMORY_WORD];
+        base::memword_write_b16_s0(&mut word, 0, level);
+        base::memword_write_b16_s1(&mut word, 0, self.symbol(cmd) as CommandCode);
+        base::memword_write_b32_s1(&mut word, 0, value);
+        word
+    }
+
+    /// Set the value of the eqtb entry at *offset* from the location named by
+    /// the symbol *base*, such as a parameter or register.
+    pub(crate) fn set_eqtb(&mut self, base: &str, offset: i32, value: i32) {
+        let word = self.eqtb_word(1, "UNDEFINED_CS", value);
+        self.eqtb.insert(self.symbol(base) + offset, word);
+    }
+
+    /// Store a glue specification in `mem`, returning a pointer to it.
+    pub(crate) fn glue_spec(
+        &mut self,
+        width: i32,
+        stretch: (i32, i16),
+        shrink: (i32, i16),
+    ) -> MemPointer {
+        let p = self.mem_ptr;
+        assert!(p + 4 <= ROVER, "test format memory is full");
+        self.mem_ptr += 4;
+        base::memword_write_b16_s1(&mut self.mem, p, stretch.1);
+        base::memword_write_b16_s0(&mut self.mem, p, shrink.1);
+        base::memword_write_b32_s1(&mut self.mem, p + 1, width);
+        base::memword_write_b32_s1(&mut self.mem, p + 2, stretch.0);
+        base::memword_write_b32_s1(&mut self.mem, p + 3, shrink.0);
+        p
+    }
+
+    /// Store a token list in `mem`, with a reference count, as for a token
+    /// list parameter or register. Returns a pointer to it.
+    pub(crate) fn toks(&mut self, tokens: &[i32]) -> MemPointer {
+        let mut values = vec![0]; // the reference count
+        values.extend_from_slice(tokens);
+        self.list(&values)
+    }
+
+    /// Make a multi-letter control sequence mean the command *cmd* (named by
+    /// its symbol) with the argument *value*.
+    pub(crate) fn define(&mut self, name: &str, cmd: &str, value: i32) -> EqtbPointer {
+        let hash_prime = self.symbol("HASH_PRIME") as u32;
+        let mut h = 0;
+
+        for c in name.chars() {
+            h = (h + h + c as u32) % hash_prime;
+        }
+
+        let p = h as EqtbPointer + self.symbol("HASH_BASE");
+        assert!(
+            !self.hash.contains_key(&p),
+            "hash collision for `{}` in test format",
+            name
+        );
+        let text = self.string(name);
+        self.hash.insert(p, text);
+
+        let word = self.eqtb_word(1, cmd, value);
+        self.eqtb.insert(p, word);
+        p
+    }
+
+    /// Define a macro with the specified tokens, including the parameter text
+    /// and the `END_MATCH` token.
+    pub(crate) fn define_macro(&mut self, name: &str, tokens: &[i32]) -> EqtbPointer {
+        let p = self.toks(tokens);
+        self.define(name, "CALL", p)
+    }
+
+    /// Write out the format file.
+    pub(crate) fn build(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let w = &mut out;
+        let int = |w: &mut Vec<u8>, value: i32| w.write_i32::<BE>(value).unwrap();
+
+        int(w, HEADER_MAGIC);
+        int(w, self.engine.version as i32);
+        int(w, 0); // hash_high
+        int(w, self.symbol("MEM_TOP"));
+        int(w, self.symbol("EQTB_SIZE"));
+        int(w, self.symbol("HASH_PRIME"));
+        int(w, 607); // hyph_prime
+
+        // String table.
+
+        let pool: Vec<Vec<u16>> = self
+            .strings
+            .iter()
+            .map(|s| s.encode_utf16().collect())
+            .collect();
+        int(w, pool.iter().map(|s| s.len() as i32).sum());
+        int(w, 0xFFFF + self.strings.len() as i32);
+        let mut start = 0;
+
+        for s in &pool {
+            int(w, start);
+            start += s.len() as i32;
+        }
+
+        for c in pool.iter().flatten() {
+            w.write_u16::<BE>(*c).unwrap();
+        }
+
+        // mem. The rover's block is the only free one, and it links to itself.
+
+        let mut mem = self.mem.clone();
+        base::memword_write_b32_s0(&mut mem, ROVER, 2);
+        base::memword_write_b32_s0(&mut mem, ROVER + 1, ROVER);
+        base::memword_write_b32_s1(&mut mem, ROVER + 1, ROVER);
+
+        let mem_top = self.symbol("MEM_TOP");
+        int(w, LO_MEM_MAX);
+        int(w, ROVER);
+
+        for _ in 0..N_SERIALIZED_SA_ROOTS {
+            int(w, TEX_NULL);
+        }
+
+        w.extend_from_slice(&mem);
+        int(w, mem_top - HI_MEM_STAT_USAGE); // hi_mem_min
+        int(w, TEX_NULL); // avail
+        w.resize(
+            w.len() + (HI_MEM_STAT_USAGE as usize + 1) * SIZEOF_MEMORY_WORD,
+            0,
+        );
+        int(w, 0); // var_used
+        int(w, 0); // dyn_used
+
+        // eqtb, in the run-length compressed form. The other entries are
+        // undefined, with zero values, so that every character has category
+        // code zero.
+
+        let eqtb_size = self.symbol("EQTB_SIZE");
+        let undefined = self.eqtb_word(0, "UNDEFINED_CS", 0);
+        let mut k = 1;
+
+        for (p, word) in &self.eqtb {
+            if *p > k {
+                int(w, 1);
+                w.extend_from_slice(&undefined);
+                int(w, p - k - 1);
+            }
+
+            int(w, 1);
+            w.extend_from_slice(word);
+            int(w, 0);
+            k = p + 1;
+        }
+
+        if k <= eqtb_size {
+            int(w, 1);
+            w.extend_from_slice(&undefined);
+            int(w, eqtb_size - k);
+        }
+
+        let hash_base = self.symbol("HASH_BASE");
+        int(w, hash_base); // par_loc
+        int(w, hash_base); // write_loc
+
+        // Primitives.
+
+        w.resize(
+            w.len() + (self.symbol("PRIM_SIZE") as usize + 1) * SIZEOF_MEMORY_WORD,
+            0,
+        );
+
+        // The control sequence hash, with the sparse entries followed by the
+        // words after `hash_used`.
+
+        let hash_used = self.symbol("FROZEN_CONTROL_SEQUENCE");
+        int(w, hash_used);
+
+        for (p, text) in &self.hash {
+            let mut word = [0; SIZEOF_MEMORY_WORD];
+            base::memword_write_b32_s1(&mut word, 0, *text);
+            int(w, *p);
+            w.extend_from_slice(&word);
+        }
+
+        int(w, hash_used);
+        w.extend_from_slice(&[0; SIZEOF_MEMORY_WORD]);
+        let n_words = self.symbol("UNDEFINED_CONTROL_SEQUENCE") - 1 - hash_used;
+        w.resize(w.len() + n_words as usize * SIZEOF_MEMORY_WORD, 0);
+        int(w, self.hash.len() as i32); // cs_count
+
+        // Fonts: just the null font.
+
+        int(w, 7); // fmem_ptr
+        w.resize(w.len() + 7 * SIZEOF_MEMORY_WORD, 0);
+        int(w, 0); // font_ptr
+        w.write_i64::<BE>(0).unwrap(); // font_check
+
+        for _ in 0..7 {
+            int(w, 0); // font_size through font_area
+        }
+
+        w.write_i16::<BE>(1).unwrap(); // font_bc
+        w.write_i16::<BE>(0).unwrap(); // font_ec
+
+        for _ in 0..13 {
+            int(w, 0); // the bases through font_false_bchar
+        }
+
+        // Hyphenation exceptions.
+
+        int(w, self.hyphenation.len() as i32);
+        int(w, self.hyphenation.len() as i32); // hyph_next
+
+        for (j, (word, list)) in self.hyphenation.iter().enumerate() {
+            int(w, j as i32);
+            int(w, *word);
+            int(w, *list);
+        }
+
+        // An empty trie.
+
+        int(w, 0); // trie_max
+        int(w, 0); // hyph_start
+        int(w, 0); // trie_trl
+        int(w, 0); // trie_tro
+        w.write_u16::<BE>(0).unwrap(); // trie_trc
+        int(w, 0); // max_hyph_char
+        int(w, 0); // trie_op_ptr
+
+        int(w, FOOTER_MAGIC);
+        out
+    }
+}
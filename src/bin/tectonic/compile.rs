@@ -29,7 +29,7 @@ pub struct CompileOptions {
     #[arg(long, short, name = "path", default_value = "latex")]
     format: String,
 
-    /// Use this directory or Zip-format bundle file to find resource files instead of the default
+    /// Use this directory, Zip file, or TeX Live tree to find resource files instead of the default
     #[arg(long, short, name = "file_path")]
     bundle: Option<PathBuf>,
 
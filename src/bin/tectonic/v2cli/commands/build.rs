@@ -73,7 +73,9 @@ impl TectonicCommand for BuildCommand {
 
         let mut setup_options =
             DocumentSetupOptions::new_with_security(SecuritySettings::new(stance));
-        setup_options.only_cached(self.only_cached);
+        setup_options
+            .only_cached(self.only_cached)
+            .http_config(config.http_config()?);
 
         for output_name in doc.output_names() {
             if let Some(out) = self.target.as_ref() {
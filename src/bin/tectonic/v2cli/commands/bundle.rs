@@ -11,8 +11,8 @@ use tectonic_status_base::StatusBackend;
 
 use crate::v2cli::{CommandCustomizations, TectonicCommand};
 
-fn get_a_bundle(
-    _config: PersistentConfig,
+pub(crate) fn get_a_bundle(
+    config: PersistentConfig,
     only_cached: bool,
     status: &mut dyn StatusBackend,
 ) -> Result<Box<dyn Bundle>> {
@@ -22,7 +22,9 @@ fn get_a_bundle(
         Ok(ws) => {
             let doc = ws.first_document();
             let mut options: DocumentSetupOptions = Default::default();
-            options.only_cached(only_cached);
+            options
+                .only_cached(only_cached)
+                .http_config(config.http_config()?);
             doc.bundle(&options, status)
         }
 
@@ -32,13 +34,9 @@ fn get_a_bundle(
             } else {
                 tt_note!(
                     status,
-                    "not in a document workspace; using the built-in default bundle"
+                    "not in a document workspace; using the default bundle"
                 );
-                Ok(Box::new(tectonic_bundles::get_fallback_bundle(
-                    tectonic_engine_xetex::FORMAT_SERIAL,
-                    only_cached,
-                    status,
-                )?))
+                config.default_bundle(only_cached, status)
             }
         }
     }
@@ -61,7 +61,9 @@ impl TectonicCommand for DumpCommand {
 
         let mut setup_options =
             DocumentSetupOptions::new_with_security(SecuritySettings::new(stance));
-        setup_options.only_cached(self.only_cached);
+        setup_options
+            .only_cached(self.only_cached)
+            .http_config(config.http_config()?);
 
         // If output profile is unspecified, just grab one at (pseudo-)random.
         let output_name = self
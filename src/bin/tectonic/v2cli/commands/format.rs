@@ -0,0 +1,373 @@
+use clap::{Parser, Subcommand};
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+use tectonic::{
+    config::PersistentConfig,
+    ctry,
+    driver::ProcessingSessionBuilder,
+    errors::Result,
+    io::{
+        format_cache::{CachedFileKind, FormatCache},
+        IoProvider, OpenResult,
+    },
+    tt_note,
+};
+use tectonic_bundles::Bundle;
+use tectonic_status_base::StatusBackend;
+use tectonic_xetex_format::{
+    diff::FormatDiff,
+    export::FormatExport,
+    format::{fmt_csname, Format},
+};
+
+use super::bundle::get_a_bundle;
+use crate::v2cli::{CommandCustomizations, TectonicCommand};
+
+/// `format`: Commands relating to cached format files
+#[derive(Debug, Eq, PartialEq, Parser)]
+pub struct FormatCommand {
+    #[command(subcommand)]
+    command: FormatCommands,
+}
+
+#[derive(Debug, Eq, PartialEq, Subcommand)]
+enum FormatCommands {
+    #[command(name = "list")]
+    /// List the format files in the cache
+    List(FormatListCommand),
+
+    #[command(name = "rebuild")]
+    /// Regenerate a format file for the current bundle
+    Rebuild(FormatRebuildCommand),
+
+    #[command(name = "clear")]
+    /// Delete format files that can no longer be used
+    Clear(FormatClearCommand),
+
+    #[command(name = "inspect")]
+    /// Print out information stored in a format file
+    Inspect(FormatInspectCommand),
+
+    #[command(name = "diff")]
+    /// Compare the contents of two format files
+    Diff(FormatDiffCommand),
+}
+
+impl TectonicCommand for FormatCommand {
+    fn customize(&self, cc: &mut CommandCustomizations) {
+        match &self.command {
+            FormatCommands::List(c) => c.customize(cc),
+            FormatCommands::Rebuild(c) => c.customize(cc),
+            FormatCommands::Clear(c) => c.customize(cc),
+            FormatCommands::Inspect(c) => c.customize(cc),
+            FormatCommands::Diff(c) => c.customize(cc),
+        }
+    }
+
+    fn execute(self, config: PersistentConfig, status: &mut dyn StatusBackend) -> Result<i32> {
+        match self.command {
+            FormatCommands::List(c) => c.execute(config, status),
+            FormatCommands::Rebuild(c) => c.execute(config, status),
+            FormatCommands::Clear(c) => c.execute(config, status),
+            FormatCommands::Inspect(c) => c.execute(config, status),
+            FormatCommands::Diff(c) => c.execute(config, status),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Parser)]
+struct FormatListCommand {}
+
+impl FormatListCommand {
+    fn customize(&self, cc: &mut CommandCustomizations) {
+        cc.always_stderr = true;
+    }
+
+    fn execute(self, config: PersistentConfig, _status: &mut dyn StatusBackend) -> Result<i32> {
+        let formats_base = config.format_cache_path()?;
+        let formats = ctry!(
+            FormatCache::list_formats(&formats_base);
+            "failed to list the format cache directory `{}`", formats_base.display()
+        );
+
+        for f in &formats {
+            println!(
+                "{}  {:>4}  {:>10}  {}{}{}",
+                f.bundle_digest.to_string(),
+                f.serial,
+                f.size,
+                f.name,
+                if f.kind == CachedFileKind::Dependencies {
+                    " (dependency list)"
+                } else {
+                    ""
+                },
+                if f.is_stale() { " (stale)" } else { "" }
+            );
+        }
+
+        Ok(0)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Parser)]
+struct FormatRebuildCommand {
+    /// Use only resource files cached locally
+    #[arg(short = 'C', long)]
+    only_cached: bool,
+
+    /// The name of the format to regenerate
+    #[arg(default_value = "latex")]
+    name: String,
+}
+
+impl FormatRebuildCommand {
+    fn customize(&self, _cc: &mut CommandCustomizations) {}
+
+    fn execute(self, config: PersistentConfig, status: &mut dyn StatusBackend) -> Result<i32> {
+        let format_cache_path = config.format_cache_path()?;
+        let bundle = get_a_bundle(config, self.only_cached, status)?;
+        generate_format(bundle, &self.name, format_cache_path, status)?;
+        Ok(0)
+    }
+}
+
+/// Generate a format file from a bundle and save it in the format cache.
+fn generate_format(
+    bundle: Box<dyn Bundle>,
+    name: &str,
+    format_cache_path: PathBuf,
+    status: &mut dyn StatusBackend,
+) -> Result<()> {
+    let mut sb = ProcessingSessionBuilder::default();
+    sb.bundle(bundle)
+        .primary_input_buffer(b"")
+        .tex_input_name("texput.tex")
+        .format_name(name)
+        .format_cache_path(format_cache_path)
+        .do_not_write_output_files();
+
+    let mut sess = ctry!(sb.create(status); "failed to initialize the processing session");
+    ctry!(sess.generate_format(status); "failed to generate the format `{}`", name);
+    Ok(())
+}
+
+/// Load and parse a format file.
+///
+/// If *name* is the path to an existing file, that file is loaded. Otherwise,
+/// it names a format for the current bundle, which is taken from the format
+/// cache, generating it if needed.
+pub(crate) fn load_format(
+    config: PersistentConfig,
+    name: &str,
+    only_cached: bool,
+    status: &mut dyn StatusBackend,
+) -> Result<Format> {
+    let path = Path::new(name);
+
+    let data = if path.is_file() {
+        ctry!(std::fs::read(path); "failed to read `{}`", path.display())
+    } else {
+        let format_cache_path = config.format_cache_path()?;
+        let mut bundle = get_a_bundle(config, only_cached, status)?;
+        let digest = bundle.get_digest(status)?;
+
+        let mut cache = FormatCache::new(digest, format_cache_path.clone());
+
+        if let OpenResult::NotAvailable = cache.input_open_format(name, status) {
+            generate_format(bundle, name, format_cache_path, status)?;
+        }
+
+        let mut ih = ctry!(
+            cache.input_open_format(name, status).must_exist();
+            "failed to open the cached format `{}`", name
+        );
+        let mut data = Vec::new();
+        ih.read_to_end(&mut data)?;
+        data
+    };
+
+    Ok(ctry!(Format::parse(&data); "failed to parse the format `{}`", name))
+}
+
+#[derive(Debug, Eq, PartialEq, Parser)]
+struct FormatClearCommand {
+    /// Delete all cached format files, not just unusable ones
+    #[arg(long)]
+    all: bool,
+}
+
+impl FormatClearCommand {
+    fn customize(&self, _cc: &mut CommandCustomizations) {}
+
+    fn execute(self, config: PersistentConfig, status: &mut dyn StatusBackend) -> Result<i32> {
+        let formats_base = config.format_cache_path()?;
+        let formats = ctry!(
+            FormatCache::list_formats(&formats_base);
+            "failed to list the format cache directory `{}`", formats_base.display()
+        );
+        let n_files = formats.len();
+
+        let removed = if self.all {
+            for f in &formats {
+                ctry!(f.remove(); "failed to delete `{}`", f.path.display());
+            }
+
+            formats
+        } else {
+            ctry!(
+                FormatCache::remove_stale(&formats_base);
+                "failed to delete stale files from `{}`", formats_base.display()
+            )
+        };
+
+        tt_note!(
+            status,
+            "deleted {} of {} files in the format cache, freeing {} bytes",
+            removed.len(),
+            n_files,
+            removed.iter().map(|f| f.size).sum::<u64>()
+        );
+        Ok(0)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Parser)]
+struct FormatInspectCommand {
+    /// Use only resource files cached locally
+    #[arg(short = 'C', long)]
+    only_cached: bool,
+
+    /// Print the string table
+    #[arg(long)]
+    strings: bool,
+
+    /// Print the character category codes
+    #[arg(long)]
+    catcodes: bool,
+
+    /// Print the active characters
+    #[arg(long)]
+    actives: bool,
+
+    /// Print all defined control sequences (the default)
+    #[arg(long)]
+    cseqs: bool,
+
+    /// When printing control sequences, include the contents of macros
+    #[arg(long, short = 'e')]
+    extended: bool,
+
+    /// Show the meaning of a control sequence, like TeX's \show
+    #[arg(long, value_name = "CSNAME")]
+    show: Vec<String>,
+
+    /// Print the decoded contents of the format as JSON (box registers, character
+    /// code tables other than catcodes, and math fonts are not yet included)
+    #[arg(
+        long,
+        conflicts_with_all = ["strings", "catcodes", "actives", "cseqs", "show"]
+    )]
+    json: bool,
+
+    /// The name of the format (e.g. `latex`), or the path to a format file
+    name: String,
+}
+
+impl FormatInspectCommand {
+    fn customize(&self, cc: &mut CommandCustomizations) {
+        cc.always_stderr = true;
+    }
+
+    fn execute(self, config: PersistentConfig, status: &mut dyn StatusBackend) -> Result<i32> {
+        let fmt = load_format(config, &self.name, self.only_cached, status)?;
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+
+        if self.json {
+            FormatExport::new(&fmt)?.write_json(&mut out)?;
+            return Ok(0);
+        }
+
+        let cseqs =
+            self.cseqs || !(self.strings || self.catcodes || self.actives || !self.show.is_empty());
+
+        if self.strings {
+            fmt.dump_string_table(&mut out)?;
+        }
+
+        if self.catcodes {
+            fmt.dump_catcodes(&mut out)?;
+        }
+
+        if self.actives {
+            fmt.dump_actives(&mut out)?;
+        }
+
+        if cseqs {
+            fmt.dump_cseqs(&mut out, self.extended)?;
+        }
+
+        let mut n_undefined = 0;
+
+        for name in &self.show {
+            // Let people write `\foo` as well as `foo`.
+            let name = match name.strip_prefix('\\') {
+                Some(n) if !n.is_empty() => n,
+                _ => name,
+            };
+
+            match fmt.show_cseq(name) {
+                Some(text) => writeln!(out, "{text}")?,
+                None => {
+                    writeln!(out, "{}=undefined", fmt_csname(name))?;
+                    n_undefined += 1;
+                }
+            }
+        }
+
+        Ok(if n_undefined > 0 { 1 } else { 0 })
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Parser)]
+struct FormatDiffCommand {
+    /// Use only resource files cached locally
+    #[arg(short = 'C', long)]
+    only_cached: bool,
+
+    /// Print the differences as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// The old format: a format name (e.g. `latex`) or the path to a format file
+    old: String,
+
+    /// The new format: a format name (e.g. `latex`) or the path to a format file
+    new: String,
+}
+
+impl FormatDiffCommand {
+    fn customize(&self, cc: &mut CommandCustomizations) {
+        cc.always_stderr = true;
+    }
+
+    fn execute(self, config: PersistentConfig, status: &mut dyn StatusBackend) -> Result<i32> {
+        let old = load_format(config.clone(), &self.old, self.only_cached, status)?;
+        let new = load_format(config, &self.new, self.only_cached, status)?;
+        let diff = FormatDiff::new(&old, &new)?;
+
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+
+        if self.json {
+            diff.write_json(&mut out)?;
+        } else {
+            diff.write_text(&mut out)?;
+        }
+
+        Ok(if diff.is_empty() { 0 } else { 1 })
+    }
+}
@@ -1,6 +1,7 @@
 pub mod build;
 pub mod bundle;
 pub mod dump;
+pub mod format;
 pub mod new;
 pub mod show;
 pub mod watch;
@@ -19,6 +19,7 @@ use self::commands::{
     build::BuildCommand,
     bundle::BundleCommand,
     dump::DumpCommand,
+    format::FormatCommand,
     new::{InitCommand, NewCommand},
     show::ShowCommand,
     watch::WatchCommand,
@@ -101,6 +102,7 @@ pub fn v2_main(effective_args: &[OsString]) {
         Commands::Bundle(o) => o.customize(&mut customizations),
         Commands::Compile(_) => {} // avoid namespacing/etc issues
         Commands::Dump(o) => o.customize(&mut customizations),
+        Commands::Format(o) => o.customize(&mut customizations),
         Commands::New(o) => o.customize(&mut customizations),
         Commands::Init(o) => o.customize(&mut customizations),
         Commands::Show(o) => o.customize(&mut customizations),
@@ -139,6 +141,7 @@ pub fn v2_main(effective_args: &[OsString]) {
         Commands::Bundle(o) => o.execute(config, &mut *status),
         Commands::Compile(o) => o.execute(config, &mut *status),
         Commands::Dump(o) => o.execute(config, &mut *status),
+        Commands::Format(o) => o.execute(config, &mut *status),
         Commands::New(o) => o.execute(config, &mut *status),
         Commands::Init(o) => o.execute(config, &mut *status),
         Commands::Show(o) => o.execute(config, &mut *status),
@@ -179,6 +182,10 @@ enum Commands {
     /// Run a partial compilation and output an intermediate file
     Dump(DumpCommand),
 
+    #[command(name = "format")]
+    /// Commands relating to cached format files
+    Format(FormatCommand),
+
     #[command(name = "new")]
     /// Create a new document project
     New(NewCommand),
@@ -8,20 +8,59 @@
 //! we at least need a mechanism for specifying the default bundle to use when
 //! running the command-line client. So we begrudgingly have a *little*
 //! configuration.
+//!
+//! The default bundle may be served from several mirrors, which are tried in
+//! order if the main URL can't be reached. If the digest of the bundle is
+//! specified, mirrors serving anything else are ignored:
+//!
+//! ```toml
+//! [[default_bundles]]
+//! url = "https://relay.fullyjustified.net/default_bundle_v33.tar"
+//! mirrors = ["https://bundles.example.com/default_bundle_v33.tar"]
+//! digest = "<64 hex digits>"
+//! ```
+//!
+//! The configuration file can also contain an `[http]` section with network
+//! settings, for users behind proxies and the like:
+//!
+//! ```toml
+//! [http]
+//! proxy = "http://proxy.example.com:3128"
+//! ca_certificates = ["/etc/ssl/internal-ca.pem"]
+//! connect_timeout = 10.0 # seconds
+//! timeout = 60.0 # seconds
+//! max_attempts = 6
+//!
+//! [http.headers]
+//! Authorization = "Bearer s3cr3t"
+//! ```
+//!
+//! Each of these settings can be overridden with a `TECTONIC_HTTP_*`
+//! environment variable; see [`HttpConfig::apply_env`].
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     path::{Path, PathBuf},
+    str::FromStr,
     sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
 };
 use tectonic_bundles::{
-    cache::Cache, dir::DirBundle, itar::IndexedTarBackend, zip::ZipBundle, Bundle,
+    cache::Cache,
+    dir::DirBundle,
+    itar::IndexedTarBackend,
+    texlive::{self, TexLiveBundle},
+    zip::ZipBundle,
+    Bundle,
 };
-use tectonic_io_base::app_dirs;
+use tectonic_geturl::HttpConfig;
+use tectonic_io_base::{app_dirs, digest::DigestData};
 use url::Url;
 
 use crate::{
+    ctry,
     errors::{ErrorKind, Result},
     status::StatusBackend,
 };
@@ -63,14 +102,99 @@ pub fn maybe_return_test_bundle(web_bundle: Option<String>) -> Result<Box<dyn Bu
     }
 }
 
+#[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct PersistentConfig {
     default_bundles: Vec<BundleInfo>,
+
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "HttpSettings::is_empty")
+    )]
+    http: HttpSettings,
 }
 
+#[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct BundleInfo {
     url: String,
+
+    /// Alternative URLs for the same bundle, tried in order if `url` fails.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    mirrors: Vec<String>,
+
+    /// The expected digest of the bundle, in hex.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    digest: Option<String>,
+}
+
+/// Network settings stored in the `[http]` section of the configuration file.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct HttpSettings {
+    /// The URL of a proxy to route all requests through.
+    proxy: Option<String>,
+
+    /// PEM files with additional trusted CA certificates.
+    ca_certificates: Vec<PathBuf>,
+
+    /// Extra headers to send with every request.
+    headers: BTreeMap<String, String>,
+
+    /// The connection timeout, in seconds.
+    connect_timeout: Option<f64>,
+
+    /// The per-request timeout, in seconds.
+    timeout: Option<f64>,
+
+    /// The maximum number of attempts to make for each request.
+    max_attempts: Option<u32>,
+}
+
+impl HttpSettings {
+    #[cfg(feature = "serde")]
+    fn is_empty(&self) -> bool {
+        *self == HttpSettings::default()
+    }
+
+    fn to_http_config(&self) -> Result<HttpConfig> {
+        fn seconds(name: &str, value: Option<f64>) -> Result<Option<Duration>> {
+            match value {
+                Some(s) if s.is_finite() && s >= 0. => Ok(Some(Duration::from_secs_f64(s))),
+                Some(_) => Err(ErrorKind::Msg(format!(
+                    "the `http.{name}` setting must be a nonnegative number of seconds"
+                ))
+                .into()),
+                None => Ok(None),
+            }
+        }
+
+        let mut config = HttpConfig {
+            proxy: self.proxy.clone(),
+            ca_certificates: self.ca_certificates.clone(),
+            headers: self
+                .headers
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            connect_timeout: seconds("connect_timeout", self.connect_timeout)?,
+            timeout: seconds("timeout", self.timeout)?,
+            ..Default::default()
+        };
+
+        if let Some(n) = self.max_attempts {
+            config.retry.max_attempts = n;
+        }
+
+        Ok(config)
+    }
 }
 
 impl PersistentConfig {
@@ -141,17 +265,50 @@ impl PersistentConfig {
         custom_cache_root: Option<&Path>,
         status: &mut dyn StatusBackend,
     ) -> Result<Box<dyn Bundle>> {
-        if let Ok(test_bundle) = maybe_return_test_bundle(Some(url.to_owned())) {
-            return Ok(test_bundle);
+        self.make_mirrored_url_provider(
+            &[url.to_owned()],
+            None,
+            only_cached,
+            custom_cache_root,
+            status,
+        )
+    }
+
+    /// Open a network bundle that may be served from several mirrors.
+    ///
+    /// The *urls* are tried in order, starting with whichever one worked most
+    /// recently. If *digest* is given, it is the hex-encoded digest that the
+    /// bundle must have; mirrors serving a different bundle are skipped.
+    pub fn make_mirrored_url_provider(
+        &self,
+        urls: &[String],
+        digest: Option<&str>,
+        only_cached: bool,
+        custom_cache_root: Option<&Path>,
+        status: &mut dyn StatusBackend,
+    ) -> Result<Box<dyn Bundle>> {
+        if let Some(url) = urls.first() {
+            if let Ok(test_bundle) = maybe_return_test_bundle(Some(url.to_owned())) {
+                return Ok(test_bundle);
+            }
         }
 
+        let digest = match digest {
+            Some(d) => Some(ctry!(
+                DigestData::from_str(d);
+                "invalid bundle digest `{}`", d
+            )),
+            None => None,
+        };
+
         let mut cache = if let Some(root) = custom_cache_root {
             Cache::get_for_custom_directory(root)
         } else {
             Cache::get_user_default()?
         };
+        cache.set_http_config(self.http_config()?);
 
-        let bundle = cache.open::<IndexedTarBackend>(url, only_cached, status)?;
+        let bundle = cache.open_mirrored::<IndexedTarBackend>(urls, digest, only_cached, status)?;
         Ok(Box::new(bundle) as _)
     }
 
@@ -160,7 +317,9 @@ impl PersistentConfig {
         file_path: PathBuf,
         _status: &mut dyn StatusBackend,
     ) -> Result<Box<dyn Bundle>> {
-        let bundle: Box<dyn Bundle> = if file_path.is_dir() {
+        let bundle: Box<dyn Bundle> = if texlive::is_texlive_tree(&file_path) {
+            Box::new(TexLiveBundle::open(file_path)?)
+        } else if file_path.is_dir() {
             Box::new(DirBundle::new(file_path))
         } else {
             Box::new(ZipBundle::open(file_path)?)
@@ -168,10 +327,30 @@ impl PersistentConfig {
         Ok(bundle)
     }
 
+    /// Get the HTTP settings to use for network access.
+    ///
+    /// These come from the `[http]` section of the configuration file, with
+    /// overrides from the `TECTONIC_HTTP_*` environment variables.
+    pub fn http_config(&self) -> Result<HttpConfig> {
+        let mut config = self.http.to_http_config()?;
+        config.apply_env()?;
+        Ok(config)
+    }
+
     pub fn default_bundle_loc(&self) -> &str {
         &self.default_bundles[0].url
     }
 
+    /// Get the locations of the default bundle: its main URL, followed by any
+    /// configured mirrors.
+    pub fn default_bundle_urls(&self) -> Vec<String> {
+        let info = &self.default_bundles[0];
+        std::iter::once(&info.url)
+            .chain(info.mirrors.iter())
+            .cloned()
+            .collect()
+    }
+
     pub fn default_bundle(
         &self,
         only_cached: bool,
@@ -199,8 +378,13 @@ impl PersistentConfig {
             })?;
             return self.make_local_file_provider(file_path, status);
         }
-        let bundle =
-            self.make_cached_url_provider(&self.default_bundles[0].url, only_cached, None, status)?;
+        let bundle = self.make_mirrored_url_provider(
+            &self.default_bundle_urls(),
+            self.default_bundles[0].digest.as_deref(),
+            only_cached,
+            None,
+            status,
+        )?;
         Ok(Box::new(bundle) as _)
     }
 
@@ -218,7 +402,12 @@ impl Default for PersistentConfig {
         let url = tectonic_bundles::get_fallback_bundle_url(tectonic_engine_xetex::FORMAT_SERIAL);
 
         PersistentConfig {
-            default_bundles: vec![BundleInfo { url }],
+            default_bundles: vec![BundleInfo {
+                url,
+                mirrors: Vec::new(),
+                digest: None,
+            }],
+            http: HttpSettings::default(),
         }
     }
 }
@@ -11,16 +11,25 @@ use std::{
     fmt::Write as FmtWrite,
     fs, io,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 use tectonic_bridge_core::SecuritySettings;
 use tectonic_bundles::{
-    cache::Cache, dir::DirBundle, itar::IndexedTarBackend, zip::ZipBundle, Bundle,
+    cache::Cache,
+    dir::DirBundle,
+    itar::IndexedTarBackend,
+    layered::LayeredBundle,
+    texlive::{self, TexLiveBundle},
+    zip::ZipBundle,
+    Bundle,
 };
 use tectonic_docmodel::{
     document::{BuildTargetType, Document, InputFile},
     workspace::{Workspace, WorkspaceCreator},
 };
-use tectonic_geturl::{DefaultBackend, GetUrlBackend};
+use tectonic_engine_spx2html::EpubMetadata;
+use tectonic_geturl::{DefaultBackend, GetUrlBackend, HttpConfig};
+use tectonic_io_base::digest::DigestData;
 use url::Url;
 
 use crate::{
@@ -28,7 +37,7 @@ use crate::{
     driver::{OutputFormat, PassSetting, ProcessingSessionBuilder},
     errors::{ErrorKind, Result},
     status::StatusBackend,
-    tt_note,
+    tt_note, tt_warning,
     unstable_opts::UnstableOptions,
 };
 
@@ -44,6 +53,10 @@ pub struct DocumentSetupOptions {
 
     /// Ensure a deterministic build environment.
     deterministic_mode: bool,
+
+    /// HTTP settings for network-based bundles. If unset, they are derived
+    /// from the `TECTONIC_HTTP_*` environment variables.
+    http: Option<HttpConfig>,
 }
 
 impl DocumentSetupOptions {
@@ -54,6 +67,7 @@ impl DocumentSetupOptions {
             only_cached: false,
             deterministic_mode: false,
             security,
+            http: None,
         }
     }
 
@@ -72,6 +86,25 @@ impl DocumentSetupOptions {
         self.deterministic_mode = s;
         self
     }
+
+    /// Specify the HTTP settings to use if the document’s bundle is
+    /// network-based.
+    ///
+    /// These will usually come from [`config::PersistentConfig::http_config`].
+    /// If no settings are specified, the ones given by the environment are
+    /// used; see [`HttpConfig::apply_env`].
+    pub fn http_config(&mut self, c: HttpConfig) -> &mut Self {
+        self.http = Some(c);
+        self
+    }
+
+    /// Get the HTTP settings that network-based bundles should use.
+    fn effective_http_config(&self) -> Result<HttpConfig> {
+        Ok(match &self.http {
+            Some(c) => c.clone(),
+            None => HttpConfig::from_env()?,
+        })
+    }
 }
 
 pub trait DocumentExt {
@@ -104,33 +137,91 @@ impl DocumentExt for Document {
         status: &mut dyn StatusBackend,
     ) -> Result<Box<dyn Bundle>> {
         fn bundle_from_path(p: PathBuf) -> Result<Box<dyn Bundle>> {
-            if p.is_dir() {
+            if texlive::is_texlive_tree(&p) {
+                Ok(Box::new(TexLiveBundle::open(p)?))
+            } else if p.is_dir() {
                 Ok(Box::new(DirBundle::new(p)))
             } else {
                 Ok(Box::new(ZipBundle::open(p)?))
             }
         }
 
-        if let Ok(test_bundle) = config::maybe_return_test_bundle(None) {
-            Ok(test_bundle)
-        } else if let Ok(url) = Url::parse(&self.bundle_loc) {
-            if url.scheme() != "file" {
-                let mut cache = Cache::get_user_default()?;
-                let bundle = cache.open::<IndexedTarBackend>(
-                    &self.bundle_loc,
-                    setup_options.only_cached,
-                    status,
-                )?;
-                Ok(Box::new(bundle))
+        // Relative paths are relative to the document source, not to wherever
+        // we happen to be running.
+        fn bundle_from_loc(
+            loc: &str,
+            src_dir: &Path,
+            mirrors: &[String],
+            digest: Option<DigestData>,
+            setup_options: &DocumentSetupOptions,
+            status: &mut dyn StatusBackend,
+        ) -> Result<Box<dyn Bundle>> {
+            if let Ok(url) = Url::parse(loc) {
+                if url.scheme() != "file" {
+                    let mut cache = Cache::get_user_default()?;
+                    cache.set_http_config(setup_options.effective_http_config()?);
+                    let urls: Vec<String> = std::iter::once(loc)
+                        .chain(mirrors.iter().map(|s| s.as_ref()))
+                        .map(|s| s.to_owned())
+                        .collect();
+                    let bundle = cache.open_mirrored::<IndexedTarBackend>(
+                        &urls,
+                        digest,
+                        setup_options.only_cached,
+                        status,
+                    )?;
+                    Ok(Box::new(bundle))
+                } else {
+                    let file_path = url.to_file_path().map_err(|_| {
+                        io::Error::new(io::ErrorKind::InvalidInput, "failed to parse local path")
+                    })?;
+                    bundle_from_path(file_path)
+                }
             } else {
-                let file_path = url.to_file_path().map_err(|_| {
-                    io::Error::new(io::ErrorKind::InvalidInput, "failed to parse local path")
-                })?;
-                bundle_from_path(file_path)
+                bundle_from_path(src_dir.join(loc))
             }
-        } else {
-            bundle_from_path(Path::new(&self.bundle_loc).to_owned())
         }
+
+        if let Ok(test_bundle) = config::maybe_return_test_bundle(None) {
+            return Ok(test_bundle);
+        }
+
+        let digest = match self.bundle_digest.as_ref() {
+            Some(d) => Some(ctry!(
+                DigestData::from_str(d);
+                "invalid `bundle_digest` value `{}`", d
+            )),
+            None => None,
+        };
+
+        let base = bundle_from_loc(
+            &self.bundle_loc,
+            self.src_dir(),
+            &self.bundle_mirrors,
+            digest,
+            setup_options,
+            status,
+        )?;
+
+        if self.bundle_overlays.is_empty() {
+            return Ok(base);
+        }
+
+        let mut layers = Vec::with_capacity(self.bundle_overlays.len() + 1);
+
+        for loc in &self.bundle_overlays {
+            layers.push(bundle_from_loc(
+                loc,
+                self.src_dir(),
+                &[],
+                None,
+                setup_options,
+                status,
+            )?);
+        }
+
+        layers.push(base);
+        Ok(Box::new(LayeredBundle::new(layers)?))
     }
 
     fn setup_session(
@@ -148,19 +239,32 @@ impl DocumentExt for Document {
         let output_format = match profile.target_type {
             BuildTargetType::Html => OutputFormat::Html,
             BuildTargetType::Pdf => OutputFormat::Pdf,
+            BuildTargetType::Epub => OutputFormat::Epub,
         };
 
         let mut input_buffer = String::new();
+        let mut preamble_buffer = None;
 
-        for input in &profile.inputs {
-            match input {
-                InputFile::Inline(s) => {
-                    writeln!(input_buffer, "{}", s)?;
-                }
-                InputFile::File(f) => {
-                    writeln!(input_buffer, "\\input{{{}}}", f)?;
-                }
-            };
+        let mut inputs = &profile.inputs[..];
+
+        if profile.preamble_format {
+            if inputs.len() > 1 {
+                let mut preamble = String::new();
+                write_input(&mut preamble, &inputs[0])?;
+                preamble_buffer = Some(preamble);
+                inputs = &inputs[1..];
+            } else {
+                tt_warning!(
+                    status,
+                    "output profile \"{}\" asks for a preamble format, but it has only one input; \
+                     ignoring the request",
+                    output_profile
+                );
+            }
+        }
+
+        for input in inputs {
+            write_input(&mut input_buffer, input)?;
         }
 
         let mut sess_builder =
@@ -178,6 +282,21 @@ impl DocumentExt for Document {
             .primary_input_buffer(input_buffer.as_bytes())
             .tex_input_name(output_profile);
 
+        if let Some(preamble) = preamble_buffer {
+            sess_builder.preamble_format(&preamble);
+        }
+
+        if output_format != OutputFormat::Pdf {
+            sess_builder
+                .html_source_position_attributes(profile.source_positions)
+                .html_source_position_maps(profile.source_maps)
+                .html_woff2_fonts(profile.woff2_fonts);
+        }
+
+        if output_format == OutputFormat::Epub {
+            sess_builder.epub_metadata(epub_metadata(self.metadata.as_ref()));
+        }
+
         if profile.shell_escape {
             // For now, this is the only option we allow.
             if let Some(cwd) = &profile.shell_escape_cwd {
@@ -208,6 +327,62 @@ impl DocumentExt for Document {
     }
 }
 
+/// Write the TeX code that pulls in one of a document's inputs.
+fn write_input(buf: &mut String, input: &InputFile) -> Result<()> {
+    match input {
+        InputFile::Inline(s) => {
+            writeln!(buf, "{}", s)?;
+        }
+        InputFile::File(f) => {
+            writeln!(buf, "\\input{{{}}}", f)?;
+        }
+    };
+
+    Ok(())
+}
+
+/// Get the publication metadata of an EPUB output from the `[doc.metadata]`
+/// table.
+///
+/// The recognized keys are `title`, `author` or `authors` (a string or an
+/// array of strings), `language`, `identifier`, `publisher`, `description`,
+/// and `date`. Anything else is ignored.
+fn epub_metadata(metadata: Option<&toml::Value>) -> EpubMetadata {
+    let mut md = EpubMetadata::default();
+
+    let table = match metadata.and_then(|m| m.as_table()) {
+        Some(t) => t,
+        None => return md,
+    };
+
+    let get = |key: &str| -> Option<String> {
+        match table.get(key)? {
+            toml::Value::String(s) => Some(s.clone()),
+            toml::Value::Datetime(d) => Some(d.to_string()),
+            _ => None,
+        }
+    };
+
+    md.title = get("title");
+    md.language = get("language");
+    md.identifier = get("identifier");
+    md.publisher = get("publisher");
+    md.description = get("description");
+    md.date = get("date");
+
+    for key in ["author", "authors"] {
+        match table.get(key) {
+            Some(toml::Value::String(s)) => md.creators.push(s.clone()),
+            Some(toml::Value::Array(a)) => md
+                .creators
+                .extend(a.iter().filter_map(|v| v.as_str().map(|s| s.to_owned()))),
+            _ => {}
+        }
+    }
+
+    md
+}
+
 /// Extension methods for [`WorkspaceCreator`].
 pub trait WorkspaceCreatorExt {
     /// Create the new workspace with a good default for the bundle location.
@@ -233,11 +408,158 @@ impl WorkspaceCreatorExt for WorkspaceCreator {
         let bundle_loc = if config::is_test_bundle_wanted(web_bundle.clone()) {
             "test-bundle://".to_owned()
         } else {
-            let unresolved_loc = web_bundle.unwrap_or(config.default_bundle_loc().to_owned());
+            // Without an explicit bundle, fall back through the configured
+            // mirrors of the default one.
+            let urls = match web_bundle {
+                Some(loc) => vec![loc],
+                None => config.default_bundle_urls(),
+            };
             let mut gub = DefaultBackend::default();
-            gub.resolve_url(&unresolved_loc, status)?
+            gub.configure(&config.http_config()?)?;
+            resolve_first_reachable(&mut gub, &urls, status)?
         };
 
         Ok(self.create(bundle_loc)?)
     }
 }
+
+/// Resolve the first of several equivalent bundle URLs that can be reached.
+fn resolve_first_reachable(
+    gub: &mut DefaultBackend,
+    urls: &[String],
+    status: &mut dyn StatusBackend,
+) -> Result<String> {
+    let (last, others) = match urls.split_last() {
+        Some(t) => t,
+        None => return Err(ErrorKind::Msg("no bundle URLs were specified".to_owned()).into()),
+    };
+
+    for loc in others {
+        match gub.resolve_url(loc, status) {
+            Ok(url) => return Ok(url),
+            Err(e) => {
+                tt_warning!(status, "failed to connect to bundle mirror `{}`; trying the next one", loc; e);
+            }
+        }
+    }
+
+    Ok(gub.resolve_url(last, status)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata_of(toml_text: &str) -> EpubMetadata {
+        let value: toml::Value = toml::from_str(toml_text).unwrap();
+        epub_metadata(Some(&value))
+    }
+
+    #[test]
+    fn epub_metadata_fields() {
+        let md = metadata_of(
+            r#"
+            title = "A Book"
+            language = "fr"
+            identifier = "urn:isbn:9780000000000"
+            publisher = "Tectonic"
+            description = "About things."
+            date = 2026-01-02
+            author = "First Author"
+            authors = ["Second Author", 3, "Third Author"]
+            "#,
+        );
+
+        assert_eq!(md.title.as_deref(), Some("A Book"));
+        assert_eq!(md.language.as_deref(), Some("fr"));
+        assert_eq!(md.identifier.as_deref(), Some("urn:isbn:9780000000000"));
+        assert_eq!(md.publisher.as_deref(), Some("Tectonic"));
+        assert_eq!(md.description.as_deref(), Some("About things."));
+        assert_eq!(md.date.as_deref(), Some("2026-01-02"));
+        assert_eq!(
+            md.creators,
+            ["First Author", "Second Author", "Third Author"]
+        );
+        assert!(md.modified.is_none());
+    }
+
+    #[test]
+    fn relative_bundle_paths() {
+        const TOML: &str = r#"
+        [doc]
+        name = "test"
+        bundle = ["overlay", "main"]
+
+        [[output]]
+        name = "o"
+        type = "pdf"
+        "#;
+
+        let dir = tempfile::tempdir().unwrap();
+
+        for (sub, name, text) in [
+            ("overlay", "a.tex", "overlay"),
+            ("main", "a.tex", "main"),
+            ("main", "b.tex", "main"),
+        ] {
+            fs::create_dir_all(dir.path().join(sub)).unwrap();
+            fs::write(dir.path().join(sub).join(name), text).unwrap();
+        }
+
+        // The bundle locations are relative to the document, not to the
+        // directory that we're running in.
+        let mut c = io::Cursor::new(TOML.as_bytes());
+        let doc = Document::new_from_toml(dir.path(), dir.path().join("build"), &mut c).unwrap();
+        assert_ne!(std::env::current_dir().unwrap(), dir.path());
+
+        let mut status = crate::status::NoopStatusBackend::default();
+        let mut bundle = doc
+            .bundle(&DocumentSetupOptions::default(), &mut status)
+            .unwrap();
+
+        for (name, expected) in [("a.tex", "overlay"), ("b.tex", "main")] {
+            let mut text = String::new();
+            io::Read::read_to_string(
+                &mut bundle
+                    .input_open_name(name, &mut status)
+                    .must_exist()
+                    .unwrap(),
+                &mut text,
+            )
+            .unwrap();
+            assert_eq!(text, expected);
+        }
+    }
+
+    #[test]
+    fn http_config_from_env() {
+        std::env::set_var("TECTONIC_HTTP_PROXY", "http://proxy.example:3128");
+        std::env::set_var("TECTONIC_HTTP_MAX_ATTEMPTS", "7");
+        let from_env = DocumentSetupOptions::default().effective_http_config();
+        let explicit = DocumentSetupOptions::default()
+            .http_config(HttpConfig::default())
+            .effective_http_config();
+        std::env::remove_var("TECTONIC_HTTP_PROXY");
+        std::env::remove_var("TECTONIC_HTTP_MAX_ATTEMPTS");
+
+        let from_env = from_env.unwrap();
+        assert_eq!(from_env.proxy.as_deref(), Some("http://proxy.example:3128"));
+        assert_eq!(from_env.retry.max_attempts, 7);
+
+        // Explicit settings have already had the environment applied, if
+        // appropriate, so it isn't consulted again.
+        assert_eq!(explicit.unwrap(), HttpConfig::default());
+    }
+
+    #[test]
+    fn epub_metadata_defaults() {
+        let md = metadata_of("title = 1\nauthor = true\ndate = \"soon\"");
+        assert!(md.title.is_none());
+        assert!(md.creators.is_empty());
+        assert_eq!(md.date.as_deref(), Some("soon"));
+
+        let md = epub_metadata(Some(&toml::Value::String("title".into())));
+        assert!(md.title.is_none());
+        assert!(epub_metadata(None).creators.is_empty());
+    }
+}
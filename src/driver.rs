@@ -19,6 +19,7 @@ use byte_unit::{Byte, UnitType};
 use quick_xml::{events::Event, NsReader};
 use std::{
     collections::{HashMap, HashSet},
+    fmt::Write as FmtWrite,
     fs::File,
     io::{Cursor, Read, Write},
     path::{Path, PathBuf},
@@ -30,9 +31,9 @@ use std::{
 };
 use tectonic_bridge_core::{CoreBridgeLauncher, DriverHooks, SecuritySettings, SystemRequestError};
 use tectonic_bundles::Bundle;
-use tectonic_engine_spx2html::AssetSpecification;
+use tectonic_engine_spx2html::{AssetSpecification, DriverIoOutput, EpubMetadata, EpubOutput};
 use tectonic_io_base::{
-    digest::DigestData,
+    digest::{self, Digest, DigestData},
     filesystem::{FilesystemIo, FilesystemPrimaryInputIo},
     stdstreams::{BufferedPrimaryIo, GenuineStdoutIo},
     InputHandle, IoProvider, OpenResult, OutputHandle,
@@ -49,7 +50,8 @@ use crate::{
     status::StatusBackend,
     tt_error, tt_note, tt_warning,
     unstable_opts::UnstableOptions,
-    BibtexEngine, Spx2HtmlEngine, TexEngine, TexOutcome, XdvipdfmxEngine,
+    BibtexEngine, Spx2HtmlEngine, TexEngine, TexOutcome, Xdv2SvgEngine, Xdv2TextEngine,
+    XdvipdfmxEngine,
 };
 
 /// Different patterns with which files may have been accessed by the
@@ -127,10 +129,24 @@ pub enum OutputFormat {
     /// A '.pdf' file.
     #[default]
     Pdf,
+    /// A '.txt' file of plain text extracted from the typeset output.
+    Txt,
+    /// A set of '.svg' files, one per page.
+    Svg,
+    /// An '.epub' e-book packaging the HTML output.
+    Epub,
     /// A '.fmt' file, for initializing the TeX engine.
     Format,
 }
 
+impl OutputFormat {
+    /// Whether this format is produced from HTML generated by the TeX engine,
+    /// rather than from ordinary paginated output.
+    fn is_html(self) -> bool {
+        matches!(self, OutputFormat::Html | OutputFormat::Epub)
+    }
+}
+
 impl FromStr for OutputFormat {
     type Err = &'static str;
 
@@ -140,6 +156,9 @@ impl FromStr for OutputFormat {
             "html" => Ok(OutputFormat::Html),
             "xdv" => Ok(OutputFormat::Xdv),
             "pdf" => Ok(OutputFormat::Pdf),
+            "txt" => Ok(OutputFormat::Txt),
+            "svg" => Ok(OutputFormat::Svg),
+            "epub" => Ok(OutputFormat::Epub),
             "fmt" => Ok(OutputFormat::Format),
             _ => Err("unsupported or unknown format"),
         }
@@ -252,6 +271,11 @@ struct BridgeState {
     /// None.
     format_primary: Option<BufferedPrimaryIo>,
 
+    /// A possible alternative "primary input" when generating a format with
+    /// the document preamble preloaded. Unlike `format_primary`, this does not
+    /// disable filesystem I/O, since the preamble may well use local files.
+    preamble_primary: Option<BufferedPrimaryIo>,
+
     /// The I/O events that occurred while processing.
     events: HashMap<String, FileSummary>,
 }
@@ -271,6 +295,39 @@ impl BridgeState {
         self.format_primary = None;
     }
 
+    /// Compute the digest of a file as it currently exists in the project
+    /// filesystem, or the digest of nothing if it doesn't exist. This
+    /// doesn't count as an I/O event.
+    fn filesystem_digest(
+        &mut self,
+        name: &str,
+        status: &mut dyn StatusBackend,
+    ) -> Result<DigestData> {
+        let mut handle = None;
+
+        for fsio in std::iter::once(&mut self.filesystem).chain(self.extra_search_paths.iter_mut())
+        {
+            match fsio.input_open_name(name, status) {
+                OpenResult::Ok(h) => {
+                    handle = Some(h);
+                    break;
+                }
+                OpenResult::NotAvailable => {}
+                OpenResult::Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut dc = digest::create();
+
+        if let Some(mut h) = handle {
+            let mut data = Vec::new();
+            h.read_to_end(&mut data)?;
+            dc.update(&data);
+        }
+
+        Ok(DigestData::from(dc))
+    }
+
     /// Invoke an external tool as a pass in the processing pipeline.
     fn external_tool_pass(
         &mut self,
@@ -450,6 +507,9 @@ macro_rules! bridgestate_ioprovider_cascade {
         let use_fs = if let Some(ref mut p) = $self.format_primary {
             bridgestate_ioprovider_try!(p, $($inner)+);
             false
+        } else if let Some(ref mut p) = $self.preamble_primary {
+            bridgestate_ioprovider_try!(p, $($inner)+);
+            true
         } else {
             bridgestate_ioprovider_try!($self.primary_input, $($inner)+);
             true
@@ -804,6 +864,7 @@ pub struct ProcessingSessionBuilder {
     filesystem_root: Option<PathBuf>,
     format_name: Option<String>,
     format_cache_path: Option<PathBuf>,
+    preamble_format: Option<String>,
     output_format: OutputFormat,
     makefile_output_path: Option<PathBuf>,
     hidden_input_paths: HashSet<PathBuf>,
@@ -821,6 +882,11 @@ pub struct ProcessingSessionBuilder {
     html_precomputed_assets: Option<AssetSpecification>,
     html_do_not_emit_files: bool,
     html_do_not_emit_assets: bool,
+    html_woff2_fonts: bool,
+    html_hashed_asset_names: bool,
+    html_source_position_attributes: bool,
+    html_source_position_maps: bool,
+    epub_metadata: EpubMetadata,
 }
 
 impl ProcessingSessionBuilder {
@@ -911,6 +977,21 @@ impl ProcessingSessionBuilder {
         self
     }
 
+    /// Precompile a document preamble into a custom format file.
+    ///
+    /// The *preamble* is TeX source that is processed on top of the format
+    /// specified with [`Self::format_name`], after which the engine state is
+    /// dumped into a new format that is then used to process the primary
+    /// input. The primary input should therefore contain only the rest of the
+    /// document. The custom format is cached, and reused as long as the
+    /// preamble, the bundle, and any files that the preamble reads from the
+    /// filesystem stay the same. For documents that load lots of packages,
+    /// this can save a great deal of time.
+    pub fn preamble_format(&mut self, preamble: &str) -> &mut Self {
+        self.preamble_format = Some(preamble.to_owned());
+        self
+    }
+
     /// The type of output to create.
     pub fn output_format(&mut self, f: OutputFormat) -> &mut Self {
         self.output_format = f;
@@ -1089,7 +1170,9 @@ impl ProcessingSessionBuilder {
     /// Set whether templated outputs should be created during HTML processing.
     ///
     /// This mode can be useful if you want to analyze what *would* be created
-    /// during HTML processing without actually creating the files.
+    /// during HTML processing without actually creating the files. If files
+    /// are emitted but no output directory has been specified, they're kept in
+    /// memory and can be obtained with [`ProcessingSession::into_file_data`].
     pub fn html_emit_files(&mut self, do_emit: bool) -> &mut Self {
         self.html_do_not_emit_files = !do_emit;
         self
@@ -1107,6 +1190,63 @@ impl ProcessingSessionBuilder {
         self
     }
 
+    /// Set whether font files should be emitted in the WOFF2 format during
+    /// HTML processing.
+    ///
+    /// Emitted fonts are always subsetted to the glyphs that the document
+    /// uses. WOFF2 compression makes them smaller still. When using precomputed
+    /// assets, this setting must match the one used to create them.
+    pub fn html_woff2_fonts(&mut self, woff2: bool) -> &mut Self {
+        self.html_woff2_fonts = woff2;
+        self
+    }
+
+    /// Set whether asset files should be given content-hashed names during
+    /// HTML processing.
+    ///
+    /// This allows the assets to be cached indefinitely by web servers. The
+    /// `tduxAsset` template function and filter resolve logical asset paths to
+    /// the hashed ones. When using precomputed assets, this setting must match
+    /// the one used to create them.
+    pub fn html_hashed_asset_names(&mut self, hashed: bool) -> &mut Self {
+        self.html_hashed_asset_names = hashed;
+        self
+    }
+
+    /// Set whether HTML elements should be annotated with the TeX source
+    /// positions that produced them.
+    ///
+    /// If enabled, the TeX engine records source positions in the SPX file,
+    /// and paragraphs and canvases in the HTML output get attributes like
+    /// `data-src="chapter1.tex:42"`. This is the HTML analogue of SyncTeX.
+    pub fn html_source_position_attributes(&mut self, enabled: bool) -> &mut Self {
+        self.html_source_position_attributes = enabled;
+        self
+    }
+
+    /// Set whether JSON maps of TeX source positions should be written
+    /// alongside the HTML pages.
+    ///
+    /// If enabled, the TeX engine records source positions in the SPX file,
+    /// and each HTML page is accompanied by a file with the same name plus
+    /// `.srcmap.json`, giving the source positions of its paragraphs and
+    /// canvases. This provides the same information as
+    /// [`Self::html_source_position_attributes`] without altering the HTML. The
+    /// maps are not written for EPUB output.
+    pub fn html_source_position_maps(&mut self, enabled: bool) -> &mut Self {
+        self.html_source_position_maps = enabled;
+        self
+    }
+
+    /// Set the metadata of the publication when creating EPUB output.
+    ///
+    /// If the metadata don't specify a modification time, the build date of
+    /// the session is used.
+    pub fn epub_metadata(&mut self, metadata: EpubMetadata) -> &mut Self {
+        self.epub_metadata = metadata;
+        self
+    }
+
     /// Creates a `ProcessingSession`.
     pub fn create(self, status: &mut dyn StatusBackend) -> Result<ProcessingSession> {
         // First, work on the "bridge state", which gathers the subset of our
@@ -1198,6 +1338,7 @@ impl ProcessingSessionBuilder {
             bundle,
             genuine_stdout,
             format_primary: None,
+            preamble_primary: None,
             events: HashMap::new(),
         };
 
@@ -1215,13 +1356,17 @@ impl ProcessingSessionBuilder {
         let mut aux_path = PathBuf::from(tex_input_name.clone());
         aux_path.set_extension("aux");
         let mut xdv_path = aux_path.clone();
-        xdv_path.set_extension(if self.output_format == OutputFormat::Html {
+        xdv_path.set_extension(if self.output_format.is_html() {
             "spx"
         } else {
             "xdv"
         });
         let mut pdf_path = aux_path.clone();
         pdf_path.set_extension("pdf");
+        let mut txt_path = aux_path.clone();
+        txt_path.set_extension("txt");
+        let mut epub_path = aux_path.clone();
+        epub_path.set_extension("epub");
 
         let shell_escape_mode = if !self.security.allow_shell_escape() {
             ShellEscapeMode::Disabled
@@ -1248,9 +1393,13 @@ impl ProcessingSessionBuilder {
             primary_input_path,
             primary_input_tex_path: tex_input_name,
             format_name: self.format_name.unwrap(),
+            preamble: self.preamble_format,
+            preamble_format_name: None,
             tex_aux_path: aux_path.display().to_string(),
             tex_xdv_path: xdv_path.display().to_string(),
             tex_pdf_path: pdf_path.display().to_string(),
+            tex_txt_path: txt_path.display().to_string(),
+            tex_epub_path: epub_path.display().to_string(),
             output_format: self.output_format,
             makefile_output_path: self.makefile_output_path,
             output_path,
@@ -1265,10 +1414,69 @@ impl ProcessingSessionBuilder {
             html_precomputed_assets: self.html_precomputed_assets,
             html_emit_files: !self.html_do_not_emit_files,
             html_emit_assets: !self.html_do_not_emit_assets,
+            html_woff2_fonts: self.html_woff2_fonts,
+            html_hashed_asset_names: self.html_hashed_asset_names,
+            html_source_position_attributes: self.html_source_position_attributes,
+            html_source_position_maps: self.html_source_position_maps,
+            epub_metadata: self.epub_metadata,
         })
     }
 }
 
+/// Get the "stem" of a format file name, which is what the format cache uses
+/// to identify it.
+#[allow(clippy::manual_split_once)] // requires Rust 1.52 (note that we don't actually define our MSRV)
+fn format_stem(format_name: &str) -> Result<&str> {
+    // PathBuf.file_stem() doesn't do what we want since it only strips
+    // one extension.
+    format_name.split('.').next().ok_or_else(|| {
+        ErrorKind::Msg(format!(
+            "incomprehensible format file name \"{format_name}\""
+        ))
+        .into()
+    })
+}
+
+/// TeX code that is processed before a document preamble that is being
+/// compiled into a format.
+///
+/// Preambles commonly end with `\begin{document}`, as in the one that
+/// `tectonic -X new` creates, but the document can't be begun inside the
+/// format: `\dump` isn't allowed inside the group that `\begin` opens, and
+/// LaTeX would start writing its output files. So we intercept
+/// `\begin{document}`, dump the format right there, and arrange for the main
+/// pass to begin the document as soon as it starts.
+const PREAMBLE_PASS_HEADER: &str = r"\let\tectonicpreamblebegin\begin
+\def\tectonicpreambledocumentenv{document}%
+\def\begin#1{%
+  \def\tectonicpreambleenv{#1}%
+  \ifx\tectonicpreambleenv\tectonicpreambledocumentenv
+    \expandafter\tectonicpreambledump
+  \else
+    \expandafter\tectonicpreamblebegin
+  \fi{#1}}%
+\def\tectonicpreambledump#1{%
+  \let\begin\tectonicpreamblebegin
+  \everyjob\expandafter{\the\everyjob\begin{document}}%
+  \dump}%
+";
+
+/// TeX code that is processed after a document preamble that is being
+/// compiled into a format, if it didn't begin the document.
+const PREAMBLE_PASS_FOOTER: &str = r"\let\begin\tectonicpreamblebegin
+\dump
+";
+
+/// Get the name of a preamble format, given the key of its dependency list
+/// and the contents of that list.
+fn preamble_format_name(deps_key: &str, deps: &str) -> String {
+    let mut dc = digest::create();
+    dc.update(deps_key.as_bytes());
+    dc.update(b"\0");
+    dc.update(deps.as_bytes());
+    format!("preamble-{}", DigestData::from(dc).to_string())
+}
+
 #[derive(Debug, Clone)]
 enum RerunReason {
     Biber,
@@ -1299,11 +1507,21 @@ pub struct ProcessingSession {
     /// internally, so it has to be String compatible.
     format_name: String,
 
+    /// If set, TeX source for a document preamble that should be precompiled
+    /// into a custom format, which is then used instead of `format_name`.
+    preamble: Option<String>,
+
+    /// The name of the custom format with the preamble preloaded, once it
+    /// has been found or generated.
+    preamble_format_name: Option<String>,
+
     /// These are the paths of the various output files as TeX knows them --
     /// just `primary_input_tex_path` with the extension changed.
     tex_aux_path: String,
     tex_xdv_path: String,
     tex_pdf_path: String,
+    tex_txt_path: String,
+    tex_epub_path: String,
 
     /// If we're writing out Makefile rules, this is where they go. The TeX
     /// engine doesn't know about this path at all.
@@ -1336,6 +1554,11 @@ pub struct ProcessingSession {
     html_precomputed_assets: Option<AssetSpecification>,
     html_emit_files: bool,
     html_emit_assets: bool,
+    html_woff2_fonts: bool,
+    html_hashed_asset_names: bool,
+    html_source_position_attributes: bool,
+    html_source_position_maps: bool,
+    epub_metadata: EpubMetadata,
 }
 
 const DEFAULT_MAX_TEX_PASSES: usize = 6;
@@ -1344,6 +1567,12 @@ const ALWAYS_INTERMEDIATE_EXTENSIONS: &[&str] = &[
 ];
 
 impl ProcessingSession {
+    /// Whether source maps should be written alongside the HTML pages. They
+    /// are skipped for EPUB output, since they aren't part of the publication.
+    fn writes_source_maps(&self) -> bool {
+        self.html_source_position_maps && self.output_format != OutputFormat::Epub
+    }
+
     /// Assess whether we need to rerun an engine. This is the case if there
     /// was a file that the engine read and then rewrote, and the rewritten
     /// version is different than the version that it read in.
@@ -1401,6 +1630,16 @@ impl ProcessingSession {
         }
     }
 
+    /// Generate the session's format file and save it in the format cache,
+    /// whether or not a cached version already exists.
+    ///
+    /// Unlike [`Self::run`], this doesn't process the primary input at all.
+    pub fn generate_format(&mut self, status: &mut dyn StatusBackend) -> Result<()> {
+        tt_note!(status, "generating format \"{}\"", self.format_name);
+        self.make_format_pass(status)?;
+        Ok(())
+    }
+
     /// Runs the session, generating the desired outputs.
     ///
     /// What this does depends on which [`PassSetting`] you asked for. The most common choice is
@@ -1483,6 +1722,12 @@ impl ProcessingSession {
             self.make_format_pass(status)?;
         }
 
+        if self.output_format != OutputFormat::Format {
+            if let Some(preamble) = self.preamble.clone() {
+                self.preamble_format_name = Some(self.prepare_preamble_format(&preamble, status)?);
+            }
+        }
+
         // Do the meat of the work.
 
         let result = match self.pass {
@@ -1745,12 +1990,23 @@ impl ProcessingSession {
             tt_warning!(status, "{}", warnings);
         }
 
-        // And finally, xdvipdfmx or spx2html. Maybe.
+        // And finally, xdvipdfmx, spx2html, text extraction, or SVG
+        // rendering. Maybe.
 
-        if let OutputFormat::Pdf = self.output_format {
-            self.xdvipdfmx_pass(status)?;
-        } else if let OutputFormat::Html = self.output_format {
-            self.spx2html_pass(status)?;
+        match self.output_format {
+            OutputFormat::Pdf => {
+                self.xdvipdfmx_pass(status)?;
+            }
+            OutputFormat::Html | OutputFormat::Epub => {
+                self.spx2html_pass(status)?;
+            }
+            OutputFormat::Txt => {
+                self.xdv2text_pass(status)?;
+            }
+            OutputFormat::Svg => {
+                self.xdv2svg_pass(status)?;
+            }
+            _ => {}
         }
 
         Ok(0)
@@ -1773,19 +2029,8 @@ impl ProcessingSession {
     }
 
     /// Use the TeX engine to generate a format file.
-    #[allow(clippy::manual_split_once)] // requires Rust 1.52 (note that we don't actually define our MSRV)
     fn make_format_pass(&mut self, status: &mut dyn StatusBackend) -> Result<i32> {
-        // PathBuf.file_stem() doesn't do what we want since it only strips
-        // one extension. As of 1.17, the compiler needs a type annotation for
-        // some reason, which is why we use the `r` variable.
-        let r: Result<&str> = self.format_name.split('.').next().ok_or_else(|| {
-            ErrorKind::Msg(format!(
-                "incomprehensible format file name \"{}\"",
-                self.format_name
-            ))
-            .into()
-        });
-        let stem = r?;
+        let stem = format_stem(&self.format_name)?;
 
         let result = {
             self.bs
@@ -1840,6 +2085,178 @@ impl ProcessingSession {
         Ok(0)
     }
 
+    /// Make sure that a custom format with the document preamble preloaded
+    /// is available, generating it if needed, and return its name.
+    ///
+    /// Such a format depends not only on the bundle and the preamble text, but
+    /// also on whatever local files the preamble reads. Since we can't know
+    /// what those are without running TeX, we record them in a dependency list
+    /// keyed on the preamble text, and then key the format itself on the
+    /// contents of that list.
+    fn prepare_preamble_format(
+        &mut self,
+        preamble: &str,
+        status: &mut dyn StatusBackend,
+    ) -> Result<String> {
+        let mut dc = digest::create();
+        dc.update(format_stem(&self.format_name)?.as_bytes());
+        dc.update(b"\0");
+        dc.update(preamble.as_bytes());
+        let deps_key = format!("preamble-{}", DigestData::from(dc).to_string());
+
+        let old_deps = self.bs.format_cache.read_dependencies(&deps_key)?;
+
+        if let Some(deps) = old_deps.as_ref() {
+            if self.preamble_dependencies_unchanged(deps, status)? {
+                let name = preamble_format_name(&deps_key, deps);
+
+                if let OpenResult::Ok(_) = self.bs.format_cache.input_open_format(&name, status) {
+                    return Ok(name);
+                }
+            }
+        }
+
+        tt_note!(
+            status,
+            "generating a format with the document preamble preloaded"
+        );
+        let (data, deps) = self.make_preamble_format_pass(preamble, status)?;
+        let name = preamble_format_name(&deps_key, &deps);
+
+        ctry!(self.bs.format_cache.write_format(&name, &data, status); "cannot write format file {}", name);
+        ctry!(self.bs.format_cache.write_dependencies(&deps_key, &deps); "cannot write dependency list for format file {}", name);
+
+        // The format made from the previous version of the preamble's
+        // dependencies, if any, will never be used again.
+        if let Some(old_name) = old_deps.map(|d| preamble_format_name(&deps_key, &d)) {
+            if old_name != name {
+                if let Err(e) = self.bs.format_cache.remove_format(&old_name) {
+                    tt_warning!(status, "failed to delete the stale format file {}", old_name; e);
+                }
+            }
+        }
+
+        Ok(name)
+    }
+
+    /// Check whether all of the files listed in a preamble format's
+    /// dependency list still have the same contents.
+    fn preamble_dependencies_unchanged(
+        &mut self,
+        deps: &str,
+        status: &mut dyn StatusBackend,
+    ) -> Result<bool> {
+        for line in deps.lines() {
+            let (digest_text, name) = match line.split_once(' ') {
+                Some(t) => t,
+                None => return Ok(false),
+            };
+
+            let expected = match DigestData::from_str(digest_text) {
+                Ok(d) => d,
+                Err(_) => return Ok(false),
+            };
+
+            if self.bs.filesystem_digest(name, status)? != expected {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Use the TeX engine to process the document preamble on top of the main
+    /// format, and dump the result into a new format. Returns the format data
+    /// and the dependency list of the new format.
+    fn make_preamble_format_pass(
+        &mut self,
+        preamble: &str,
+        status: &mut dyn StatusBackend,
+    ) -> Result<(Vec<u8>, String)> {
+        // The I/O events of this pass shouldn't affect the rerun logic of the
+        // main passes, so we keep them separate.
+        let saved_events = std::mem::take(&mut self.bs.events);
+
+        let result = {
+            self.bs.preamble_primary = Some(BufferedPrimaryIo::from_text(format!(
+                "{PREAMBLE_PASS_HEADER}{preamble}\n{PREAMBLE_PASS_FOOTER}"
+            )));
+            let mut launcher =
+                CoreBridgeLauncher::new_with_security(&mut self.bs, status, self.security.clone());
+            let r = TexEngine::default()
+                .halt_on_error_mode(true)
+                .initex_mode(true)
+                .initex_load_format(true)
+                .shell_escape(self.shell_escape_mode != ShellEscapeMode::Disabled)
+                .build_date(self.build_date)
+                .process(
+                    &mut launcher,
+                    &self.format_name,
+                    &self.primary_input_tex_path,
+                );
+            self.bs.preamble_primary = None;
+            r
+        };
+
+        let events = std::mem::replace(&mut self.bs.events, saved_events);
+
+        match result {
+            Ok(TexOutcome::Spotless) => {}
+            Ok(TexOutcome::Warnings) => {
+                tt_warning!(status, "warnings were issued by the TeX engine while processing the preamble; use --print and/or --keep-logs for details.");
+            }
+            Ok(TexOutcome::Errors) => {
+                tt_error!(status, "errors were issued by the TeX engine while processing the preamble; use --print and/or --keep-logs for details.");
+                return Err(ErrorKind::Msg("unhandled TeX engine error".to_owned()).into());
+            }
+            Err(e) => {
+                return Err(e.into());
+            }
+        }
+
+        let data = {
+            let files = self.bs.mem.files.borrow();
+            let file = files
+                .iter()
+                .find(|(name, _)| name.ends_with(".fmt"))
+                .map(|(_, file)| file);
+
+            match file {
+                Some(f) => f.data.clone(),
+                None => {
+                    return Err(errmsg!(
+                        "processing the preamble did not produce a format file"
+                    ));
+                }
+            }
+        };
+
+        // Files from the bundle are already accounted for by the bundle
+        // digest, so we only need to track ones from the filesystem. That
+        // includes files that didn't exist, since they might appear later.
+        let mut names: Vec<&String> = events
+            .iter()
+            .filter(|(_, summ)| {
+                summ.access_pattern == AccessPattern::Read
+                    && summ.input_origin != InputOrigin::Other
+            })
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+
+        let mut deps = String::new();
+
+        for name in names {
+            let digest = self.bs.filesystem_digest(name, status)?;
+            writeln!(deps, "{} {}", digest.to_string(), name)?;
+        }
+
+        // As with the standard format pass, clear out the memory layer.
+        self.bs.mem.files.borrow_mut().clear();
+
+        Ok((data, deps))
+    }
+
     /// Run one pass of the TeX engine.
     fn tex_pass(
         &mut self,
@@ -1853,6 +2270,8 @@ impl ProcessingSession {
                 status.note_highlighted("Running ", "TeX", " ...");
             }
 
+            let source_positions =
+                self.html_source_position_attributes || self.writes_source_maps();
             let mut launcher =
                 CoreBridgeLauncher::new_with_security(&mut self.bs, status, self.security.clone());
 
@@ -1872,12 +2291,15 @@ impl ProcessingSession {
                 .halt_on_error_mode(!self.unstables.continue_on_errors)
                 .initex_mode(self.output_format == OutputFormat::Format)
                 .synctex(self.synctex_enabled)
-                .semantic_pagination(self.output_format == OutputFormat::Html)
+                .semantic_pagination(self.output_format.is_html())
+                .source_positions(source_positions)
                 .shell_escape(self.shell_escape_mode != ShellEscapeMode::Disabled)
                 .build_date(self.build_date)
                 .process(
                     &mut launcher,
-                    &self.format_name,
+                    self.preamble_format_name
+                        .as_deref()
+                        .unwrap_or(&self.format_name),
                     &self.primary_input_tex_path,
                 )
         };
@@ -1985,9 +2407,22 @@ impl ProcessingSession {
             let mut engine = Spx2HtmlEngine::default();
 
             match (self.html_emit_files, self.output_path.as_ref()) {
-                (true, Some(p)) => engine.output_base(p),
                 (false, _) => engine.do_not_emit_files(),
-                (true, None) => return Err(errmsg!("HTML output must be saved directly to disk")),
+                // The EPUB file is an ordinary output, so it goes through our
+                // I/O layer regardless of the output directory.
+                (true, _) if self.output_format == OutputFormat::Epub => {
+                    let mut metadata = self.epub_metadata.clone();
+                    metadata.modified.get_or_insert(self.build_date);
+                    engine.output_tree(EpubOutput::new(
+                        DriverIoOutput,
+                        &self.tex_epub_path,
+                        metadata,
+                    ))
+                }
+                (true, Some(p)) => engine.output_base(p),
+                // Without an output directory, send the HTML tree through our
+                // I/O layer so that it's captured in memory.
+                (true, None) => engine.output_tree(DriverIoOutput),
             };
 
             if let Some(p) = self.html_assets_spec_path.as_ref() {
@@ -2000,6 +2435,11 @@ impl ProcessingSession {
                 engine.precomputed_assets(a.clone());
             }
 
+            engine.woff2_fonts(self.html_woff2_fonts);
+            engine.hashed_asset_names(self.html_hashed_asset_names);
+            engine.source_position_attributes(self.html_source_position_attributes);
+            engine.source_position_maps(self.writes_source_maps());
+
             status.note_highlighted("Running ", "spx2html", " ...");
             engine.process_to_filesystem(&mut self.bs, status, &self.tex_xdv_path)?;
         }
@@ -2008,6 +2448,46 @@ impl ProcessingSession {
         Ok(0)
     }
 
+    fn xdv2text_pass(&mut self, status: &mut dyn StatusBackend) -> Result<i32> {
+        {
+            status.note_highlighted("Running ", "xdv2text", " ...");
+            Xdv2TextEngine::default().process(
+                &mut self.bs,
+                status,
+                &self.tex_xdv_path,
+                &self.tex_txt_path,
+            )?;
+        }
+
+        self.bs.mem.files.borrow_mut().remove(&self.tex_xdv_path);
+        Ok(0)
+    }
+
+    fn xdv2svg_pass(&mut self, status: &mut dyn StatusBackend) -> Result<i32> {
+        {
+            let stem = Path::new(&self.primary_input_tex_path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "texput".to_owned());
+
+            let mut engine = Xdv2SvgEngine::default();
+            engine.page_stem(stem);
+
+            match self.output_path.as_ref() {
+                Some(p) => engine.output_base(p),
+                // As with HTML, send the files through our I/O layer so that
+                // they're captured in memory.
+                None => engine.output_tree(DriverIoOutput),
+            };
+
+            status.note_highlighted("Running ", "xdv2svg", " ...");
+            engine.process_to_filesystem(&mut self.bs, status, &self.tex_xdv_path)?;
+        }
+
+        self.bs.mem.files.borrow_mut().remove(&self.tex_xdv_path);
+        Ok(0)
+    }
+
     /// Get what was printed to standard output, if anything.
     pub fn get_stdout_content(&self) -> Vec<u8> {
         self.bs
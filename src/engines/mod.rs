@@ -15,5 +15,8 @@ pub mod tex;
 pub mod xdvipdfmx;
 
 pub use self::{
-    bibtex::BibtexEngine, spx2html::Spx2HtmlEngine, tex::TexEngine, xdvipdfmx::XdvipdfmxEngine,
+    bibtex::BibtexEngine,
+    spx2html::{Spx2HtmlEngine, Xdv2SvgEngine, Xdv2TextEngine},
+    tex::TexEngine,
+    xdvipdfmx::XdvipdfmxEngine,
 };
@@ -1,4 +1,7 @@
 // Copyright 2018-2021 the Tectonic Project
 // Licensed under the MIT License.
 
-pub use tectonic_engine_spx2html::Spx2HtmlEngine;
+pub use tectonic_engine_spx2html::{
+    DriverIoOutput, EpubMetadata, EpubOutput, FilesystemOutput, OutputTree, Spx2HtmlEngine,
+    Xdv2SvgEngine, Xdv2TextEngine,
+};
@@ -6,14 +6,89 @@
 //! Code for locally caching compiled format files.
 
 use std::{
-    io::{BufReader, Write},
-    path::PathBuf,
+    fs,
+    io::{self, BufReader, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 use tectonic_errors::{anyhow::bail, Result};
 
 use super::{InputHandle, InputOrigin, IoProvider, OpenResult};
 use crate::{digest::DigestData, status::StatusBackend};
 
+/// The kinds of files saved in a format cache directory.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CachedFileKind {
+    /// A format file.
+    Format,
+
+    /// A list of input dependencies, saved by
+    /// [`FormatCache::write_dependencies`].
+    Dependencies,
+}
+
+/// Information about a file found in a format cache directory.
+#[derive(Clone, Debug)]
+pub struct CachedFormatInfo {
+    /// The path to the file.
+    pub path: PathBuf,
+
+    /// What kind of file this is.
+    pub kind: CachedFileKind,
+
+    /// The digest of the bundle that the format was generated from.
+    pub bundle_digest: DigestData,
+
+    /// The name of the format, without any extension (e.g. `latex`). For
+    /// dependency lists, this is the key that they were saved with.
+    pub name: String,
+
+    /// The engine format serial number that the format was generated with.
+    pub serial: u32,
+
+    /// The size of the file, in bytes.
+    pub size: u64,
+}
+
+impl CachedFormatInfo {
+    /// Returns true if this format was generated with a different format
+    /// serial number than the one used by this version of the engine, which
+    /// means that it can never be loaded again.
+    pub fn is_stale(&self) -> bool {
+        self.serial != crate::FORMAT_SERIAL
+    }
+
+    /// Delete this file.
+    ///
+    /// Dependency lists are keyed differently than the formats that they
+    /// describe, so they aren't deleted along with them. Instead, they're
+    /// listed as separate entries.
+    pub fn remove(&self) -> Result<()> {
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+
+    /// Try to interpret a path as the name of a file saved by a format cache.
+    ///
+    /// Files are named `<bundle digest>-<name>-<serial>.<ext>`, where the
+    /// extension is `fmt` for formats and `deps` for dependency lists. Note
+    /// that the name may itself contain hyphens.
+    fn from_path(path: &Path) -> Option<(CachedFileKind, DigestData, String, u32)> {
+        let kind = match path.extension()?.to_str()? {
+            "fmt" => CachedFileKind::Format,
+            "deps" => CachedFileKind::Dependencies,
+            _ => return None,
+        };
+
+        let stem = path.file_stem()?.to_str()?;
+        let (digest, rest) = stem.split_once('-')?;
+        let (name, serial) = rest.rsplit_once('-')?;
+        let digest = DigestData::from_str(digest).ok()?;
+        let serial = serial.parse().ok()?;
+        Some((kind, digest, name.to_owned(), serial))
+    }
+}
+
 /// A local cache for compiled format files.
 ///
 /// The format cache takes care of saving compiled format files. It uses the
@@ -38,10 +113,68 @@ impl FormatCache {
         }
     }
 
+    /// List all of the format files and dependency lists in a format cache
+    /// directory, for any bundle or format serial number.
+    ///
+    /// Files in the directory that don't look like they were saved by a
+    /// `FormatCache` are ignored. The results are sorted by path.
+    pub fn list_formats(formats_base: &Path) -> Result<Vec<CachedFormatInfo>> {
+        let mut formats = Vec::new();
+
+        for entry in fs::read_dir(formats_base)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            let (kind, bundle_digest, name, serial) = match CachedFormatInfo::from_path(&path) {
+                Some(t) => t,
+                None => continue,
+            };
+
+            formats.push(CachedFormatInfo {
+                size: entry.metadata()?.len(),
+                path,
+                kind,
+                bundle_digest,
+                name,
+                serial,
+            });
+        }
+
+        formats.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(formats)
+    }
+
+    /// Delete all of the files in a format cache directory that can never be
+    /// used again, returning information about the ones that were deleted.
+    ///
+    /// Only files with stale format serial numbers are deleted. Formats are
+    /// named after the digest of the bundle that they were generated from, but
+    /// there's no way to tell from a digest alone whether that bundle still
+    /// exists: it might be a local directory or ZIP file that we've never
+    /// heard of. So formats for other bundles are kept.
+    pub fn remove_stale(formats_base: &Path) -> Result<Vec<CachedFormatInfo>> {
+        let mut removed = Vec::new();
+
+        for f in Self::list_formats(formats_base)? {
+            if f.is_stale() {
+                f.remove()?;
+                removed.push(f);
+            }
+        }
+
+        Ok(removed)
+    }
+
     /// Get an on-disk path name for a given format file. This function simply
     /// produces a path that may or may not exist.
-    #[allow(clippy::manual_split_once)] // requires Rust 1.52 (note that we don't actually define our MSRV)
     fn path_for_format(&mut self, name: &str) -> Result<PathBuf> {
+        self.path_with_extension(name, "fmt")
+    }
+
+    /// Get an on-disk path name for a file associated with a format, with the
+    /// given extension.
+    #[allow(clippy::manual_split_once)] // requires Rust 1.52 (note that we don't actually define our MSRV)
+    fn path_with_extension(&self, name: &str, ext: &str) -> Result<PathBuf> {
         // Remove all extensions from the format name. PathBuf.file_stem() doesn't
         // do what we want since it only strips one extension, so here we go:
 
@@ -54,13 +187,55 @@ impl FormatCache {
 
         let mut p = self.formats_base.clone();
         p.push(format!(
-            "{}-{}-{}.fmt",
+            "{}-{}-{}.{}",
             self.bundle_digest.to_string(),
             stem,
-            crate::FORMAT_SERIAL
+            crate::FORMAT_SERIAL,
+            ext
         ));
         Ok(p)
     }
+
+    /// Delete a format file from the cache, if it exists.
+    pub fn remove_format(&mut self, name: &str) -> Result<()> {
+        let path = self.path_for_format(name)?;
+
+        match fs::remove_file(path) {
+            Ok(()) => Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Read the list of input dependencies recorded for a format, if any.
+    ///
+    /// Custom formats, such as ones with a document preamble preloaded, depend
+    /// on files other than those in the bundle. Such formats should only be
+    /// reused if those files haven't changed, so we need to keep track of
+    /// them. The contents of the list are up to the caller.
+    pub fn read_dependencies(&self, name: &str) -> Result<Option<String>> {
+        let path = self.path_with_extension(name, "deps")?;
+
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Some(text)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Save the list of input dependencies for a format.
+    ///
+    /// See [`Self::read_dependencies`].
+    pub fn write_dependencies(&self, name: &str, text: &str) -> Result<()> {
+        let final_path = self.path_with_extension(name, "deps")?;
+        let mut temp_dest = tempfile::Builder::new()
+            .prefix("format_")
+            .rand_bytes(6)
+            .tempfile_in(&self.formats_base)?;
+        temp_dest.write_all(text.as_bytes())?;
+        temp_dest.persist(final_path)?;
+        Ok(())
+    }
 }
 
 impl IoProvider for FormatCache {
@@ -103,3 +278,97 @@ impl IoProvider for FormatCache {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tectonic_bundles::{dir::DirBundle, Bundle};
+
+    const DIGEST: &str = "a6e9bc1c8e5a7ea8a2bdc9a49e0ba3a4a1e1db17e9ba9f3f0d3cb2e0d7f57c47";
+
+    #[test]
+    fn parse_format_paths() {
+        let p = PathBuf::from(format!("{DIGEST}-latex-33.fmt"));
+        let (kind, d, name, serial) = CachedFormatInfo::from_path(&p).unwrap();
+        assert_eq!(kind, CachedFileKind::Format);
+        assert_eq!(d.to_string(), DIGEST);
+        assert_eq!(name, "latex");
+        assert_eq!(serial, 33);
+
+        let p = PathBuf::from(format!("{DIGEST}-preamble-abc-33.fmt"));
+        let (_, _, name, _) = CachedFormatInfo::from_path(&p).unwrap();
+        assert_eq!(name, "preamble-abc");
+
+        let p = PathBuf::from(format!("{DIGEST}-preamble-def-33.deps"));
+        let (kind, _, name, _) = CachedFormatInfo::from_path(&p).unwrap();
+        assert_eq!(kind, CachedFileKind::Dependencies);
+        assert_eq!(name, "preamble-def");
+
+        assert!(CachedFormatInfo::from_path(&PathBuf::from("format_a1b2c3")).is_none());
+        assert!(CachedFormatInfo::from_path(&PathBuf::from("xyz-latex-33.fmt")).is_none());
+        assert!(
+            CachedFormatInfo::from_path(&PathBuf::from(format!("{DIGEST}-latex-33.txt"))).is_none()
+        );
+    }
+
+    #[test]
+    fn list_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = FormatCache::new(DigestData::from_str(DIGEST).unwrap(), dir.path().into());
+        let mut status = crate::status::NoopStatusBackend::default();
+
+        // As with preamble formats, the dependency list has a different key
+        // than the format.
+        cache
+            .write_format("preamble-fmt.fmt", b"format", &mut status)
+            .unwrap();
+        cache.write_dependencies("preamble-key", "deps").unwrap();
+
+        let files = FormatCache::list_formats(dir.path()).unwrap();
+        let summary: Vec<_> = files
+            .iter()
+            .map(|f| (f.kind, &f.name[..], f.size))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (CachedFileKind::Format, "preamble-fmt", 6),
+                (CachedFileKind::Dependencies, "preamble-key", 4),
+            ]
+        );
+
+        for f in &files {
+            f.remove().unwrap();
+        }
+
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn remove_stale_keeps_other_bundles() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle_dir = tempfile::tempdir().unwrap();
+        let mut status = crate::status::NoopStatusBackend::default();
+
+        // A format for a local directory bundle, which the bundle cache knows
+        // nothing about, should survive.
+        fs::write(bundle_dir.path().join("plain.tex"), b"\\dump").unwrap();
+        let digest = DirBundle::new(bundle_dir.path())
+            .get_digest(&mut status)
+            .unwrap();
+        let mut cache = FormatCache::new(digest, dir.path().into());
+        cache.write_format("plain", b"format", &mut status).unwrap();
+
+        let stale = dir.path().join(format!("{DIGEST}-latex-1.fmt"));
+        fs::write(&stale, b"old").unwrap();
+
+        let removed = FormatCache::remove_stale(dir.path()).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].path, stale);
+
+        let left = FormatCache::list_formats(dir.path()).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].name, "plain");
+        assert_eq!(left[0].bundle_digest, digest);
+    }
+}
@@ -94,7 +94,7 @@ pub mod unstable_opts;
 pub mod test_util;
 
 pub use crate::engines::bibtex::BibtexEngine;
-pub use crate::engines::spx2html::Spx2HtmlEngine;
+pub use crate::engines::spx2html::{Spx2HtmlEngine, Xdv2SvgEngine, Xdv2TextEngine};
 pub use crate::engines::tex::{TexEngine, TexOutcome};
 pub use crate::engines::xdvipdfmx::XdvipdfmxEngine;
 pub use crate::errors::{Error, ErrorKind, Result};
@@ -420,6 +420,50 @@ fn test_cached_url_provider() {
     check_req_count(&requests, TectonicRequest::File("other.tex".into()), 1);
 }
 
+#[test]
+fn test_mirror_failover() {
+    let tar_index = {
+        let mut builder = TarIndexBuilder::new();
+        builder.push("plain.tex", b"test").push(
+            tectonic::digest::DIGEST_NAME,
+            b"0000000000000000000000000000000000000000000000000000000000000000",
+        );
+        builder.finish()
+    };
+
+    let requests = run_test(Some(tar_index), |_, url| {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut status = TermcolorStatusBackend::new(ChatterLevel::Minimal);
+        let config = PersistentConfig::default();
+
+        // The first "mirror" gives a 404, so we should fall back to the
+        // second one, and accept it because it has the expected digest.
+        let urls = [
+            url.replace("tectonic-default", "missing-bundle"),
+            url.to_owned(),
+        ];
+        let digest = "0000000000000000000000000000000000000000000000000000000000000000";
+
+        let mut cache = config
+            .make_mirrored_url_provider(
+                &urls,
+                Some(digest),
+                false,
+                Some(tempdir.path()),
+                &mut status,
+            )
+            .unwrap();
+
+        match cache.input_open_name("plain.tex", &mut status) {
+            OpenResult::Ok(_) => {}
+            _ => panic!("Failed to open plain.tex"),
+        }
+    });
+
+    check_req_count(&requests, TectonicRequest::Index, 1);
+    check_req_count(&requests, TectonicRequest::File("plain.tex".into()), 1);
+}
+
 #[test]
 fn test_bundle_update() {
     let tempdir = tempfile::tempdir().unwrap();
@@ -8,15 +8,190 @@
 //! ProcessingSessionBuilder will need to learn how to tell `xdvipdfmx` to
 //! enable the reproducibility options used in the `tex-outputs` test rig.
 
+use std::{fs, path::Path, time::SystemTime};
 use tectonic::{
     config::PersistentConfig, driver::ProcessingSessionBuilder,
     status::termcolor::TermcolorStatusBackend, status::ChatterLevel, test_util::TestBundle,
 };
+use tectonic_bundles::{dir::DirBundle, layered::LayeredBundle, Bundle};
 
 mod util;
 
 // Keep these alphabetized.
 
+/// A tiny stand-in for LaTeX, with just enough of its document structure to
+/// process the preamble from the default `tectonic -X new` workspace.
+const MINI_LATEX_FORMAT: &str = r"\input plain
+\let\plainend\end
+\def\documentclass#1{}
+\def\title#1{\gdef\thetitle{#1}}
+\def\begin#1{\begingroup\csname #1\endcsname}
+\def\document{\endgroup\global\let\documentbegun=y}
+\def\end#1{\csname end#1\endcsname}
+\def\enddocument{\plainend}
+\dump
+";
+
+/// Process `body` with `\input{_preamble.tex}` as a precompiled preamble,
+/// reading local files from `src_dir` and caching formats in `format_dir`.
+fn run_with_preamble(
+    src_dir: &Path,
+    format_dir: &Path,
+    format_name: &str,
+    bundle: Box<dyn Bundle>,
+    body: &str,
+) {
+    let mut status = TermcolorStatusBackend::new(ChatterLevel::Minimal);
+
+    let mut pbuilder = ProcessingSessionBuilder::default();
+    pbuilder
+        .primary_input_buffer(body.as_bytes())
+        .tex_input_name("texput.tex")
+        .filesystem_root(src_dir)
+        .format_name(format_name)
+        .format_cache_path(format_dir)
+        .preamble_format("\\input{_preamble.tex}\n")
+        .output_dir(src_dir)
+        .bundle(bundle);
+
+    let mut session = pbuilder
+        .create(&mut status)
+        .expect("couldn't create processing session");
+
+    session
+        .run(&mut status)
+        .expect("failed to execute processing session");
+}
+
+/// List the preamble formats in `format_dir`, with their modification times.
+fn preamble_formats(format_dir: &Path) -> Vec<(String, SystemTime)> {
+    let mut formats: Vec<_> = fs::read_dir(format_dir)
+        .unwrap()
+        .filter_map(|entry| {
+            let entry = entry.unwrap();
+            let name = entry.file_name().into_string().unwrap();
+
+            if name.contains("-preamble-") && name.ends_with(".fmt") {
+                Some((name, entry.metadata().unwrap().modified().unwrap()))
+            } else {
+                None
+            }
+        })
+        .collect();
+    formats.sort();
+    formats
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn preamble_format_default_workspace() {
+    util::set_test_root();
+
+    let root = tempfile::Builder::new()
+        .prefix("tectonic_driver_test")
+        .tempdir()
+        .unwrap();
+    let formats = tempfile::Builder::new()
+        .prefix("tectonic_driver_test")
+        .tempdir()
+        .unwrap();
+
+    // Get the preamble and postamble from a freshly created workspace.
+    let ws_dir = root.path().join("doc");
+
+    tectonic_docmodel::workspace::WorkspaceCreator::new(&ws_dir)
+        .create("https://example.com/bundle.tar".to_owned())
+        .unwrap();
+
+    let src_dir = ws_dir.join("src");
+    let preamble = fs::read_to_string(src_dir.join("_preamble.tex")).unwrap();
+    assert!(preamble.contains("\\begin{document}"));
+    let postamble = fs::read_to_string(src_dir.join("_postamble.tex")).unwrap();
+
+    let base_dir = root.path().join("base");
+    fs::create_dir_all(&base_dir).unwrap();
+    fs::write(
+        base_dir.join("tectonic-format-minilatex.tex"),
+        MINI_LATEX_FORMAT,
+    )
+    .unwrap();
+
+    // The main pass must start with the document already begun, the
+    // preamble's definitions in place, and a real date.
+    let body = format!(
+        "\\ifx\\documentbegun\\undefined \\errmessage{{document not begun}}\\fi
+\\ifnum\\year=0 \\errmessage{{no date}}\\fi
+\\thetitle
+{postamble}"
+    );
+
+    let bundle = LayeredBundle::new(vec![
+        Box::new(DirBundle::new(&base_dir)),
+        Box::new(TestBundle::default()),
+    ])
+    .unwrap();
+
+    run_with_preamble(
+        &src_dir,
+        formats.path(),
+        "minilatex",
+        Box::new(bundle),
+        &body,
+    );
+    assert_eq!(preamble_formats(formats.path()).len(), 1);
+}
+
+#[test]
+fn preamble_format_reuse_and_prune() {
+    util::set_test_root();
+
+    let src = tempfile::Builder::new()
+        .prefix("tectonic_driver_test")
+        .tempdir()
+        .unwrap();
+    let formats = tempfile::Builder::new()
+        .prefix("tectonic_driver_test")
+        .tempdir()
+        .unwrap();
+
+    fs::write(
+        src.path().join("_preamble.tex"),
+        "\\input defs\n\\edef\\preambleyear{\\the\\year}\n",
+    )
+    .unwrap();
+    fs::write(src.path().join("defs.tex"), "\\def\\greeting{Hello}\n").unwrap();
+
+    let body = "\\ifnum\\preambleyear=0 \\errmessage{no date in the preamble}\\fi
+\\greeting
+\\end
+";
+    let run = || {
+        run_with_preamble(
+            src.path(),
+            formats.path(),
+            "plain",
+            Box::new(TestBundle::default()),
+            body,
+        )
+    };
+
+    run();
+    let first = preamble_formats(formats.path());
+    assert_eq!(first.len(), 1);
+
+    // Nothing changed, so the format should be reused as-is.
+    run();
+    assert_eq!(preamble_formats(formats.path()), first);
+
+    // A file that the preamble reads changed, so we need a new format, and
+    // the old one should be cleaned up.
+    fs::write(src.path().join("defs.tex"), "\\def\\greeting{Howdy}\n").unwrap();
+    run();
+    let second = preamble_formats(formats.path());
+    assert_eq!(second.len(), 1);
+    assert_ne!(second[0].0, first[0].0);
+}
+
 #[test]
 fn the_letter_a() {
     util::set_test_root();